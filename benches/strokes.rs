@@ -0,0 +1,245 @@
+//! Benchmarks of the CPU work behind drawing: building segments from pointer
+//! input, simplifying strokes into levels of detail, the spatial index, and
+//! getting segments into vertex buffers
+//!
+//! `cargo bench` runs them all; a name filter picks groups, e.g.
+//! `cargo bench -- upload`. The Vulkan upload benchmarks need the same
+//! device and compiled shaders as the headless tests, and are skipped
+//! without them.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::f32::consts::TAU;
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use scribble_vk::config::VertexFormat;
+use scribble_vk::lod::{decimate, simplify, StrokeLod};
+use scribble_vk::spatial::StrokeIndex;
+use scribble_vk::strokes::{StrokeBuilder, StrokeStore};
+use scribble_vk::types::{Color, Line, Rect, Vec2};
+use scribble_vk::vulkan::buffer::{create_buffers, upload_lines};
+use scribble_vk::vulkan::owned::guard;
+use scribble_vk::vulkan::upload::LineUploads;
+use scribble_vk::Stroke;
+use vulkanalia::prelude::v1_0::*;
+
+/// Pointer positions of a hand-drawn spiral, about as dense as a mouse at 120 Hz
+fn spiral(points: usize) -> Vec<Vec2> {
+    (0..points)
+        .map(|i| {
+            let t = i as f32 / points as f32;
+            let angle = t * 12.0 * TAU;
+            let radius = 0.05 + 0.8 * t;
+            Vec2::new(radius * angle.cos(), radius * angle.sin())
+        })
+        .collect()
+}
+
+fn spiral_lines(points: usize) -> Vec<Line> {
+    Stroke::from_points(&spiral(points), Color::WHITE).lines()
+}
+
+/// `count` short strokes spread over a canvas of a few screens
+fn scattered_strokes(count: usize, segments: usize) -> Vec<Vec<Line>> {
+    (0..count)
+        .map(|i| {
+            let center = Vec2::new(
+                ((i * 7919) % 1000) as f32 / 100.0 - 5.0,
+                ((i * 104_729) % 1000) as f32 / 100.0 - 5.0,
+            );
+            let points: Vec<Vec2> = spiral(segments + 1)
+                .into_iter()
+                .map(|p| center + p * 0.1)
+                .collect();
+            Stroke::from_points(&points, Color::WHITE).lines()
+        })
+        .collect()
+}
+
+/// `StrokeBuilder::extend_to` as `App::append_vertex` drives it, committing full strokes
+fn segment_generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("append_vertex");
+    let points = spiral(10_000);
+
+    for capacity in [256, 10_000] {
+        group.bench_with_input(
+            BenchmarkId::new("staging", capacity),
+            &capacity,
+            |b, &capacity| {
+                b.iter_batched(
+                    || (StrokeBuilder::new(capacity), StrokeStore::default()),
+                    |(mut builder, mut store)| {
+                        for &point in &points {
+                            if let Some(line) = builder.extend_to(point, Color::WHITE) {
+                                black_box(Rect::around_line(&line, 0.01));
+                            }
+                            if builder.is_full() {
+                                let end = builder.end();
+                                store.push(builder.take());
+                                builder.start_at(end);
+                            }
+                        }
+                        store.push(builder.take());
+                        store
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn simplification(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lod");
+    let lines = spiral_lines(10_000);
+
+    for min_length in [0.012, 0.048, 0.192] {
+        group.bench_with_input(
+            BenchmarkId::new("decimate", min_length),
+            &min_length,
+            |b, &min_length| b.iter(|| decimate(black_box(&lines), min_length)),
+        );
+    }
+    for tolerance in [0.001, 0.005] {
+        group.bench_with_input(
+            BenchmarkId::new("simplify", tolerance),
+            &tolerance,
+            |b, &tolerance| b.iter(|| simplify(black_box(&lines), tolerance)),
+        );
+    }
+    group.bench_function("stroke_lod", |b| {
+        b.iter(|| StrokeLod::new(black_box(&lines)))
+    });
+    group.finish();
+}
+
+fn spatial_index(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spatial");
+    let mut store = StrokeStore::default();
+    for lines in scattered_strokes(5_000, 32) {
+        store.push(lines);
+    }
+    let index = StrokeIndex::new(&store);
+    let view = Rect::from_corners(Vec2::new(-1.0, -1.0), Vec2::new(1.0, 1.0));
+
+    group.bench_function("build", |b| b.iter(|| StrokeIndex::new(black_box(&store))));
+    group.bench_function("line_ranges", |b| {
+        b.iter(|| index.line_ranges(black_box(&view)))
+    });
+    group.finish();
+}
+
+/// Converting segments into staging memory, which every upload does
+fn staging_writes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("upload/write");
+    let lines = spiral_lines(10_000);
+
+    for (name, format) in [("f32", VertexFormat::F32), ("f16", VertexFormat::F16)] {
+        let mut staging = vec![0u8; lines.len() * Line::stride(format)];
+        group.bench_function(name, |b| {
+            b.iter(|| unsafe { Line::write(format, black_box(&lines), staging.as_mut_ptr()) })
+        });
+    }
+    group.finish();
+}
+
+/// Committing strokes one blocking `upload_lines` at a time against queuing
+/// them on `LineUploads`, which coalesces adjacent strokes into one copy
+fn upload_batching(c: &mut Criterion) {
+    let Some((config, context)) = common::setup("f32") else {
+        return;
+    };
+    let strokes = scattered_strokes(100, 64);
+    let device = &context.device;
+
+    unsafe {
+        let (
+            vertex_buffer,
+            vertex_buffer_memory,
+            staging_buffer,
+            staging_buffer_memory,
+            geometry_buffer,
+            geometry_buffer_memory,
+            geometry_index_buffer,
+            geometry_index_buffer_memory,
+        ) = create_buffers(
+            &context.instance,
+            device,
+            context.physical_device,
+            context.graphics_queue,
+            context.command_pool,
+            config.vulkan.max_vertices,
+            config.vulkan.staging_buffer_vertex_count,
+            context.vertex_format,
+        )
+        .unwrap();
+        let _buffers = guard(
+            device,
+            vec![
+                (vertex_buffer, vertex_buffer_memory),
+                (staging_buffer, staging_buffer_memory),
+                (geometry_buffer, geometry_buffer_memory),
+                (geometry_index_buffer, geometry_index_buffer_memory),
+            ],
+        );
+        let staging_ptr = device
+            .map_memory(
+                staging_buffer_memory,
+                0,
+                vk::WHOLE_SIZE,
+                vk::MemoryMapFlags::empty(),
+            )
+            .unwrap()
+            .cast();
+        let mut uploads = guard(device, LineUploads::create(&context, 1, 64).unwrap());
+
+        let mut group = c.benchmark_group("upload/strokes");
+        group.bench_function("one_by_one", |b| {
+            b.iter(|| {
+                let mut first_line = 0;
+                for lines in &strokes {
+                    upload_lines(
+                        device,
+                        context.graphics_queue,
+                        context.command_pool,
+                        staging_buffer,
+                        staging_ptr,
+                        config.vulkan.staging_buffer_vertex_count as usize,
+                        context.vertex_format,
+                        vertex_buffer,
+                        first_line,
+                        lines,
+                    )
+                    .unwrap();
+                    first_line += lines.len();
+                }
+            })
+        });
+        group.bench_function("queued", |b| {
+            b.iter(|| {
+                let mut first_line = 0;
+                for lines in &strokes {
+                    uploads.queue(vertex_buffer, first_line, lines);
+                    first_line += lines.len();
+                }
+                uploads.flush(&context).unwrap();
+            })
+        });
+        group.finish();
+
+        device.device_wait_idle().unwrap();
+        device.unmap_memory(staging_buffer_memory);
+    }
+}
+
+criterion_group!(
+    benches,
+    segment_generation,
+    simplification,
+    spatial_index,
+    staging_writes,
+    upload_batching
+);
+criterion_main!(benches);
@@ -0,0 +1,41 @@
+#version 450
+
+layout(location = 0) in vec2 frag_uv;
+
+layout(location = 0) out vec4 outColor;
+
+layout(push_constant) uniform PushConstants {
+    // The canvas background in linear light
+    vec4 color;
+    // Viewport width over height
+    float aspect;
+    // Seconds of animation
+    float time;
+} push;
+
+// Set when the attachment doesn't encode sRGB itself (a UNORM swapchain)
+layout(constant_id = 0) const bool ENCODE_SRGB = false;
+
+vec3 linear_to_srgb(vec3 linear) {
+    bvec3 cutoff = lessThan(linear, vec3(0.0031308));
+    vec3 lower = linear * vec3(12.92);
+    vec3 higher = vec3(1.055) * pow(linear, vec3(1.0 / 2.4)) - vec3(0.055);
+    return mix(higher, lower, cutoff);
+}
+
+void finish(vec3 color) {
+    outColor = vec4(color, push.color.a);
+    if (ENCODE_SRGB) {
+        outColor.rgb = linear_to_srgb(outColor.rgb);
+    }
+}
+
+// The background color lightened along a slowly turning diagonal
+void main() {
+    float angle = push.time * 0.1;
+    vec2 direction = vec2(cos(angle), sin(angle));
+    vec2 p = (frag_uv - 0.5) * vec2(push.aspect, 1.0);
+    float t = clamp(dot(p, direction) + 0.5, 0.0, 1.0);
+    vec3 light = mix(push.color.rgb, vec3(0.2, 0.25, 0.45), 0.5) + 0.05;
+    finish(mix(push.color.rgb, light, t));
+}
@@ -0,0 +1,64 @@
+#version 450
+
+layout(location = 0) in vec2 frag_uv;
+
+layout(location = 0) out vec4 outColor;
+
+layout(push_constant) uniform PushConstants {
+    // The canvas background in linear light
+    vec4 color;
+    // Viewport width over height
+    float aspect;
+    // Seconds of animation
+    float time;
+} push;
+
+// Set when the attachment doesn't encode sRGB itself (a UNORM swapchain)
+layout(constant_id = 0) const bool ENCODE_SRGB = false;
+
+vec3 linear_to_srgb(vec3 linear) {
+    bvec3 cutoff = lessThan(linear, vec3(0.0031308));
+    vec3 lower = linear * vec3(12.92);
+    vec3 higher = vec3(1.055) * pow(linear, vec3(1.0 / 2.4)) - vec3(0.055);
+    return mix(higher, lower, cutoff);
+}
+
+void finish(vec3 color) {
+    outColor = vec4(color, push.color.a);
+    if (ENCODE_SRGB) {
+        outColor.rgb = linear_to_srgb(outColor.rgb);
+    }
+}
+
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(127.1, 311.7))) * 43758.5453);
+}
+
+float value_noise(vec2 p) {
+    vec2 i = floor(p);
+    vec2 f = fract(p);
+    vec2 u = f * f * (3.0 - 2.0 * f);
+    return mix(
+        mix(hash(i), hash(i + vec2(1.0, 0.0)), u.x),
+        mix(hash(i + vec2(0.0, 1.0)), hash(i + vec2(1.0, 1.0)), u.x),
+        u.y);
+}
+
+// Four octaves of drifting value noise
+float fbm(vec2 p) {
+    float sum = 0.0;
+    float amplitude = 0.5;
+    for (int i = 0; i < 4; i++) {
+        sum += value_noise(p) * amplitude;
+        p *= 2.0;
+        amplitude *= 0.5;
+    }
+    return sum;
+}
+
+// Slow clouds a little lighter than the background color
+void main() {
+    vec2 p = frag_uv * vec2(push.aspect, 1.0) * 3.0;
+    float n = fbm(p + vec2(push.time * 0.05, push.time * 0.02));
+    finish(push.color.rgb + vec3(0.06, 0.07, 0.1) * n);
+}
@@ -0,0 +1,82 @@
+#version 450
+
+layout(location = 0) in vec2 frag_uv;
+
+layout(location = 0) out vec4 outColor;
+
+layout(push_constant) uniform PushConstants {
+    // The canvas background in linear light
+    vec4 color;
+    // Viewport width over height
+    float aspect;
+    // Seconds of animation
+    float time;
+} push;
+
+// The frame being drawn, see FrameUniforms in src/vulkan/uniforms.rs
+layout(set = 0, binding = 0) uniform Frame {
+    // Pixels of the frame
+    vec2 resolution;
+    // The pointer in frame pixels from the top left, (-1, -1) before it moved over the canvas
+    vec2 cursor;
+    // Seconds since the app started
+    float time;
+} frame;
+
+// [shader_params.backgrounds.starfield], in name order
+layout(set = 0, binding = 1) uniform Params {
+    vec4 values[16];
+} params;
+
+// The color of the stars, white when left out
+vec3 tint() {
+    vec4 tint = params.values[0];
+    return tint.a > 0.0 ? tint.rgb : vec3(1.0);
+}
+
+// Set when the attachment doesn't encode sRGB itself (a UNORM swapchain)
+layout(constant_id = 0) const bool ENCODE_SRGB = false;
+
+vec3 linear_to_srgb(vec3 linear) {
+    bvec3 cutoff = lessThan(linear, vec3(0.0031308));
+    vec3 lower = linear * vec3(12.92);
+    vec3 higher = vec3(1.055) * pow(linear, vec3(1.0 / 2.4)) - vec3(0.055);
+    return mix(higher, lower, cutoff);
+}
+
+void finish(vec3 color) {
+    outColor = vec4(color, push.color.a);
+    if (ENCODE_SRGB) {
+        outColor.rgb = linear_to_srgb(outColor.rgb);
+    }
+}
+
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(127.1, 311.7))) * 43758.5453);
+}
+
+// One star in some cells of a grid, twinkling at its own pace
+float stars(vec2 p, float size) {
+    vec2 cell = floor(p);
+    float seed = hash(cell);
+    if (seed < 0.8) {
+        return 0.0;
+    }
+    vec2 center = vec2(hash(cell + 17.0), hash(cell + 31.0)) * 0.8 + 0.1;
+    float d = length(fract(p) - center);
+    float twinkle = 0.6 + 0.4 * sin(push.time * (1.0 + seed * 3.0) + seed * 40.0);
+    return smoothstep(size, 0.0, d) * twinkle;
+}
+
+// Two layers of stars drifting sideways at different speeds over the
+// background color, shining brighter around the pointer
+void main() {
+    vec2 p = frag_uv * vec2(push.aspect, 1.0);
+    float light = stars(p * 40.0 + vec2(push.time * 0.3, 0.0), 0.08)
+        + stars(p * 20.0 + vec2(push.time * 0.6, 0.0), 0.06) * 1.5;
+    if (frame.cursor.x >= 0.0) {
+        float reach = frame.resolution.y * 0.2;
+        light *= 1.0 + 2.0 * smoothstep(reach, 0.0, distance(gl_FragCoord.xy, frame.cursor));
+    }
+    finish(push.color.rgb + tint() * light);
+}
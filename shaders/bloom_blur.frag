@@ -0,0 +1,37 @@
+#version 450
+
+layout(location = 0) in vec2 frag_uv;
+
+layout(location = 0) out vec4 outColor;
+
+// Both bindings hold the image to blur
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform PushConstants {
+    // One texel of the source along the blur direction, in UV units
+    vec2 step;
+    // Luminance kept out of the glow, 0 once the bright parts were picked
+    float threshold;
+    float intensity;
+} push;
+
+// A 9-tap Gaussian, weights for offsets 0 to 4
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+vec3 bright(vec3 color) {
+    if (push.threshold <= 0.) {
+        return color;
+    }
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    return color * max(luminance - push.threshold, 0.) / max(luminance, 1e-4);
+}
+
+void main() {
+    vec3 sum = bright(texture(source, frag_uv).rgb) * weights[0];
+    for (int i = 1; i < 5; i++) {
+        vec2 offset = push.step * float(i);
+        sum += bright(texture(source, frag_uv + offset).rgb) * weights[i];
+        sum += bright(texture(source, frag_uv - offset).rgb) * weights[i];
+    }
+    outColor = vec4(sum, 1.0);
+}
@@ -0,0 +1,37 @@
+#version 450
+
+layout(location = 0) in vec2 frag_uv;
+
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform sampler2D scene;
+layout(set = 0, binding = 1) uniform sampler2D glow;
+
+layout(push_constant) uniform PushConstants {
+    vec2 step;
+    float threshold;
+    float intensity;
+} push;
+
+// Set when the attachment doesn't encode sRGB itself (a UNORM swapchain)
+layout(constant_id = 0) const bool ENCODE_SRGB = false;
+
+vec3 linear_to_srgb(vec3 linear) {
+    bvec3 cutoff = lessThan(linear, vec3(0.0031308));
+    vec3 lower = linear * vec3(12.92);
+    vec3 higher = vec3(1.055) * pow(linear, vec3(1.0 / 2.4)) - vec3(0.055);
+    return mix(higher, lower, cutoff);
+}
+
+void main() {
+    vec4 color = texture(scene, frag_uv);
+    vec3 added = texture(glow, frag_uv).rgb * push.intensity;
+    // The glow is light of its own, so it covers transparent backgrounds too
+    float luminance = dot(added, vec3(0.2126, 0.7152, 0.0722));
+    color.a = clamp(color.a + luminance, 0., 1.);
+    color.rgb = min(color.rgb + added, vec3(color.a));
+    if (ENCODE_SRGB && color.a > 0.) {
+        color.rgb = linear_to_srgb(color.rgb / color.a) * color.a;
+    }
+    outColor = color;
+}
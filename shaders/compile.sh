@@ -1,3 +1,14 @@
 /usr/bin/glslc shader.vert -o vert.spv
 /usr/bin/glslc shader.frag -o frag.spv
-
+/usr/bin/glslc ui.vert -o ui_vert.spv
+/usr/bin/glslc ui.frag -o ui_frag.spv
+/usr/bin/glslc neon.frag -o neon_frag.spv
+/usr/bin/glslc pencil.frag -o pencil_frag.spv
+/usr/bin/glslc fullscreen.vert -o fullscreen_vert.spv
+/usr/bin/glslc bloom_blur.frag -o bloom_blur_frag.spv
+/usr/bin/glslc bloom_composite.frag -o bloom_composite_frag.spv
+/usr/bin/glslc background_gradient.frag -o background_gradient_frag.spv
+/usr/bin/glslc background_noise.frag -o background_noise_frag.spv
+/usr/bin/glslc background_starfield.frag -o background_starfield_frag.spv
+/usr/bin/glslc erase_mask.frag -o erase_mask_frag.spv
+/usr/bin/glslc erase_composite.frag -o erase_composite_frag.spv
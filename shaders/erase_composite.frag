@@ -0,0 +1,34 @@
+#version 450
+
+layout(location = 0) in vec2 frag_uv;
+
+layout(location = 0) out vec4 outColor;
+
+// Coverage of the pixel erasers, see EraseLayer in src/vulkan/erase.rs
+layout(set = 0, binding = 0) uniform sampler2D mask;
+
+layout(push_constant) uniform PushConstants {
+    // The canvas background in linear light, premultiplied where the clear color is
+    vec4 color;
+} push;
+
+// Set when the attachment doesn't encode sRGB itself (a UNORM swapchain)
+layout(constant_id = 0) const bool ENCODE_SRGB = false;
+
+vec3 linear_to_srgb(vec3 linear) {
+    bvec3 cutoff = lessThan(linear, vec3(0.0031308));
+    vec3 lower = linear * vec3(12.92);
+    vec3 higher = vec3(1.055) * pow(linear, vec3(1.0 / 2.4)) - vec3(0.055);
+    return mix(higher, lower, cutoff);
+}
+
+void main() {
+    float erased = texture(mask, frag_uv).r;
+    vec3 background = push.color.rgb;
+    if (ENCODE_SRGB) {
+        background = linear_to_srgb(background);
+    }
+    // Blended as ONE, ONE_MINUS_SRC_ALPHA, with the background's own alpha
+    // coming from the blend constants, so the strokes give way to it by `erased`
+    outColor = vec4(background * erased, erased);
+}
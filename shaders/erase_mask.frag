@@ -0,0 +1,42 @@
+#version 450
+
+layout(location = 0) in vec2 local_position;
+layout(location = 1) in vec2 projected_position;
+layout(location = 2) in vec2 instance_position;
+layout(location = 3) in float thickness;
+layout(location = 4) in vec2 direction;
+layout(location = 5) in vec4 color;
+layout(location = 6) flat in uint style;
+
+// How much of the pixel the eraser covers, kept at the highest of all
+// erasers by the pipeline's MAX blend
+layout(location = 0) out vec4 outCoverage;
+
+layout(push_constant) uniform PushConstants {
+    vec4 transform;
+    float min_luminance;
+    float aspect;
+    float time;
+    uint hidden_tags;
+    uint brush;
+} push;
+
+const float aaborder = 0.00445;
+
+float line_segment(in vec2 p, in vec2 a, in vec2 b) {
+    vec2 ba = b - a;
+    vec2 pa = p - a;
+    float h = clamp(dot(pa, ba) / dot(ba, ba), 0., 1.);
+    return length(pa - h * ba);
+}
+
+void main() {
+    vec2 a = instance_position - direction / 2.;
+    vec2 b = instance_position + direction / 2.;
+    float d = line_segment(projected_position, a, b) - thickness;
+    // The same anti-aliased edge as a stroke of this width, see shader.frag
+    float scaled_border = aaborder / push.transform.z;
+    float coverage = 1. - smoothstep(-scaled_border, 0., d);
+    // Fading erasers give the strokes under them back
+    outCoverage = vec4(coverage * color.a);
+}
@@ -0,0 +1,63 @@
+#version 450
+
+// A neon tube: a white-hot core fading out into the stroke color towards the
+// edges. Inputs and push constants are those of shader.frag, and
+// [shader_params.brushes.neon] spread widens the glow (0 leaves it as is).
+
+layout(location = 0) in vec2 local_position;
+layout(location = 1) in vec2 projected_position;
+layout(location = 2) in vec2 instance_position;
+layout(location = 3) in float thickness;
+layout(location = 4) in vec2 direction;
+layout(location = 5) in vec4 color;
+layout(location = 6) flat in uint style;
+
+layout(location = 0) out vec4 outColor;
+
+layout(push_constant) uniform PushConstants {
+    vec4 transform;
+    float min_luminance;
+    float aspect;
+    float time;
+    uint hidden_tags;
+    uint brush;
+} push;
+
+layout(set = 0, binding = 1) uniform Params {
+    vec4 values[16];
+} params;
+
+float spread() {
+    return max(params.values[0].x, 0.);
+}
+
+layout(constant_id = 0) const bool ENCODE_SRGB = false;
+
+vec3 linear_to_srgb(vec3 linear) {
+    bvec3 cutoff = lessThan(linear, vec3(0.0031308));
+    vec3 lower = linear * vec3(12.92);
+    vec3 higher = vec3(1.055) * pow(linear, vec3(1.0 / 2.4)) - vec3(0.055);
+    return mix(higher, lower, cutoff);
+}
+
+float line_segment(in vec2 p, in vec2 a, in vec2 b) {
+    vec2 ba = b - a;
+    vec2 pa = p - a;
+    float h = clamp(dot(pa, ba) / dot(ba, ba), 0., 1.);
+    return length(pa - h * ba);
+}
+
+void main() {
+    vec2 a = instance_position - direction / 2.;
+    vec2 b = instance_position + direction / 2.;
+    // 0 on the centerline, 1 at the edge
+    float d = line_segment(projected_position, a, b) / max(thickness, 1e-6);
+
+    float core = 1. - smoothstep(0.15, 0.35, d);
+    float glow = exp(-4. * d * d / (1. + spread())) * (1. - smoothstep(0.9, 1., d));
+    vec3 rgb = mix(color.rgb * 1.5, vec3(1.), core);
+    outColor = vec4(min(rgb, vec3(1.)), color.a * max(core, glow));
+    if (ENCODE_SRGB) {
+        outColor.rgb = linear_to_srgb(outColor.rgb);
+    }
+}
@@ -0,0 +1,69 @@
+#version 450
+
+// A sketchy pencil: grainy, uneven coverage with a ragged edge that stays
+// put on the canvas as the view moves. Inputs and push constants are those
+// of shader.frag.
+
+layout(location = 0) in vec2 local_position;
+layout(location = 1) in vec2 projected_position;
+layout(location = 2) in vec2 instance_position;
+layout(location = 3) in float thickness;
+layout(location = 4) in vec2 direction;
+layout(location = 5) in vec4 color;
+layout(location = 6) flat in uint style;
+
+layout(location = 0) out vec4 outColor;
+
+layout(push_constant) uniform PushConstants {
+    vec4 transform;
+    float min_luminance;
+    float aspect;
+    float time;
+    uint hidden_tags;
+    uint brush;
+} push;
+
+layout(constant_id = 0) const bool ENCODE_SRGB = false;
+
+vec3 linear_to_srgb(vec3 linear) {
+    bvec3 cutoff = lessThan(linear, vec3(0.0031308));
+    vec3 lower = linear * vec3(12.92);
+    vec3 higher = vec3(1.055) * pow(linear, vec3(1.0 / 2.4)) - vec3(0.055);
+    return mix(higher, lower, cutoff);
+}
+
+float line_segment(in vec2 p, in vec2 a, in vec2 b) {
+    vec2 ba = b - a;
+    vec2 pa = p - a;
+    float h = clamp(dot(pa, ba) / dot(ba, ba), 0., 1.);
+    return length(pa - h * ba);
+}
+
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(127.1, 311.7))) * 43758.5453);
+}
+
+// Value noise with cells one unit across
+float noise(vec2 p) {
+    vec2 cell = floor(p);
+    vec2 f = fract(p);
+    vec2 u = f * f * (3. - 2. * f);
+    return mix(mix(hash(cell), hash(cell + vec2(1., 0.)), u.x),
+               mix(hash(cell + vec2(0., 1.)), hash(cell + vec2(1., 1.)), u.x), u.y);
+}
+
+void main() {
+    vec2 a = instance_position - direction / 2.;
+    vec2 b = instance_position + direction / 2.;
+    float width = max(thickness, 1e-6);
+    float d = line_segment(projected_position, a, b) / width;
+
+    // Graphite grain a few times finer than the stroke, and a wobbling edge
+    float grain = noise(projected_position / width * 3.);
+    float edge = 0.75 + 0.25 * noise(projected_position / width * 0.7);
+    float coverage = (1. - smoothstep(edge - 0.15, edge, d)) * mix(0.35, 1., grain);
+    outColor = vec4(color.rgb, color.a * coverage);
+    if (ENCODE_SRGB) {
+        outColor.rgb = linear_to_srgb(outColor.rgb);
+    }
+}
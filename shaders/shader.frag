@@ -5,15 +5,51 @@ layout(location = 1) in vec2 projected_position;
 layout(location = 2) in vec2 instance_position;
 layout(location = 3) in float thickness;
 layout(location = 4) in vec2 direction;
+layout(location = 5) in vec4 color;
+layout(location = 6) flat in uint style;
 
 layout(location = 0) out vec4 outColor;
 
 layout(push_constant) uniform PushConstants {
-    vec3 transform;
+    vec4 transform;
+    float min_luminance;
+    float aspect;
+    float time;
+    uint hidden_tags;
+    uint brush;
 } push;
 
+// The frame being drawn, see FrameUniforms in src/vulkan/uniforms.rs
+layout(set = 0, binding = 0) uniform Frame {
+    // Pixels of the frame
+    vec2 resolution;
+    // The pointer in frame pixels from the top left, (-1, -1) before it moved over the canvas
+    vec2 cursor;
+    // Seconds since the app started
+    float time;
+} frame;
+
+// This shader's [shader_params] in name order, numbers in x and colors as
+// linear RGBA, zero when left out; see ParamBlock in src/vulkan/uniforms.rs
+layout(set = 0, binding = 1) uniform Params {
+    vec4 values[16];
+} params;
+
 const float aaborder = 0.00445;
 
+// Set when the attachment doesn't encode sRGB itself (a UNORM swapchain)
+layout(constant_id = 0) const bool ENCODE_SRGB = false;
+
+vec3 linear_to_srgb(vec3 linear) {
+    bvec3 cutoff = lessThan(linear, vec3(0.0031308));
+    vec3 lower = linear * vec3(12.92);
+    vec3 higher = vec3(1.055) * pow(linear, vec3(1.0 / 2.4)) - vec3(0.055);
+    return mix(higher, lower, cutoff);
+}
+
+// Line::style bits, see LINE_STYLE_* in types.rs
+const uint STYLE_DASHED = 1u;
+
 float line_segment(in vec2 p, in vec2 a, in vec2 b) {
     vec2 ba = b - a;
     vec2 pa = p - a;
@@ -25,6 +61,13 @@ void main() {
     vec2 a = instance_position - direction / 2.;
     vec2 b = instance_position + direction / 2.;
     float d = line_segment(projected_position, a, b) - thickness;
+    // Dashes and gaps three half widths long, measured from the segment's start
+    if ((style & STYLE_DASHED) != 0u && thickness > 0.) {
+        float along = dot(projected_position - a, normalize(direction));
+        if (mod(along, 6. * thickness) > 3. * thickness) {
+            d = 1.;
+        }
+    }
     // Use scale component (z) for anti-aliasing border
     float scaled_border = aaborder / push.transform.z;
     float edge1 = -scaled_border;
@@ -36,9 +79,11 @@ void main() {
         if (d > edge1) {
             alpha = 1. - smoothstep(edge1, edge2, d);
         }
-        vec4 color = vec4(1, 1, 1, alpha);
-        outColor = color;
+        outColor = vec4(color.rgb, color.a * alpha);
     } else {
-        outColor = vec4(1, 1, 1, 0.0);
+        outColor = vec4(color.rgb, 0.0);
+    }
+    if (ENCODE_SRGB) {
+        outColor.rgb = linear_to_srgb(outColor.rgb);
     }
 }
@@ -3,32 +3,95 @@
 layout(location = 0) in vec2 pos;
 layout(location = 1) in vec2 dir;
 layout(location = 2) in vec2 inst_pos;
+layout(location = 3) in vec4 inst_color;
+layout(location = 4) in vec2 inst_fade;
+layout(location = 5) in uint inst_tag;
+layout(location = 6) in float inst_width;
+layout(location = 7) in float inst_pressure;
+layout(location = 8) in uint inst_style;
+layout(location = 9) in vec2 inst_tilt;
 
 layout(location = 0) out vec2 local_position;
 layout(location = 1) out vec2 projected_position;
 layout(location = 2) out vec2 instance_position;
 layout(location = 3) out float thickness;
 layout(location = 4) out vec2 direction;
+layout(location = 5) out vec4 color;
+layout(location = 6) flat out uint style;
 
-const float THICKNESS = 0.004;
-
+// transform = (offset_x, offset_y, scale, stroke half width), aspect = viewport width / height,
+// time = seconds since the app started, hidden_tags = tag bits not to draw,
+// brush = which brush's fragment shader the pipeline has
 layout(push_constant) uniform PushConstants {
-    vec3 transform;
+    vec4 transform;
+    float min_luminance;
+    float aspect;
+    float time;
+    uint hidden_tags;
+    uint brush;
 } push;
 
+// Line::style bits from here up are the brush, see LINE_STYLE_BRUSH_SHIFT in types.rs
+const uint STYLE_BRUSH_SHIFT = 8u;
+
+// Stroke colors are sRGB, but blending happens on linear colors
+vec3 srgb_to_linear(vec3 srgb) {
+    bvec3 cutoff = lessThan(srgb, vec3(0.04045));
+    vec3 lower = srgb / vec3(12.92);
+    vec3 higher = pow((srgb + vec3(0.055)) / vec3(1.055), vec3(2.4));
+    return mix(higher, lower, cutoff);
+}
+
+// Mixes dark colors toward white until they reach `min_luminance` (high-contrast mode)
+vec3 lift_luminance(vec3 linear) {
+    float luminance = dot(linear, vec3(0.2126, 0.7152, 0.0722));
+    if (luminance >= push.min_luminance) {
+        return linear;
+    }
+    return mix(linear, vec3(1.0), (push.min_luminance - luminance) / (1.0 - luminance));
+}
+
 void main() {
+    // Segments with a hidden tag, or left to another brush's pipeline,
+    // collapse to a point outside the viewport
+    if ((inst_tag & push.hidden_tags) != 0u || (inst_style >> STYLE_BRUSH_SHIFT) != push.brush) {
+        gl_Position = vec4(-2.0, -2.0, 0.0, 1.0);
+        return;
+    }
+
+    // inst_width = own half width, 0 for the push constant's; pressure scales either
+    float half_width = (inst_width > 0.0 ? inst_width : push.transform.w)
+        * clamp(inst_pressure, 0.0, 1.0);
+
     vec2 n = vec2(-dir.y, dir.x) / length(dir);
-    vec2 apos = pos.y * dir + pos.x * n * THICKNESS;
+
+    // inst_tilt stretches the round footprint along its direction by its
+    // length, so the segment is as wide as that ellipse is across it
+    float stretch = length(inst_tilt);
+    if (stretch > 0.0) {
+        float across = dot(n, inst_tilt / stretch);
+        half_width *= sqrt(1.0 + ((1.0 + stretch) * (1.0 + stretch) - 1.0) * across * across);
+    }
+
+    vec2 apos = pos.y * dir + pos.x * n * half_width;
     vec2 world_pos = apos + inst_pos;
 
-    // Apply transform: push.transform = (offset_x, offset_y, scale)
+    // Apply transform: push.transform = (offset_x, offset_y, scale). Canvas
+    // units are square, so x is squeezed by the aspect ratio to land in NDC.
     vec2 scaled_pos = world_pos * push.transform.z;
-    vec2 final_pos = scaled_pos + push.transform.xy;
+    vec2 final_pos = vec2(scaled_pos.x / push.aspect, scaled_pos.y) + push.transform.xy;
     gl_Position = vec4(final_pos, 0.0, 1.0);
 
     local_position = pos;
     projected_position = vec2(world_pos.x, world_pos.y);
     instance_position = inst_pos;
     direction = dir;
-    thickness = THICKNESS;
+    thickness = half_width;
+    style = inst_style;
+    // inst_fade = (fade start, fade end) in push.time; equal times never fade
+    float fade = 1.0;
+    if (inst_fade.y > inst_fade.x) {
+        fade = clamp((inst_fade.y - push.time) / (inst_fade.y - inst_fade.x), 0.0, 1.0);
+    }
+    color = vec4(lift_luminance(srgb_to_linear(inst_color.rgb)), inst_color.a * fade);
 }
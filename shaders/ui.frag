@@ -0,0 +1,26 @@
+#version 450
+
+layout(location = 0) in vec4 frag_color;
+layout(location = 1) in vec2 frag_uv;
+
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform sampler2D ui_texture;
+
+// Set when the attachment doesn't encode sRGB itself (a UNORM swapchain)
+layout(constant_id = 0) const bool ENCODE_SRGB = false;
+
+vec3 linear_to_srgb(vec3 linear) {
+    bvec3 cutoff = lessThan(linear, vec3(0.0031308));
+    vec3 lower = linear * vec3(12.92);
+    vec3 higher = vec3(1.055) * pow(linear, vec3(1.0 / 2.4)) - vec3(0.055);
+    return mix(higher, lower, cutoff);
+}
+
+void main() {
+    // Colors are premultiplied by alpha
+    outColor = frag_color * texture(ui_texture, frag_uv);
+    if (ENCODE_SRGB && outColor.a > 0.) {
+        outColor.rgb = linear_to_srgb(outColor.rgb / outColor.a) * outColor.a;
+    }
+}
@@ -0,0 +1,28 @@
+#version 450
+
+layout(location = 0) in vec2 pos;
+layout(location = 1) in vec2 uv;
+layout(location = 2) in vec4 color;
+
+layout(location = 0) out vec4 frag_color;
+layout(location = 1) out vec2 frag_uv;
+
+layout(push_constant) uniform PushConstants {
+    vec2 screen_size;
+} push;
+
+// egui vertex colors are sRGB, but blending happens on linear colors
+vec3 srgb_to_linear(vec3 srgb) {
+    bvec3 cutoff = lessThan(srgb, vec3(0.04045));
+    vec3 lower = srgb / vec3(12.92);
+    vec3 higher = pow((srgb + vec3(0.055)) / vec3(1.055), vec3(2.4));
+    return mix(higher, lower, cutoff);
+}
+
+void main() {
+    // egui positions are in points with the origin at the top left
+    gl_Position = vec4(2.0 * pos / push.screen_size - 1.0, 0.0, 1.0);
+
+    frag_color = vec4(srgb_to_linear(color.rgb), color.a);
+    frag_uv = uv;
+}
@@ -1,46 +1,243 @@
-use anyhow::Result;
-use cgmath::AbsDiffEq;
-use std::time::Instant;
+use anyhow::{anyhow, Context, Result};
+use cgmath::{AbsDiffEq, InnerSpace};
+use egui_winit::EventResponse;
+use log::*;
+use std::f32::consts::PI;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::info_span;
 use vulkanalia::prelude::v1_0::*;
+use winit::dpi::PhysicalPosition;
+use winit::event::{MouseButton, WindowEvent};
+use winit::keyboard::{KeyCode, ModifiersState};
 use winit::window::Window;
 
-use crate::config::Config;
-use crate::types::{Line, Vec2};
-use crate::vulkan::buffer::{copy_buffer, create_buffers};
+use crate::camera::Camera;
+use crate::config::{
+    BrushConfig, Config, MonitorChoice, SavedSettings, Theme, VulkanConfig, WindowConfig,
+    MAX_FADE_SECONDS,
+};
+use crate::crash;
+use crate::document::{Document, Stroke};
+use crate::edit::EditCommand;
+use crate::export::{write_tiled_png, ExportView};
+use crate::history::{History, Revision};
+use crate::input::MotionPredictor;
+use crate::keybindings::{Action, ButtonBinding, KeyBindings};
+use crate::lod::{simplify, StrokeLod, LOD_MAX_ZOOM};
+use crate::logging;
+use crate::output::VideoOutput;
+use crate::paths;
+use crate::scripting::{ScriptCommand, ScriptEngine};
+use crate::spatial::StrokeIndex;
+use crate::stats::{FrameRecord, FrameStats};
+use crate::strokes::{StoredStroke, StrokeBuilder, StrokeId, StrokeStore};
+use crate::tools::select::PICK_RADIUS;
+use crate::tools::{Measure, ToolRegistry};
+use crate::types::{
+    Color, Line, Rect, StrokeStyle, Transform, Vec2, PIXEL_ERASER_BRUSH, POINT_EPSILON,
+    STROKE_THICKNESS,
+};
+use crate::ui::{HistoryEntry, Measurement, UiAction, UiLayer, UiState, MINIMAP_SIZE};
+use crate::vulkan::buffer::{create_buffer, create_buffers};
 use crate::vulkan::context::VulkanContext;
+use crate::vulkan::memory::{heap_usage, HeapUsage, MIB};
+use crate::vulkan::offscreen::OffscreenRenderer;
+use crate::vulkan::owned::{guard, Destroy};
 use crate::vulkan::renderer::Renderer;
+use crate::vulkan::watchdog::Watchdog;
+
+/// Extra canvas space around a segment covered by its quad (thickness plus AA border)
+const STROKE_MARGIN: f32 = 0.01;
+
+/// Relative luminance strokes are lifted to in high-contrast mode, a 7:1 contrast ratio against black
+const HIGH_CONTRAST_LUMINANCE: f32 = 0.3;
+
+/// How close (in canvas units) the eraser has to come to a stroke to remove it
+const ERASER_RADIUS: f32 = 0.02;
+
+/// Weight of the newest frame in the smoothed frame time shown by the stats overlay
+const FRAME_TIME_SMOOTHING: f32 = 0.1;
+
+/// How often GPU memory usage is queried for the stats overlay and low-memory warnings
+const MEMORY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How far (in canvas units, along each axis) duplicated strokes land from the originals
+const DUPLICATE_OFFSET: f32 = 0.03;
+
+/// Seconds a fading stroke takes to go from fully visible to gone
+const FADE_OUT_SECONDS: f32 = 1.0;
+
+/// How many tags strokes can be filed under, one per bit of [`Line::tag`]
+const MAX_TAGS: usize = u32::BITS as usize;
+
+/// Radius of the arc marking a measured angle, as a fraction of its shorter ray
+const ANGLE_ARC_RADIUS: f32 = 0.3;
+
+/// Segments the arc marking a measured angle is drawn with
+const ANGLE_ARC_SEGMENTS: usize = 24;
+
+/// Zoom factor per scroll wheel line
+const ZOOM_STEP: f32 = 1.1;
 
 /// The main Vulkan application
+///
+/// Every change to the strokes is an [`EditCommand`] passed to [`App::apply`].
+/// All methods creating or using Vulkan objects are `unsafe`; dropping the
+/// app destroys them.
 pub struct App {
-    context: VulkanContext,
     renderer: Renderer,
 
     // Scene resources (immutable for app lifetime)
-    line_start: Option<Vec2>,
-    lines: Vec<Vec<Line>>,
-    new_lines: Vec<Line>,
+    strokes: StrokeStore,
+    new_stroke: StrokeBuilder,
+    /// The last piece committed of an in-progress stroke longer than the
+    /// staging buffer, which the next piece joins in the undo history
+    stroke_piece: Option<StrokeId>,
+    /// Recent points of the in-progress stroke, to draw its tip ahead of the pointer
+    predictor: MotionPredictor,
+    /// The predicted tip drawn last frame, to erase it when it moves
+    predicted: Option<Line>,
+    history: History,
+    /// Bounds of the strokes in `strokes`, for finding the ones near a point or in view
+    index: StrokeIndex,
+    /// Bumped whenever the committed strokes or the buffers they are drawn from change
+    stroke_generation: u64,
+    /// Whether a committed stroke erased pixels as of the stroke generation
+    /// in `.0`, so the strokes are only searched after they change
+    pixel_erasers: (u64, bool),
+    /// The selected strokes, which stay selected while other strokes come and go
+    selection: Vec<StrokeId>,
+    /// The selected strokes as they were before the transform being dragged, and that transform so far
+    transforming: Option<(Vec<(StrokeId, Vec<Line>)>, Transform)>,
+    /// The canvas bounds of the last [`EditCommand::Crop`] and the canvas
+    /// size in pixels they were given, which replaces `[window] canvas_size`
+    crop: Option<(Rect, [u32; 2])>,
+    /// Strokes copied with [`Action::Copy`], in canvas coordinates
+    clipboard: Vec<Stroke>,
+    /// The system clipboard, opened on first use and kept open since on X11
+    /// what was copied is only available while its owner is alive
+    system_clipboard: Option<arboard::Clipboard>,
+    recording: bool,
+    recorded: Vec<EditCommand>,
+    /// Spectating: only edits from the peer change the canvas
+    read_only: bool,
+    /// Applying a peer's edit, which stays out of the undo history
+    remote: bool,
 
     vertex_buffer: vk::Buffer,
     vertex_buffer_memory: vk::DeviceMemory,
     staging_buffer: vk::Buffer,
     staging_buffer_memory: vk::DeviceMemory,
-    staging_buffer_ptr: *mut Line,
+    staging_buffer_ptr: *mut u8,
     geometry_buffer: vk::Buffer,
     geometry_buffer_memory: vk::DeviceMemory,
     geometry_index_buffer: vk::Buffer,
     geometry_index_buffer_memory: vk::DeviceMemory,
 
+    // Strokes that fade out on their own, kept apart from `strokes` so they
+    // never shift stroke indices or show up in the undo history
+    /// Seconds strokes drawn from now on stay before fading, `None` for permanent strokes
+    fade_after: Option<f32>,
+    /// Fading strokes, oldest first, with their fade times in every segment
+    fading: Vec<Vec<Line>>,
+    fade_buffer: vk::Buffer,
+    fade_buffer_memory: vk::DeviceMemory,
+
+    // Tags strokes are filed under, to show or hide them together
+    /// Tag names, each owning the bit of its index in [`Line::tag`]
+    tags: Vec<String>,
+    /// Bits of the tags whose strokes are hidden
+    hidden_tags: u32,
+    /// Whether every tag is shown for now, without forgetting which are hidden
+    tags_revealed: bool,
+    /// Tag strokes drawn from now on are filed under
+    brush_tag: Option<String>,
+    /// Index of the `[shaders]` fragment shader strokes are drawn with from
+    /// now on, see [`ShaderConfig::brush_index`](crate::config::ShaderConfig::brush_index)
+    brush_shader: u32,
+    /// Whether the animated `[background] shader` is drawn, `toggle_background` switches it
+    show_background: bool,
+
+    // Simplified strokes for far zoomed out views
+    /// Levels of detail per stroke, built when first needed after an edit
+    lods: Option<Vec<StrokeLod>>,
+    /// Level of each stroke in `lod_buffer`, `None` while full detail is drawn
+    lod_levels: Option<Vec<usize>>,
+    lod_line_count: u32,
+    lod_buffer: vk::Buffer,
+    lod_buffer_memory: vk::DeviceMemory,
+
+    // Input
+    tools: ToolRegistry,
+    scripts: ScriptEngine,
+    bindings: KeyBindings,
+    brush_color: Color,
+    /// Whether Shift is held, flipping `[brush] angle_snap`
+    snap_held: bool,
+    palette: Vec<Color>,
+    /// Name of the `[theme]` entry in use and its colors
+    theme_name: String,
+    theme: Theme,
+
+    // View
+    camera: Camera,
+    /// Last pointer position in canvas coordinates
+    cursor: Option<Vec2>,
+    /// Screen position (in NDC) the view was last dragged to, while panning
+    pan_from: Option<Vec2>,
+    /// The tool to switch back to once the button holding another one is released
+    held_tool: Option<usize>,
+    /// Key held for [`Action::HoldToPan`], while which the left button pans
+    pan_key: Option<KeyCode>,
+    /// Tilt reported by a tablet integration, replacing the `[brush]` nib
+    pen_tilt: Option<Vec2>,
+    /// Bright, wide strokes and a large crosshair for projectors and low vision
+    high_contrast: bool,
+    /// Window pixels per logical pixel, 2 on a display scaled to 200%
+    scale_factor: f64,
+
+    // Overlay UI and the region it covered in the last frame
+    ui: UiLayer,
+    ui_bounds: Option<Rect>,
+    /// Renders the minimap, created when it is first shown
+    minimap: Option<OffscreenRenderer>,
+    /// Stroke generation, hidden tags and background the minimap was last rendered with
+    minimap_drawn: Option<(u64, u32, Color)>,
+
     // App state
+    /// Set when the window was resized so the swapchain is recreated on the next frame
     pub resized: bool,
     start: Instant,
+    /// Smoothed CPU time spent in `render`
+    frame_time: Duration,
+    /// Drawing saved by a panic in an earlier run, until restoring or discarding it
+    recovered: Option<PathBuf>,
+    /// Timings of the latest frames, for [`Action::SaveFrameStats`]
+    frame_stats: FrameStats,
+    /// Usage of each GPU memory heap as of `memory_polled`
+    memory: Vec<HeapUsage>,
+    memory_polled: Option<Instant>,
+    /// Where the canvas is published as a video source, if `[output]` is enabled
+    video_output: Option<VideoOutput>,
     config: Config,
+    /// The settings [`App::save_settings`] may write, as they were loaded
+    loaded_settings: SavedSettings,
+
+    // Fields drop in order, so the context goes last, after the renderer and `App::drop`
+    context: VulkanContext,
 }
 
 impl App {
-    /// Creates our Vulkan app
+    /// Creates our Vulkan app using `config.toml` (or the embedded defaults)
     pub unsafe fn create(window: &Window) -> Result<Self> {
-        let config = Config::load()?;
+        Self::create_with_config(window, Config::load()?)
+    }
 
+    /// Creates our Vulkan app rendering into `window` with an explicit configuration
+    pub unsafe fn create_with_config(window: &Window, config: Config) -> Result<Self> {
         // Create core Vulkan context
         let context = VulkanContext::create(window, &config)?;
 
@@ -62,29 +259,107 @@ impl App {
             context.command_pool,
             config.vulkan.max_vertices,
             config.vulkan.staging_buffer_vertex_count,
+            context.vertex_format,
         )?;
 
+        // Until `App` owns them, these are destroyed if anything below fails
+        let device = &context.device;
+        let vertex = guard(device, (vertex_buffer, vertex_buffer_memory));
+        let staging = guard(device, (staging_buffer, staging_buffer_memory));
+        let geometry = guard(device, (geometry_buffer, geometry_buffer_memory));
+        let geometry_index = guard(
+            device,
+            (geometry_index_buffer, geometry_index_buffer_memory),
+        );
+
+        // Same size as the vertex buffer, since simplified strokes never have more segments
+        let lod = guard(
+            device,
+            create_buffer(
+                &context.instance,
+                device,
+                context.physical_device,
+                (Line::stride(context.vertex_format) * config.vulkan.max_vertices as usize) as u64,
+                vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?,
+        );
+
+        let fade = guard(
+            device,
+            create_buffer(
+                &context.instance,
+                device,
+                context.physical_device,
+                (Line::stride(context.vertex_format) * config.vulkan.fade_vertex_count as usize)
+                    as u64,
+                vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?,
+        );
+
         // Persistently map staging buffer for efficient updates
-        let staging_buffer_ptr = context.device.map_memory(
+        let staging_buffer_ptr = device.map_memory(
             staging_buffer_memory,
             0,
             vk::WHOLE_SIZE,
             vk::MemoryMapFlags::empty(),
-        )? as *mut Line;
+        )? as *mut u8;
 
         // Create renderer
         let renderer = Renderer::create(window, &context, &config)?;
 
-        let lines = vec![vec![]];
-        let new_lines = vec![];
+        let limits = context
+            .instance
+            .get_physical_device_properties(context.physical_device)
+            .limits;
+        let ui = UiLayer::new(window, limits.max_image_dimension_2d as usize);
+
+        let theme = config.theme.active();
+        let extent = renderer.canvas_extent;
+        let camera = Camera::default().with_viewport(extent.width, extent.height);
+        // Streaming is optional, so a missing NDI runtime only costs the output
+        let mut video_output = None;
+        if config.output.enabled {
+            match VideoOutput::create(&config) {
+                Ok(output) => video_output = Some(output),
+                Err(e) => error!("Not publishing the canvas: {:#}", e),
+            }
+        }
+        let mut tools = ToolRegistry::default();
+        if let Some(tool) = tools.active() {
+            // Nothing is being drawn yet, so there is no preview to update
+            tool.angle_snap_changed(angle_snap_step(&config.brush, false));
+        }
+        let (lod_buffer, lod_buffer_memory) = lod.release();
+        let (fade_buffer, fade_buffer_memory) = fade.release();
+        vertex.release();
+        staging.release();
+        geometry.release();
+        geometry_index.release();
 
         // Copy lines to staging buffer
         Ok(Self {
             context,
             renderer,
-            line_start: None,
-            lines,
-            new_lines,
+            strokes: StrokeStore::default(),
+            new_stroke: StrokeBuilder::new(config.vulkan.staging_buffer_vertex_count as usize),
+            stroke_piece: None,
+            predictor: MotionPredictor::default(),
+            predicted: None,
+            history: History::new(config.history.budget()),
+            index: StrokeIndex::default(),
+            stroke_generation: 0,
+            pixel_erasers: (u64::MAX, false),
+            selection: Vec::new(),
+            transforming: None,
+            crop: None,
+            clipboard: Vec::new(),
+            system_clipboard: None,
+            recording: false,
+            read_only: false,
+            remote: false,
+            recorded: Vec::new(),
             vertex_buffer,
             vertex_buffer_memory,
             staging_buffer,
@@ -94,179 +369,2772 @@ impl App {
             geometry_buffer_memory,
             geometry_index_buffer,
             geometry_index_buffer_memory,
+            lods: None,
+            lod_levels: None,
+            lod_line_count: 0,
+            lod_buffer,
+            lod_buffer_memory,
+            fade_after: None,
+            fading: Vec::new(),
+            fade_buffer,
+            fade_buffer_memory,
+            tags: Vec::new(),
+            hidden_tags: 0,
+            tags_revealed: false,
+            brush_tag: None,
+            brush_shader: brush_index(&config),
+            show_background: true,
+            tools,
+            scripts: ScriptEngine::new(&config.scripts),
+            bindings: KeyBindings::new(&config.keybindings),
+            brush_color: config.brush.color,
+            snap_held: false,
+            palette: theme.palette.clone(),
+            theme_name: config.theme.active.clone(),
+            theme,
+            camera,
+            cursor: None,
+            pan_from: None,
+            held_tool: None,
+            pan_key: None,
+            pen_tilt: None,
+            high_contrast: config.accessibility.high_contrast,
+            scale_factor: window.scale_factor(),
+            ui,
+            ui_bounds: None,
+            minimap: None,
+            minimap_drawn: None,
             resized: false,
             start: Instant::now(),
+            frame_time: Duration::ZERO,
+            frame_stats: FrameStats::new(config.stats.frames),
+            recovered: paths::emergency_file().filter(|path| path.exists()),
+            memory: Vec::new(),
+            memory_polled: None,
+            video_output,
+            loaded_settings: config.saved_settings(),
             config,
         })
     }
 
     /// Renders a frame for our Vulkan app
     pub unsafe fn render(&mut self, window: &Window) -> Result<()> {
-        let new_line_count = if !self.new_lines.is_empty() {
-            let lines_to_copy = self
-                .new_lines
-                .len()
-                .min(self.config.vulkan.staging_buffer_vertex_count as usize);
-            std::ptr::copy_nonoverlapping(
-                self.new_lines.as_ptr(),
+        let _frame = info_span!("frame").entered();
+        let frame_start = Instant::now();
+        let extent_before = self.renderer.canvas_extent;
+
+        self.poll_memory();
+        self.prune_faded();
+        let style = self.stroke_style();
+        let marquee = self.tools.active().and_then(|t| t.marquee());
+        let handles = self.tools.active().map(|t| t.handles()).unwrap_or_default();
+        let measurement = self.tools.active().and_then(|t| t.measurement());
+        if self.ui.show_minimap {
+            self.refresh_minimap()?;
+        }
+        let crosshair = self
+            .cursor
+            .filter(|_| self.high_contrast)
+            .map(|c| self.camera.to_screen(c));
+        let state = UiState {
+            tools: self.tools.names().collect(),
+            active_tool: self.tools.active_index(),
+            color: self.brush_color,
+            palette: &self.palette,
+            thickness: style.thickness * self.camera.zoom,
+            selection: self
+                .selection
+                .iter()
+                .filter_map(|&id| self.strokes.by_id(id)?.bounds_with(self.stroke_margin()))
+                .map(|bounds| self.camera.rect_to_screen(&bounds))
+                .collect(),
+            marquee: marquee.map(|m| self.camera.rect_to_screen(&m)),
+            handles: handles.iter().map(|&h| self.camera.to_screen(h)).collect(),
+            measurement: measurement.map(|m| self.measurement(&m)),
+            cursor: self.cursor,
+            zoom: self.camera.zoom,
+            visible: self.camera.visible_rect(),
+            fade_after: self.fade_after,
+            history: if self.ui.show_history {
+                self.history()
+            } else {
+                Vec::new()
+            },
+            history_applied: self.history.undo_len(),
+            tags: self
+                .tags
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (name.as_str(), self.hidden_tags & (1 << i) == 0))
+                .collect(),
+            brush_tag: self.brush_tag.as_deref(),
+            brush_shader: self.config.shaders.brush_name(self.brush_shader),
+            tags_revealed: self.tags_revealed,
+            read_only: self.read_only,
+            stroke_count: self.strokes.len(),
+            segment_count: self.line_count(),
+            pending_segment_count: self.new_stroke.len(),
+            max_vertices: self.config.vulkan.max_vertices as usize,
+            frame_time: self.frame_time,
+            gpu_time: self.renderer.gpu_time(),
+            memory: &self.memory,
+            shortcuts: self.bindings.shortcuts(),
+            can_paste: !self.clipboard.is_empty(),
+            high_contrast: self.high_contrast,
+            crosshair,
+            crosshair_size: self.config.accessibility.crosshair_size,
+            themes: self
+                .config
+                .theme
+                .themes
+                .keys()
+                .map(String::as_str)
+                .collect(),
+            theme_name: &self.theme_name,
+            theme: &self.theme,
+            // Kept for a later run, since a spectator's canvas can't take it
+            recovered: self.recovered.is_some() && !self.read_only,
+        };
+        let ui_span = info_span!("ui").entered();
+        let ui = self.ui.run(window, self.frame_rect(), &state);
+        drop(ui_span);
+        self.renderer
+            .ui
+            .set_textures(&self.context, &ui.textures_delta.set)?;
+        // egui's points map to frame pixels, which a resolution scale multiplies
+        let pixels_per_point = ui.pixels_per_point * self.renderer.resolution_scale();
+        self.renderer
+            .ui
+            .set_primitives(ui.primitives, pixels_per_point);
+
+        // Redraw wherever the UI is now, and wherever it was last frame
+        for bounds in [self.ui_bounds, ui.bounds].into_iter().flatten() {
+            self.renderer.damage(bounds);
+        }
+        self.ui_bounds = ui.bounds;
+
+        let mut batches = self.drawn_lines()?;
+        batches.push((self.fade_buffer, 0..self.fading_line_count() as u32));
+
+        let predicted = self.predicted_tip();
+        for line in [self.predicted, predicted].iter().flatten() {
+            let bounds = Rect::around_line(line, self.stroke_margin());
+            self.renderer.damage(self.camera.rect_to_screen(&bounds));
+        }
+        self.predicted = predicted;
+
+        let new_line_count = if !self.new_stroke.is_empty() {
+            let _upload = info_span!("upload_stroke").entered();
+            let lines_to_copy = self.new_stroke.len().min(self.new_stroke.capacity());
+            Line::write(
+                self.context.vertex_format,
+                &self.new_stroke.lines()[..lines_to_copy],
                 self.staging_buffer_ptr,
-                lines_to_copy,
             );
-            lines_to_copy as u32
+            // The staging buffer keeps room for the tip, since a full stroke is committed
+            match predicted.filter(|_| lines_to_copy < self.new_stroke.capacity()) {
+                Some(tip) => {
+                    Line::write(
+                        self.context.vertex_format,
+                        &[tip],
+                        self.staging_buffer_ptr
+                            .add(lines_to_copy * Line::stride(self.context.vertex_format)),
+                    );
+                    lines_to_copy as u32 + 1
+                }
+                None => lines_to_copy as u32,
+            }
         } else {
             0
         };
+        batches.push((self.staging_buffer, 0..new_line_count));
 
-        let line_count = self.lines.iter().map(|v| v.len()).sum::<usize>() as u32;
-
-        let needs_recreate = self.renderer.render(
+        let pixel_erasing = self.has_pixel_erasers();
+        self.renderer.render(
             window,
             &self.context,
             &self.config,
             self.geometry_buffer,
-            self.vertex_buffer,
-            self.staging_buffer,
             self.geometry_index_buffer,
             self.start,
-            line_count,
-            new_line_count,
+            self.clear_color(),
+            self.background_time(),
+            self.cursor.map(|c| self.camera.to_screen(c)),
+            &self.camera,
+            &style,
+            &batches,
+            pixel_erasing,
+            // The stroke being drawn changes every frame without an edit
+            self.new_stroke.is_empty().then_some(self.stroke_generation),
         )?;
+        self.publish_frame(&style, &batches);
+
+        self.renderer
+            .ui
+            .free_textures(&self.context.device, &ui.textures_delta.free)?;
+
+        for action in ui.actions {
+            match action {
+                UiAction::SelectTool(index) => {
+                    self.select_tool(index)?;
+                }
+                UiAction::SetColor(color) => self.brush_color = color,
+                UiAction::AddSwatch(color) => {
+                    if !self.palette.contains(&color) {
+                        self.palette.push(color);
+                    }
+                }
+                UiAction::Perform(action) => {
+                    self.perform(&action)?;
+                }
+                UiAction::PasteAt(position) => {
+                    self.paste_at(position)?;
+                }
+                UiAction::DeleteStroke(index) => {
+                    self.apply(EditCommand::RemoveStrokes(vec![index]))?
+                }
+                UiAction::RollBack(applied) => self.roll_back(applied)?,
+                UiAction::Recover(restore) => self.recover(restore),
+                UiAction::CenterOn(point) => {
+                    self.set_camera(self.camera.panned(-self.camera.to_screen(point)))
+                }
+                UiAction::SetTheme(name) => self.set_theme(&name),
+                UiAction::SetTagVisible(tag, visible) => self.set_tag_visible(&tag, visible),
+                UiAction::SetBrushTag(tag) => self.set_brush_tag(tag),
+                UiAction::TagSelection(tag) => {
+                    let selection = self.selection();
+                    self.apply(EditCommand::TagStrokes(selection, tag))?
+                }
+            }
+        }
 
         if self.resized {
             self.resized = false;
             self.renderer
                 .recreate_swapchain(window, &self.context, &self.config)?;
         }
+        // Also after an out of date swapchain, e.g. while switching to fullscreen
+        if self.renderer.canvas_extent != extent_before {
+            let extent = self.renderer.canvas_extent;
+            self.camera = self.camera.with_viewport(extent.width, extent.height);
+        }
+
+        self.frame_time = self
+            .frame_time
+            .mul_f32(1.0 - FRAME_TIME_SMOOTHING)
+            .saturating_add(frame_start.elapsed().mul_f32(FRAME_TIME_SMOOTHING));
+        let gpu_time = self.renderer.gpu_time();
+        self.renderer
+            .pacer
+            .frame_rendered(frame_start.elapsed() + gpu_time.unwrap_or_default());
+
+        let waits = self.renderer.waits;
+        let stride = Line::stride(self.context.vertex_format);
+        self.frame_stats.push(FrameRecord {
+            at: frame_start.saturating_duration_since(self.start),
+            cpu: frame_start.elapsed(),
+            gpu: gpu_time,
+            fence: waits.fence,
+            acquire: waits.acquire,
+            present: waits.present,
+            upload_bytes: waits.upload_bytes + new_line_count as usize * stride,
+        });
 
         Ok(())
     }
 
-    pub unsafe fn append_vertex(&mut self, new_vertex: Vec2) -> Result<()> {
-        match self.new_lines.last() {
-            Some(last_element) => {
-                // Calculate the endpoint of the last line (position + dir/2)
-                let last_end_point = last_element.position + last_element.dir / 2.0;
-                // If the points are far enough apart, add a new line
-                if !last_end_point.abs_diff_eq(&new_vertex, 1e-3) {
-                    self.new_lines.push(Line::new(last_end_point, new_vertex));
+    /// When to start the next frame, no earlier than `earliest`
+    ///
+    /// With `[vulkan] low_latency` and a way to tell when frames are shown,
+    /// this is as late as the frame can start and still make the coming
+    /// vblank of a display refreshing every `refresh`; input handled until
+    /// then ends up in it. Otherwise it is `earliest`. May block for up to
+    /// `refresh` while the previous frame is still on its way to the screen.
+    pub unsafe fn frame_start(&mut self, earliest: Instant, refresh: Duration) -> Instant {
+        if !self.config.vulkan.low_latency {
+            return earliest;
+        }
+
+        let swapchain = self.renderer.swapchain;
+        self.renderer
+            .pacer
+            .frame_start(&self.context.device, swapchain, earliest, refresh)
+    }
+
+    /// How the UI draws what the active tool measures
+    ///
+    /// Distances are labeled in canvas units and in pixels at the current
+    /// zoom, angles in degrees from 0 to 180 with an arc between the rays.
+    fn measurement(&self, measure: &Measure) -> Measurement {
+        let screen = |p: Vec2| self.camera.to_screen(p);
+        match *measure {
+            Measure::Distance(from, to) => {
+                let (from_ndc, to_ndc) = (screen(from), screen(to));
+                // NDC spans two units across each side of the canvas
+                let extent = self.renderer.canvas_extent;
+                let pixels = Vec2::new(
+                    (to_ndc.x - from_ndc.x) * extent.width as f32 / 2.0,
+                    (to_ndc.y - from_ndc.y) * extent.height as f32 / 2.0,
+                );
+                Measurement {
+                    lines: vec![(from_ndc, to_ndc)],
+                    label: format!(
+                        "{:.3} ({:.0} px)",
+                        (to - from).magnitude(),
+                        pixels.magnitude()
+                    ),
+                    anchor: (from_ndc + to_ndc) / 2.0,
                 }
             }
-            None => match self.line_start {
-                Some(line_start) => {
-                    if !line_start.abs_diff_eq(&new_vertex, 1e-3) {
-                        self.new_lines.push(Line::new(line_start, new_vertex));
-                    }
+            Measure::Angle {
+                vertex,
+                first,
+                second,
+            } => {
+                let mut lines = vec![(screen(vertex), screen(first))];
+                let mut label = String::new();
+                if let Some(second) = second {
+                    lines.push((screen(vertex), screen(second)));
+                    let (a, b) = (first - vertex, second - vertex);
+                    let start = a.y.atan2(a.x);
+                    // Signed, so the arc sweeps the smaller side
+                    let sweep = (b.y.atan2(b.x) - start + PI).rem_euclid(2.0 * PI) - PI;
+                    let radius = a.magnitude().min(b.magnitude()) * ANGLE_ARC_RADIUS;
+                    let arc: Vec<Vec2> = (0..=ANGLE_ARC_SEGMENTS)
+                        .map(|i| {
+                            let angle = start + sweep * i as f32 / ANGLE_ARC_SEGMENTS as f32;
+                            screen(vertex + Vec2::new(angle.cos(), angle.sin()) * radius)
+                        })
+                        .collect();
+                    lines.extend(arc.windows(2).map(|w| (w[0], w[1])));
+                    label = format!("{:.1}°", sweep.abs().to_degrees());
                 }
-                None => {
-                    self.line_start = Some(new_vertex);
+                Measurement {
+                    lines,
+                    label,
+                    anchor: screen(vertex),
                 }
-            },
+            }
+        }
+    }
+
+    /// Sends what was just drawn, minus the UI, to the video output when one is due
+    ///
+    /// An output that fails is logged and switched off rather than taking the app down.
+    unsafe fn publish_frame(&mut self, style: &StrokeStyle, batches: &[(vk::Buffer, Range<u32>)]) {
+        let extent = self.renderer.canvas_extent;
+        let background = self.background();
+        let Some(output) = &mut self.video_output else {
+            return;
         };
+        if !output.is_due() || extent.width == 0 || extent.height == 0 {
+            return;
+        }
 
-        if self.new_lines.len() >= self.config.vulkan.staging_buffer_vertex_count as usize {
-            self.commit_new_line()?;
+        let result = output.publish(
+            &self.context,
+            &self.config,
+            extent,
+            background,
+            &self.camera,
+            style,
+            (self.geometry_buffer, self.geometry_index_buffer),
+            batches,
+        );
+        if let Err(e) = result {
+            error!("Stopped publishing the canvas: {:#}", e);
+            self.video_output = None;
         }
+    }
 
-        Ok(())
+    /// Refreshes the GPU memory usage every [`MEMORY_POLL_INTERVAL`]
+    ///
+    /// Warns once when a GPU heap gets close to its budget, so running out on
+    /// a small integrated GPU doesn't come as a surprise.
+    fn poll_memory(&mut self) {
+        if self
+            .memory_polled
+            .is_some_and(|t| t.elapsed() < MEMORY_POLL_INTERVAL)
+        {
+            return;
+        }
+
+        let was_full = self
+            .memory
+            .iter()
+            .any(|h| h.device_local && h.is_nearly_full());
+        self.memory = unsafe {
+            heap_usage(
+                &self.context.instance,
+                &self.context.device,
+                self.context.physical_device,
+            )
+        };
+        self.memory_polled = Some(Instant::now());
+
+        let full = self
+            .memory
+            .iter()
+            .find(|h| h.device_local && h.is_nearly_full());
+        if let Some(heap) = full.filter(|_| !was_full) {
+            warn!(
+                "GPU memory is nearly full: {:.0} of {:.0} MiB used, {:.0} MiB by this app.",
+                heap.used() as f64 / MIB,
+                heap.budget as f64 / MIB,
+                heap.allocated as f64 / MIB,
+            );
+        }
     }
 
-    pub unsafe fn commit_new_line(&mut self) -> Result<()> {
-        if self.new_lines.is_empty() {
-            self.line_start = None;
+    /// Applies a canvas edit
+    ///
+    /// This is the only place committed strokes, the in-progress stroke and
+    /// the undo history change, whether the edit came from a tool, a script
+    /// or the event loop.
+    pub unsafe fn apply(&mut self, command: EditCommand) -> Result<()> {
+        if self.read_only {
             return Ok(());
         }
 
-        let new_line_count = if !self.new_lines.is_empty() {
-            let lines_to_copy = self
-                .new_lines
-                .len()
-                .min(self.config.vulkan.staging_buffer_vertex_count as usize);
-            std::ptr::copy_nonoverlapping(
-                self.new_lines.as_ptr(),
-                self.staging_buffer_ptr,
-                lines_to_copy,
+        // A transform being dragged is finished before anything else touches the strokes
+        if !matches!(
+            command,
+            EditCommand::TransformSelection(_) | EditCommand::CommitTransform
+        ) {
+            self.commit_transform();
+        }
+
+        match command {
+            EditCommand::StrokeTo(position) => self.append_vertex(position),
+            EditCommand::SetStroke(points) => self.set_new_line(&points),
+            EditCommand::CommitStroke => self.commit_new_line(),
+            EditCommand::AddStroke(stroke) => self.add_stroke(&stroke),
+            EditCommand::Erase(position) => self.erase(position)?,
+            EditCommand::RemoveStrokes(indices) => self.remove_strokes(&indices)?,
+            EditCommand::Select(area) => self.select(area),
+            EditCommand::TransformSelection(transform) => self.transform_selection(&transform),
+            EditCommand::CommitTransform => self.commit_transform(),
+            EditCommand::TransformStrokes(indices, transform) => {
+                self.transform_strokes(&indices, &transform)
+            }
+            EditCommand::TagStrokes(indices, tag) => self.tag_strokes(&indices, tag.as_deref()),
+            EditCommand::InsertStrokes(strokes) => self.insert_strokes(strokes),
+            EditCommand::ReplaceStrokes(strokes) => self.replace_strokes(strokes),
+            EditCommand::Crop(area) => self.crop(area)?,
+            EditCommand::Clear => self.clear(),
+            EditCommand::ReplaceAll(strokes) => self.replace_all(&strokes),
+            EditCommand::Undo => self.undo()?,
+            EditCommand::Redo => self.redo()?,
+        }
+
+        Ok(())
+    }
+
+    /// Applies `commands` in order, returning whether there were any
+    pub unsafe fn apply_all(&mut self, commands: Vec<EditCommand>) -> Result<bool> {
+        let changed = !commands.is_empty();
+        for command in commands {
+            self.apply(command)?;
+        }
+
+        Ok(changed)
+    }
+
+    /// Applies an edit that came from elsewhere (e.g. a peer) without
+    /// recording it or keeping it in the undo history, so undo only ever
+    /// reverts local edits
+    ///
+    /// A local transform still being dragged is committed (and recorded) first.
+    pub unsafe fn apply_remote(&mut self, command: EditCommand) -> Result<()> {
+        self.commit_transform();
+        let recording = std::mem::replace(&mut self.recording, false);
+        let read_only = std::mem::replace(&mut self.read_only, false);
+        let remote = std::mem::replace(&mut self.remote, true);
+        let result = self.apply(command);
+        self.recording = recording;
+        self.read_only = read_only;
+        self.remote = remote;
+        result
+    }
+
+    /// Ignores local edits from now on, leaving [`App::apply_remote`] the only way to change the canvas
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Starts or stops collecting committed edits for [`App::take_recorded_edits`]
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+        if !recording {
+            self.recorded.clear();
+        }
+    }
+
+    /// Committed edits since the last call, with finished strokes as
+    /// [`EditCommand::AddStroke`] rather than the points they were drawn with
+    pub fn take_recorded_edits(&mut self) -> Vec<EditCommand> {
+        std::mem::take(&mut self.recorded)
+    }
+
+    fn record(&mut self, edit: EditCommand) {
+        if self.recording {
+            self.recorded.push(edit);
+        }
+    }
+
+    /// Keeps a committed edit for undo, unless it is a peer's
+    fn push_revision(&mut self, revision: Revision) {
+        if !self.remote {
+            self.history.push(revision);
+        }
+    }
+
+    /// Extends the in-progress stroke to `new_vertex` (in canvas units)
+    ///
+    /// The first point only marks where the stroke starts. Strokes longer than
+    /// the staging buffer are committed in pieces automatically, each piece
+    /// starting where the previous one ended, and undone together.
+    fn append_vertex(&mut self, new_vertex: Vec2) {
+        if self.new_stroke.end().is_none() {
+            self.predictor.clear();
+            self.stroke_piece = None;
+        }
+        self.predictor.push(Instant::now(), new_vertex);
+        let tilt = self
+            .pen_tilt
+            .unwrap_or_else(|| self.config.brush.nib_tilt());
+        self.new_stroke.set_tilt(tilt);
+        let brush = if self.tools.erases_pixels() {
+            PIXEL_ERASER_BRUSH
+        } else {
+            self.brush_shader
+        };
+        self.new_stroke.set_brush(brush);
+        if let Some(line) = self.new_stroke.extend_to(new_vertex, self.brush_color) {
+            let bounds = Rect::around_line(&line, self.stroke_margin());
+            self.renderer.damage(self.camera.rect_to_screen(&bounds));
+        }
+
+        if let Some(piece) = self.new_stroke.take_full() {
+            self.stroke_piece = self.commit_lines(piece);
+        }
+    }
+
+    /// A segment from the end of the in-progress stroke to where the pointer
+    /// is predicted to be `[brush] prediction_ms` from now, drawn but never committed
+    fn predicted_tip(&self) -> Option<Line> {
+        let ahead = self.config.brush.prediction_ms;
+        if ahead <= 0.0 {
+            return None;
+        }
+
+        let last = self.new_stroke.lines().last()?;
+        let end = self.new_stroke.end()?;
+        let tip = self
+            .predictor
+            .predict(Instant::now(), Duration::from_secs_f32(ahead / 1000.0))?;
+        if end.abs_diff_eq(&tip, POINT_EPSILON) {
+            return None;
+        }
+        Some(Line {
+            position: (end + tip) / 2.0,
+            dir: tip - end,
+            ..*last
+        })
+    }
+
+    /// Finishes the in-progress stroke and uploads it to the device-local buffer
+    fn commit_new_line(&mut self) {
+        let lines = self.new_stroke.take();
+        self.commit_lines(lines);
+        self.stroke_piece = None;
+    }
+
+    /// Commits segments of the in-progress stroke as one stroke, simplified
+    /// by `[brush] simplify_pixels` at the current zoom, and returns its id
+    /// unless it fades
+    ///
+    /// After a piece in `stroke_piece` the stroke joins that piece's revision.
+    fn commit_lines(&mut self, lines: Vec<Line>) -> Option<StrokeId> {
+        if lines.is_empty() {
+            return None;
+        }
+        let mut lines = self.simplified(lines);
+        if let Some(seconds) = self.fade_after {
+            self.add_fading(lines, seconds);
+            return None;
+        }
+
+        let tag = self.brush_tag.clone().map_or(0, |name| self.tag_bit(&name));
+        lines.iter_mut().for_each(|l| l.tag = tag);
+        let stroke = self.push_stroke(lines)?;
+        let id = stroke.id;
+        match self.stroke_piece {
+            Some(previous) if !self.remote => self.history.push_piece(previous, stroke),
+            _ => self.push_revision(Revision::Stroke(vec![stroke])),
+        }
+        Some(id)
+    }
+
+    /// `lines` simplified by `[brush] simplify_pixels` at the current zoom
+    fn simplified(&self, lines: Vec<Line>) -> Vec<Line> {
+        let pixels = self.config.brush.simplify_pixels;
+        if pixels <= 0.0 || lines.is_empty() {
+            return lines;
+        }
+
+        let pixels_per_unit = self.camera.zoom * self.renderer.render_extent.height as f32 / 2.0;
+        let simplified = simplify(&lines, pixels / pixels_per_unit);
+        trace!(
+            "Simplified a stroke from {} to {} segments.",
+            lines.len(),
+            simplified.len()
+        );
+        simplified
+    }
+
+    /// Commits a whole stroke, leaving the one in progress alone
+    fn add_stroke(&mut self, stroke: &Stroke) {
+        if let Some(seconds) = stroke.fade_after {
+            let lines = self.brushed_lines(stroke);
+            self.add_fading(lines, seconds);
+            return;
+        }
+
+        let lines = self.committed_lines(stroke);
+        if lines.is_empty() {
+            return;
+        }
+        if let Some(bounds) = Rect::around_lines(&lines, self.stroke_margin()) {
+            self.renderer.damage(self.camera.rect_to_screen(&bounds));
+        }
+        if let Some(stroke) = self.push_stroke(lines) {
+            self.push_revision(Revision::Stroke(vec![stroke]));
+        }
+    }
+
+    /// The segments of a stroke from a peer, script or document, drawn with its brush
+    fn brushed_lines(&self, stroke: &Stroke) -> Vec<Line> {
+        let mut lines = stroke.lines();
+        let brush = stroke.brush.as_deref().map_or(0, |name| {
+            let index = self.config.shaders.brush_index(name);
+            if index.is_none() {
+                debug!(
+                    "No brush `{}`, drawing the stroke with the default shader.",
+                    name
+                );
+            }
+            index.unwrap_or(0)
+        });
+        lines.iter_mut().for_each(|l| l.set_brush(brush));
+        lines
+    }
+
+    /// The segments of a stroke from a peer, script or document as they are
+    /// committed, drawn with its brush and filed under its tag
+    fn committed_lines(&mut self, stroke: &Stroke) -> Vec<Line> {
+        let mut lines = self.brushed_lines(stroke);
+        let tag = stroke.tag.as_deref().map_or(0, |name| self.tag_bit(name));
+        lines.iter_mut().for_each(|l| l.tag = tag);
+        lines
+    }
+
+    /// Puts strokes back at their indices, ascending, e.g. a removal a peer undid
+    ///
+    /// Like [`App::replace_strokes`], this is how undo and redo reach other
+    /// canvases, so it isn't an edit of its own in the undo history.
+    fn insert_strokes(&mut self, strokes: Vec<(usize, Stroke)>) {
+        let max_vertices = self.config.vulkan.max_vertices as usize;
+        let mut first = None;
+        for (index, stroke) in &strokes {
+            let lines = self.committed_lines(stroke);
+            if *index > self.strokes.len() || lines.is_empty() {
+                continue;
+            }
+            if self.line_count() + lines.len() > max_vertices {
+                warn!(
+                    "Dropped a stroke of {} segments, the vertex buffer is full (max_vertices is {}).",
+                    lines.len(),
+                    max_vertices
+                );
+                continue;
+            }
+
+            if let Some(bounds) = Rect::around_lines(&lines, self.stroke_margin()) {
+                self.renderer.damage(self.camera.rect_to_screen(&bounds));
+            }
+            self.strokes.insert_lines(*index, lines);
+            first = Some(first.map_or(*index, |first: usize| first.min(*index)));
+        }
+
+        let Some(first) = first else {
+            return;
+        };
+        // Everything after the first inserted stroke moves up in the vertex buffer
+        self.upload_strokes_from(first);
+        self.strokes_changed();
+        self.retain_selection();
+        self.record(EditCommand::InsertStrokes(strokes));
+    }
+
+    /// Swaps strokes for other versions by index, e.g. a transform a peer undid
+    ///
+    /// A version with as many segments is re-uploaded in place; one with a
+    /// different count (points that round-tripped differently) moves the
+    /// strokes after it in the vertex buffer.
+    fn replace_strokes(&mut self, strokes: Vec<(usize, Stroke)>) {
+        let mut moved = None;
+        for (index, stroke) in &strokes {
+            let lines = self.committed_lines(stroke);
+            if *index >= self.strokes.len() || lines.is_empty() {
+                continue;
+            }
+
+            let old_count = self.strokes[*index].lines.len();
+            if lines.len() == old_count {
+                self.replace_stroke(*index, lines);
+                continue;
+            }
+
+            let max_vertices = self.config.vulkan.max_vertices as usize;
+            if self.line_count() - old_count + lines.len() > max_vertices {
+                warn!(
+                    "Kept a stroke as it was, its replacement of {} segments doesn't fit into the vertex buffer (max_vertices is {}).",
+                    lines.len(),
+                    max_vertices
+                );
+                continue;
+            }
+            for stroke in [&self.strokes[*index].lines, &lines] {
+                if let Some(bounds) = Rect::around_lines(stroke, self.stroke_margin()) {
+                    self.renderer.damage(self.camera.rect_to_screen(&bounds));
+                }
+            }
+            self.strokes.remove(*index);
+            self.strokes.insert_lines(*index, lines);
+            moved = Some(moved.map_or(*index, |first: usize| first.min(*index)));
+        }
+
+        if let Some(first) = moved {
+            self.upload_strokes_from(first);
+        }
+        self.strokes_changed();
+        self.retain_selection();
+        self.record(EditCommand::ReplaceStrokes(strokes));
+    }
+
+    /// Commits `lines` as a stroke on top of the others, uploads them to
+    /// the end of the vertex buffer and returns the stroke for the undo history
+    ///
+    /// A stroke that doesn't fit into `vulkan.max_vertices` any more is
+    /// dropped with a warning rather than failing whatever drew it.
+    fn push_stroke(&mut self, lines: Vec<Line>) -> Option<StoredStroke> {
+        let edit = EditCommand::AddStroke(self.to_stroke(&lines));
+        let count = lines.len();
+        let max_vertices = self.config.vulkan.max_vertices as usize;
+        let Some(id) = self.strokes.push_within(lines, max_vertices) else {
+            warn!(
+                "Dropped a stroke of {} segments, the vertex buffer is full (max_vertices is {}).",
+                count, max_vertices
             );
-            lines_to_copy as u32
+            return None;
+        };
+        self.record(edit);
+        self.stroke_generation += 1;
+        let stroke = self.strokes.by_id(id).unwrap().clone();
+        self.upload(stroke.range.start as usize, &stroke.lines);
+        self.index.push(&stroke);
+        self.lods = None;
+        self.lod_levels = None;
+        Some(stroke)
+    }
+
+    /// Adds a stroke that stays for `seconds` and then fades out
+    ///
+    /// When the fade buffer is full the oldest fading strokes make room.
+    /// `seconds` is kept within `MAX_FADE_SECONDS`, whoever asked for more.
+    fn add_fading(&mut self, mut lines: Vec<Line>, seconds: f32) {
+        if lines.is_empty() {
+            return;
+        }
+        // NaN fades right away
+        let seconds = if seconds >= 0.0 {
+            seconds.min(MAX_FADE_SECONDS)
         } else {
-            0
+            0.0
         };
 
-        // Safety check: ensure we don't exceed staging buffer capacity
-        let lines_to_copy = self
-            .new_lines
-            .len()
-            .min(self.config.vulkan.staging_buffer_vertex_count as usize);
-        let size = (std::mem::size_of::<Line>() * lines_to_copy) as u64;
-        let current_line_count = self.lines.iter().map(|v| v.len()).sum::<usize>();
-        let dst_offset = (std::mem::size_of::<Line>() * current_line_count) as u64;
-
-        // GPU copy from staging buffer to device-local buffer
-        // (staging buffer already contains the data from render() updates)
-        copy_buffer(
-            &self.context.device,
-            self.context.graphics_queue,
-            self.context.command_pool,
-            self.staging_buffer,
-            self.vertex_buffer,
-            dst_offset,
-            size,
-        )?;
+        self.record(EditCommand::AddStroke(Stroke {
+            fade_after: Some(seconds),
+            ..self.to_stroke(&lines)
+        }));
+
+        let start = self.clock() + seconds;
+        for line in &mut lines {
+            line.fade = Vec2::new(start, start + FADE_OUT_SECONDS);
+        }
+        if let Some(bounds) = Rect::around_lines(&lines, self.stroke_margin()) {
+            self.renderer.damage(self.camera.rect_to_screen(&bounds));
+        }
+
+        let capacity = self.config.vulkan.fade_vertex_count as usize;
+        lines.truncate(capacity);
+        let mut dropped = false;
+        while self.fading_line_count() + lines.len() > capacity {
+            let oldest = self.fading.remove(0);
+            if let Some(bounds) = Rect::around_lines(&oldest, self.stroke_margin()) {
+                self.renderer.damage(self.camera.rect_to_screen(&bounds));
+            }
+            dropped = true;
+        }
 
-        // Update CPU-side tracking (only add the lines we actually copied)
-        if lines_to_copy < self.new_lines.len() {
-            self.lines.push(self.new_lines[..lines_to_copy].to_vec());
-            self.new_lines = self.new_lines[lines_to_copy..].to_vec();
+        let first_line = self.fading_line_count();
+        self.fading.push(lines);
+        if dropped {
+            self.upload_fading();
         } else {
-            self.lines.push(self.new_lines.clone());
-            self.new_lines.clear();
-            self.line_start = None;
+            let lines = self.fading[self.fading.len() - 1].clone();
+            self.upload_into(self.fade_buffer, first_line, &lines);
         }
+    }
 
-        Ok(())
+    /// Drops the strokes that finished fading and redraws the ones fading now, once per frame
+    fn prune_faded(&mut self) {
+        let now = self.clock();
+        let count = self.fading.len();
+        self.fading
+            .retain(|stroke| stroke.first().is_some_and(|l| l.fade.y > now));
+        if self.fading.len() != count {
+            self.upload_fading();
+        }
+
+        for stroke in &self.fading {
+            if stroke[0].fade.x <= now {
+                if let Some(bounds) = Rect::around_lines(stroke, self.stroke_margin()) {
+                    self.renderer.damage(self.camera.rect_to_screen(&bounds));
+                }
+            }
+        }
+    }
+
+    /// Rewrites the whole fade buffer, after strokes left it
+    fn upload_fading(&mut self) {
+        let lines = self.fading.concat();
+        self.upload_into(self.fade_buffer, 0, &lines);
+    }
+
+    /// Whether any stroke, fading or being drawn too, erases pixels, so frames need the erase layer
+    fn has_pixel_erasers(&mut self) -> bool {
+        let erases = |lines: &[Line]| {
+            lines
+                .first()
+                .is_some_and(|l| l.brush() == PIXEL_ERASER_BRUSH)
+        };
+        if self.pixel_erasers.0 != self.stroke_generation {
+            let any = self.strokes.iter().any(|s| erases(&s.lines));
+            self.pixel_erasers = (self.stroke_generation, any);
+        }
+        self.pixel_erasers.1
+            || self.fading.iter().any(|lines| erases(lines))
+            || erases(self.new_stroke.lines())
+    }
+
+    fn fading_line_count(&self) -> usize {
+        self.fading.iter().map(|s| s.len()).sum()
+    }
+
+    /// Seconds since the app started, the clock fading strokes count against
+    fn clock(&self) -> f32 {
+        self.start.elapsed().as_secs_f32()
+    }
+
+    /// When the next fading stroke starts to fade, right away if one already is
+    fn fade_repaint_at(&self) -> Option<Instant> {
+        let next = self
+            .fading
+            .iter()
+            .map(|stroke| stroke[0].fade.x)
+            .min_by(f32::total_cmp)?;
+        let after = Duration::try_from_secs_f32(next.max(0.0)).ok()?;
+        self.start.checked_add(after)
+    }
+
+    /// Seconds strokes drawn from now on stay before fading, `None` for permanent strokes
+    pub fn fade_after(&self) -> Option<f32> {
+        self.fade_after
+    }
+
+    /// Switches between permanent strokes and strokes that fade after `[brush] fade_seconds`
+    pub fn toggle_fade(&mut self) {
+        self.fade_after = match self.fade_after {
+            Some(_) => None,
+            None => Some(self.config.brush.fade_seconds),
+        };
+    }
+
+    /// Feeds a window event to the UI, which reports whether it used the event
+    pub fn window_event(&mut self, window: &Window, event: &WindowEvent) -> EventResponse {
+        self.ui.on_window_event(window, event)
+    }
+
+    /// Whether the UI wants another frame even without new input (e.g. for animations)
+    pub fn needs_repaint(&self) -> bool {
+        self.ui.needs_repaint()
+    }
+
+    /// When the next frame is due without new input, `None` while idle
+    ///
+    /// Set by UI animations, delayed tooltips and fading strokes; an event
+    /// loop can sleep until then instead of polling.
+    pub fn repaint_at(&self) -> Option<Instant> {
+        // A predicted tip is taken back once the pointer has stopped
+        let prediction = self.predicted.and_then(|_| self.predictor.expires_at());
+        // An animated background moves on every frame
+        let background = self.background_time().map(|_| Instant::now());
+        [
+            self.ui.repaint_at(),
+            self.fade_repaint_at(),
+            prediction,
+            background,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// Color of strokes drawn from now on
+    pub fn brush_color(&self) -> Color {
+        self.brush_color
+    }
+
+    pub fn set_brush_color(&mut self, color: Color) {
+        self.brush_color = color;
+    }
+
+    /// Switches to a reloaded configuration
+    ///
+    /// Brush defaults, key and script bindings, the undo memory budget,
+    /// accessibility settings, themes, the frame rate, the present mode,
+    /// low-latency pacing, the resolution scale and shader parameters take
+    /// effect right away. The window, shaders, buffer sizes and video output
+    /// are only read at startup, so changes to those keep their current
+    /// values and log a reminder to restart.
+    pub fn apply_config(&mut self, mut config: Config) {
+        crash::set_config(&config);
+        if let Some((_, size)) = self.crop {
+            config.window.canvas_size = Some(size);
+        }
+        let old = &self.config;
+
+        let mut restart = Vec::new();
+        let window = WindowConfig {
+            title: config.window.title.clone(),
+            monitor: config.window.monitor.clone(),
+            canvas_size: old.window.canvas_size,
+            frame_rate: old.window.frame_rate,
+            animation_frame_rate: old.window.animation_frame_rate,
+            ..config.window
+        };
+        if window != old.window {
+            restart.push("[window]");
+        }
+        let vulkan = VulkanConfig {
+            present_mode: old.vulkan.present_mode,
+            resolution_scale: old.vulkan.resolution_scale,
+            low_latency: old.vulkan.low_latency,
+            ..config.vulkan
+        };
+        if vulkan != old.vulkan {
+            restart.push("[vulkan]");
+        }
+        if config.shaders != old.shaders {
+            restart.push("[shaders]");
+        }
+        if config.output != old.output {
+            restart.push("[output]");
+        }
+        if !restart.is_empty() {
+            warn!("Restart to apply changes to {}.", restart.join(", "));
+        }
+
+        if config.brush.color != old.brush.color {
+            self.brush_color = config.brush.color;
+        }
+        let snap = angle_snap_step(&config.brush, self.snap_held);
+        if snap != self.angle_snap() {
+            if let Some(tool) = self.tools.active() {
+                // A shape being dragged catches up with the next pointer move
+                tool.angle_snap_changed(snap);
+            }
+        }
+        if config.keybindings != old.keybindings {
+            self.bindings = KeyBindings::new(&config.keybindings);
+        }
+        if config.scripts != old.scripts {
+            self.scripts = ScriptEngine::new(&config.scripts);
+        }
+        if config.history != old.history {
+            self.history.set_budget(config.history.budget());
+        }
+        if config.logging != old.logging {
+            if let Err(e) = logging::configure(&config.logging) {
+                warn!("Not logging to a file: {:#}", e);
+            }
+        }
+        if config.stats != old.stats {
+            self.frame_stats.set_capacity(config.stats.frames);
+        }
+        if config.accessibility.high_contrast != old.accessibility.high_contrast {
+            self.high_contrast = config.accessibility.high_contrast;
+        }
+        if config.vulkan.present_mode != old.vulkan.present_mode
+            || config.vulkan.resolution_scale != old.vulkan.resolution_scale
+            || config.window.canvas_size != old.window.canvas_size
+            || config.bloom != old.bloom
+            || config.background.shader != old.background.shader
+        {
+            self.resized = true;
+        }
+        if config.shader_params != old.shader_params {
+            // Frames pick the values up as they are drawn, the minimap on its next render
+            self.minimap_drawn = None;
+        }
+        let brush_changed = config.brush.shader != old.brush.shader;
+        let theme_changed = config.theme != old.theme;
+        let new_active = config.theme.active != old.theme.active;
+        self.renderer.damage_all();
+
+        let previous = std::mem::replace(&mut self.config, config);
+        self.config.window = WindowConfig {
+            canvas_size: self.config.window.canvas_size,
+            frame_rate: self.config.window.frame_rate,
+            animation_frame_rate: self.config.window.animation_frame_rate,
+            ..previous.window
+        };
+        self.config.vulkan = VulkanConfig {
+            present_mode: self.config.vulkan.present_mode,
+            resolution_scale: self.config.vulkan.resolution_scale,
+            low_latency: self.config.vulkan.low_latency,
+            ..previous.vulkan
+        };
+        self.config.shaders = previous.shaders;
+        self.config.output = previous.output;
+        self.config.session.recent_files = previous.session.recent_files;
+        if new_active {
+            self.theme_name = self.config.theme.active.clone();
+        }
+        if theme_changed {
+            self.set_theme(&self.theme_name.clone());
+        }
+        if brush_changed {
+            // Against the shaders in use, which only change on restart
+            self.brush_shader = brush_index(&self.config);
+        }
+        info!("Reloaded the configuration.");
     }
 
-    pub fn undo(&mut self) {
-        // Remove the last committed stroke if there is one
-        if self.lines.len() > 1 {
-            self.lines.pop();
+    /// Switches high-contrast mode, redrawing everything in the new style
+    pub fn set_high_contrast(&mut self, high_contrast: bool) {
+        if high_contrast != self.high_contrast {
+            self.high_contrast = high_contrast;
+            self.renderer.damage_all();
         }
     }
 
-    /// Destroys our Vulkan app
-    pub unsafe fn destroy(&mut self) {
-        self.context.device.device_wait_idle().unwrap();
+    /// Follows the window to a monitor with another scale factor
+    ///
+    /// The window's pixel size changes with it, and some platforms don't
+    /// report that as a resize, so the swapchain is recreated either way.
+    pub fn scale_factor_changed(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+        self.resized = true;
+        self.renderer.damage_all();
+    }
+
+    /// How strokes are drawn at the current zoom
+    ///
+    /// High-contrast mode lightens dark strokes and keeps them at least
+    /// `min_stroke_width` logical pixels wide however far the view zooms out.
+    fn stroke_style(&self) -> StrokeStyle {
+        // The clock only runs while something fades, so recorded frames stay reusable otherwise
+        let time = if self.fading.is_empty() {
+            0.0
+        } else {
+            self.clock()
+        };
+        if !self.high_contrast {
+            return StrokeStyle {
+                time,
+                hidden_tags: self.hidden_tag_bits(),
+                ..Default::default()
+            };
+        }
+
+        // A window pixel is 2 / height in NDC, so this is half of `min_stroke_width` logical pixels
+        let height = self.renderer.letterbox.extent.height.max(1) as f32;
+        let min_width = self.config.accessibility.min_stroke_width * self.scale_factor as f32;
+        let min_thickness = min_width / height;
+        StrokeStyle {
+            thickness: STROKE_THICKNESS.max(min_thickness / self.camera.zoom),
+            min_luminance: HIGH_CONTRAST_LUMINANCE,
+            time,
+            hidden_tags: self.hidden_tag_bits(),
+        }
+    }
 
-        self.renderer.destroy(&self.context.device);
+    /// Switches to the `[theme]` entry called `name`
+    ///
+    /// The palette is replaced by the theme's (dropping swatches added this
+    /// session), and the brush moves to the theme's first swatch unless its
+    /// color is in the new palette.
+    pub fn set_theme(&mut self, name: &str) {
+        let theme = self.config.theme.get(name);
+        if name == self.theme_name && theme == self.theme {
+            return;
+        }
 
-        // Unmap persistently mapped staging buffer
-        self.context.device.unmap_memory(self.staging_buffer_memory);
+        if !theme.palette.contains(&self.brush_color) {
+            if let Some(&first) = theme.palette.first() {
+                self.brush_color = first;
+            }
+        }
+        self.palette = theme.palette.clone();
+        self.theme_name = name.to_string();
+        self.theme = theme;
+        self.renderer.damage_all();
+    }
 
-        self.context
-            .device
-            .free_memory(self.staging_buffer_memory, None);
-        self.context
-            .device
-            .destroy_buffer(self.staging_buffer, None);
+    /// Name of the theme in use
+    pub fn theme_name(&self) -> &str {
+        &self.theme_name
+    }
 
-        self.context
-            .device
-            .free_memory(self.vertex_buffer_memory, None);
-        self.context.device.destroy_buffer(self.vertex_buffer, None);
+    /// The configuration in effect, including changes made since startup
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
 
-        self.context
-            .device
-            .free_memory(self.geometry_buffer_memory, None);
-        self.context
-            .device
-            .destroy_buffer(self.geometry_buffer, None);
+    /// Remembers a document that was opened or saved
+    pub fn add_recent_file(&mut self, path: &Path) {
+        self.config.session.add_recent_file(path);
+    }
 
-        self.context
-            .device
-            .free_memory(self.geometry_index_buffer_memory, None);
-        self.context
-            .device
-            .destroy_buffer(self.geometry_index_buffer, None);
+    /// Writes the brush color, theme, window geometry and recent files that
+    /// changed since the config was loaded to the file at `path` (see [`Config::save`])
+    ///
+    /// The window's monitor is saved by name, so the next start opens on the
+    /// same one. The size and position are left alone while the window is
+    /// fullscreen, an overlay or minimized, so the next start opens where it
+    /// last was as a window. Platforms that hide window positions (Wayland)
+    /// keep the configured one.
+    pub fn save_settings(&mut self, window: &Window, path: &Path) -> Result<()> {
+        self.config.brush.color = self.brush_color;
+        self.config.theme.active = self.theme_name.clone();
 
-        self.context.destroy();
+        let size = window.inner_size().to_logical::<u32>(window.scale_factor());
+        let windowed = window.fullscreen().is_none() && !self.config.window.overlay;
+        if windowed && size.width > 0 && size.height > 0 {
+            self.config.window.width = size.width;
+            self.config.window.height = size.height;
+        }
+
+        if let Some(monitor) = window.current_monitor() {
+            if let (true, Ok(position)) = (windowed, window.outer_position()) {
+                let origin = monitor.position();
+                self.config.window.position = Some([position.x - origin.x, position.y - origin.y]);
+            }
+            if let Some(name) = monitor.name() {
+                self.config.window.monitor = Some(MonitorChoice::Name(name));
+            }
+        }
+
+        self.config.save(path, &self.loaded_settings)
+    }
+
+    /// Canvas color, forced to black in high-contrast mode
+    fn background(&self) -> Color {
+        if self.high_contrast {
+            Color::BLACK
+        } else {
+            self.theme.background
+        }
+    }
+
+    /// What the window clears to: the canvas, see-through by
+    /// `window.background_opacity` in a transparent window, or nothing at all
+    /// in overlay mode
+    fn clear_color(&self) -> Color {
+        let window = &self.config.window;
+        if window.overlay {
+            Color::TRANSPARENT
+        } else if window.transparent {
+            Color {
+                a: (window.background_opacity * 255.0).round() as u8,
+                ..self.background()
+            }
+        } else {
+            self.background()
+        }
+    }
+
+    /// Margin around stroke segments covering their drawn width and anti-aliasing
+    fn stroke_margin(&self) -> f32 {
+        STROKE_MARGIN + self.stroke_style().thickness - STROKE_THICKNESS
+    }
+
+    /// Converts a window position in pixels to NDC (-1 to 1 across the canvas)
+    ///
+    /// Positions and the swapchain are both in physical pixels, so this holds
+    /// at any scale factor as long as the swapchain is recreated when the
+    /// factor changes. With a fixed canvas size positions on the bars around
+    /// it fall outside -1 to 1.
+    pub fn to_ndc(&self, position: PhysicalPosition<f64>) -> Vec2 {
+        self.renderer.to_ndc(position.x as f32, position.y as f32)
+    }
+
+    /// Where the canvas is shown in the window, in physical pixels
+    fn frame_rect(&self) -> egui::Rect {
+        let vk::Rect2D { offset, extent } = self.renderer.letterbox;
+        egui::Rect::from_min_size(
+            egui::pos2(offset.x as f32, offset.y as f32),
+            egui::vec2(extent.width as f32, extent.height as f32),
+        )
+    }
+
+    /// The view onto the canvas
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Moves the view, redrawing everything
+    pub fn set_camera(&mut self, camera: Camera) {
+        if camera != self.camera {
+            self.camera = camera;
+            self.renderer.damage_all();
+        }
+    }
+
+    /// The registered tools; new tools can be added with [`ToolRegistry::register`]
+    pub fn tools(&mut self) -> &mut ToolRegistry {
+        &mut self.tools
+    }
+
+    /// Switches to the tool at `index`, finishing whatever the previous tool was doing
+    pub unsafe fn select_tool(&mut self, index: usize) -> Result<bool> {
+        match self.tools.select(index) {
+            Some(edits) => {
+                self.apply_all(edits)?;
+                self.selection_changed();
+                self.angle_snap_changed()?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Tracks the modifier keys, returning whether the canvas changed
+    ///
+    /// Holding Shift switches angle snapping for lines and shapes, updating
+    /// one that is being dragged right away.
+    pub unsafe fn modifiers_changed(&mut self, modifiers: ModifiersState) -> Result<bool> {
+        if modifiers.shift_key() == self.snap_held {
+            return Ok(false);
+        }
+
+        self.snap_held = modifiers.shift_key();
+        self.angle_snap_changed()
+    }
+
+    /// The step (in radians) directions snap to right now, `None` while off
+    fn angle_snap(&self) -> Option<f32> {
+        angle_snap_step(&self.config.brush, self.snap_held)
+    }
+
+    /// Tells the active tool about the current angle snapping
+    unsafe fn angle_snap_changed(&mut self) -> Result<bool> {
+        let step = self.angle_snap();
+        let edits = match self.tools.active() {
+            Some(tool) => tool.angle_snap_changed(step),
+            None => vec![],
+        };
+        self.apply_all(edits)
+    }
+
+    /// Forwards a pointer press (in screen NDC) to the active tool, returning whether the canvas changed
+    ///
+    /// While [`Action::HoldToPan`]'s key is held it starts panning instead.
+    pub unsafe fn pointer_down(&mut self, position: Vec2) -> Result<bool> {
+        if self.pan_key.is_some() {
+            self.begin_pan(position);
+            return Ok(false);
+        }
+
+        let position = self.camera.to_canvas(position);
+        let edits = match self.tools.active() {
+            Some(tool) => tool.pointer_down(position),
+            None => vec![],
+        };
+        self.apply_all(edits)
+    }
+
+    /// Forwards a cursor move (in screen NDC) to the active tool, returning whether the canvas changed
+    ///
+    /// While panning the view follows the cursor instead.
+    pub unsafe fn pointer_moved(&mut self, position: Vec2) -> Result<bool> {
+        if let Some(from) = self.pan_from.replace(position) {
+            self.set_camera(self.camera.panned(position - from));
+            self.cursor = Some(self.camera.to_canvas(position));
+            return Ok(true);
+        }
+
+        let position = self.camera.to_canvas(position);
+        self.cursor = Some(position);
+        let edits = match self.tools.active() {
+            Some(tool) => tool.pointer_moved(position),
+            None => vec![],
+        };
+        self.apply_all(edits)
+    }
+
+    /// Forwards a pointer release (in screen NDC) to the active tool, returning whether the canvas changed
+    pub unsafe fn pointer_up(&mut self, position: Vec2) -> Result<bool> {
+        if self.pan_key.is_some() {
+            self.end_pan();
+            return Ok(false);
+        }
+
+        let position = self.camera.to_canvas(position);
+        let edits = match self.tools.active() {
+            Some(tool) => tool.pointer_up(position),
+            None => vec![],
+        };
+        self.apply_all(edits)
+    }
+
+    /// Starts dragging the view with the cursor at `position` (in screen NDC)
+    pub fn begin_pan(&mut self, position: Vec2) {
+        self.pan_from = Some(position);
+    }
+
+    pub fn end_pan(&mut self) {
+        self.pan_from = None;
+    }
+
+    /// Makes the left button pan until `key` is released, finishing whatever
+    /// the active tool was doing; the tool stays selected for afterwards
+    ///
+    /// Key repeats while it is held change nothing.
+    pub unsafe fn hold_pan(&mut self, key: KeyCode) -> Result<bool> {
+        if self.pan_key.is_some() {
+            return Ok(false);
+        }
+
+        self.pan_key = Some(key);
+        let edits = match self.tools.active() {
+            Some(tool) => tool.deactivate(),
+            None => vec![],
+        };
+        self.apply_all(edits)
+    }
+
+    /// Hands the left button back to the active tool if `key` was held to
+    /// pan, returning whether it was
+    pub fn key_released(&mut self, key: KeyCode) -> bool {
+        if self.pan_key != Some(key) {
+            return false;
+        }
+
+        self.pan_key = None;
+        self.end_pan();
+        true
+    }
+
+    /// Zooms by `lines` scroll wheel lines (positive zooms in), keeping the canvas point under the cursor in place
+    pub fn scroll_zoom(&mut self, lines: f32) {
+        let anchor = match self.cursor {
+            Some(cursor) => self.camera.to_screen(cursor),
+            None => Vec2::new(0.0, 0.0),
+        };
+        self.set_camera(self.camera.zoomed_around(anchor, ZOOM_STEP.powf(lines)));
+    }
+
+    /// Abandons the current press (e.g. when a touch turns into a gesture), dropping the in-progress stroke
+    pub unsafe fn pointer_cancel(&mut self) -> Result<bool> {
+        let mut edits = match self.tools.active() {
+            Some(tool) => tool.cancel(),
+            None => vec![],
+        };
+        edits.push(EditCommand::SetStroke(vec![]));
+        self.apply_all(edits)
+    }
+
+    /// Opens the quick action menu at the cursor
+    pub fn open_context_menu(&mut self) {
+        if let Some(cursor) = self.cursor {
+            let stroke = self.stroke_at(cursor);
+            self.ui
+                .open_context_menu(self.camera.to_screen(cursor), cursor, stroke);
+        }
+    }
+
+    /// Draws the next stroke points with the pen leaning along `tilt`, or
+    /// with the `[brush]` nib for `None`
+    ///
+    /// winit reports no tilt, so this is for tablet integrations that read
+    /// it themselves: the direction is where the pen leans on the canvas and
+    /// the length how far, stretching the brush by that much along it.
+    pub fn set_pen_tilt(&mut self, tilt: Option<Vec2>) {
+        self.pen_tilt = tilt;
+    }
+
+    /// What `[keybindings.buttons]` binds to a mouse or pen button, if anything
+    pub fn button_for(&self, button: MouseButton) -> Option<ButtonBinding> {
+        self.bindings.button_for(button).cloned()
+    }
+
+    /// Switches to the tool called `name` and starts using it at `position`
+    /// (in screen NDC), for a button bound to it being pressed
+    pub unsafe fn hold_tool(&mut self, name: &str, position: Vec2) -> Result<bool> {
+        if self.held_tool.is_some() {
+            return Ok(false);
+        }
+        let Some(index) = self.tools.names().position(|n| n == name) else {
+            warn!("No tool called `{}` to bind a button to.", name);
+            return Ok(false);
+        };
+
+        let previous = self.tools.active_index();
+        let switched = self.select_tool(index)?;
+        self.held_tool = Some(previous);
+        Ok(self.pointer_down(position)? | switched)
+    }
+
+    /// Finishes using the held tool at `position` and switches back to the one before
+    pub unsafe fn release_tool(&mut self, position: Vec2) -> Result<bool> {
+        let Some(previous) = self.held_tool.take() else {
+            return Ok(false);
+        };
+
+        let changed = self.pointer_up(position)?;
+        Ok(self.select_tool(previous)? | changed)
+    }
+
+    /// The action `[keybindings]` binds to this chord, if any
+    pub fn action_for(&self, key: KeyCode, modifiers: ModifiersState) -> Option<Action> {
+        self.bindings.action_for(key, modifiers).cloned()
+    }
+
+    /// Carries out a bound action, returning whether anything on screen changed
+    ///
+    /// [`Action::Save`] needs a file name, [`Action::ToggleFullscreen`] and
+    /// [`Action::TogglePassThrough`] the window and [`Action::HoldToPan`] the
+    /// key, so those are left to the caller.
+    pub unsafe fn perform(&mut self, action: &Action) -> Result<bool> {
+        match action {
+            Action::Undo => self.apply(EditCommand::Undo)?,
+            Action::Redo => self.apply(EditCommand::Redo)?,
+            Action::Clear => self.apply(EditCommand::Clear)?,
+            Action::Copy => {
+                self.copy_selection();
+                return Ok(false);
+            }
+            Action::Paste => {
+                let position = self.cursor.unwrap_or(Vec2::new(0.0, 0.0));
+                return self.paste_at(position);
+            }
+            Action::Duplicate => return self.duplicate_selection(),
+            Action::CopyScreenshot => {
+                if let Err(e) = self.copy_screenshot() {
+                    error!("Failed to copy a screenshot: {:#}", e);
+                }
+                return Ok(false);
+            }
+            Action::Save
+            | Action::ToggleFullscreen
+            | Action::TogglePassThrough
+            | Action::HoldToPan => return Ok(false),
+            Action::NextColor => self.cycle_color(1),
+            Action::PreviousColor => self.cycle_color(-1),
+            Action::ToggleHelp => self.ui.show_help = !self.ui.show_help,
+            Action::ToggleStats => self.ui.show_stats = !self.ui.show_stats,
+            Action::ToggleHighContrast => self.set_high_contrast(!self.high_contrast),
+            Action::ToggleFade => self.toggle_fade(),
+            Action::ToggleTags => self.ui.show_tags = !self.ui.show_tags,
+            Action::ToggleRulers => self.ui.show_rulers = !self.ui.show_rulers,
+            Action::ToggleMinimap => self.ui.show_minimap = !self.ui.show_minimap,
+            Action::ToggleHistory => self.ui.show_history = !self.ui.show_history,
+            Action::SaveFrameStats => {
+                if let Err(e) = self.save_frame_stats() {
+                    error!("Failed to save frame statistics: {:#}", e);
+                }
+                return Ok(false);
+            }
+            Action::RevealTags => self.toggle_revealed_tags(),
+            Action::NextBrush => self.cycle_brush_shader(),
+            Action::ToggleBackground => self.toggle_background(),
+            Action::NextTheme => {
+                let next = self.config.theme.next_after(&self.theme_name);
+                if let Some(next) = next.map(str::to_string) {
+                    self.set_theme(&next);
+                }
+            }
+            Action::SelectTool(name) => {
+                let index = self.tools.names().position(|n| n == name);
+                return match index {
+                    Some(index) => self.select_tool(index),
+                    None => Ok(false),
+                };
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Moves the brush color `step` swatches through the palette
+    fn cycle_color(&mut self, step: isize) {
+        if self.palette.is_empty() {
+            return;
+        }
+
+        let len = self.palette.len() as isize;
+        let next = match self.palette.iter().position(|&c| c == self.brush_color) {
+            Some(i) => (i as isize + step).rem_euclid(len),
+            None => 0,
+        };
+        self.brush_color = self.palette[next as usize];
+    }
+
+    /// Runs the script bound to `key`, or forwards the key press to the
+    /// active tool. Returns whether the canvas changed.
+    pub unsafe fn key_pressed(&mut self, key: KeyCode, modifiers: ModifiersState) -> Result<bool> {
+        if let Some(path) = self.scripts.script_for(key) {
+            return Ok(self.run_script(&path));
+        }
+
+        let edits = match self.tools.active() {
+            Some(tool) => tool.key_pressed(key, modifiers),
+            None => vec![],
+        };
+        self.apply_all(edits)
+    }
+
+    /// Runs a script file, applying the canvas commands it issued
+    ///
+    /// Script errors are logged rather than returned so a broken script
+    /// cannot take the drawing down with it, and so are failures of the
+    /// commands it issued, e.g. exporting to a folder that doesn't exist.
+    pub unsafe fn run_script(&mut self, path: &Path) -> bool {
+        let commands = match self.scripts.run_file(path, self.brush_color) {
+            Ok(commands) => commands,
+            Err(e) => {
+                error!("Script `{}` failed: {:#}", path.display(), e);
+                return false;
+            }
+        };
+
+        let changed = !commands.is_empty();
+        for command in commands {
+            let result = match command {
+                ScriptCommand::Edit(edit) => self.apply(edit),
+                ScriptCommand::Export(path) => self.export_png(&path),
+            };
+            if let Err(e) = result {
+                error!("Script `{}` failed: {:#}", path.display(), e);
+            }
+        }
+
+        changed
+    }
+
+    /// Replaces the in-progress stroke with the polyline through `points`
+    fn set_new_line(&mut self, points: &[Vec2]) {
+        if let Some(bounds) = Rect::around_lines(self.new_stroke.lines(), self.stroke_margin()) {
+            self.renderer.damage(self.camera.rect_to_screen(&bounds));
+        }
+
+        let mut lines = Stroke::from_points(points, self.brush_color).lines();
+        lines
+            .iter_mut()
+            .for_each(|l| l.set_brush(self.brush_shader));
+        self.new_stroke.replace(lines, points.first().copied());
+
+        if let Some(bounds) = Rect::around_lines(self.new_stroke.lines(), self.stroke_margin()) {
+            self.renderer.damage(self.camera.rect_to_screen(&bounds));
+        }
+    }
+
+    /// Appends every stroke of `document` to the canvas
+    pub unsafe fn load_document(&mut self, document: &Document) -> Result<()> {
+        if self.line_count() + document.line_count() > self.config.vulkan.max_vertices as usize {
+            return Err(anyhow!("Document does not fit into max_vertices."));
+        }
+
+        for stroke in &document.strokes {
+            self.apply(EditCommand::AddStroke(stroke.clone()))?;
+        }
+
+        self.renderer.damage_all();
+
+        Ok(())
+    }
+
+    /// Adds the drawing an earlier run saved when it panicked, if `restore`,
+    /// and deletes the file either way so it is only offered once
+    pub unsafe fn recover(&mut self, restore: bool) {
+        let Some(path) = self.recovered.take() else {
+            return;
+        };
+        if restore {
+            match Document::load(&path).and_then(|document| self.load_document(&document)) {
+                Ok(()) => info!("Restored the drawing from `{}`.", path.display()),
+                Err(e) => {
+                    error!("Failed to restore `{}`: {:#}", path.display(), e);
+                    return;
+                }
+            }
+        }
+        if let Err(e) = fs::remove_file(&path) {
+            warn!("Failed to delete `{}`: {}", path.display(), e);
+        }
+    }
+
+    /// Saves the drawing to [`paths::emergency_file`] when the app can't go
+    /// on, after a panic or a GPU hang, to be offered back on the next start
+    pub fn save_emergency(&self) {
+        if self.strokes.is_empty() {
+            return;
+        }
+        let Some(path) = paths::emergency_file() else {
+            return;
+        };
+        let saved = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .map_err(anyhow::Error::from)
+            .and_then(|()| self.document().save(&path));
+        match saved {
+            Ok(()) => error!(
+                "Saved the drawing to `{}`; it will be offered back on the next start.",
+                path.display()
+            ),
+            Err(e) => error!("Failed to save the drawing before exiting: {:#}", e),
+        }
+    }
+
+    /// Snapshot of all committed strokes
+    pub fn document(&self) -> Document {
+        Document {
+            strokes: self
+                .strokes
+                .iter()
+                .filter(|stroke| !stroke.lines.is_empty())
+                .map(|stroke| self.to_stroke(&stroke.lines))
+                .collect(),
+        }
+    }
+
+    /// Number of committed segments in the vertex buffer
+    pub fn line_count(&self) -> usize {
+        self.strokes.line_count()
+    }
+
+    fn clear(&mut self) {
+        if self.line_count() > 0 {
+            let previous = self.strokes.take_all();
+            self.push_revision(Revision::Clear(previous));
+        }
+
+        self.fading.clear();
+        self.new_stroke.clear();
+        self.selection.clear();
+        self.strokes_changed();
+        self.selection_changed();
+        self.renderer.damage_all();
+        self.record(EditCommand::Clear);
+    }
+
+    /// Replaces every committed stroke with `strokes`, e.g. a snapshot of a shared board
+    ///
+    /// The undo history goes too, since its edits were made on the board
+    /// being replaced. Fading strokes and the one being drawn stay.
+    fn replace_all(&mut self, strokes: &[Stroke]) {
+        self.strokes.take_all();
+        self.history.clear();
+        let max_vertices = self.config.vulkan.max_vertices as usize;
+        for stroke in strokes.iter().filter(|s| s.fade_after.is_none()) {
+            let lines = self.committed_lines(stroke);
+            let count = lines.len();
+            if count > 0 && self.strokes.push_within(lines, max_vertices).is_none() {
+                warn!(
+                    "Dropped a stroke of {} segments, the vertex buffer is full (max_vertices is {}).",
+                    count, max_vertices
+                );
+            }
+        }
+
+        self.upload_strokes_from(0);
+        self.strokes_changed();
+        self.retain_selection();
+        self.renderer.damage_all();
+    }
+
+    /// Removes every committed stroke with a segment within [`ERASER_RADIUS`] of `position`
+    unsafe fn erase(&mut self, position: Vec2) -> Result<()> {
+        let hits: Vec<usize> = self
+            .index
+            .query(&Rect::around_point(position, ERASER_RADIUS))
+            .into_iter()
+            .filter(|&i| {
+                !self.is_hidden(i)
+                    && self.strokes[i]
+                        .lines
+                        .iter()
+                        .any(|l| l.distance_to(position) <= ERASER_RADIUS)
+            })
+            .collect();
+
+        self.remove_strokes(&hits)
+    }
+
+    unsafe fn remove_strokes(&mut self, indices: &[usize]) -> Result<()> {
+        let mut indices: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|&i| i < self.strokes.len())
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        let Some(&first) = indices.first() else {
+            return Ok(());
+        };
+
+        let mut removed: Vec<(usize, StoredStroke)> = indices
+            .iter()
+            .rev()
+            .map(|&i| (i, self.strokes.remove(i)))
+            .collect();
+        removed.reverse();
+
+        for (_, stroke) in &removed {
+            if let Some(bounds) = stroke.bounds_with(self.stroke_margin()) {
+                self.renderer.damage(self.camera.rect_to_screen(&bounds));
+            }
+        }
+
+        // Everything after the first removed stroke moves down in the vertex buffer
+        self.upload_strokes_from(first);
+        self.retain_selection();
+        self.strokes_changed();
+        self.selection_changed();
+        self.push_revision(Revision::Remove(removed));
+        self.record(EditCommand::RemoveStrokes(indices));
+
+        Ok(())
+    }
+
+    /// Makes `area` the canvas, at as many pixels as it covers on screen now
+    ///
+    /// Strokes with no segment in it are removed, as one edit that undo
+    /// brings back; strokes crossing its edge stay whole and are cut off by
+    /// the canvas bounds. The bounds themselves stay when the removal is undone.
+    unsafe fn crop(&mut self, area: Rect) -> Result<()> {
+        let outside: Vec<usize> = self
+            .strokes
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| !s.lines.iter().any(|l| area.intersects_line(l)))
+            .map(|(i, _)| i)
+            .collect();
+        self.remove_strokes(&outside)?;
+
+        let extent = self.renderer.canvas_extent;
+        let screen = self.camera.rect_to_screen(&area);
+        let pixels = |ndc: f32, size: u32| ((ndc.abs() / 2.0 * size as f32).round() as u32).max(1);
+        let size = [
+            pixels(screen.max.x - screen.min.x, extent.width),
+            pixels(screen.max.y - screen.min.y, extent.height),
+        ];
+        info!(
+            "Cropped the canvas to {}x{} pixels, removing {} strokes.",
+            size[0],
+            size[1],
+            outside.len()
+        );
+
+        self.crop = Some((area, size));
+        self.config.window.canvas_size = Some(size);
+        self.camera = Camera::framing(&area, size[0], size[1]);
+        self.resized = true;
+        self.renderer.damage_all();
+        Ok(())
+    }
+
+    /// What exports show at `width` x `height`: the cropped canvas after a
+    /// crop, else the canvas around the origin at zoom 1
+    fn export_camera(&self, width: u32, height: u32) -> Camera {
+        match &self.crop {
+            Some((area, _)) => Camera::framing(area, width, height),
+            None => Camera::default().with_viewport(width, height),
+        }
+    }
+
+    /// Selects the strokes with a segment inside `area`
+    fn select(&mut self, area: Rect) {
+        self.selection = self
+            .index
+            .query(&area)
+            .into_iter()
+            .filter(|&i| {
+                !self.is_hidden(i)
+                    && self.strokes[i]
+                        .lines
+                        .iter()
+                        .any(|l| area.intersects_line(l))
+            })
+            .map(|i| self.strokes[i].id)
+            .collect();
+        self.selection_changed();
+    }
+
+    /// Indices (in drawing order) of the selected strokes
+    pub fn selection(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = self
+            .selection
+            .iter()
+            .filter_map(|&id| self.strokes.index_of(id))
+            .collect();
+        indices.sort_unstable();
+        indices
+    }
+
+    /// Selects the strokes at `indices`, e.g. ones just added
+    fn set_selection(&mut self, indices: impl IntoIterator<Item = usize>) {
+        self.selection = indices.into_iter().map(|i| self.strokes[i].id).collect();
+        self.selection_changed();
+    }
+
+    /// Drops strokes that are gone or hidden from the selection, keeping the rest
+    fn retain_selection(&mut self) {
+        let selection = std::mem::take(&mut self.selection);
+        self.selection = selection
+            .into_iter()
+            .filter(|&id| {
+                self.strokes
+                    .index_of(id)
+                    .is_some_and(|i| !self.is_hidden(i))
+            })
+            .collect();
+        self.selection_changed();
+    }
+
+    /// Bounds (in canvas coordinates) of the selected strokes' centerlines, `None` when nothing is selected
+    pub fn selection_bounds(&self) -> Option<Rect> {
+        self.selection
+            .iter()
+            .filter_map(|&id| self.strokes.by_id(id)?.bounds)
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// Tells the active tool where the selected strokes are now
+    fn selection_changed(&mut self) {
+        let bounds = self.selection_bounds();
+        if let Some(tool) = self.tools.active() {
+            tool.selection_changed(bounds);
+        }
+    }
+
+    /// Transforms the selected strokes right away, merging into one edit with
+    /// earlier calls until [`App::commit_transform`]
+    fn transform_selection(&mut self, transform: &Transform) {
+        if self.selection.is_empty() {
+            return;
+        }
+
+        let selection = self.selection();
+        let strokes = &self.strokes;
+        let (_, total) = self.transforming.get_or_insert_with(|| {
+            let before = selection
+                .iter()
+                .map(|&i| (strokes[i].id, strokes[i].lines.clone()))
+                .collect();
+            (before, Transform::default())
+        });
+        *total = total.then(transform);
+
+        self.transform_in_place(&selection, transform);
+    }
+
+    /// Finishes the transform started by [`App::transform_selection`], if any,
+    /// recording it as [`EditCommand::TransformStrokes`]
+    fn commit_transform(&mut self) {
+        if let Some((before, transform)) = self.transforming.take() {
+            let indices = before
+                .iter()
+                .filter_map(|(id, _)| self.strokes.index_of(*id))
+                .collect();
+            self.push_revision(Revision::Transform(before, transform));
+            self.record(EditCommand::TransformStrokes(indices, transform));
+        }
+    }
+
+    fn transform_strokes(&mut self, indices: &[usize], transform: &Transform) {
+        let mut indices: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|&i| i < self.strokes.len())
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.is_empty() {
+            return;
+        }
+
+        let before = indices
+            .iter()
+            .map(|&i| (self.strokes[i].id, self.strokes[i].lines.clone()))
+            .collect();
+        self.transform_in_place(&indices, transform);
+        self.push_revision(Revision::Transform(before, *transform));
+        self.record(EditCommand::TransformStrokes(indices, *transform));
+    }
+
+    /// Applies `transform` to the strokes at `indices` without touching the history
+    fn transform_in_place(&mut self, indices: &[usize], transform: &Transform) {
+        for &i in indices {
+            let lines = self.strokes[i]
+                .lines
+                .iter()
+                .map(|l| transform.apply_line(l))
+                .collect();
+            self.replace_stroke(i, lines);
+        }
+        self.strokes_changed();
+        self.selection_changed();
+    }
+
+    /// Swaps in new segments for stroke `index`, as many as it had, and re-uploads just those
+    fn replace_stroke(&mut self, index: usize, lines: Vec<Line>) {
+        for stroke in [&self.strokes[index].lines, &lines] {
+            if let Some(bounds) = Rect::around_lines(stroke, self.stroke_margin()) {
+                self.renderer.damage(self.camera.rect_to_screen(&bounds));
+            }
+        }
+
+        let first_line = self.strokes[index].range.start as usize;
+        self.upload(first_line, &lines);
+        self.strokes.replace_lines(index, lines);
+    }
+
+    /// The topmost stroke passing within picking distance of `position` (in canvas coordinates)
+    pub fn stroke_at(&self, position: Vec2) -> Option<usize> {
+        self.index
+            .query(&Rect::around_point(position, PICK_RADIUS))
+            .into_iter()
+            .rev()
+            .find(|&i| {
+                !self.is_hidden(i)
+                    && self.strokes[i]
+                        .lines
+                        .iter()
+                        .any(|l| l.distance_to(position) <= PICK_RADIUS)
+            })
+    }
+
+    /// Files the strokes at `indices` under `tag`, or untags them with `None`
+    fn tag_strokes(&mut self, indices: &[usize], tag: Option<&str>) {
+        let mut indices: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|&i| i < self.strokes.len())
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.is_empty() {
+            return;
+        }
+
+        let bit = tag.map_or(0, |name| self.tag_bit(name));
+        let before = indices
+            .iter()
+            .map(|&i| (self.strokes[i].id, self.strokes[i].lines.clone()))
+            .collect();
+        for &i in &indices {
+            self.retag_stroke(i, bit);
+        }
+        self.strokes_changed();
+        self.deselect_hidden();
+        self.push_revision(Revision::Tag(before, bit));
+        self.record(EditCommand::TagStrokes(indices, tag.map(str::to_string)));
+    }
+
+    /// Sets the tag bit of every segment of stroke `index` and re-uploads it
+    fn retag_stroke(&mut self, index: usize, bit: u32) {
+        let lines = self.strokes[index]
+            .lines
+            .iter()
+            .map(|&l| Line { tag: bit, ..l })
+            .collect();
+        self.replace_stroke(index, lines);
+    }
+
+    /// The bit of the tag called `name`, taking the next free one for a new tag
+    ///
+    /// Past [`MAX_TAGS`] new tags get no bit and their strokes stay untagged.
+    fn tag_bit(&mut self, name: &str) -> u32 {
+        let index = match self.tags.iter().position(|t| t == name) {
+            Some(index) => index,
+            None if self.tags.len() < MAX_TAGS => {
+                self.tags.push(name.to_string());
+                self.tags.len() - 1
+            }
+            None => {
+                warn!(
+                    "Only {} tags are supported, not tagging strokes `{}`.",
+                    MAX_TAGS, name
+                );
+                return 0;
+            }
+        };
+        1 << index
+    }
+
+    /// The stroke as saved or sent to peers, with its tag's and brush's names
+    fn to_stroke(&self, lines: &[Line]) -> Stroke {
+        let tag = lines.first().map_or(0, |l| l.tag);
+        let brush = lines.first().map_or(0, Line::brush);
+        Stroke {
+            tag: (tag != 0).then(|| self.tags[tag.trailing_zeros() as usize].clone()),
+            brush: self.config.shaders.brush_name(brush).map(str::to_string),
+            ..Stroke::from_lines(lines)
+        }
+    }
+
+    /// Bits of the tags not drawn right now
+    fn hidden_tag_bits(&self) -> u32 {
+        if self.tags_revealed {
+            0
+        } else {
+            self.hidden_tags
+        }
+    }
+
+    /// Whether stroke `index` is filed under a hidden tag, which also keeps
+    /// it from being erased, selected or picked
+    fn is_hidden(&self, index: usize) -> bool {
+        self.strokes[index].tag & self.hidden_tag_bits() != 0
+    }
+
+    /// Names of the tags strokes were filed under this session, in the order they came up
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Shows or hides the strokes filed under `tag`, ending [`App::toggle_revealed_tags`]
+    ///
+    /// Visibility is local, like the selection, and not part of the undo history.
+    pub fn set_tag_visible(&mut self, tag: &str, visible: bool) {
+        let bit = self.tag_bit(tag);
+        if visible {
+            self.hidden_tags &= !bit;
+        } else {
+            self.hidden_tags |= bit;
+        }
+        self.tags_revealed = false;
+        self.tags_visibility_changed();
+    }
+
+    /// Shows every hidden tag for now, or hides them again, e.g. to reveal answers
+    pub fn toggle_revealed_tags(&mut self) {
+        self.tags_revealed = !self.tags_revealed;
+        self.tags_visibility_changed();
+    }
+
+    fn tags_visibility_changed(&mut self) {
+        self.deselect_hidden();
+        self.renderer.damage_all();
+    }
+
+    /// Drops hidden strokes from the selection, since they can't be seen or moved
+    fn deselect_hidden(&mut self) {
+        self.retain_selection();
+    }
+
+    /// Tag strokes drawn from now on are filed under, `None` for untagged strokes
+    pub fn brush_tag(&self) -> Option<&str> {
+        self.brush_tag.as_deref()
+    }
+
+    pub fn set_brush_tag(&mut self, tag: Option<String>) {
+        if let Some(name) = &tag {
+            self.tag_bit(name);
+        }
+        self.brush_tag = tag;
+    }
+
+    /// Name of the `[shaders.brushes]` entry strokes are drawn with, `None` for the default shader
+    pub fn brush_shader(&self) -> Option<&str> {
+        self.config.shaders.brush_name(self.brush_shader)
+    }
+
+    /// Draws strokes from now on with the `[shaders.brushes]` entry called
+    /// `name`, or the default shader for `None` or a name there is no brush for
+    pub fn set_brush_shader(&mut self, name: Option<&str>) {
+        let index = name.and_then(|name| self.config.shaders.brush_index(name));
+        if let (Some(name), None) = (name, index) {
+            warn!("No brush `{}` in `[shaders.brushes]`.", name);
+        }
+        self.brush_shader = index.unwrap_or(0);
+    }
+
+    /// Whether the animated background is drawn; always false without a `[background] shader`
+    pub fn shows_background(&self) -> bool {
+        self.show_background && self.renderer.has_background()
+    }
+
+    /// Shows or hides the animated background, which stays off for now
+    /// if there is no `[background] shader` to show
+    pub fn toggle_background(&mut self) {
+        self.show_background = !self.show_background;
+        if self.show_background && !self.renderer.has_background() {
+            warn!("No animated background to show, set `[background] shader`.");
+        }
+        self.renderer.damage_all();
+    }
+
+    /// Seconds the animated background has run, `None` while there is none to draw
+    fn background_time(&self) -> Option<f32> {
+        self.shows_background()
+            .then(|| self.clock() * self.config.background.speed)
+    }
+
+    /// Switches to the next brush in `[shaders.brushes]`, after the last one back to the default shader
+    fn cycle_brush_shader(&mut self) {
+        let count = self.config.shaders.brushes.len() as u32 + 1;
+        self.brush_shader = (self.brush_shader + 1) % count;
+    }
+
+    /// Copies the selected strokes, keeping the previous copy if nothing is selected
+    pub fn copy_selection(&mut self) {
+        if self.selection.is_empty() {
+            return;
+        }
+
+        self.clipboard = self
+            .selection()
+            .into_iter()
+            .map(|i| self.to_stroke(&self.strokes[i].lines))
+            .collect();
+    }
+
+    /// Adds the copied strokes centered on `position` (in canvas coordinates) and selects them
+    ///
+    /// Each pasted stroke is its own edit, so undo takes them back one at a time.
+    pub unsafe fn paste_at(&mut self, position: Vec2) -> Result<bool> {
+        let lines: Vec<Line> = self.clipboard.iter().flat_map(|s| s.lines()).collect();
+        let Some(bounds) = Rect::around_lines(&lines, 0.0) else {
+            return Ok(false);
+        };
+
+        let offset = Transform::translation(position - (bounds.min + bounds.max) / 2.0);
+        let first = self.strokes.len();
+        let edits = self
+            .clipboard
+            .iter()
+            .map(|stroke| EditCommand::AddStroke(stroke.transformed(&offset)))
+            .collect();
+        self.apply_all(edits)?;
+        self.set_selection(first..self.strokes.len());
+
+        Ok(true)
+    }
+
+    /// Adds a copy of each selected stroke [`DUPLICATE_OFFSET`] down and to
+    /// the right, and selects the copies, leaving the clipboard alone
+    ///
+    /// Like pasting, each copy is its own edit.
+    pub unsafe fn duplicate_selection(&mut self) -> Result<bool> {
+        if self.selection.is_empty() {
+            return Ok(false);
+        }
+
+        let offset = Transform::translation(Vec2::new(DUPLICATE_OFFSET, DUPLICATE_OFFSET));
+        let first = self.strokes.len();
+        let edits = self
+            .selection()
+            .into_iter()
+            .map(|i| {
+                EditCommand::AddStroke(self.to_stroke(&self.strokes[i].lines).transformed(&offset))
+            })
+            .collect();
+        self.apply_all(edits)?;
+        self.set_selection(first..self.strokes.len());
+
+        Ok(true)
+    }
+
+    /// Renders the committed strokes offscreen at the canvas size and writes a PNG
+    ///
+    /// After a crop the image covers the cropped canvas. `[export]` can scale
+    /// it or have it cover every stroke instead, drawn in tiles when large.
+    pub unsafe fn export_png(&mut self, path: &Path) -> Result<()> {
+        self.renderer.uploads.flush(&self.context)?;
+        let canvas = self.renderer.canvas_extent;
+        let drawing = self
+            .strokes
+            .iter()
+            .filter_map(|s| s.bounds_with(STROKE_MARGIN))
+            .reduce(|a, b| a.union(&b));
+        let view = ExportView::new(
+            &self.config.export,
+            [canvas.width, canvas.height],
+            &self.export_camera(canvas.width, canvas.height),
+            drawing,
+        );
+
+        let batches = [(self.vertex_buffer, 0..self.line_count() as u32)];
+        write_tiled_png(
+            &self.context,
+            &self.config,
+            &view,
+            self.theme.background,
+            path,
+            |offscreen, camera| {
+                offscreen.render_view(
+                    &self.context,
+                    self.geometry_buffer,
+                    self.geometry_index_buffer,
+                    camera,
+                    &StrokeStyle::default(),
+                    &batches,
+                )
+            },
+        )?;
+        info!("Exported canvas to `{}`.", path.display());
+
+        Ok(())
+    }
+
+    /// Renders every visible committed stroke small for the minimap, when
+    /// they (or how they are drawn) changed since the last time
+    unsafe fn refresh_minimap(&mut self) -> Result<()> {
+        let hidden_tags = self.hidden_tag_bits();
+        let drawn = (self.stroke_generation, hidden_tags, self.theme.background);
+        if self.minimap_drawn == Some(drawn) {
+            return Ok(());
+        }
+        self.minimap_drawn = Some(drawn);
+
+        let bounds = self
+            .strokes
+            .iter()
+            .filter(|s| s.tag & hidden_tags == 0)
+            .filter_map(|s| s.bounds_with(STROKE_MARGIN))
+            .reduce(|a, b| a.union(&b));
+        let Some(bounds) = bounds else {
+            self.ui.set_minimap(None);
+            return Ok(());
+        };
+        // A little room around the drawing, so strokes at its edges aren't cut off
+        let size = bounds.max - bounds.min;
+        let bounds = bounds.grown(0.05 * size.x.max(size.y));
+
+        let scaled = |points: f32| (points * self.scale_factor as f32).round().max(1.0) as u32;
+        let (width, height) = (scaled(MINIMAP_SIZE[0]), scaled(MINIMAP_SIZE[1]));
+        let extent = vk::Extent2D { width, height };
+        if self.minimap.as_ref().map(|m| m.extent) != Some(extent) {
+            self.minimap = Some(OffscreenRenderer::create(
+                &self.context,
+                &self.config,
+                width,
+                height,
+                false,
+            )?);
+        }
+
+        self.renderer.uploads.flush(&self.context)?;
+        let line_count = self.line_count() as u32;
+        let camera = Camera::framing(&bounds, width, height);
+        // At least a pixel wide, however small the drawing is shown
+        let style = StrokeStyle {
+            thickness: STROKE_THICKNESS.max(1.0 / (height as f32 * camera.zoom)),
+            hidden_tags,
+            ..StrokeStyle::default()
+        };
+        let minimap = self.minimap.as_mut().unwrap();
+        minimap.background = self.theme.background;
+        minimap.set_shader_params(&self.config);
+        let pixels = minimap.render_view(
+            &self.context,
+            self.geometry_buffer,
+            self.geometry_index_buffer,
+            &camera,
+            &style,
+            &[(self.vertex_buffer, 0..line_count)],
+        )?;
+        self.ui.set_minimap(Some((
+            [width as usize, height as usize],
+            &pixels,
+            camera.visible_rect(),
+        )));
+
+        Ok(())
+    }
+
+    /// Writes the timings of the latest frames to the log directory (or the
+    /// working directory if there is none), in `[stats] format`
+    pub fn save_frame_stats(&self) -> Result<()> {
+        let directory = paths::log_dir().unwrap_or_default();
+        let path = self
+            .frame_stats
+            .save(&directory, self.config.stats.format)?;
+        info!(
+            "Saved the timings of {} frames to `{}`.",
+            self.frame_stats.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Renders the canvas as it is on screen, without the UI or the stroke
+    /// being drawn, and puts the image on the system clipboard
+    pub unsafe fn copy_screenshot(&mut self) -> Result<()> {
+        self.renderer.uploads.flush(&self.context)?;
+        let extent = self.renderer.canvas_extent;
+        let batches = [
+            (self.vertex_buffer, 0..self.line_count() as u32),
+            (self.fade_buffer, 0..self.fading_line_count() as u32),
+        ];
+
+        let mut offscreen = OffscreenRenderer::create(
+            &self.context,
+            &self.config,
+            extent.width,
+            extent.height,
+            false,
+        )?;
+        offscreen.background = self.background();
+        let pixels = offscreen.render_view(
+            &self.context,
+            self.geometry_buffer,
+            self.geometry_index_buffer,
+            &self.camera,
+            &self.stroke_style(),
+            &batches,
+        );
+        drop(offscreen);
+
+        let clipboard = match &mut self.system_clipboard {
+            Some(clipboard) => clipboard,
+            None => self
+                .system_clipboard
+                .insert(arboard::Clipboard::new().context("Failed to open the clipboard")?),
+        };
+        clipboard
+            .set_image(arboard::ImageData {
+                width: extent.width as usize,
+                height: extent.height as usize,
+                bytes: pixels?.into(),
+            })
+            .context("Failed to put the screenshot on the clipboard")?;
+        info!(
+            "Copied a {}x{} screenshot to the clipboard.",
+            extent.width, extent.height
+        );
+
+        Ok(())
+    }
+
+    /// The undo stack, oldest first, followed by the edits undone since
+    fn history(&self) -> Vec<HistoryEntry> {
+        self.history
+            .iter()
+            .enumerate()
+            .map(|(i, (revision, undone))| HistoryEntry {
+                label: revision.label(&self.tags),
+                applied: i + 1,
+                undone,
+            })
+            .collect()
+    }
+
+    /// Undoes or redoes edits, one [`EditCommand`] at a time so peers and
+    /// recordings follow along, until `applied` of them are left applied
+    unsafe fn roll_back(&mut self, applied: usize) -> Result<()> {
+        while self.history.undo_len() > applied {
+            self.apply(EditCommand::Undo)?;
+        }
+        while self.history.undo_len() < applied && self.history.redo_len() > 0 {
+            self.apply(EditCommand::Redo)?;
+        }
+
+        Ok(())
+    }
+
+    unsafe fn undo(&mut self) -> Result<()> {
+        let revision = match self.history.pop_undo() {
+            Some(revision) => revision,
+            None => return Ok(()),
+        };
+
+        let restored = match &revision {
+            Revision::Remove(removed) => removed.iter().map(|(_, s)| s.lines.len()).sum(),
+            Revision::Clear(previous) => previous.iter().map(|s| s.lines.len()).sum(),
+            _ => 0,
+        };
+        if !self.fits(restored) {
+            warn!(
+                "Can't undo, the strokes it brings back don't fit into the vertex buffer (max_vertices is {}).",
+                self.config.vulkan.max_vertices
+            );
+            self.history.redone(revision);
+            return Ok(());
+        }
+
+        // Peers get the edit that reverts it, since their history isn't ours.
+        // Their edits may have moved or removed our strokes since, so strokes
+        // are looked up by id, and the revision kept for redo is brought up to date.
+        let (revision, inverse) = match revision {
+            Revision::Stroke(mut pieces) => {
+                let mut found: Vec<(usize, usize)> = pieces
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, piece)| {
+                        self.strokes.index_of(piece.id).map(|index| (index, i))
+                    })
+                    .collect();
+                found.sort_unstable();
+                for &(index, i) in found.iter().rev() {
+                    let stroke = self.strokes.remove(index);
+                    if let Some(bounds) = stroke.bounds_with(self.stroke_margin()) {
+                        self.renderer.damage(self.camera.rect_to_screen(&bounds));
+                    }
+                    pieces[i] = stroke;
+                }
+                let indices: Vec<usize> = found.iter().map(|&(index, _)| index).collect();
+                if let Some(&first) = indices.first() {
+                    self.upload_strokes_from(first);
+                }
+                let inverse = (!indices.is_empty()).then_some(EditCommand::RemoveStrokes(indices));
+                (Revision::Stroke(pieces), inverse)
+            }
+            Revision::Remove(removed) => {
+                let mut inserted = Vec::with_capacity(removed.len());
+                for (i, stroke) in &removed {
+                    if let Some(bounds) = stroke.bounds_with(self.stroke_margin()) {
+                        self.renderer.damage(self.camera.rect_to_screen(&bounds));
+                    }
+                    let index = (*i).min(self.strokes.len());
+                    self.strokes.insert(index, stroke.clone());
+                    inserted.push((index, self.to_stroke(&stroke.lines)));
+                }
+                if let Some(&(first, _)) = inserted.first() {
+                    self.upload_strokes_from(first);
+                }
+                let inverse =
+                    (!inserted.is_empty()).then_some(EditCommand::InsertStrokes(inserted));
+                (Revision::Remove(removed), inverse)
+            }
+            Revision::Transform(before, transform) => {
+                let inverse = self.restore_lines(&before);
+                (Revision::Transform(before, transform), inverse)
+            }
+            Revision::Tag(before, bit) => {
+                let inverse = self.restore_lines(&before);
+                (Revision::Tag(before, bit), inverse)
+            }
+            Revision::Clear(previous) => {
+                self.strokes.restore_all(previous.clone());
+                self.upload_strokes_from(0);
+                self.renderer.damage_all();
+                let inserted = previous
+                    .iter()
+                    .enumerate()
+                    .map(|(i, stroke)| (i, self.to_stroke(&stroke.lines)))
+                    .collect();
+                (
+                    Revision::Clear(previous),
+                    Some(EditCommand::InsertStrokes(inserted)),
+                )
+            }
+        };
+
+        self.strokes_changed();
+        self.retain_selection();
+        self.history.undone(revision);
+        if let Some(inverse) = inverse {
+            self.record(inverse);
+        }
+        Ok(())
+    }
+
+    /// Puts back the segments strokes had before a transform or tagging,
+    /// skipping strokes that are gone, and returns the edit doing the same
+    fn restore_lines(&mut self, before: &[(StrokeId, Vec<Line>)]) -> Option<EditCommand> {
+        let mut replaced = Vec::with_capacity(before.len());
+        for (id, lines) in before {
+            let Some(index) = self.strokes.index_of(*id) else {
+                continue;
+            };
+            if self.strokes[index].lines.len() != lines.len() {
+                continue;
+            }
+            self.replace_stroke(index, lines.clone());
+            replaced.push((index, self.to_stroke(lines)));
+        }
+        replaced.sort_unstable_by_key(|(index, _)| *index);
+        (!replaced.is_empty()).then_some(EditCommand::ReplaceStrokes(replaced))
+    }
+
+    unsafe fn redo(&mut self) -> Result<()> {
+        let revision = match self.history.pop_redo() {
+            Some(revision) => revision,
+            None => return Ok(()),
+        };
+
+        if let Revision::Stroke(pieces) = &revision {
+            if !self.fits(pieces.iter().map(|s| s.lines.len()).sum()) {
+                warn!(
+                    "Can't redo, the stroke doesn't fit into the vertex buffer (max_vertices is {}).",
+                    self.config.vulkan.max_vertices
+                );
+                self.history.undone(revision);
+                return Ok(());
+            }
+        }
+
+        let (revision, edit) = match revision {
+            Revision::Stroke(pieces) => {
+                let mut inserted = Vec::with_capacity(pieces.len());
+                for stroke in &pieces {
+                    self.upload(self.line_count(), &stroke.lines);
+                    if let Some(bounds) = stroke.bounds_with(self.stroke_margin()) {
+                        self.renderer.damage(self.camera.rect_to_screen(&bounds));
+                    }
+                    let index = self.strokes.len();
+                    self.strokes.insert(index, stroke.clone());
+                    inserted.push((index, self.to_stroke(&stroke.lines)));
+                }
+                let edit = if inserted.len() == 1 {
+                    EditCommand::AddStroke(inserted.remove(0).1)
+                } else {
+                    EditCommand::InsertStrokes(inserted)
+                };
+                (Revision::Stroke(pieces), Some(edit))
+            }
+            Revision::Remove(removed) => {
+                let mut indices: Vec<usize> = removed
+                    .iter()
+                    .filter_map(|(_, stroke)| self.strokes.index_of(stroke.id))
+                    .collect();
+                indices.sort_unstable();
+                let mut removed: Vec<(usize, StoredStroke)> = indices
+                    .iter()
+                    .rev()
+                    .map(|&i| (i, self.strokes.remove(i)))
+                    .collect();
+                removed.reverse();
+                for (_, stroke) in &removed {
+                    if let Some(bounds) = stroke.bounds_with(self.stroke_margin()) {
+                        self.renderer.damage(self.camera.rect_to_screen(&bounds));
+                    }
+                }
+                if let Some(&first) = indices.first() {
+                    self.upload_strokes_from(first);
+                }
+                let edit = (!indices.is_empty()).then_some(EditCommand::RemoveStrokes(indices));
+                (Revision::Remove(removed), edit)
+            }
+            Revision::Transform(before, transform) => {
+                let (before, indices) = self.current_lines(&before);
+                for &i in &indices {
+                    let lines = self.strokes[i]
+                        .lines
+                        .iter()
+                        .map(|l| transform.apply_line(l))
+                        .collect();
+                    self.replace_stroke(i, lines);
+                }
+                let edit = (!indices.is_empty())
+                    .then_some(EditCommand::TransformStrokes(indices, transform));
+                (Revision::Transform(before, transform), edit)
+            }
+            Revision::Tag(before, tag) => {
+                let (before, indices) = self.current_lines(&before);
+                for &i in &indices {
+                    self.retag_stroke(i, tag);
+                }
+                let name = (tag != 0).then(|| self.tags[tag.trailing_zeros() as usize].clone());
+                let edit = (!indices.is_empty()).then_some(EditCommand::TagStrokes(indices, name));
+                (Revision::Tag(before, tag), edit)
+            }
+            Revision::Clear(_) => {
+                let previous = self.strokes.take_all();
+                self.renderer.damage_all();
+                (Revision::Clear(previous), Some(EditCommand::Clear))
+            }
+        };
+
+        self.strokes_changed();
+        self.retain_selection();
+        self.history.redone(revision);
+        if let Some(edit) = edit {
+            self.record(edit);
+        }
+        Ok(())
+    }
+
+    /// The strokes of a transform or tagging that are still there, with
+    /// their segments as they are now, and their indices, ascending
+    fn current_lines(
+        &self,
+        before: &[(StrokeId, Vec<Line>)],
+    ) -> (Vec<(StrokeId, Vec<Line>)>, Vec<usize>) {
+        let mut current: Vec<(usize, StrokeId)> = before
+            .iter()
+            .filter_map(|(id, _)| self.strokes.index_of(*id).map(|i| (i, *id)))
+            .collect();
+        current.sort_unstable();
+        let lines = current
+            .iter()
+            .map(|&(i, id)| (id, self.strokes[i].lines.clone()))
+            .collect();
+        (lines, current.into_iter().map(|(i, _)| i).collect())
+    }
+
+    /// Whether `lines` more segments fit into the vertex buffer
+    fn fits(&self, lines: usize) -> bool {
+        self.line_count() + lines <= self.config.vulkan.max_vertices as usize
+    }
+
+    /// The committed strokes to draw this frame, as ranges of line buffers
+    ///
+    /// Strokes are drawn in full from the vertex buffer, skipping those the
+    /// stroke index places outside the view. Far zoomed out (below
+    /// [`LOD_MAX_ZOOM`]) each stroke is instead drawn at the level of detail
+    /// that keeps its segments a few pixels long, gathered in `lod_buffer`.
+    /// That buffer is only refilled when a stroke changes level or after an
+    /// edit, and is drawn whole since most strokes are in view at that zoom.
+    unsafe fn drawn_lines(&mut self) -> Result<Vec<(vk::Buffer, Range<u32>)>> {
+        let view = self.camera.visible_rect();
+        let margin = self.stroke_margin() / self.camera.zoom;
+        let visible = Rect {
+            min: view.min - Vec2::new(margin, margin),
+            max: view.max + Vec2::new(margin, margin),
+        };
+        let full: Vec<_> = self
+            .index
+            .line_ranges(&visible)
+            .into_iter()
+            .map(|lines| (self.vertex_buffer, lines))
+            .collect();
+
+        if self.camera.zoom >= LOD_MAX_ZOOM {
+            self.lod_levels = None;
+            return Ok(full);
+        }
+
+        let strokes = &self.strokes;
+        let lods = self
+            .lods
+            .get_or_insert_with(|| strokes.iter().map(|s| StrokeLod::new(&s.lines)).collect());
+
+        // A canvas unit spans half the window height at zoom 1
+        let pixels_per_unit = self.camera.zoom * self.renderer.render_extent.height as f32 / 2.0;
+        let levels: Vec<usize> = lods.iter().map(|l| l.level_for(pixels_per_unit)).collect();
+        if levels.iter().all(|&level| level == 0) {
+            self.lod_levels = None;
+            return Ok(full);
+        }
+
+        if self.lod_levels.as_ref() != Some(&levels) {
+            let simplified: Vec<Line> = lods
+                .iter()
+                .zip(strokes)
+                .zip(&levels)
+                .flat_map(|((lod, full), &level)| lod.lines(level, &full.lines).iter().copied())
+                .collect();
+            self.upload_into(self.lod_buffer, 0, &simplified);
+            self.lod_line_count = simplified.len() as u32;
+            self.lod_levels = Some(levels);
+            self.renderer.damage_all();
+        }
+
+        Ok(vec![(self.lod_buffer, 0..self.lod_line_count)])
+    }
+
+    /// Rebuilds the stroke index and drops the levels of detail after the committed strokes changed
+    fn strokes_changed(&mut self) {
+        self.stroke_generation += 1;
+        self.index = StrokeIndex::new(&self.strokes);
+        self.lods = None;
+        self.lod_levels = None;
+    }
+
+    /// Queues `lines` for the vertex buffer starting at segment `first_line`
+    fn upload(&mut self, first_line: usize, lines: &[Line]) {
+        self.upload_into(self.vertex_buffer, first_line, lines)
+    }
+
+    /// Queues `lines` for `destination` starting at segment `first_line`
+    ///
+    /// The copy is recorded into the next frame's command buffer, ahead of
+    /// the draws that read it.
+    fn upload_into(&mut self, destination: vk::Buffer, first_line: usize, lines: &[Line]) {
+        self.stroke_generation += 1;
+        self.renderer.uploads.queue(destination, first_line, lines);
+    }
+
+    /// Re-uploads the strokes from index `first` on, after earlier strokes changed
+    fn upload_strokes_from(&mut self, first: usize) {
+        let (first_line, lines) = self.strokes.lines_from(first);
+        self.upload(first_line, &lines)
+    }
+}
+
+/// The brush shader `[brush] shader` names, 0 (the default shader) without one
+fn brush_index(config: &Config) -> u32 {
+    let name = config.brush.shader.as_deref();
+    name.and_then(|name| config.shaders.brush_index(name))
+        .unwrap_or(0)
+}
+
+/// The snapping step (in radians) per `brush`, flipped while Shift is held
+fn angle_snap_step(brush: &BrushConfig, shift_held: bool) -> Option<f32> {
+    (brush.angle_snap != shift_held).then(|| brush.angle_snap_degrees.to_radians())
+}
+
+/// Destroys the scene buffers; the renderer and then the context drop right after
+impl Drop for App {
+    fn drop(&mut self) {
+        // The strokes are plain CPU data, so they can still be saved while a panic unwinds
+        if std::thread::panicking() {
+            self.save_emergency();
+        }
+
+        let device = &self.context.device;
+        unsafe {
+            let watchdog = Watchdog::arm(
+                "its work before shutting down",
+                self.config.vulkan.gpu_timeout(),
+                self.renderer.diagnostics(None),
+            );
+            if let Err(e) = device.device_wait_idle() {
+                warn!("Failed to wait for the GPU before shutting down: {}", e);
+            }
+            drop(watchdog);
+
+            // Freeing the memory unmaps the persistently mapped staging buffer
+            (self.staging_buffer, self.staging_buffer_memory).destroy(device);
+            (self.vertex_buffer, self.vertex_buffer_memory).destroy(device);
+            (self.lod_buffer, self.lod_buffer_memory).destroy(device);
+            (self.fade_buffer, self.fade_buffer_memory).destroy(device);
+            (self.geometry_buffer, self.geometry_buffer_memory).destroy(device);
+            (
+                self.geometry_index_buffer,
+                self.geometry_index_buffer_memory,
+            )
+                .destroy(device);
+        }
     }
 }
@@ -0,0 +1,133 @@
+use crate::types::{Rect, Vec2, Vec3};
+
+/// Zoom limits, so the canvas can't vanish or blow up past float precision
+pub const MIN_ZOOM: f32 = 0.05;
+pub const MAX_ZOOM: f32 = 50.0;
+
+/// 2D view onto the canvas
+///
+/// Strokes are stored in canvas coordinates, where one unit is the same
+/// length on both axes; the camera maps them to screen NDC as
+/// `screen = canvas * zoom * (1 / aspect, 1) + offset`, which is the
+/// transform `shader.vert` applies from its push constants. At zoom 1 the
+/// window height spans the canvas from -1 to 1, so circles stay round
+/// whatever the window's shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Camera {
+    /// Screen position (in NDC) of the canvas origin
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::vec2_serde"))]
+    pub offset: Vec2,
+    /// Screen units per canvas unit, vertically
+    pub zoom: f32,
+    /// Width over height of the viewport the camera draws into
+    pub aspect: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::new(0.0, 0.0),
+            zoom: 1.0,
+            aspect: 1.0,
+        }
+    }
+}
+
+impl Camera {
+    pub fn to_canvas(&self, screen: Vec2) -> Vec2 {
+        let relative = screen - self.offset;
+        Vec2::new(relative.x * self.aspect, relative.y) / self.zoom
+    }
+
+    pub fn to_screen(&self, canvas: Vec2) -> Vec2 {
+        let scaled = canvas * self.zoom;
+        Vec2::new(scaled.x / self.aspect, scaled.y) + self.offset
+    }
+
+    /// The same view drawn into a `width` x `height` viewport
+    pub fn with_viewport(&self, width: u32, height: u32) -> Self {
+        Self {
+            aspect: width.max(1) as f32 / height.max(1) as f32,
+            ..*self
+        }
+    }
+
+    /// A view of all of `rect`, centered in a `width` x `height` viewport
+    ///
+    /// Zoom isn't limited to [`MIN_ZOOM`], so any drawing fits.
+    pub fn framing(rect: &Rect, width: u32, height: u32) -> Self {
+        let aspect = width.max(1) as f32 / height.max(1) as f32;
+        let size = rect.max - rect.min;
+        let zoom = (2.0 / size.y).min(2.0 * aspect / size.x);
+        let center = (rect.min + rect.max) / 2.0;
+        Self {
+            offset: -Vec2::new(center.x * zoom / aspect, center.y * zoom),
+            zoom: if zoom.is_finite() { zoom } else { 1.0 },
+            aspect,
+        }
+    }
+
+    /// What `size` pixels from `origin` show of this view drawn into
+    /// `width` x `height` pixels, as a view filling them
+    ///
+    /// The pixels may reach past the view, e.g. for tiles at its edges.
+    pub fn tile(&self, [width, height]: [u32; 2], origin: [u32; 2], size: [u32; 2]) -> Self {
+        let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+        let (tile_width, tile_height) = (size[0].max(1) as f32, size[1].max(1) as f32);
+        // The tile's center in this view's NDC
+        let center = Vec2::new(
+            (2.0 * origin[0] as f32 + tile_width) / width - 1.0,
+            (2.0 * origin[1] as f32 + tile_height) / height - 1.0,
+        );
+        let (scale_x, scale_y) = (width / tile_width, height / tile_height);
+        Self {
+            offset: Vec2::new(
+                (self.offset.x - center.x) * scale_x,
+                (self.offset.y - center.y) * scale_y,
+            ),
+            zoom: self.zoom * scale_y,
+            aspect: self.aspect * scale_y / scale_x,
+        }
+    }
+
+    /// The view moved by `delta` in screen NDC
+    pub fn panned(&self, delta: Vec2) -> Self {
+        Self {
+            offset: self.offset + delta,
+            ..*self
+        }
+    }
+
+    /// The view zoomed by `factor`, keeping the canvas point under `anchor` (in screen NDC) in place
+    pub fn zoomed_around(&self, anchor: Vec2, factor: f32) -> Self {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let factor = zoom / self.zoom;
+        Self {
+            offset: anchor - (anchor - self.offset) * factor,
+            zoom,
+            ..*self
+        }
+    }
+
+    /// Canvas rectangle visible on screen
+    pub fn visible_rect(&self) -> Rect {
+        Rect::from_corners(
+            self.to_canvas(Vec2::new(-1.0, -1.0)),
+            self.to_canvas(Vec2::new(1.0, 1.0)),
+        )
+    }
+
+    /// Screen rectangle covered by a canvas rectangle
+    pub fn rect_to_screen(&self, rect: &Rect) -> Rect {
+        Rect {
+            min: self.to_screen(rect.min),
+            max: self.to_screen(rect.max),
+        }
+    }
+
+    /// The `(offset_x, offset_y, scale)` push constant of the stroke pipeline
+    pub fn push_constant(&self) -> Vec3 {
+        Vec3::new(self.offset.x, self.offset.y, self.zoom)
+    }
+}
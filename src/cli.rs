@@ -0,0 +1,178 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use toml::{Table, Value};
+
+use scribble_vk::config::{parse_value, set_override};
+use scribble_vk::paths;
+use scribble_vk::Config;
+
+/// A Vulkan line-drawing canvas
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Configuration file to use instead of `config.toml`
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Index of the GPU to render with
+    #[arg(long, value_name = "INDEX")]
+    pub gpu: Option<usize>,
+
+    /// Start in borderless fullscreen
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Annotate the screen in a transparent window on top of everything
+    #[arg(long)]
+    pub overlay: bool,
+
+    /// Drawing to open on startup
+    #[arg(long, value_name = "FILE")]
+    pub open: Option<PathBuf>,
+
+    /// Render a drawing to a PNG without opening a window, then exit
+    #[arg(long, num_args = 2, value_names = ["IN", "OUT"])]
+    pub headless_export: Option<Vec<PathBuf>>,
+
+    /// Draw together with the peer at ADDR (host:port)
+    #[arg(long, value_name = "ADDR", conflicts_with = "listen")]
+    pub connect: Option<String>,
+
+    /// Only watch the board at --connect; local input doesn't edit it
+    #[arg(long, requires = "connect")]
+    pub spectate: bool,
+
+    /// Wait on PORT for a peer to connect and draw together
+    #[arg(long, value_name = "PORT")]
+    pub listen: Option<u16>,
+
+    /// Serve a shared board on PORT without a window (seeded from --open)
+    #[arg(long, value_name = "PORT", conflicts_with_all = ["connect", "listen", "headless_export"])]
+    pub host: Option<u16>,
+
+    /// Monitor to open on, by index or (part of) its name
+    #[arg(long, value_name = "INDEX|NAME")]
+    pub monitor: Option<String>,
+
+    /// Window position in pixels from the monitor's top left corner
+    #[arg(long, value_name = "X,Y", value_parser = parse_position)]
+    pub position: Option<(i32, i32)>,
+
+    /// Window size (also the headless export resolution without a `window.canvas_size`)
+    #[arg(long, value_name = "WxH", value_parser = parse_size)]
+    pub size: Option<(u32, u32)>,
+
+    /// Override any config key, e.g. `--set vulkan.max_vertices=200000` (repeatable)
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_setting)]
+    pub settings: Vec<(String, String)>,
+}
+
+/// Things to do instead of opening the canvas
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Print the Vulkan devices, queue families and surface support, then exit
+    Info,
+    /// Convert a drawing into a script that draws it again, then exit
+    ExportScript {
+        /// Drawing to convert
+        input: PathBuf,
+        /// Script to write, e.g. `drawing.rhai`
+        output: PathBuf,
+    },
+}
+
+impl Args {
+    /// The configuration file in use, whether or not it exists
+    pub fn config_path(&self) -> PathBuf {
+        self.config.clone().unwrap_or_else(paths::config_file)
+    }
+
+    /// Loads the configuration with the command line flags as the topmost layer
+    pub fn config(&self) -> Result<Config> {
+        let mut overrides = Table::new();
+
+        for (key, value) in &self.settings {
+            set_override(&mut overrides, key, parse_value(value));
+        }
+
+        if let Some(gpu) = self.gpu {
+            set_override(&mut overrides, "vulkan.gpu", Value::from(gpu as i64));
+        }
+
+        if self.fullscreen {
+            set_override(&mut overrides, "window.fullscreen", Value::from(true));
+        }
+
+        if self.overlay {
+            set_override(&mut overrides, "window.overlay", Value::from(true));
+        }
+
+        if let Some(monitor) = &self.monitor {
+            let monitor = match monitor.parse::<i64>() {
+                Ok(index) => Value::from(index),
+                Err(_) => Value::from(monitor.as_str()),
+            };
+            set_override(&mut overrides, "window.monitor", monitor);
+        }
+
+        if let Some((x, y)) = self.position {
+            let position = vec![Value::from(x as i64), Value::from(y as i64)];
+            set_override(&mut overrides, "window.position", Value::Array(position));
+        }
+
+        if let Some((width, height)) = self.size {
+            set_override(&mut overrides, "window.width", Value::from(width as i64));
+            set_override(&mut overrides, "window.height", Value::from(height as i64));
+        }
+
+        Config::load_with(self.config.as_deref(), overrides)
+    }
+}
+
+fn parse_setting(setting: &str) -> Result<(String, String), String> {
+    let (key, value) = setting
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{}`", setting))?;
+
+    Ok((key.trim().to_string(), value.trim().to_string()))
+}
+
+fn parse_size(size: &str) -> Result<(u32, u32), String> {
+    let (width, height) = size
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected WxH, got `{}`", size))?;
+
+    let width = width
+        .parse::<u32>()
+        .map_err(|e| format!("invalid width: {}", e))?;
+    let height = height
+        .parse::<u32>()
+        .map_err(|e| format!("invalid height: {}", e))?;
+
+    if width == 0 || height == 0 {
+        return Err("size must be non-zero".to_string());
+    }
+
+    Ok((width, height))
+}
+
+fn parse_position(position: &str) -> Result<(i32, i32), String> {
+    let (x, y) = position
+        .split_once(',')
+        .ok_or_else(|| format!("expected X,Y, got `{}`", position))?;
+
+    let x = x
+        .trim()
+        .parse::<i32>()
+        .map_err(|e| format!("invalid x: {}", e))?;
+    let y = y
+        .trim()
+        .parse::<i32>()
+        .map_err(|e| format!("invalid y: {}", e))?;
+
+    Ok((x, y))
+}
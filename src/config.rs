@@ -1,45 +1,1581 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use log::*;
 use serde::Deserialize;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use toml::{Table, Value};
+use toml_edit::{Array, DocumentMut, Item};
+
+use crate::paths;
+use crate::types::{Color, Vec2, PIXEL_ERASER_BRUSH};
 
 const DEFAULT_CONFIG: &str = include_str!("../config.toml");
 
-#[derive(Debug, Deserialize)]
+/// Prefix of environment variables overriding config keys
+const ENV_PREFIX: &str = "SCRIBBLE_";
+
+/// How often [`watch_config`] checks the file for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Documents remembered in `session.recent_files`
+const MAX_RECENT_FILES: usize = 10;
+
+/// Bounds of `vulkan.resolution_scale`; 4 already draws 16 times the pixels
+const MIN_RESOLUTION_SCALE: f32 = 0.25;
+const MAX_RESOLUTION_SCALE: f32 = 4.0;
+
+/// Furthest ahead strokes may be predicted; beyond this the guesses overshoot every turn
+const MAX_PREDICTION_MS: f32 = 100.0;
+
+/// Longest a fading stroke stays, a day; strokes asking for longer, e.g.
+/// from a peer or a script, stay this long
+pub const MAX_FADE_SECONDS: f32 = 86_400.0;
+
+/// Most `[bloom] passes`; the glow hardly spreads further after this many
+const MAX_BLOOM_PASSES: u32 = 8;
+
+/// Most `[shader_params]` one shader can take, the length of its `Params` block
+pub const MAX_SHADER_PARAMS: usize = 16;
+
+/// Brush name of pixel eraser strokes, which no `[shaders.brushes]` entry may take
+pub const PIXEL_ERASER: &str = "pixel_eraser";
+
+#[derive(Debug, PartialEq, Deserialize)]
 pub struct Config {
     pub window: WindowConfig,
     pub vulkan: VulkanConfig,
     pub shaders: ShaderConfig,
+    #[serde(default)]
+    pub brush: BrushConfig,
+    #[serde(default)]
+    pub scripts: ScriptConfig,
+    #[serde(default)]
+    pub keybindings: KeyBindingConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub accessibility: AccessibilityConfig,
+    #[serde(default)]
+    pub bloom: BloomConfig,
+    #[serde(default)]
+    pub background: BackgroundConfig,
+    #[serde(default)]
+    pub shader_params: ShaderParamConfig,
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    #[serde(default)]
+    pub session: SessionConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub export: ExportConfig,
+    #[serde(default)]
+    pub stats: StatsConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, PartialEq, Deserialize)]
 pub struct WindowConfig {
     pub title: String,
     pub width: u32,
     pub height: u32,
+    /// Monitor to open on, `None` for the primary one
+    #[serde(default)]
+    pub monitor: Option<MonitorChoice>,
+    /// Top left corner of the window in physical pixels from the monitor's,
+    /// `None` centers it (or leaves it to the platform without a `monitor`)
+    #[serde(default)]
+    pub position: Option<[i32; 2]>,
+    /// Fixed canvas size in pixels, letterboxed into the window at any size,
+    /// `None` to draw at the window's size
+    #[serde(default)]
+    pub canvas_size: Option<[u32; 2]>,
+    #[serde(default)]
+    pub fullscreen: bool,
+    /// Switch the monitor's video mode when going fullscreen, where the
+    /// platform supports it, instead of covering it with a borderless window
+    #[serde(default)]
+    pub exclusive_fullscreen: bool,
+    /// Annotate the screen: a transparent, borderless window covering the
+    /// monitor and staying on top of everything else
+    #[serde(default)]
+    pub overlay: bool,
+    /// Let the desktop show through the canvas, by `background_opacity`
+    #[serde(default)]
+    pub transparent: bool,
+    /// Alpha the canvas clears to in a transparent window, from 0 to 1
+    #[serde(default = "default_background_opacity")]
+    pub background_opacity: f32,
+    #[serde(default)]
+    pub frame_rate: FrameRate,
+    /// Cap while only UI animations are running, `None` uses `frame_rate`
+    #[serde(default)]
+    pub animation_frame_rate: Option<FrameRate>,
+}
+
+/// A monitor as written in the config: its place in the system's list of
+/// monitors, or its name (or part of it, ignoring case), e.g. `"HDMI"`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum MonitorChoice {
+    Index(usize),
+    Name(String),
+}
+
+impl MonitorChoice {
+    /// Whether this picks the monitor at `index` in the list, called `name`
+    pub fn matches(&self, index: usize, name: Option<&str>) -> bool {
+        match self {
+            MonitorChoice::Index(i) => *i == index,
+            MonitorChoice::Name(part) => {
+                name.is_some_and(|name| name.to_lowercase().contains(&part.to_lowercase()))
+            }
+        }
+    }
+}
+
+fn default_background_opacity() -> f32 {
+    1.0
 }
 
-#[derive(Debug, Deserialize)]
+impl WindowConfig {
+    /// Whether the swapchain and stroke blending must keep the frame's alpha
+    pub fn is_transparent(&self) -> bool {
+        self.transparent || self.overlay
+    }
+}
+
+/// How often the window redraws while something is changing
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "FrameRateSetting")]
+pub enum FrameRate {
+    /// At most this many frames per second
+    Fps(u32),
+    /// As fast as the present mode allows
+    Uncapped,
+    /// The refresh rate of the monitor the window is on, 60 Hz if unknown
+    #[default]
+    Monitor,
+}
+
+/// `frame_rate` as written in the file: a number, `"uncapped"` or `"monitor"`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FrameRateSetting {
+    Fps(u32),
+    Name(String),
+}
+
+impl TryFrom<FrameRateSetting> for FrameRate {
+    type Error = String;
+
+    fn try_from(setting: FrameRateSetting) -> Result<Self, Self::Error> {
+        match setting {
+            FrameRateSetting::Fps(fps) => Ok(FrameRate::Fps(fps)),
+            FrameRateSetting::Name(name) => match name.as_str() {
+                "uncapped" => Ok(FrameRate::Uncapped),
+                "monitor" => Ok(FrameRate::Monitor),
+                _ => Err(format!(
+                    "invalid frame rate `{}`, expected a number, \"uncapped\" or \"monitor\"",
+                    name
+                )),
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
 pub struct VulkanConfig {
     pub validation_enabled: bool,
     pub max_frames_in_flight: usize,
     pub max_vertices: u32,
     pub staging_buffer_vertex_count: u32,
+    /// Segments of fading strokes kept at once; the oldest go early when more are drawn
+    pub fade_vertex_count: u32,
+    pub partial_redraw: bool,
+    /// Index into the physical device list, `None` picks the first suitable GPU
+    #[serde(default)]
+    pub gpu: Option<usize>,
+    #[serde(default)]
+    pub present_mode: PresentMode,
+    #[serde(default)]
+    pub composite_alpha: CompositeAlpha,
+    /// The canvas is drawn at the window size times this and scaled to fit,
+    /// above 1 to supersample, below to save fill rate
+    #[serde(default = "default_resolution_scale")]
+    pub resolution_scale: f32,
+    #[serde(default)]
+    pub vertex_format: VertexFormat,
+    /// Start frames just before the vblank they are shown at, with present
+    /// wait or display timing where the device has it, to cut input latency
+    #[serde(default)]
+    pub low_latency: bool,
+    /// Seconds to wait for the GPU to finish a frame before taking it for a hang
+    #[serde(default = "default_gpu_timeout_seconds")]
+    pub gpu_timeout_seconds: f32,
+}
+
+fn default_resolution_scale() -> f32 {
+    1.0
+}
+
+fn default_gpu_timeout_seconds() -> f32 {
+    5.0
+}
+
+impl VulkanConfig {
+    /// `gpu_timeout_seconds` as a duration
+    pub fn gpu_timeout(&self) -> Duration {
+        Duration::from_secs_f32(self.gpu_timeout_seconds)
+    }
 }
 
-#[derive(Debug, Deserialize)]
+/// Preferred swapchain present mode; FIFO is used when the device lacks it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresentMode {
+    /// Low latency without tearing
+    #[default]
+    Mailbox,
+    /// Vsync, always available
+    Fifo,
+    /// No vsync, may tear
+    Immediate,
+}
+
+/// How stroke segments are stored in vertex buffers
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VertexFormat {
+    /// 32-bit floats, as the segments are kept in memory
+    #[default]
+    F32,
+    /// Half floats for positions, directions, widths, pressure and tilt: 36
+    /// bytes per segment instead of 52, at the cost of precision away from the origin
+    F16,
+}
+
+/// How the compositor blends the window with what is behind it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompositeAlpha {
+    /// Opaque, unless the window is transparent, which takes the first the
+    /// surface supports of `pre_multiplied`, `inherit` and `post_multiplied`
+    #[default]
+    Auto,
+    /// Alpha is ignored
+    Opaque,
+    /// Colors are already multiplied by alpha, as the renderer writes them
+    PreMultiplied,
+    /// The compositor multiplies colors by alpha, darkening anti-aliased edges
+    PostMultiplied,
+    /// Whatever the windowing system is set to, by platform means
+    Inherit,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
 pub struct ShaderConfig {
     pub vertex: PathBuf,
     pub fragment: PathBuf,
+    #[serde(default = "default_ui_vertex")]
+    pub ui_vertex: PathBuf,
+    #[serde(default = "default_ui_fragment")]
+    pub ui_fragment: PathBuf,
+    /// Fragment shaders strokes can be drawn with instead of `fragment`, by brush name
+    #[serde(default)]
+    pub brushes: BTreeMap<String, PathBuf>,
+    /// Fragment shaders `[background] shader` can animate the canvas with, by name
+    #[serde(default)]
+    pub backgrounds: BTreeMap<String, PathBuf>,
+    /// Vertex shader of the `[bloom]` passes and backgrounds, a triangle covering the target
+    #[serde(default = "default_fullscreen_vertex")]
+    pub fullscreen_vertex: PathBuf,
+    #[serde(default = "default_bloom_blur")]
+    pub bloom_blur: PathBuf,
+    #[serde(default = "default_bloom_composite")]
+    pub bloom_composite: PathBuf,
+    /// Draws pixel eraser strokes into the erase layer's mask
+    #[serde(default = "default_erase_mask")]
+    pub erase_mask: PathBuf,
+    /// Shows the background through the erase layer's mask
+    #[serde(default = "default_erase_composite")]
+    pub erase_composite: PathBuf,
+}
+
+fn default_fullscreen_vertex() -> PathBuf {
+    PathBuf::from("shaders/fullscreen_vert.spv")
+}
+
+fn default_bloom_blur() -> PathBuf {
+    PathBuf::from("shaders/bloom_blur_frag.spv")
+}
+
+fn default_bloom_composite() -> PathBuf {
+    PathBuf::from("shaders/bloom_composite_frag.spv")
+}
+
+fn default_erase_mask() -> PathBuf {
+    PathBuf::from("shaders/erase_mask_frag.spv")
+}
+
+fn default_erase_composite() -> PathBuf {
+    PathBuf::from("shaders/erase_composite_frag.spv")
+}
+
+impl ShaderConfig {
+    /// The stroke fragment shaders: `fragment`, then the brushes' in name order
+    ///
+    /// A stroke's brush index (see [`ShaderConfig::brush_index`]) is its
+    /// position here, and that of the pipeline drawing it.
+    pub fn stroke_fragments(&self) -> impl Iterator<Item = &PathBuf> {
+        std::iter::once(&self.fragment).chain(self.brushes.values())
+    }
+
+    /// Index of the brush called `name` among [`ShaderConfig::stroke_fragments`],
+    /// or [`PIXEL_ERASER_BRUSH`] for [`PIXEL_ERASER`]
+    pub fn brush_index(&self, name: &str) -> Option<u32> {
+        if name == PIXEL_ERASER {
+            return Some(PIXEL_ERASER_BRUSH);
+        }
+        let position = self.brushes.keys().position(|n| n == name)?;
+        Some(position as u32 + 1)
+    }
+
+    /// Name of the brush at `index`, `None` for `fragment` or an unknown one
+    pub fn brush_name(&self, index: u32) -> Option<&str> {
+        if index == PIXEL_ERASER_BRUSH {
+            return Some(PIXEL_ERASER);
+        }
+        let position = (index as usize).checked_sub(1)?;
+        self.brushes.keys().nth(position).map(String::as_str)
+    }
+
+    /// How many shaders have a block of `[shader_params]`: the stroke
+    /// fragment shaders at their brush index, then the backgrounds in name order
+    pub fn param_block_count(&self) -> usize {
+        1 + self.brushes.len() + self.backgrounds.len()
+    }
+
+    /// Parameter block of the background called `name`
+    pub fn background_param_block(&self, name: &str) -> Option<u32> {
+        let position = self.backgrounds.keys().position(|n| n == name)?;
+        Some((1 + self.brushes.len() + position) as u32)
+    }
+}
+
+fn default_ui_vertex() -> PathBuf {
+    PathBuf::from("shaders/ui_vert.spv")
+}
+
+fn default_ui_fragment() -> PathBuf {
+    PathBuf::from("shaders/ui_frag.spv")
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BrushConfig {
+    /// Color strokes start out with
+    pub color: Color,
+    /// Seconds strokes drawn in fade mode stay before fading out
+    pub fade_seconds: f32,
+    /// Step in degrees line directions and shape diagonals snap to
+    pub angle_snap_degrees: f32,
+    /// Whether lines and shapes snap by default; holding Shift does the opposite
+    pub angle_snap: bool,
+    /// Pixels a drawn stroke may be straightened by when it is committed, 0 to keep every point
+    pub simplify_pixels: f32,
+    /// Milliseconds ahead of the pointer the in-progress stroke's tip is drawn, 0 to not predict
+    pub prediction_ms: f32,
+    /// Stretches the brush along `nib_angle_degrees` by this much (1 doubles
+    /// it), like a calligraphy nib, 0 for a round brush; pen tilt overrides it
+    pub nib_stretch: f32,
+    /// Direction the nib is stretched in, clockwise from pointing right
+    pub nib_angle_degrees: f32,
+    /// `[shaders.brushes]` entry new strokes are drawn with, `None` for `[shaders] fragment`
+    pub shader: Option<String>,
+}
+
+impl BrushConfig {
+    /// The [`Line::tilt`](crate::types::Line::tilt) of strokes drawn without pen tilt
+    pub fn nib_tilt(&self) -> Vec2 {
+        let angle = self.nib_angle_degrees.to_radians();
+        Vec2::new(angle.cos(), angle.sin()) * self.nib_stretch
+    }
+}
+
+impl Default for BrushConfig {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            fade_seconds: 5.0,
+            angle_snap_degrees: 15.0,
+            angle_snap: false,
+            simplify_pixels: 0.0,
+            prediction_ms: 0.0,
+            nib_stretch: 0.0,
+            nib_angle_degrees: 45.0,
+            shader: None,
+        }
+    }
+}
+
+/// Named themes and the one to start with
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub active: String,
+    /// Every `[theme.<name>]` table, switchable at runtime
+    #[serde(flatten)]
+    pub themes: BTreeMap<String, Theme>,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        Self {
+            active: "dark".to_string(),
+            themes: BTreeMap::from([("dark".to_string(), Theme::default())]),
+        }
+    }
+}
+
+impl ThemeConfig {
+    /// The theme called `name`, falling back to the first one (or the built-in
+    /// dark theme) when there is no such theme
+    pub fn get(&self, name: &str) -> Theme {
+        self.themes
+            .get(name)
+            .or_else(|| self.themes.values().next())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The theme to start with
+    pub fn active(&self) -> Theme {
+        self.get(&self.active)
+    }
+
+    /// The theme after `name` in alphabetical order, wrapping around
+    pub fn next_after(&self, name: &str) -> Option<&str> {
+        let mut names = self.themes.keys().map(String::as_str);
+        let first = names.clone().next()?;
+        Some(
+            names
+                .by_ref()
+                .find(|&n| n == name)
+                .and_then(|_| names.next())
+                .unwrap_or(first),
+        )
+    }
+}
+
+/// Canvas background, palette and UI colors that go together
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Canvas color behind the strokes
+    pub background: Color,
+    /// Swatches offered next to the color picker
+    pub palette: Vec<Color>,
+    /// Start from egui's light visuals instead of the dark ones
+    pub light: bool,
+    /// Window and panel fill, `None` keeps egui's
+    pub panel: Option<Color>,
+    /// Text color, `None` keeps egui's
+    pub text: Option<Color>,
+    /// Selected and hovered widgets, `None` keeps egui's
+    pub accent: Option<Color>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            background: Color::BLACK,
+            palette: vec![
+                Color::WHITE,
+                Color::rgb(0x20, 0x20, 0x20),
+                Color::rgb(0xe5, 0x48, 0x4d),
+                Color::rgb(0xf5, 0xa6, 0x23),
+                Color::rgb(0xf8, 0xe7, 0x1c),
+                Color::rgb(0x4c, 0xc3, 0x5a),
+                Color::rgb(0x3b, 0x8e, 0xf0),
+                Color::rgb(0xa3, 0x5b, 0xd9),
+            ],
+            light: false,
+            panel: None,
+            text: None,
+            accent: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Memory the undo and redo history may take, in MiB, before the oldest edits are dropped
+    pub memory_mib: f32,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self { memory_mib: 256.0 }
+    }
+}
+
+impl HistoryConfig {
+    /// `memory_mib` in bytes
+    pub fn budget(&self) -> usize {
+        (self.memory_mib as f64 * 1024.0 * 1024.0) as usize
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// Leave the canvas background out of exported PNGs, so they composite
+    /// over whatever they are placed on
+    pub transparent_background: bool,
+    /// Pixels exported per canvas pixel; strokes are vectors, so larger
+    /// exports stay sharp
+    pub scale: f32,
+    /// What the image covers
+    pub area: ExportArea,
+    /// Largest image drawn at once, in pixels a side; bigger exports are
+    /// drawn in tiles of this size and stitched together
+    pub max_tile_size: u32,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            transparent_background: false,
+            scale: 1.0,
+            area: ExportArea::Canvas,
+            max_tile_size: 4096,
+        }
+    }
+}
+
+/// What `[export] area` covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportArea {
+    /// The canvas as it is framed for exports, at the origin or cropped
+    Canvas,
+    /// Every stroke however far out, at the canvas's pixel density
+    Drawing,
+}
+
+impl ExportConfig {
+    /// Size of an export of a `[width, height]` canvas, at least a pixel a side
+    pub fn size(&self, [width, height]: [u32; 2]) -> [u32; 2] {
+        let scaled = |pixels: u32| (pixels as f64 * self.scale as f64).round().max(1.0) as u32;
+        [scaled(width), scaled(height)]
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct StatsConfig {
+    /// Recent frames kept for a frame timing report
+    pub frames: usize,
+    /// Format reports are written in
+    pub format: StatsFormat,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            frames: 1000,
+            format: StatsFormat::Csv,
+        }
+    }
+}
+
+/// File format of frame timing reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsFormat {
+    /// One row per frame, for spreadsheets and plotting
+    Csv,
+    /// A summary and an array of frames
+    Json,
+}
+
+impl StatsFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            StatsFormat::Csv => "csv",
+            StatsFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Also write the log to a file, which is kept across runs
+    pub file: bool,
+    /// Most detailed messages written to the file; the terminal follows `RUST_LOG`
+    pub level: LogLevel,
+    /// Size in MiB past which the file is moved aside and a new one started
+    pub max_size_mib: f32,
+    /// Moved-aside files kept, the oldest deleted beyond that
+    pub keep: usize,
+    /// Where the file goes, `None` for the platform's log directory
+    pub directory: Option<PathBuf>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            file: false,
+            level: LogLevel::Info,
+            max_size_mib: 5.0,
+            keep: 3,
+            directory: None,
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// `max_size_mib` in bytes
+    pub fn max_bytes(&self) -> u64 {
+        (self.max_size_mib as f64 * 1024.0 * 1024.0) as u64
+    }
+}
+
+/// Most detailed messages `[logging]` writes to the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AccessibilityConfig {
+    /// Start in high-contrast mode (it can also be toggled at runtime)
+    pub high_contrast: bool,
+    /// Narrowest a stroke is drawn in high-contrast mode, in logical pixels at any zoom
+    pub min_stroke_width: f32,
+    /// Arm length of the cursor crosshair drawn in high-contrast mode, in points
+    pub crosshair_size: f32,
+}
+
+impl Default for AccessibilityConfig {
+    fn default() -> Self {
+        Self {
+            high_contrast: false,
+            min_stroke_width: 4.0,
+            crosshair_size: 24.0,
+        }
+    }
+}
+
+/// A glow around bright strokes, added by post-processing the window's frames
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BloomConfig {
+    pub enabled: bool,
+    /// Linear luminance colors must exceed to glow; the background's is the least
+    pub threshold: f32,
+    /// How strongly the glow is added over the strokes
+    pub intensity: f32,
+    /// Blur passes at half resolution, each spreading the glow further
+    pub passes: u32,
+}
+
+impl Default for BloomConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 0.3,
+            intensity: 1.0,
+            passes: 2,
+        }
+    }
+}
+
+/// An animated backdrop drawn under the strokes instead of the plain background color
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct BackgroundConfig {
+    /// `[shaders.backgrounds]` entry to draw, `None` for the plain color
+    pub shader: Option<String>,
+    /// How fast the animation runs, 1 for real time
+    pub speed: f32,
+}
+
+impl Default for BackgroundConfig {
+    fn default() -> Self {
+        Self {
+            shader: None,
+            speed: 1.0,
+        }
+    }
+}
+
+/// A value handed to a shader through its `Params` uniform block
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum ShaderParam {
+    Float(f32),
+    /// Written as a hex string, handed over in linear light
+    Color(Color),
+}
+
+/// One shader's parameters by name; it reads them in name order
+pub type ShaderParams = BTreeMap<String, ShaderParam>;
+
+static NO_SHADER_PARAMS: ShaderParams = BTreeMap::new();
+
+/// Values shaders read from their `Params` block, so effects can be tuned
+/// without recompiling them
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ShaderParamConfig {
+    /// Of `[shaders] fragment`
+    pub stroke: ShaderParams,
+    /// By `[shaders.brushes]` name
+    pub brushes: BTreeMap<String, ShaderParams>,
+    /// By `[shaders.backgrounds]` name
+    pub backgrounds: BTreeMap<String, ShaderParams>,
+}
+
+impl ShaderParamConfig {
+    /// The parameters of each of `shaders`' blocks (see
+    /// [`ShaderConfig::param_block_count`]), empty for shaders without any
+    pub fn blocks<'a>(
+        &'a self,
+        shaders: &'a ShaderConfig,
+    ) -> impl Iterator<Item = &'a ShaderParams> {
+        let of = |params: &'a BTreeMap<String, ShaderParams>, name: &String| {
+            params.get(name).unwrap_or(&NO_SHADER_PARAMS)
+        };
+        std::iter::once(&self.stroke)
+            .chain(
+                shaders
+                    .brushes
+                    .keys()
+                    .map(move |name| of(&self.brushes, name)),
+            )
+            .chain(
+                shaders
+                    .backgrounds
+                    .keys()
+                    .map(move |name| of(&self.backgrounds, name)),
+            )
+    }
+}
+
+/// Publishing the canvas as a video source for streaming software
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    pub enabled: bool,
+    pub backend: OutputBackend,
+    /// Source name receivers see
+    pub name: String,
+    /// Most frames published per second; frames are only sent when the canvas is redrawn
+    pub frame_rate: u32,
+    /// Path to the backend's runtime library, found in the usual places if unset
+    pub library: Option<PathBuf>,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: OutputBackend::Ndi,
+            name: "scribble-vk".to_string(),
+            frame_rate: 30,
+            library: None,
+        }
+    }
+}
+
+/// How published frames leave the app
+///
+/// Spout and Syphon share GPU textures through DirectX, OpenGL or Metal
+/// interop, which a Vulkan-only renderer can't offer, so NDI (which sends
+/// frames from host memory) is the one backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputBackend {
+    /// NewTek NDI, loaded at runtime from the NDI runtime library
+    Ndi,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ScriptConfig {
+    /// Directory script file names are resolved against
+    pub directory: PathBuf,
+    /// Key name (e.g. `"F5"`) to script file name
+    pub bindings: HashMap<String, PathBuf>,
+}
+
+impl Default for ScriptConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("scripts"),
+            bindings: HashMap::new(),
+        }
+    }
+}
+
+/// What [`Config::save`] compares to find the settings changed at runtime
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedSettings {
+    pub width: u32,
+    pub height: u32,
+    pub monitor: Option<MonitorChoice>,
+    pub position: Option<[i32; 2]>,
+    pub brush_color: Color,
+    pub theme: String,
+    pub recent_files: Vec<PathBuf>,
+}
+
+/// Settings the app writes back on exit, see [`Config::save`]
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// Save the brush color, window size, theme and recent files to the
+    /// per-user config file when the window closes, never to a
+    /// `config.toml` in the working directory
+    pub save_on_exit: bool,
+    /// Documents opened or saved lately, most recent first
+    pub recent_files: Vec<PathBuf>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            save_on_exit: true,
+            recent_files: Vec::new(),
+        }
+    }
+}
+
+impl SessionConfig {
+    /// Moves `path` to the front of the recent files, dropping the oldest past [`MAX_RECENT_FILES`]
+    pub fn add_recent_file(&mut self, path: &Path) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.recent_files.retain(|p| *p != path);
+        self.recent_files.insert(0, path);
+        self.recent_files.truncate(MAX_RECENT_FILES);
+    }
+}
+
+/// One key chord or a list of them
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    pub fn as_slice(&self) -> &[String] {
+        match self {
+            KeyList::One(chord) => std::slice::from_ref(chord),
+            KeyList::Many(chords) => chords,
+        }
+    }
+}
+
+impl From<&[&str]> for KeyList {
+    fn from(chords: &[&str]) -> Self {
+        KeyList::Many(chords.iter().map(|c| c.to_string()).collect())
+    }
+}
+
+/// Key chords (e.g. `"Ctrl+Shift+Z"`) per action; actions left out keep their
+/// default chords and an empty list unbinds one
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct KeyBindingConfig {
+    /// Tool name to the chords selecting it
+    pub tools: HashMap<String, KeyList>,
+    /// Action name to system-wide hotkeys, registered only in overlay mode
+    pub global: HashMap<String, KeyList>,
+    /// Mouse or pen button name (`Middle`, `Right`, `Back`, `Forward`,
+    /// `Button8`, ...) to `pan`, `menu`, an action name, or a tool name to
+    /// draw with while the button is held
+    pub buttons: HashMap<String, String>,
+    /// Action name (`undo`, `redo`, `clear`, `save`, `next_color`,
+    /// `previous_color`, `toggle_help`, `toggle_stats`, ...) to its chords
+    #[serde(flatten)]
+    pub actions: HashMap<String, KeyList>,
+}
+
+impl Default for KeyBindingConfig {
+    fn default() -> Self {
+        let actions: &[(&str, &[&str])] = &[
+            ("undo", &["Ctrl+Z", "U"]),
+            ("redo", &["Ctrl+Y", "Ctrl+Shift+Z"]),
+            ("clear", &["Ctrl+Delete"]),
+            ("copy", &["Ctrl+C"]),
+            ("paste", &["Ctrl+V"]),
+            ("duplicate", &["Ctrl+D"]),
+            ("save", &["Ctrl+S"]),
+            ("next_color", &["C"]),
+            ("previous_color", &["Shift+C"]),
+            ("toggle_help", &["F1"]),
+            ("toggle_stats", &["F3"]),
+            ("toggle_high_contrast", &["F4"]),
+            ("toggle_fade", &["F"]),
+            ("toggle_tags", &["F7"]),
+            ("toggle_rulers", &["F9"]),
+            ("toggle_minimap", &["F10"]),
+            ("toggle_history", &["F2"]),
+            ("save_frame_stats", &["Shift+F3"]),
+            ("copy_screenshot", &["Ctrl+Shift+C"]),
+            ("reveal_tags", &["H"]),
+            ("next_theme", &["F6"]),
+            ("next_brush", &["B"]),
+            ("toggle_background", &["F12"]),
+            ("toggle_fullscreen", &["F11"]),
+            ("toggle_pass_through", &["F8"]),
+            ("hold_to_pan", &["Space"]),
+        ];
+        let tools: &[(&str, &[&str])] = &[
+            ("pen", &["1"]),
+            ("line", &["2"]),
+            ("rectangle", &["3"]),
+            ("ellipse", &["4"]),
+            ("eraser", &["5"]),
+            ("select", &["6"]),
+            ("laser", &["7"]),
+            ("ruler", &["8"]),
+            ("protractor", &["9"]),
+            ("pixel_eraser", &["0"]),
+            ("crop", &["K"]),
+        ];
+        let global: &[(&str, &[&str])] = &[
+            ("toggle_pass_through", &["Ctrl+Shift+F8"]),
+            ("clear", &["Ctrl+Shift+F9"]),
+        ];
+        let buttons: &[(&str, &str)] = &[("Middle", "pan"), ("Right", "menu")];
+
+        Self {
+            tools: tools
+                .iter()
+                .map(|&(name, chords)| (name.to_string(), chords.into()))
+                .collect(),
+            actions: actions
+                .iter()
+                .map(|&(name, chords)| (name.to_string(), chords.into()))
+                .collect(),
+            global: global
+                .iter()
+                .map(|&(name, chords)| (name.to_string(), chords.into()))
+                .collect(),
+            buttons: buttons
+                .iter()
+                .map(|&(button, binding)| (button.to_string(), binding.to_string()))
+                .collect(),
+        }
+    }
 }
 
 impl Config {
+    /// Loads the config file from [`paths::config_file`] over the embedded defaults, then applies `SCRIBBLE_*` environment variables
     pub fn load() -> Result<Self> {
-        let config_str = std::fs::read_to_string("config.toml").unwrap_or_else(|_| {
-            warn!("config.toml not found, using embedded defaults");
-            DEFAULT_CONFIG.to_string()
+        Self::load_with(None, Table::new())
+    }
+
+    /// Loads the configuration from its layered sources
+    ///
+    /// Each source overrides the ones before it key by key: the embedded
+    /// defaults, the config file, `SCRIBBLE_*` environment variables and
+    /// finally `overrides` (e.g. from the command line, see [`set_override`]).
+    /// An explicit `path` must exist; without one the file comes from
+    /// [`paths::config_file`] and may be missing, which leaves the defaults.
+    pub fn load_with(path: Option<&Path>, overrides: Table) -> Result<Self> {
+        let mut table: Table = toml::from_str(DEFAULT_CONFIG)?;
+
+        match path {
+            Some(path) => merge(&mut table, read_table(path)?),
+            None => {
+                let path = paths::config_file();
+                if path.exists() {
+                    info!("Using config `{}`", path.display());
+                    merge(&mut table, read_table(&path)?);
+                } else {
+                    warn!("{} not found, using embedded defaults", path.display());
+                }
+            }
+        }
+
+        let env = env_overrides(&table);
+        merge(&mut table, env);
+        merge(&mut table, overrides);
+
+        Self::from_table(table)
+    }
+
+    /// The settings [`Config::save`] writes back, as they are now
+    pub fn saved_settings(&self) -> SavedSettings {
+        SavedSettings {
+            width: self.window.width,
+            height: self.window.height,
+            monitor: self.window.monitor.clone(),
+            position: self.window.position,
+            brush_color: self.brush.color,
+            theme: self.theme.active.clone(),
+            recent_files: self.session.recent_files.clone(),
+        }
+    }
+
+    /// Writes the settings the app changed at runtime back to the file at `path`
+    ///
+    /// Only `window.width`, `window.height`, `window.monitor`,
+    /// `window.position`, `brush.color`, `theme.active` and
+    /// `session.recent_files` are written, and only those that differ from
+    /// `loaded`, the settings as the config was loaded, so values from
+    /// `--set` or the environment aren't persisted unless they were changed
+    /// since. Everything else in the file, comments included, stays as it
+    /// was. A missing file is created with just those keys.
+    pub fn save(&self, path: &Path, loaded: &SavedSettings) -> Result<()> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read config `{}`", path.display()))
+            }
+        };
+        let mut document: DocumentMut = text
+            .parse()
+            .with_context(|| format!("Failed to parse config `{}`", path.display()))?;
+
+        let now = self.saved_settings();
+        if now.width != loaded.width {
+            set_saved(
+                &mut document,
+                "window",
+                "width",
+                i64::from(now.width).into(),
+            );
+        }
+        if now.height != loaded.height {
+            set_saved(
+                &mut document,
+                "window",
+                "height",
+                i64::from(now.height).into(),
+            );
+        }
+        if let Some(monitor) = now
+            .monitor
+            .as_ref()
+            .filter(|_| now.monitor != loaded.monitor)
+        {
+            let monitor = match monitor {
+                MonitorChoice::Index(index) => (*index as i64).into(),
+                MonitorChoice::Name(name) => name.as_str().into(),
+            };
+            set_saved(&mut document, "window", "monitor", monitor);
+        }
+        if let Some([x, y]) = now.position.filter(|_| now.position != loaded.position) {
+            let position: Array = [i64::from(x), i64::from(y)].into_iter().collect();
+            set_saved(&mut document, "window", "position", position.into());
+        }
+        if now.brush_color != loaded.brush_color {
+            set_saved(
+                &mut document,
+                "brush",
+                "color",
+                now.brush_color.to_string().into(),
+            );
+        }
+        if now.theme != loaded.theme {
+            set_saved(&mut document, "theme", "active", now.theme.as_str().into());
+        }
+        if now.recent_files != loaded.recent_files {
+            let recent_files: Array = now
+                .recent_files
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect();
+            set_saved(
+                &mut document,
+                "session",
+                "recent_files",
+                recent_files.into(),
+            );
+        }
+
+        let saved = document.to_string();
+        if saved == text {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, saved)
+            .with_context(|| format!("Failed to write config `{}`", path.display()))?;
+        info!("Saved settings to `{}`.", path.display());
+
+        Ok(())
+    }
+
+    /// Deserializes the merged sources and checks the result, reporting every
+    /// problem at once rather than failing later during Vulkan setup
+    fn from_table(table: Table) -> Result<Self> {
+        let mut problems = Vec::new();
+        let config: Config = serde_ignored::deserialize(Value::Table(table.clone()), |path| {
+            problems.push(format!("unknown key `{}`", path))
+        })
+        .context("Invalid configuration")?;
+
+        problems.extend(unknown_theme_keys(&table));
+        problems.extend(config.validate());
+        if problems.is_empty() {
+            return Ok(config);
+        }
+
+        let list: Vec<String> = problems.iter().map(|p| format!("  - {}", p)).collect();
+        bail!("Invalid configuration:\n{}", list.join("\n"))
+    }
+
+    /// Problems with values that parse but cannot work, e.g. an empty vertex
+    /// buffer or a shader file that does not exist
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut at_least_one = |key: &str, value: u64| {
+            if value == 0 {
+                problems.push(format!("`{}` must be at least 1", key));
+            }
+        };
+        at_least_one("window.width", self.window.width.into());
+        at_least_one("window.height", self.window.height.into());
+        if let FrameRate::Fps(fps) = self.window.frame_rate {
+            at_least_one("window.frame_rate", fps.into());
+        }
+        if let Some(FrameRate::Fps(fps)) = self.window.animation_frame_rate {
+            at_least_one("window.animation_frame_rate", fps.into());
+        }
+        at_least_one(
+            "vulkan.max_frames_in_flight",
+            self.vulkan.max_frames_in_flight as u64,
+        );
+        at_least_one("vulkan.max_vertices", self.vulkan.max_vertices.into());
+        at_least_one(
+            "vulkan.staging_buffer_vertex_count",
+            self.vulkan.staging_buffer_vertex_count.into(),
+        );
+        at_least_one(
+            "vulkan.fade_vertex_count",
+            self.vulkan.fade_vertex_count.into(),
+        );
+        at_least_one("output.frame_rate", self.output.frame_rate.into());
+        at_least_one("export.max_tile_size", self.export.max_tile_size.into());
+        if !(0.0..=MAX_FADE_SECONDS).contains(&self.brush.fade_seconds) {
+            problems.push(format!(
+                "`brush.fade_seconds` must be between 0 and {}, got {}",
+                MAX_FADE_SECONDS, self.brush.fade_seconds
+            ));
+        }
+        let timeout = self.vulkan.gpu_timeout_seconds;
+        if !timeout.is_finite() || timeout <= 0.0 {
+            problems.push(format!(
+                "`vulkan.gpu_timeout_seconds` must be more than zero, got {}",
+                timeout
+            ));
+        }
+        let log_size = self.logging.max_size_mib;
+        if !log_size.is_finite() || log_size <= 0.0 {
+            problems.push(format!(
+                "`logging.max_size_mib` must be more than zero, got {}",
+                log_size
+            ));
+        }
+        let scale = self.export.scale;
+        if !scale.is_finite() || scale <= 0.0 {
+            problems.push(format!(
+                "`export.scale` must be more than zero, got {}",
+                scale
+            ));
+        }
+        if self.stats.frames == 0 {
+            problems.push("`stats.frames` must be at least 1".to_string());
+        }
+        if !self.brush.simplify_pixels.is_finite() || self.brush.simplify_pixels < 0.0 {
+            problems.push(format!(
+                "`brush.simplify_pixels` must be zero or more, got {}",
+                self.brush.simplify_pixels
+            ));
+        }
+        if !self.brush.nib_stretch.is_finite() || self.brush.nib_stretch < 0.0 {
+            problems.push(format!(
+                "`brush.nib_stretch` must be zero or more, got {}",
+                self.brush.nib_stretch
+            ));
+        }
+        if !(0.0..=MAX_PREDICTION_MS).contains(&self.brush.prediction_ms) {
+            problems.push(format!(
+                "`brush.prediction_ms` must be between 0 and {}, got {}",
+                MAX_PREDICTION_MS, self.brush.prediction_ms
+            ));
+        }
+        let opacity = self.window.background_opacity;
+        if !(0.0..=1.0).contains(&opacity) {
+            problems.push(format!(
+                "`window.background_opacity` must be from 0 to 1, got {}",
+                opacity
+            ));
+        }
+        if let Some([width, height]) = self.window.canvas_size {
+            if width == 0 || height == 0 {
+                problems.push(format!(
+                    "`window.canvas_size` must not be zero, got [{}, {}]",
+                    width, height
+                ));
+            }
+        }
+        let scale = self.vulkan.resolution_scale;
+        if !(MIN_RESOLUTION_SCALE..=MAX_RESOLUTION_SCALE).contains(&scale) {
+            problems.push(format!(
+                "`vulkan.resolution_scale` must be from {} to {}, got {}",
+                MIN_RESOLUTION_SCALE, MAX_RESOLUTION_SCALE, scale
+            ));
+        }
+        let snap = self.brush.angle_snap_degrees;
+        if !(snap > 0.0 && snap <= 180.0) {
+            problems.push(format!(
+                "`brush.angle_snap_degrees` must be more than 0 and at most 180, got {}",
+                snap
+            ));
+        }
+
+        if self.vulkan.staging_buffer_vertex_count > self.vulkan.max_vertices {
+            problems.push(format!(
+                "`vulkan.staging_buffer_vertex_count` ({}) must not exceed `vulkan.max_vertices` ({})",
+                self.vulkan.staging_buffer_vertex_count, self.vulkan.max_vertices
+            ));
+        }
+
+        let shaders = [
+            ("shaders.vertex", &self.shaders.vertex),
+            ("shaders.fragment", &self.shaders.fragment),
+            ("shaders.ui_vertex", &self.shaders.ui_vertex),
+            ("shaders.ui_fragment", &self.shaders.ui_fragment),
+            ("shaders.fullscreen_vertex", &self.shaders.fullscreen_vertex),
+            ("shaders.erase_mask", &self.shaders.erase_mask),
+            ("shaders.erase_composite", &self.shaders.erase_composite),
+        ];
+        let brushes = self
+            .shaders
+            .brushes
+            .iter()
+            .map(|(name, path)| (format!("shaders.brushes.{}", name), path));
+        let backgrounds = self
+            .shaders
+            .backgrounds
+            .iter()
+            .map(|(name, path)| (format!("shaders.backgrounds.{}", name), path));
+        let bloom = [
+            ("shaders.bloom_blur", &self.shaders.bloom_blur),
+            ("shaders.bloom_composite", &self.shaders.bloom_composite),
+        ]
+        .into_iter()
+        .filter(|_| self.bloom.enabled);
+        for (key, path) in shaders
+            .into_iter()
+            .chain(bloom)
+            .map(|(key, path)| (key.to_string(), path))
+            .chain(brushes)
+            .chain(backgrounds)
+        {
+            if !path.is_file() {
+                problems.push(format!(
+                    "`{}`: shader `{}` not found (run shaders/compile.sh)",
+                    key,
+                    path.display()
+                ));
+            }
+        }
+        if self.shaders.brushes.contains_key(PIXEL_ERASER) {
+            problems.push(format!(
+                "`shaders.brushes.{}`: the name is taken by the pixel eraser",
+                PIXEL_ERASER
+            ));
+        }
+        if let Some(name) = &self.brush.shader {
+            if !self.shaders.brushes.contains_key(name) {
+                problems.push(format!(
+                    "`brush.shader`: no brush `{}` in `[shaders.brushes]`",
+                    name
+                ));
+            }
+        }
+
+        if let Some(name) = &self.background.shader {
+            if !self.shaders.backgrounds.contains_key(name) {
+                problems.push(format!(
+                    "`background.shader`: no background `{}` in `[shaders.backgrounds]`",
+                    name
+                ));
+            }
+        }
+        if !self.background.speed.is_finite() || self.background.speed < 0.0 {
+            problems.push(format!(
+                "`background.speed` must be zero or more, got {}",
+                self.background.speed
+            ));
+        }
+
+        let params = &self.shader_params;
+        let brushes = params.brushes.iter().map(|(name, params)| {
+            let known = self.shaders.brushes.contains_key(name);
+            (format!("shader_params.brushes.{}", name), known, params)
+        });
+        let backgrounds = params.backgrounds.iter().map(|(name, params)| {
+            let known = self.shaders.backgrounds.contains_key(name);
+            (format!("shader_params.backgrounds.{}", name), known, params)
         });
+        for (key, known, params) in
+            std::iter::once(("shader_params.stroke".to_string(), true, &params.stroke))
+                .chain(brushes)
+                .chain(backgrounds)
+        {
+            if !known {
+                problems.push(format!("`{}`: no such shader in `[shaders]`", key));
+            }
+            if params.len() > MAX_SHADER_PARAMS {
+                problems.push(format!(
+                    "`{}` has {} parameters, at most {} fit",
+                    key,
+                    params.len(),
+                    MAX_SHADER_PARAMS
+                ));
+            }
+            for (name, param) in params {
+                if matches!(param, ShaderParam::Float(value) if !value.is_finite()) {
+                    problems.push(format!("`{}.{}` must be a finite number", key, name));
+                }
+            }
+        }
+
+        let bloom = &self.bloom;
+        for (key, value) in [
+            ("bloom.threshold", bloom.threshold),
+            ("bloom.intensity", bloom.intensity),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                problems.push(format!("`{}` must be zero or more, got {}", key, value));
+            }
+        }
+        if !(1..=MAX_BLOOM_PASSES).contains(&bloom.passes) {
+            problems.push(format!(
+                "`bloom.passes` must be from 1 to {}, got {}",
+                MAX_BLOOM_PASSES, bloom.passes
+            ));
+        }
+
+        if !self.history.memory_mib.is_finite() || self.history.memory_mib < 0.0 {
+            problems.push(format!(
+                "`history.memory_mib` must be zero or more, got {}",
+                self.history.memory_mib
+            ));
+        }
+
+        let accessibility = &self.accessibility;
+        if !accessibility.min_stroke_width.is_finite() || accessibility.min_stroke_width <= 0.0 {
+            problems.push(format!(
+                "`accessibility.min_stroke_width` must be a positive number, got {}",
+                accessibility.min_stroke_width
+            ));
+        }
+        if !accessibility.crosshair_size.is_finite() || accessibility.crosshair_size < 0.0 {
+            problems.push(format!(
+                "`accessibility.crosshair_size` must be zero or more, got {}",
+                accessibility.crosshair_size
+            ));
+        }
+
+        if !self.theme.themes.contains_key(&self.theme.active) {
+            problems.push(format!(
+                "`theme.active` is `{}`, but there is no `[theme.{}]`",
+                self.theme.active, self.theme.active
+            ));
+        }
 
-        Ok(toml::from_str(&config_str)?)
+        problems
+    }
+}
+
+fn read_table(path: &Path) -> Result<Table> {
+    let config_str = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config `{}`", path.display()))?;
+
+    toml::from_str(&config_str)
+        .with_context(|| format!("Failed to parse config `{}`", path.display()))
+}
+
+/// Keys inside `[theme.<name>]` tables that `Theme` does not have
+///
+/// Themes are collected into a map, which keeps `serde_ignored` from seeing
+/// inside them, so each one is checked on its own.
+fn unknown_theme_keys(table: &Table) -> Vec<String> {
+    let Some(Value::Table(themes)) = table.get("theme") else {
+        return Vec::new();
+    };
+
+    let mut unknown = Vec::new();
+    for (name, theme) in themes.iter().filter(|(name, _)| *name != "active") {
+        let _: Result<Theme, _> = serde_ignored::deserialize(theme.clone(), |path| {
+            unknown.push(format!("unknown key `theme.{}.{}`", name, path))
+        });
     }
+    unknown
+}
+
+/// Sets `section.key` in a config file being saved, unless it already holds
+/// `value`, so untouched keys keep their formatting and comments
+fn set_saved(document: &mut DocumentMut, section: &str, key: &str, value: toml_edit::Value) {
+    let plain = |value: &toml_edit::Value| {
+        let mut value = value.clone();
+        value.decor_mut().clear();
+        value.to_string()
+    };
+
+    let current = document
+        .get(section)
+        .and_then(|s| s.get(key))
+        .and_then(Item::as_value);
+    if current.map(plain) == Some(plain(&value)) {
+        return;
+    }
+
+    if !document.contains_key(section) {
+        document.insert(section, Item::Table(toml_edit::Table::new()));
+    }
+    document[section][key] = Item::Value(value);
+}
+
+/// Recursively overrides the keys of `base` with those in `layer`
+fn merge(base: &mut Table, layer: Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(layer)) => merge(base, layer),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Sets a dotted key such as `"window.width"` in an override table
+pub fn set_override(table: &mut Table, key: &str, value: Value) {
+    let Some((section, rest)) = key.split_once('.') else {
+        table.insert(key.to_string(), value);
+        return;
+    };
+
+    let entry = table
+        .entry(section)
+        .or_insert_with(|| Value::Table(Table::new()));
+    if !entry.is_table() {
+        *entry = Value::Table(Table::new());
+    }
+    if let Value::Table(inner) = entry {
+        set_override(inner, rest, value);
+    }
+}
+
+/// Reads a value written as in TOML (`1920`, `true`, `[1, 2]`), taking anything else as a string
+pub fn parse_value(value: &str) -> Value {
+    toml::from_str::<Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(value.to_string()))
+}
+
+/// Overrides from `SCRIBBLE_<SECTION>_<KEY>` variables, e.g. `SCRIBBLE_WINDOW_WIDTH=1920`
+///
+/// Names are matched against the keys already in `table`, so keys containing
+/// underscores work (`SCRIBBLE_VULKAN_MAX_VERTICES`); a name that only
+/// matches a section sets that key in it (`SCRIBBLE_VULKAN_GPU`).
+fn env_overrides(table: &Table) -> Table {
+    let mut overrides = Table::new();
+
+    for (name, value) in std::env::vars() {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+
+        match env_key_path(table, &key.to_ascii_lowercase()) {
+            Some(path) => {
+                debug!("Config override from {}", name);
+                set_override(&mut overrides, &path.join("."), parse_value(&value));
+            }
+            None => warn!("Ignoring {}, which is not a configuration key", name),
+        }
+    }
+
+    overrides
+}
+
+/// The dotted path an environment variable name (without the prefix, lowercased) refers to
+fn env_key_path(table: &Table, key: &str) -> Option<Vec<String>> {
+    if table.contains_key(key) {
+        return Some(vec![key.to_string()]);
+    }
+
+    for (name, value) in table {
+        let (Some(rest), Value::Table(inner)) = (key.strip_prefix(&format!("{}_", name)), value)
+        else {
+            continue;
+        };
+
+        let path = env_key_path(inner, rest).unwrap_or_else(|| vec![rest.to_string()]);
+        return Some(std::iter::once(name.clone()).chain(path).collect());
+    }
+
+    None
+}
+
+/// Calls `on_change` on a background thread whenever the file at `path` is modified
+///
+/// Polls the modification time, so it works the same on every platform and
+/// for editors that replace the file instead of writing to it. A file that
+/// appears after startup counts as a change.
+pub fn watch_config(path: PathBuf, on_change: impl Fn() + Send + 'static) {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    thread::spawn(move || {
+        let mut last = modified(&path);
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let current = modified(&path);
+            if current.is_some() && current != last {
+                on_change();
+            }
+            last = current;
+        }
+    });
 }
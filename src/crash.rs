@@ -0,0 +1,113 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+
+use crate::config::Config;
+use crate::logging;
+use crate::paths;
+
+/// What a crash report includes besides the error and the latest log lines
+#[derive(Debug, Default)]
+struct Environment {
+    /// The configuration in effect, pretty-printed
+    config: Option<String>,
+    /// From [`crate::vulkan::info::describe_context`], once a device was created
+    gpu: Option<String>,
+}
+
+static ENVIRONMENT: Mutex<Environment> = Mutex::new(Environment {
+    config: None,
+    gpu: None,
+});
+
+/// Writes a crash report whenever the app panics, after the usual panic message
+///
+/// `main` also calls [`report`] when it returns an error, so failures before
+/// the window opens (no Vulkan driver, no suitable device) leave one too.
+pub fn install() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info: &PanicHookInfo| {
+        previous(info);
+        report(&info.to_string());
+    }));
+}
+
+/// Notes the configuration for crash reports from now on
+pub fn set_config(config: &Config) {
+    lock().config = Some(format!("{:#?}", config));
+}
+
+/// Notes the device in use for crash reports from now on
+pub fn set_gpu(description: String) {
+    lock().gpu = Some(description);
+}
+
+/// Writes a crash report for `reason` and says where on stderr
+pub fn report(reason: &str) {
+    match write_report(reason) {
+        Ok(path) => eprintln!(
+            "A crash report was saved to `{}`; please attach it when reporting the problem.",
+            path.display()
+        ),
+        Err(e) => eprintln!("Failed to save a crash report: {:#}", e),
+    }
+}
+
+/// Writes the report into the log directory, or the working directory without one
+pub fn write_report(reason: &str) -> Result<PathBuf> {
+    let directory = paths::log_dir().unwrap_or_default();
+    fs::create_dir_all(&directory)
+        .with_context(|| format!("Failed to create `{}`", directory.display()))?;
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = directory.join(format!("crash-{}.txt", seconds));
+
+    fs::write(&path, report_text(reason))
+        .with_context(|| format!("Failed to write `{}`", path.display()))?;
+    Ok(path)
+}
+
+/// The report: what went wrong, the app version, the GPU, the configuration and the recent log
+pub fn report_text(reason: &str) -> String {
+    let environment = lock();
+    let mut text = String::new();
+    let _ = writeln!(text, "scribble-vk {} crashed", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(text, "Time: {}", logging::timestamp(SystemTime::now()));
+    let _ = writeln!(
+        text,
+        "Platform: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = writeln!(text, "\n## Error\n\n{}", reason);
+    let _ = writeln!(
+        text,
+        "\n## GPU\n\n{}",
+        environment
+            .gpu
+            .as_deref()
+            .unwrap_or("No device was created.\n")
+    );
+    let _ = writeln!(
+        text,
+        "## Configuration\n\n{}",
+        environment.config.as_deref().unwrap_or("Not loaded yet.")
+    );
+    let _ = writeln!(text, "\n## Recent log\n");
+    for line in logging::recent_lines() {
+        text.push_str(&line);
+    }
+    text
+}
+
+/// The environment, even if a panic poisoned the lock
+fn lock() -> std::sync::MutexGuard<'static, Environment> {
+    ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner())
+}
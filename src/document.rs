@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use cgmath::AbsDiffEq;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::types::{Color, Line, Transform, Vec2, POINT_EPSILON};
+
+/// Length circles are split into segments of, in canvas units
+const CIRCLE_SEGMENT_LENGTH: f32 = 0.01;
+
+/// A saved drawing, stored as JSON
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Document {
+    pub strokes: Vec<Stroke>,
+}
+
+/// A single stroke as the polyline of points it was drawn through (in canvas units)
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stroke {
+    pub points: Vec<[f32; 2]>,
+    /// Drawings saved before strokes had colors are white
+    #[serde(default)]
+    pub color: Color,
+    /// Seconds the stroke stays before fading out, for annotations that clean
+    /// themselves up; such strokes are sent to peers but never saved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fade_after: Option<f32>,
+    /// Name of the tag the stroke is filed under, so it can be hidden with
+    /// every other stroke of that tag
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Name of the `[shaders.brushes]` fragment shader the stroke is drawn
+    /// with, `None` for `[shaders] fragment`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brush: Option<String>,
+}
+
+impl Document {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to open document `{}`", path.display()))?;
+
+        serde_json::from_str(&file)
+            .with_context(|| format!("Failed to parse document `{}`", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = serde_json::to_string(self)?;
+
+        std::fs::write(path, file)
+            .with_context(|| format!("Failed to write document `{}`", path.display()))
+    }
+
+    /// Total number of line segments across all strokes
+    pub fn line_count(&self) -> usize {
+        self.strokes.iter().map(|s| s.lines().len()).sum()
+    }
+}
+
+impl Stroke {
+    pub fn from_points(points: &[Vec2], color: Color) -> Self {
+        Stroke {
+            points: points.iter().map(|p| [p.x, p.y]).collect(),
+            color,
+            fade_after: None,
+            tag: None,
+            brush: None,
+        }
+    }
+
+    /// A closed polyline around `center`, finer the larger the circle
+    pub fn circle(center: Vec2, radius: f32, color: Color) -> Self {
+        let circumference = std::f32::consts::TAU * radius.abs();
+        let segments = ((circumference / CIRCLE_SEGMENT_LENGTH).ceil() as usize).clamp(16, 1024);
+        let mut points: Vec<Vec2> = (0..segments)
+            .map(|i| {
+                let angle = std::f32::consts::TAU * i as f32 / segments as f32;
+                center + Vec2::new(angle.cos(), angle.sin()) * radius
+            })
+            .collect();
+        points.push(points[0]);
+        Stroke::from_points(&points, color)
+    }
+
+    /// The polyline's points, leaving out any that aren't finite, as a
+    /// document or a peer may hold
+    pub fn to_points(&self) -> Vec<Vec2> {
+        self.points
+            .iter()
+            .filter(|p| p.iter().all(|c| c.is_finite()))
+            .map(|&[x, y]| Vec2::new(x, y))
+            .collect()
+    }
+
+    /// Rebuilds the polyline from a chain of connected segments, taking the first one's color
+    pub fn from_lines(lines: &[Line]) -> Self {
+        let mut points = Vec::with_capacity(lines.len() + 1);
+
+        if let Some(first) = lines.first() {
+            let start = first.position - first.dir / 2.;
+            points.push([start.x, start.y]);
+        }
+
+        points.extend(lines.iter().map(|line| {
+            let end = line.position + line.dir / 2.;
+            [end.x, end.y]
+        }));
+
+        Stroke {
+            points,
+            color: lines.first().map(|l| l.color).unwrap_or_default(),
+            fade_after: None,
+            tag: None,
+            brush: None,
+        }
+    }
+
+    /// The same stroke with every point moved by `transform`
+    pub fn transformed(&self, transform: &Transform) -> Self {
+        let points: Vec<Vec2> = self
+            .to_points()
+            .iter()
+            .map(|&p| transform.apply(p))
+            .collect();
+        Stroke {
+            fade_after: self.fade_after,
+            tag: self.tag.clone(),
+            brush: self.brush.clone(),
+            ..Stroke::from_points(&points, self.color)
+        }
+    }
+
+    /// Splits the polyline into segments, skipping points too close to the previous one
+    ///
+    /// Points that aren't finite are left out, and so are segments between
+    /// points too far apart for their length to be.
+    pub fn lines(&self) -> Vec<Line> {
+        let mut lines = Vec::with_capacity(self.points.len());
+        let mut points = self.to_points().into_iter();
+
+        let Some(mut last) = points.next() else {
+            return lines;
+        };
+
+        for point in points {
+            if !last.abs_diff_eq(&point, POINT_EPSILON) {
+                let line = Line::new(last, point, self.color);
+                if line.dir.x.is_finite() && line.dir.y.is_finite() {
+                    lines.push(line);
+                }
+                last = point;
+            }
+        }
+
+        lines
+    }
+}
@@ -0,0 +1,54 @@
+use crate::document::Stroke;
+use crate::types::{Rect, Transform, Vec2};
+
+/// A change to the canvas. Positions are in canvas units.
+///
+/// Tools, scripts and the event loop never mutate strokes directly; they
+/// hand these to [`App::apply`](crate::App::apply), which is the only place
+/// the stroke data and vertex buffer change.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditCommand {
+    /// Extends the in-progress stroke to a point
+    StrokeTo(Vec2),
+    /// Replaces the in-progress stroke with a new polyline (used for previews)
+    SetStroke(Vec<Vec2>),
+    /// Makes the in-progress stroke permanent
+    CommitStroke,
+    /// Adds a complete stroke, with its own color, in one go
+    AddStroke(Stroke),
+    /// Removes the committed strokes passing near a point
+    Erase(Vec2),
+    /// Removes committed strokes by their index in drawing order
+    RemoveStrokes(Vec<usize>),
+    /// Selects the committed strokes touching an area, replacing the selection.
+    /// Selection is local and not part of the undo history.
+    Select(Rect),
+    /// Moves, scales or rotates the selected strokes on top of whatever
+    /// transform is not committed yet, e.g. while dragging a selection handle
+    TransformSelection(Transform),
+    /// Makes the selection's transform so far permanent, as one edit
+    CommitTransform,
+    /// Moves, scales or rotates committed strokes by their index in drawing order
+    TransformStrokes(Vec<usize>, Transform),
+    /// Puts strokes back at their indices in drawing order, ascending, as
+    /// undoing a removal or clear does on a peer's canvas
+    InsertStrokes(Vec<(usize, Stroke)>),
+    /// Swaps committed strokes for other versions by index in drawing order,
+    /// as undoing a transform or tagging does on a peer's canvas
+    ReplaceStrokes(Vec<(usize, Stroke)>),
+    /// Files committed strokes under a tag by their index in drawing order,
+    /// or takes them out of any tag with `None`
+    TagStrokes(Vec<usize>, Option<String>),
+    /// Makes an area the canvas: removes the committed strokes with no
+    /// segment in it and sizes and frames the canvas to it
+    Crop(Rect),
+    /// Removes every stroke, including the one in progress
+    Clear,
+    /// Replaces every committed stroke with these and forgets the undo
+    /// history, as a snapshot of a shared board does
+    ReplaceAll(Vec<Stroke>),
+    /// Reverts the most recent committed stroke, removal, transform, tagging or clear
+    Undo,
+    /// Reapplies the most recently undone edit
+    Redo,
+}
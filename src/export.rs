@@ -0,0 +1,295 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use vulkanalia::prelude::v1_0::*;
+
+use crate::camera::Camera;
+use crate::config::{Config, ExportArea, ExportConfig};
+use crate::document::Document;
+use crate::types::{Color, Line, Rect, StrokeStyle};
+use crate::vulkan::buffer::{create_buffers, upload_lines};
+use crate::vulkan::context::VulkanContext;
+use crate::vulkan::offscreen::OffscreenRenderer;
+use crate::vulkan::owned::guard;
+
+/// Room left around the strokes with `[export] area = "drawing"`, in canvas units
+const DRAWING_MARGIN: f32 = 0.02;
+
+/// What an export draws: its size in pixels and the view filling it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportView {
+    pub width: u32,
+    pub height: u32,
+    pub camera: Camera,
+}
+
+impl ExportView {
+    /// What `canvas` pixels show through `camera`, times `[export] scale`,
+    /// or with `area = "drawing"` all of `drawing` at the same pixel density
+    ///
+    /// An empty drawing exports the canvas.
+    pub fn new(
+        export: &ExportConfig,
+        canvas: [u32; 2],
+        camera: &Camera,
+        drawing: Option<Rect>,
+    ) -> Self {
+        let [width, height] = export.size(canvas);
+        let camera = camera.with_viewport(width, height);
+        match drawing {
+            Some(drawing) if export.area == ExportArea::Drawing => {
+                // At zoom 1 the height spans 2 canvas units
+                let pixels_per_unit = camera.zoom * height as f32 / 2.0;
+                let drawing = drawing.grown(DRAWING_MARGIN);
+                let size = (drawing.max - drawing.min) * pixels_per_unit;
+                let (width, height) = (
+                    size.x.round().max(1.0) as u32,
+                    size.y.round().max(1.0) as u32,
+                );
+                Self {
+                    width,
+                    height,
+                    camera: Camera::framing(&drawing, width, height),
+                }
+            }
+            _ => Self {
+                width,
+                height,
+                camera,
+            },
+        }
+    }
+}
+
+/// Renders a document without a window and writes it as a PNG of a
+/// `canvas` sized view, or what `[export]` makes of it
+pub unsafe fn export_png(
+    config: &Config,
+    document: &Document,
+    canvas: [u32; 2],
+    path: &Path,
+) -> Result<()> {
+    let lines = document
+        .strokes
+        .iter()
+        .flat_map(|s| s.lines())
+        .collect::<Vec<_>>();
+
+    if lines.len() > config.vulkan.max_vertices as usize {
+        return Err(anyhow!(
+            "Document has {} lines but max_vertices is {}.",
+            lines.len(),
+            config.vulkan.max_vertices
+        ));
+    }
+
+    let camera = Camera::default().with_viewport(canvas[0], canvas[1]);
+    let view = ExportView::new(
+        &config.export,
+        canvas,
+        &camera,
+        Rect::around_lines(&lines, 0.0),
+    );
+    let context = VulkanContext::create_headless(config)?;
+    render_lines(&context, config, &lines, &view, path)
+}
+
+/// Uploads `lines` into freshly created buffers and writes them to `path` as seen in `view`
+unsafe fn render_lines(
+    context: &VulkanContext,
+    config: &Config,
+    lines: &[Line],
+    view: &ExportView,
+    path: &Path,
+) -> Result<()> {
+    let (
+        vertex_buffer,
+        vertex_buffer_memory,
+        staging_buffer,
+        staging_buffer_memory,
+        geometry_buffer,
+        geometry_buffer_memory,
+        geometry_index_buffer,
+        geometry_index_buffer_memory,
+    ) = create_buffers(
+        &context.instance,
+        &context.device,
+        context.physical_device,
+        context.graphics_queue,
+        context.command_pool,
+        config.vulkan.max_vertices,
+        config.vulkan.staging_buffer_vertex_count,
+        context.vertex_format,
+    )?;
+
+    let device = &context.device;
+    let _buffers = guard(
+        device,
+        vec![
+            (vertex_buffer, vertex_buffer_memory),
+            (staging_buffer, staging_buffer_memory),
+            (geometry_buffer, geometry_buffer_memory),
+            (geometry_index_buffer, geometry_index_buffer_memory),
+        ],
+    );
+
+    let staging_buffer_ptr = device.map_memory(
+        staging_buffer_memory,
+        0,
+        vk::WHOLE_SIZE,
+        vk::MemoryMapFlags::empty(),
+    )? as *mut u8;
+
+    upload_lines(
+        device,
+        context.graphics_queue,
+        context.command_pool,
+        staging_buffer,
+        staging_buffer_ptr,
+        config.vulkan.staging_buffer_vertex_count as usize,
+        context.vertex_format,
+        vertex_buffer,
+        0,
+        lines,
+    )?;
+
+    let batches = [(vertex_buffer, 0..lines.len() as u32)];
+    let background = config.theme.active().background;
+    write_tiled_png(
+        context,
+        config,
+        view,
+        background,
+        path,
+        |offscreen, camera| {
+            offscreen.render_view(
+                context,
+                geometry_buffer,
+                geometry_index_buffer,
+                camera,
+                &StrokeStyle::default(),
+                &batches,
+            )
+        },
+    )?;
+
+    // The buffers are destroyed on the way out
+    device.device_wait_idle()?;
+    Ok(())
+}
+
+/// Draws `view` with `draw` and writes it to a PNG at `path`, tile by tile
+///
+/// Tiles are at most `[export] max_tile_size` pixels a side, and never past
+/// the device's largest image, so any size can be exported. `draw` renders
+/// into the one offscreen target through each tile's camera, and every row
+/// of tiles is written out before the next is drawn, so only a row's pixels
+/// are held at once.
+pub unsafe fn write_tiled_png(
+    context: &VulkanContext,
+    config: &Config,
+    view: &ExportView,
+    background: Color,
+    path: &Path,
+    mut draw: impl FnMut(&OffscreenRenderer, &Camera) -> Result<Vec<u8>>,
+) -> Result<()> {
+    let max_size = context
+        .instance
+        .get_physical_device_properties(context.physical_device)
+        .limits
+        .max_image_dimension_2d;
+    let tile = config.export.max_tile_size.min(max_size);
+    let (width, height) = (view.width, view.height);
+    let (tile_width, tile_height) = (width.min(tile), height.min(tile));
+
+    let transparent = config.export.transparent_background;
+    let mut offscreen =
+        OffscreenRenderer::create(context, config, tile_width, tile_height, transparent)?;
+    offscreen.background = background;
+
+    let mut writer = png_writer(path, width, height)?;
+    let mut stream = writer.stream_writer()?;
+    let row_bytes = width as usize * 4;
+    for y in (0..height).step_by(tile_height as usize) {
+        // Tiles at the right and bottom edges reach past the image and are cut off
+        let rows = tile_height.min(height - y) as usize;
+        let mut band = vec![0u8; row_bytes * rows];
+        for x in (0..width).step_by(tile_width as usize) {
+            let camera = view
+                .camera
+                .tile([width, height], [x, y], [tile_width, tile_height]);
+            let mut pixels = draw(&offscreen, &camera)?;
+            if transparent {
+                unpremultiply(&mut pixels);
+            }
+
+            let columns = tile_width.min(width - x) as usize * 4;
+            let tile_row_bytes = tile_width as usize * 4;
+            for row in 0..rows {
+                let from = row * tile_row_bytes;
+                let to = row * row_bytes + x as usize * 4;
+                band[to..to + columns].copy_from_slice(&pixels[from..from + columns]);
+            }
+        }
+        stream.write_all(&band)?;
+    }
+    stream.finish()?;
+
+    Ok(())
+}
+
+/// Turns premultiplied sRGB RGBA8 pixels, as a transparent offscreen target
+/// reads them back, into the straight alpha PNG files hold
+///
+/// Colors are divided by alpha in linear light, the way they were multiplied.
+pub fn unpremultiply(pixels: &mut [u8]) {
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c < 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let srgb = |c: f32| {
+        let c = c.clamp(0.0, 1.0);
+        let c = if c < 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+        (c * 255.0).round() as u8
+    };
+
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = pixel[3];
+        if alpha == 0 || alpha == 255 {
+            continue;
+        }
+        let alpha = alpha as f32 / 255.0;
+        for c in &mut pixel[..3] {
+            *c = srgb(linear(*c) / alpha);
+        }
+    }
+}
+
+/// Writes tightly packed RGBA8 pixels to a PNG file
+pub fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> Result<()> {
+    png_writer(path, width, height)?.write_image_data(pixels)?;
+
+    Ok(())
+}
+
+/// A PNG file of RGBA8 pixels with its header written
+fn png_writer(path: &Path, width: u32, height: u32) -> Result<png::Writer<BufWriter<File>>> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create image `{}`", path.display()))?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+    Ok(encoder.write_header()?)
+}
@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+use log::*;
+
+use crate::strokes::{StoredStroke, StrokeId};
+use crate::types::{Line, Transform};
+
+/// A committed edit kept for undo and redo
+///
+/// Only local edits are kept; peers' edits can come in between, so strokes
+/// are found again by [`StrokeId`] rather than by index where they can be.
+#[derive(Debug, Clone)]
+pub enum Revision {
+    /// A stroke appended to the canvas, in the pieces it was committed in
+    /// when it was longer than the staging buffer
+    Stroke(Vec<StoredStroke>),
+    /// Strokes taken out of the canvas, with the indices they had, in
+    /// ascending order (undo puts them back there, or on top if fewer strokes are left)
+    Remove(Vec<(usize, StoredStroke)>),
+    /// Strokes moved, scaled or rotated, with their segments from before
+    Transform(Vec<(StrokeId, Vec<Line>)>, Transform),
+    /// Strokes filed under the tag with this bit (0 for untagged), with
+    /// their segments from before
+    Tag(Vec<(StrokeId, Vec<Line>)>, u32),
+    /// The strokes that were on the canvas before it was cleared, which undo
+    /// puts back under any drawn since
+    Clear(Vec<StoredStroke>),
+}
+
+impl Revision {
+    /// What the edit did, for the history window; `tags` names the tag bits
+    pub fn label(&self, tags: &[String]) -> String {
+        let strokes = |count: usize| match count {
+            1 => "1 stroke".to_string(),
+            count => format!("{} strokes", count),
+        };
+        match self {
+            Revision::Stroke(_) => "Draw a stroke".to_string(),
+            Revision::Remove(removed) => format!("Erase {}", strokes(removed.len())),
+            Revision::Transform(before, _) => format!("Transform {}", strokes(before.len())),
+            Revision::Tag(before, 0) => format!("Untag {}", strokes(before.len())),
+            Revision::Tag(before, bit) => format!(
+                "Tag {} as {}",
+                strokes(before.len()),
+                tags[bit.trailing_zeros() as usize]
+            ),
+            Revision::Clear(previous) => format!("Clear {}", strokes(previous.len())),
+        }
+    }
+
+    /// Roughly the bytes the revision keeps alive, segments included
+    pub fn size(&self) -> usize {
+        let stroke =
+            |s: &StoredStroke| size_of::<StoredStroke>() + s.lines.len() * size_of::<Line>();
+        let lines =
+            |l: &Vec<Line>| size_of::<(StrokeId, Vec<Line>)>() + l.len() * size_of::<Line>();
+        size_of::<Self>()
+            + match self {
+                Revision::Stroke(pieces) => pieces.iter().map(stroke).sum::<usize>(),
+                Revision::Remove(removed) => removed
+                    .iter()
+                    .map(|(_, s)| size_of::<usize>() + stroke(s))
+                    .sum(),
+                Revision::Transform(before, _) | Revision::Tag(before, _) => {
+                    before.iter().map(|(_, l)| lines(l)).sum()
+                }
+                Revision::Clear(previous) => previous.iter().map(stroke).sum(),
+            }
+    }
+}
+
+/// Undo and redo stacks sharing a memory budget
+///
+/// When the revisions kept add up to more than the budget, the oldest ones
+/// are dropped, undoable edits first and then the furthest redoable ones.
+/// The latest edit always stays, however large, so a big clear can still
+/// be undone right after.
+#[derive(Debug)]
+pub struct History {
+    /// Undoable revisions, oldest first
+    undo: VecDeque<Revision>,
+    /// Undone revisions, the next one to redo last
+    redo: Vec<Revision>,
+    /// Sum of [`Revision::size`] over both stacks
+    bytes: usize,
+    budget: usize,
+}
+
+impl History {
+    /// An empty history keeping revisions up to `budget` bytes
+    pub fn new(budget: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            bytes: 0,
+            budget,
+        }
+    }
+
+    /// Edits that can be undone
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Edits that can be redone
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Bytes the kept revisions take, see [`Revision::size`]
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Undoable revisions, oldest first, followed by the undone ones in the order they were made
+    pub fn iter(&self) -> impl Iterator<Item = (&Revision, bool)> {
+        let applied = self.undo.iter().map(|r| (r, false));
+        let undone = self.redo.iter().rev().map(|r| (r, true));
+        applied.chain(undone)
+    }
+
+    /// Records a new edit, which makes the undone ones unreachable
+    pub fn push(&mut self, revision: Revision) {
+        for dropped in self.redo.drain(..) {
+            self.bytes -= dropped.size();
+        }
+        self.push_undo(revision);
+    }
+
+    /// Records `piece`, the next piece of a stroke committed in pieces, as
+    /// part of the latest edit if that ends with the piece `after`, else as a new edit
+    pub fn push_piece(&mut self, after: StrokeId, piece: StoredStroke) {
+        match self.pop_undo() {
+            Some(Revision::Stroke(mut pieces)) if pieces.last().map(|s| s.id) == Some(after) => {
+                pieces.push(piece);
+                self.push(Revision::Stroke(pieces));
+            }
+            latest => {
+                if let Some(latest) = latest {
+                    self.push_undo(latest);
+                }
+                self.push(Revision::Stroke(vec![piece]));
+            }
+        }
+    }
+
+    /// Takes the latest edit to undo it; hand it back with [`History::undone`]
+    pub fn pop_undo(&mut self) -> Option<Revision> {
+        let revision = self.undo.pop_back()?;
+        self.bytes -= revision.size();
+        Some(revision)
+    }
+
+    /// Keeps a revision that was just undone for redoing
+    pub fn undone(&mut self, revision: Revision) {
+        self.bytes += revision.size();
+        self.redo.push(revision);
+        self.trim();
+    }
+
+    /// Takes the latest undone edit to redo it; hand it back with [`History::redone`]
+    pub fn pop_redo(&mut self) -> Option<Revision> {
+        let revision = self.redo.pop()?;
+        self.bytes -= revision.size();
+        Some(revision)
+    }
+
+    /// Keeps a revision that was just redone for undoing again, leaving the other undone ones
+    pub fn redone(&mut self, revision: Revision) {
+        self.push_undo(revision);
+    }
+
+    /// Forgets every revision, e.g. when the whole board is replaced
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.bytes = 0;
+    }
+
+    /// Changes the budget, dropping old revisions if they no longer fit
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.trim();
+    }
+
+    fn push_undo(&mut self, revision: Revision) {
+        self.bytes += revision.size();
+        self.undo.push_back(revision);
+        self.trim();
+    }
+
+    /// Drops the oldest revisions until the rest fit the budget or one is left
+    fn trim(&mut self) {
+        let mut dropped = 0;
+        while self.bytes > self.budget && self.undo.len() + self.redo.len() > 1 {
+            let revision = if self.undo.len() > 1 || self.redo.is_empty() {
+                self.undo.pop_front()
+            } else {
+                Some(self.redo.remove(0))
+            };
+            if let Some(revision) = revision {
+                self.bytes -= revision.size();
+                dropped += 1;
+            }
+        }
+        if dropped > 0 {
+            debug!(
+                "Dropped the {} oldest edits from the undo history to stay within {} bytes.",
+                dropped, self.budget
+            );
+        }
+    }
+}
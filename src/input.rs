@@ -0,0 +1,289 @@
+use cgmath::InnerSpace;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use winit::event::{MouseButton, TouchPhase};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use crate::types::Vec2;
+
+/// A key together with the exact modifiers that must be held
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub key: KeyCode,
+    pub modifiers: ModifiersState,
+}
+
+/// Parses a key chord such as `"Ctrl+Shift+Z"`, `"Alt+F4"` or `"U"`
+///
+/// Modifiers are `Ctrl`/`Control`, `Shift`, `Alt` and `Super`/`Cmd`/`Meta`,
+/// followed by a key name accepted by [`parse_key_code`].
+pub fn parse_key_chord(chord: &str) -> Option<KeyChord> {
+    let mut parts: Vec<&str> = chord.split('+').map(str::trim).collect();
+    let key = parse_key_code(parts.pop()?)?;
+
+    let mut modifiers = ModifiersState::empty();
+    for part in parts {
+        modifiers |= match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => ModifiersState::CONTROL,
+            "shift" => ModifiersState::SHIFT,
+            "alt" => ModifiersState::ALT,
+            "super" | "cmd" | "meta" | "logo" => ModifiersState::SUPER,
+            _ => return None,
+        };
+    }
+
+    Some(KeyChord { key, modifiers })
+}
+
+/// Parses a key name as written in `config.toml` (e.g. `"F5"`, `"G"`, `"3"`, `"Space"`)
+///
+/// Letters and digits may also use winit's names (`"KeyG"`, `"Digit3"`).
+/// Matching is case-insensitive.
+pub fn parse_key_code(name: &str) -> Option<KeyCode> {
+    let name = name.trim().to_ascii_lowercase();
+    let name = name
+        .strip_prefix("key")
+        .filter(|rest| rest.len() == 1)
+        .or_else(|| name.strip_prefix("digit"))
+        .unwrap_or(&name);
+
+    let code = match name {
+        "a" => KeyCode::KeyA,
+        "b" => KeyCode::KeyB,
+        "c" => KeyCode::KeyC,
+        "d" => KeyCode::KeyD,
+        "e" => KeyCode::KeyE,
+        "f" => KeyCode::KeyF,
+        "g" => KeyCode::KeyG,
+        "h" => KeyCode::KeyH,
+        "i" => KeyCode::KeyI,
+        "j" => KeyCode::KeyJ,
+        "k" => KeyCode::KeyK,
+        "l" => KeyCode::KeyL,
+        "m" => KeyCode::KeyM,
+        "n" => KeyCode::KeyN,
+        "o" => KeyCode::KeyO,
+        "p" => KeyCode::KeyP,
+        "q" => KeyCode::KeyQ,
+        "r" => KeyCode::KeyR,
+        "s" => KeyCode::KeyS,
+        "t" => KeyCode::KeyT,
+        "u" => KeyCode::KeyU,
+        "v" => KeyCode::KeyV,
+        "w" => KeyCode::KeyW,
+        "x" => KeyCode::KeyX,
+        "y" => KeyCode::KeyY,
+        "z" => KeyCode::KeyZ,
+        "0" => KeyCode::Digit0,
+        "1" => KeyCode::Digit1,
+        "2" => KeyCode::Digit2,
+        "3" => KeyCode::Digit3,
+        "4" => KeyCode::Digit4,
+        "5" => KeyCode::Digit5,
+        "6" => KeyCode::Digit6,
+        "7" => KeyCode::Digit7,
+        "8" => KeyCode::Digit8,
+        "9" => KeyCode::Digit9,
+        "f1" => KeyCode::F1,
+        "f2" => KeyCode::F2,
+        "f3" => KeyCode::F3,
+        "f4" => KeyCode::F4,
+        "f5" => KeyCode::F5,
+        "f6" => KeyCode::F6,
+        "f7" => KeyCode::F7,
+        "f8" => KeyCode::F8,
+        "f9" => KeyCode::F9,
+        "f10" => KeyCode::F10,
+        "f11" => KeyCode::F11,
+        "f12" => KeyCode::F12,
+        "space" => KeyCode::Space,
+        "enter" | "return" => KeyCode::Enter,
+        "escape" | "esc" => KeyCode::Escape,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "left" | "arrowleft" => KeyCode::ArrowLeft,
+        "right" | "arrowright" => KeyCode::ArrowRight,
+        "up" | "arrowup" => KeyCode::ArrowUp,
+        "down" | "arrowdown" => KeyCode::ArrowDown,
+        "minus" | "-" => KeyCode::Minus,
+        "equal" | "=" => KeyCode::Equal,
+        "bracketleft" | "[" => KeyCode::BracketLeft,
+        "bracketright" | "]" => KeyCode::BracketRight,
+        "comma" | "," => KeyCode::Comma,
+        "period" | "." => KeyCode::Period,
+        "slash" | "/" => KeyCode::Slash,
+        "backquote" | "`" => KeyCode::Backquote,
+        _ => return None,
+    };
+
+    Some(code)
+}
+
+/// Parses a mouse or pen button name as written in `config.toml` (`"Left"`,
+/// `"Middle"`, `"Right"`, `"Back"`, `"Forward"`, or `"Button8"` for others)
+///
+/// Matching is case-insensitive.
+pub fn parse_mouse_button(name: &str) -> Option<MouseButton> {
+    let name = name.trim().to_ascii_lowercase();
+    let button = match name.as_str() {
+        "left" => MouseButton::Left,
+        "middle" => MouseButton::Middle,
+        "right" => MouseButton::Right,
+        "back" => MouseButton::Back,
+        "forward" => MouseButton::Forward,
+        _ => MouseButton::Other(name.strip_prefix("button")?.parse().ok()?),
+    };
+
+    Some(button)
+}
+
+/// What a tracked touch contact means for the canvas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PointerEvent {
+    Down(Vec2),
+    Moved(Vec2),
+    Up(Vec2),
+    /// A second finger turned the press into a gesture; drop what it drew
+    Cancel,
+    /// Two fingers moved: the midpoint went from `from` to `to` and their
+    /// distance changed by `scale` (all in screen NDC)
+    Pinch {
+        from: Vec2,
+        to: Vec2,
+        scale: f32,
+    },
+}
+
+/// Follows the fingers on a touchscreen, turning the first one into a pointer
+///
+/// The first finger down draws until it lifts. A second finger cancels that
+/// press and the two fingers pan and pinch-zoom instead; further contacts are
+/// tracked but ignored, and drawing resumes once every finger has lifted.
+#[derive(Debug, Default)]
+pub struct TouchTracker {
+    primary: Option<u64>,
+    gesture: Option<(u64, u64)>,
+    contacts: HashMap<u64, Vec2>,
+}
+
+impl TouchTracker {
+    /// Records a touch event, returning what it means for the canvas
+    pub fn update(&mut self, id: u64, phase: TouchPhase, position: Vec2) -> Option<PointerEvent> {
+        match phase {
+            TouchPhase::Started => {
+                self.contacts.insert(id, position);
+                if self.primary.is_none() && self.gesture.is_none() && self.contacts.len() == 1 {
+                    self.primary = Some(id);
+                    return Some(PointerEvent::Down(position));
+                }
+
+                if self.gesture.is_none() && self.contacts.len() == 2 {
+                    let mut ids = self.contacts.keys().copied();
+                    self.gesture = ids.next().zip(ids.next());
+                    if self.primary.take().is_some() {
+                        return Some(PointerEvent::Cancel);
+                    }
+                }
+            }
+            TouchPhase::Moved => {
+                if let Some((a, b)) = self.gesture.filter(|&(a, b)| id == a || id == b) {
+                    let from = self.span(a, b)?;
+                    self.contacts.insert(id, position);
+                    let to = self.span(a, b)?;
+                    return Some(PointerEvent::Pinch {
+                        from: from.0,
+                        to: to.0,
+                        scale: if from.1 > 0.0 { to.1 / from.1 } else { 1.0 },
+                    });
+                }
+
+                self.contacts.insert(id, position);
+                if self.primary == Some(id) {
+                    return Some(PointerEvent::Moved(position));
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.contacts.remove(&id);
+                if self.gesture.is_some_and(|(a, b)| id == a || id == b) {
+                    self.gesture = None;
+                }
+                if self.primary == Some(id) {
+                    self.primary = None;
+                    return Some(PointerEvent::Up(position));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Midpoint of and distance between two contacts
+    fn span(&self, a: u64, b: u64) -> Option<(Vec2, f32)> {
+        let (a, b) = (self.contacts.get(&a)?, self.contacts.get(&b)?);
+        Some(((a + b) / 2.0, (a - b).magnitude()))
+    }
+
+    /// Number of fingers currently on the screen
+    pub fn contact_count(&self) -> usize {
+        self.contacts.len()
+    }
+}
+
+/// Samples older than this, relative to the newest, don't count towards the velocity
+const VELOCITY_WINDOW: Duration = Duration::from_millis(40);
+
+/// Guesses where the pointer is headed from how it moved lately
+///
+/// The velocity is the average over the samples of the last
+/// [`VELOCITY_WINDOW`], extrapolated from the newest sample. Once no new
+/// sample came for that long the pointer is taken to have stopped and
+/// nothing is predicted.
+#[derive(Debug, Default)]
+pub struct MotionPredictor {
+    /// Recent samples, oldest first
+    samples: VecDeque<(Instant, Vec2)>,
+}
+
+impl MotionPredictor {
+    /// Records where the pointer was at `at`
+    pub fn push(&mut self, at: Instant, position: Vec2) {
+        self.samples.push_back((at, position));
+        while self
+            .samples
+            .front()
+            .is_some_and(|&(t, _)| at.saturating_duration_since(t) > VELOCITY_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Forgets every sample, e.g. when a new stroke starts
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Where the pointer will be `ahead` after `now`, if it is moving
+    pub fn predict(&self, now: Instant, ahead: Duration) -> Option<Vec2> {
+        let &(first_at, first) = self.samples.front()?;
+        let &(last_at, last) = self.samples.back()?;
+        let span = last_at.saturating_duration_since(first_at).as_secs_f32();
+        if span <= 0.0 || now.saturating_duration_since(last_at) > VELOCITY_WINDOW {
+            return None;
+        }
+
+        let velocity = (last - first) / span;
+        let ahead = (now.saturating_duration_since(last_at) + ahead).as_secs_f32();
+        Some(last + velocity * ahead)
+    }
+
+    /// When the newest sample gets too old to predict from
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.samples.back().map(|&(at, _)| at + VELOCITY_WINDOW)
+    }
+}
@@ -0,0 +1,366 @@
+use anyhow::Result;
+use global_hotkey::hotkey::{Code, HotKey, Modifiers};
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use log::*;
+use std::collections::HashMap;
+use winit::event::MouseButton;
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use crate::config::KeyBindingConfig;
+use crate::input::{parse_key_chord, parse_mouse_button, KeyChord};
+
+/// Something a key chord can trigger
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Undo,
+    Redo,
+    Clear,
+    /// Copies the selected strokes
+    Copy,
+    /// Pastes the copied strokes centered on the cursor
+    Paste,
+    /// Copies the selected strokes in place, slightly offset, and selects the copies
+    Duplicate,
+    /// Writes the drawing to its document file
+    Save,
+    NextColor,
+    PreviousColor,
+    ToggleHelp,
+    ToggleStats,
+    ToggleHighContrast,
+    /// Switches between permanent strokes and strokes that fade out after a while
+    ToggleFade,
+    /// Opens or closes the window for managing stroke tags
+    ToggleTags,
+    /// Shows or hides the rulers along the window edges
+    ToggleRulers,
+    /// Shows or hides the minimap of the whole drawing
+    ToggleMinimap,
+    /// Opens or closes the window listing recent edits
+    ToggleHistory,
+    /// Writes the recent frame timings to a report file
+    SaveFrameStats,
+    /// Puts an image of the canvas on the system clipboard
+    CopyScreenshot,
+    /// Shows every hidden tag for now, or hides them again
+    RevealTags,
+    /// Switches to the next theme in `[theme]`
+    NextTheme,
+    /// Draws with the next brush shader in `[shaders.brushes]`
+    NextBrush,
+    /// Shows or hides the animated `[background] shader`
+    ToggleBackground,
+    /// Switches between a window and fullscreen
+    ToggleFullscreen,
+    /// In overlay mode, lets clicks through to the windows below until the
+    /// window is focused again
+    TogglePassThrough,
+    /// While held, the left button pans the view instead of using the active tool
+    HoldToPan,
+    /// Switches to the tool with this name
+    SelectTool(String),
+}
+
+/// Names of the actions in `[keybindings]`, in the order the shortcut window lists them
+const ACTION_NAMES: &[&str] = &[
+    "undo",
+    "redo",
+    "clear",
+    "copy",
+    "paste",
+    "duplicate",
+    "save",
+    "next_color",
+    "previous_color",
+    "toggle_help",
+    "toggle_stats",
+    "toggle_high_contrast",
+    "toggle_fade",
+    "toggle_tags",
+    "toggle_rulers",
+    "toggle_minimap",
+    "toggle_history",
+    "save_frame_stats",
+    "copy_screenshot",
+    "reveal_tags",
+    "next_theme",
+    "next_brush",
+    "toggle_background",
+    "toggle_fullscreen",
+    "toggle_pass_through",
+    "hold_to_pan",
+];
+
+impl Action {
+    /// Action for a key in the `[keybindings]` table
+    fn from_name(name: &str) -> Option<Self> {
+        let action = match name {
+            "undo" => Action::Undo,
+            "redo" => Action::Redo,
+            "clear" => Action::Clear,
+            "copy" => Action::Copy,
+            "paste" => Action::Paste,
+            "duplicate" => Action::Duplicate,
+            "save" => Action::Save,
+            "next_color" => Action::NextColor,
+            "previous_color" => Action::PreviousColor,
+            "toggle_help" => Action::ToggleHelp,
+            "toggle_stats" => Action::ToggleStats,
+            "toggle_high_contrast" => Action::ToggleHighContrast,
+            "toggle_fade" => Action::ToggleFade,
+            "toggle_tags" => Action::ToggleTags,
+            "toggle_rulers" => Action::ToggleRulers,
+            "toggle_minimap" => Action::ToggleMinimap,
+            "toggle_history" => Action::ToggleHistory,
+            "save_frame_stats" => Action::SaveFrameStats,
+            "copy_screenshot" => Action::CopyScreenshot,
+            "reveal_tags" => Action::RevealTags,
+            "next_theme" => Action::NextTheme,
+            "next_brush" => Action::NextBrush,
+            "toggle_background" => Action::ToggleBackground,
+            "toggle_fullscreen" => Action::ToggleFullscreen,
+            "toggle_pass_through" => Action::TogglePassThrough,
+            "hold_to_pan" => Action::HoldToPan,
+            _ => return None,
+        };
+
+        Some(action)
+    }
+
+    /// Short description for the shortcut window
+    pub fn label(&self) -> String {
+        match self {
+            Action::Undo => "Undo".to_string(),
+            Action::Redo => "Redo".to_string(),
+            Action::Clear => "Clear the canvas".to_string(),
+            Action::Copy => "Copy the selection".to_string(),
+            Action::Paste => "Paste at the cursor".to_string(),
+            Action::Duplicate => "Duplicate the selection".to_string(),
+            Action::Save => "Save the drawing".to_string(),
+            Action::NextColor => "Next palette color".to_string(),
+            Action::PreviousColor => "Previous palette color".to_string(),
+            Action::ToggleHelp => "Toggle this window".to_string(),
+            Action::ToggleStats => "Toggle frame stats".to_string(),
+            Action::ToggleHighContrast => "Toggle high contrast".to_string(),
+            Action::ToggleFade => "Toggle fading strokes".to_string(),
+            Action::ToggleTags => "Toggle the tags window".to_string(),
+            Action::ToggleRulers => "Toggle rulers".to_string(),
+            Action::ToggleMinimap => "Toggle the minimap".to_string(),
+            Action::ToggleHistory => "Toggle the history window".to_string(),
+            Action::SaveFrameStats => "Save a frame timing report".to_string(),
+            Action::CopyScreenshot => "Copy a screenshot of the canvas".to_string(),
+            Action::RevealTags => "Reveal or re-hide hidden tags".to_string(),
+            Action::NextTheme => "Next theme".to_string(),
+            Action::NextBrush => "Next brush shader".to_string(),
+            Action::ToggleBackground => "Toggle the animated background".to_string(),
+            Action::ToggleFullscreen => "Toggle fullscreen".to_string(),
+            Action::TogglePassThrough => "Click through the overlay".to_string(),
+            Action::HoldToPan => "Hold to pan by dragging".to_string(),
+            Action::SelectTool(name) => format!("Switch to {}", name),
+        }
+    }
+}
+
+/// What a mouse or pen button other than the primary one does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ButtonBinding {
+    /// Drags the view while held
+    Pan,
+    /// Opens the quick action menu
+    Menu,
+    /// Draws with the tool of this name while held, then switches back
+    Tool(String),
+    Action(Action),
+}
+
+impl ButtonBinding {
+    /// Binding for a value in the `[keybindings.buttons]` table; names that
+    /// aren't `pan`, `menu` or an action are taken to be tools
+    fn from_name(name: &str) -> Self {
+        match name {
+            "pan" => ButtonBinding::Pan,
+            "menu" => ButtonBinding::Menu,
+            _ => match Action::from_name(name) {
+                Some(action) => ButtonBinding::Action(action),
+                None => ButtonBinding::Tool(name.to_string()),
+            },
+        }
+    }
+}
+
+/// Maps key chords to actions, built from `[keybindings]` in `config.toml`
+pub struct KeyBindings {
+    actions: HashMap<KeyChord, Action>,
+    buttons: HashMap<MouseButton, ButtonBinding>,
+    /// Chords as written in the config, next to their action, in a stable order
+    shortcuts: Vec<(String, Action)>,
+}
+
+impl KeyBindings {
+    /// Builds the bindings from `config`, keeping the defaults for anything it leaves out
+    pub fn new(config: &KeyBindingConfig) -> Self {
+        let mut bindings = Self {
+            actions: HashMap::new(),
+            buttons: HashMap::new(),
+            shortcuts: Vec::new(),
+        };
+
+        let mut actions = KeyBindingConfig::default().actions;
+        actions.extend(config.actions.clone());
+        for name in actions
+            .keys()
+            .filter(|n| !ACTION_NAMES.contains(&n.as_str()))
+        {
+            warn!("Ignoring key binding for unknown action `{}`.", name);
+        }
+        for name in ACTION_NAMES {
+            if let (Some(action), Some(chords)) = (Action::from_name(name), actions.get(*name)) {
+                bindings.bind(action, chords.as_slice());
+            }
+        }
+
+        let mut tools = KeyBindingConfig::default().tools;
+        tools.extend(config.tools.clone());
+        let mut tools: Vec<_> = tools.into_iter().collect();
+        tools.sort_by_key(|(_, chords)| chords.as_slice().first().cloned());
+        for (name, chords) in tools {
+            bindings.bind(Action::SelectTool(name), chords.as_slice());
+        }
+
+        let mut buttons = KeyBindingConfig::default().buttons;
+        buttons.extend(config.buttons.clone());
+        for (name, binding) in buttons {
+            match parse_mouse_button(&name) {
+                Some(MouseButton::Left) => warn!("The left button always uses the active tool."),
+                Some(button) => {
+                    bindings
+                        .buttons
+                        .insert(button, ButtonBinding::from_name(&binding));
+                }
+                None => warn!("Ignoring binding for unknown button `{}`.", name),
+            }
+        }
+
+        bindings
+    }
+
+    fn bind(&mut self, action: Action, chords: &[String]) {
+        for chord in chords {
+            let Some(parsed) = parse_key_chord(chord) else {
+                warn!("Ignoring unknown key chord `{}` for {:?}.", chord, action);
+                continue;
+            };
+
+            if let Some(previous) = self.actions.insert(parsed, action.clone()) {
+                warn!("`{}` was bound to {:?}, now {:?}.", chord, previous, action);
+            }
+        }
+
+        if !chords.is_empty() {
+            self.shortcuts.push((chords.join(" / "), action));
+        }
+    }
+
+    /// The action bound to `key` with exactly `modifiers` held
+    pub fn action_for(&self, key: KeyCode, modifiers: ModifiersState) -> Option<&Action> {
+        self.actions.get(&KeyChord { key, modifiers })
+    }
+
+    /// What `[keybindings.buttons]` binds to `button`, if anything
+    pub fn button_for(&self, button: MouseButton) -> Option<&ButtonBinding> {
+        self.buttons.get(&button)
+    }
+
+    /// Every bound action with the chords triggering it
+    pub fn shortcuts(&self) -> &[(String, Action)] {
+        &self.shortcuts
+    }
+}
+
+/// System-wide hotkeys from `[keybindings.global]`, for the overlay, which
+/// usually doesn't have focus while clicks go through it
+///
+/// Registered with the OS through `global-hotkey`; presses are reported on
+/// whatever thread the platform delivers them on. Dropping this unregisters them.
+pub struct GlobalHotkeys {
+    manager: GlobalHotKeyManager,
+    hotkeys: Vec<HotKey>,
+}
+
+impl GlobalHotkeys {
+    /// Registers the hotkeys in `config`, calling `pressed` with the action of each one pressed
+    ///
+    /// A hotkey another program already holds is skipped with a warning.
+    pub fn register(
+        config: &KeyBindingConfig,
+        pressed: impl Fn(Action) + Send + Sync + 'static,
+    ) -> Result<Self> {
+        let manager = GlobalHotKeyManager::new()?;
+
+        let mut global = KeyBindingConfig::default().global;
+        global.extend(config.global.clone());
+        let mut actions = HashMap::new();
+        let mut hotkeys = Vec::new();
+        for (name, chords) in &global {
+            let Some(action) = Action::from_name(name) else {
+                warn!("Ignoring global hotkey for unknown action `{}`.", name);
+                continue;
+            };
+
+            for chord in chords.as_slice() {
+                let Some(hotkey) = parse_key_chord(chord).and_then(|c| to_hotkey(&c)) else {
+                    warn!(
+                        "Ignoring unknown global hotkey `{}` for {:?}.",
+                        chord, action
+                    );
+                    continue;
+                };
+
+                match manager.register(hotkey) {
+                    Ok(()) => {
+                        actions.insert(hotkey.id(), action.clone());
+                        hotkeys.push(hotkey);
+                    }
+                    Err(e) => warn!("Could not register the global hotkey `{}`: {}", chord, e),
+                }
+            }
+        }
+
+        GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+            if event.state() == HotKeyState::Pressed {
+                if let Some(action) = actions.get(&event.id()) {
+                    pressed(action.clone());
+                }
+            }
+        }));
+
+        Ok(Self { manager, hotkeys })
+    }
+}
+
+impl Drop for GlobalHotkeys {
+    fn drop(&mut self) {
+        GlobalHotKeyEvent::set_event_handler(None::<fn(GlobalHotKeyEvent)>);
+        let _ = self.manager.unregister_all(&self.hotkeys);
+    }
+}
+
+/// The OS hotkey for a chord, `None` for keys `global-hotkey` doesn't know
+fn to_hotkey(chord: &KeyChord) -> Option<HotKey> {
+    // Both name keys after the W3C UI Events codes (`KeyA`, `F8`, `Delete`)
+    let code: Code = format!("{:?}", chord.key).parse().ok()?;
+
+    let mut modifiers = Modifiers::empty();
+    for (state, modifier) in [
+        (ModifiersState::CONTROL, Modifiers::CONTROL),
+        (ModifiersState::SHIFT, Modifiers::SHIFT),
+        (ModifiersState::ALT, Modifiers::ALT),
+        (ModifiersState::SUPER, Modifiers::SUPER),
+    ] {
+        if chord.modifiers.contains(state) {
+            modifiers |= modifier;
+        }
+    }
+
+    Some(HotKey::new(Some(modifiers), code))
+}
@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! A Vulkan line-drawing canvas.
+//!
+//! [`App`] owns the Vulkan context, the swapchain renderer and the stroke
+//! data. It can be driven from any winit event loop:
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! use scribble_vk::types::{Color, Vec2};
+//! use scribble_vk::{edit::EditCommand, App, Stroke};
+//! use winit::{event_loop::EventLoop, window::WindowBuilder};
+//!
+//! let event_loop = EventLoop::new()?;
+//! let window = WindowBuilder::new().build(&event_loop)?;
+//!
+//! unsafe {
+//!     let mut app = App::create(&window)?;
+//!
+//!     // Stroke points are in canvas units; the window height spans -1 to 1 at the default zoom
+//!     let points = vec![Vec2::new(-0.5, 0.0), Vec2::new(0.5, 0.0)];
+//!     let stroke = Stroke::from_points(&points, Color::rgb(255, 128, 0));
+//!     app.apply(EditCommand::AddStroke(stroke))?;
+//!
+//!     app.render(&window)?;
+//! }
+//! // Dropping the app destroys its Vulkan objects
+//! # Ok(())
+//! # }
+//! ```
+
+#![allow(
+    dead_code,
+    unsafe_op_in_unsafe_fn,
+    unused_variables,
+    clippy::manual_slice_size_calculation,
+    clippy::missing_safety_doc,
+    clippy::too_many_arguments,
+    clippy::type_complexity,
+    clippy::unnecessary_wraps
+)]
+
+pub mod app;
+pub mod camera;
+pub mod config;
+pub mod crash;
+pub mod document;
+pub mod edit;
+pub mod export;
+pub mod history;
+pub mod input;
+pub mod keybindings;
+pub mod lod;
+pub mod logging;
+pub mod net;
+pub mod output;
+pub mod paths;
+#[cfg(feature = "tracy")]
+pub mod profiling;
+pub mod scripting;
+pub mod spatial;
+pub mod stats;
+pub mod strokes;
+pub mod tools;
+#[cfg(feature = "tray")]
+pub mod tray;
+pub mod turtle;
+pub mod types;
+pub mod ui;
+pub mod vulkan;
+
+pub use app::App;
+pub use config::Config;
+pub use document::{Document, Stroke};
@@ -0,0 +1,231 @@
+use cgmath::InnerSpace;
+
+use crate::types::{Line, Vec2, POINT_EPSILON};
+
+/// Shortest segment, in canvas units, of each simplified level after the full stroke
+const LEVEL_SEGMENT_LENGTHS: [f32; 3] = [0.012, 0.048, 0.192];
+
+/// Average on-screen segment length, in pixels, below which a coarser level is drawn
+pub const MIN_SEGMENT_PIXELS: f32 = 3.0;
+
+/// Zoom below which strokes are drawn simplified; closer views always get every segment
+pub const LOD_MAX_ZOOM: f32 = 0.5;
+
+/// A stroke's segments at decreasing levels of detail
+///
+/// Level 0 is the stroke itself and is not copied; each further level keeps
+/// only points at least [`LEVEL_SEGMENT_LENGTHS`] apart. Levels that would
+/// not drop any segment are left out.
+#[derive(Debug, Clone)]
+pub struct StrokeLod {
+    levels: Vec<Vec<Line>>,
+    /// Average segment length per level, starting with the full stroke
+    mean_lengths: Vec<f32>,
+}
+
+impl StrokeLod {
+    pub fn new(lines: &[Line]) -> Self {
+        let mut lod = Self {
+            levels: Vec::new(),
+            mean_lengths: vec![mean_length(lines)],
+        };
+
+        let mut previous = lines.len();
+        for min_length in LEVEL_SEGMENT_LENGTHS {
+            let level = decimate(lines, min_length);
+            if level.is_empty() || level.len() >= previous {
+                break;
+            }
+            previous = level.len();
+            lod.mean_lengths.push(mean_length(&level));
+            lod.levels.push(level);
+        }
+
+        lod
+    }
+
+    /// Level to draw when a canvas unit spans `pixels_per_unit` pixels
+    ///
+    /// This is the finest level whose segments are on average at least
+    /// [`MIN_SEGMENT_PIXELS`] long on screen, or the coarsest one.
+    pub fn level_for(&self, pixels_per_unit: f32) -> usize {
+        self.mean_lengths
+            .iter()
+            .position(|&length| length * pixels_per_unit >= MIN_SEGMENT_PIXELS)
+            .unwrap_or(self.mean_lengths.len() - 1)
+    }
+
+    /// Segments of `level`, where `full` is the stroke this was built from
+    pub fn lines<'a>(&'a self, level: usize, full: &'a [Line]) -> &'a [Line] {
+        match level {
+            0 => full,
+            _ => &self.levels[level - 1],
+        }
+    }
+}
+
+/// Joins consecutive segments until each spans at least `min_length`
+///
+/// Gaps between segments (e.g. the sides of a loose shape) are kept, and so
+/// are the start and end of every connected run, so the outline stays put. A
+/// run too small for even one segment, like a tiny loop, becomes a dot. A
+/// joined segment keeps everything but the position of its last piece (color,
+/// width, style with the brush, tag, fade and tilt), so hidden, fading and
+/// pixel eraser strokes stay what they are when drawn simplified.
+pub fn decimate(lines: &[Line], min_length: f32) -> Vec<Line> {
+    let mut decimated = Vec::new();
+    let mut run: Option<Run> = None;
+
+    for line in lines {
+        let (start, end) = endpoints(line);
+        let connected = run
+            .as_ref()
+            .is_some_and(|r| (start - r.to).magnitude() <= POINT_EPSILON);
+
+        let mut current = match run.take() {
+            Some(run) if connected => run,
+            // A gap: finish the previous run and start a new one here
+            previous => {
+                if let Some(previous) = previous {
+                    previous.finish(&mut decimated);
+                }
+                Run {
+                    from: start,
+                    to: start,
+                    like: *line,
+                    emitted: false,
+                }
+            }
+        };
+
+        current.to = end;
+        current.like = *line;
+        if (end - current.from).magnitude() >= min_length {
+            decimated.push(joined(current.from, end, line));
+            current.from = end;
+            current.emitted = true;
+        }
+        run = Some(current);
+    }
+
+    if let Some(run) = run {
+        run.finish(&mut decimated);
+    }
+
+    decimated
+}
+
+/// Drops points that stray less than `tolerance` from a straight line
+/// between their neighbors (Ramer-Douglas-Peucker), keeping the outline
+///
+/// Each connected run is simplified on its own, so gaps stay where they
+/// are. A joined segment keeps the color, width, pressure, style and tilt of its
+/// first piece, so points where those change (by more than `tolerance` for
+/// the width) are kept too.
+pub fn simplify(lines: &[Line], tolerance: f32) -> Vec<Line> {
+    let mut simplified = Vec::with_capacity(lines.len());
+    let mut start = 0;
+    for end in 1..=lines.len() {
+        let connected = end < lines.len()
+            && (endpoints(&lines[end]).0 - endpoints(&lines[end - 1]).1).magnitude()
+                <= POINT_EPSILON;
+        if !connected {
+            simplify_run(&lines[start..end], tolerance, &mut simplified);
+            start = end;
+        }
+    }
+
+    simplified
+}
+
+/// Simplifies one connected run of segments into `simplified`
+fn simplify_run(run: &[Line], tolerance: f32, simplified: &mut Vec<Line>) {
+    // The run's points: the start of every segment, then the end of the last
+    let mut points: Vec<Vec2> = run.iter().map(|l| endpoints(l).0).collect();
+    points.push(endpoints(&run[run.len() - 1]).1);
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    let mut spans = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = spans.pop() {
+        let mut worst = None;
+        let mut worst_error = tolerance;
+        let chord = Line::new(points[first], points[last], run[first].color);
+        for i in first + 1..last {
+            let same_look = run[i].color == run[first].color
+                && run[i].style == run[first].style
+                && run[i].tilt == run[first].tilt
+                && (run[i].extra_width() - run[first].extra_width()).abs() <= tolerance;
+            let error = if same_look {
+                chord.distance_to(points[i])
+            } else {
+                f32::INFINITY
+            };
+            if error > worst_error {
+                worst = Some(i);
+                worst_error = error;
+            }
+        }
+        if let Some(i) = worst {
+            keep[i] = true;
+            spans.push((first, i));
+            spans.push((i, last));
+        }
+    }
+
+    let mut from = 0;
+    for to in (1..points.len()).filter(|&i| keep[i]) {
+        simplified.push(Line {
+            position: (points[from] + points[to]) / 2.0,
+            dir: points[to] - points[from],
+            ..run[from]
+        });
+        from = to;
+    }
+}
+
+/// A connected run of segments being joined by [`decimate`]
+struct Run {
+    /// Start of the segment being built
+    from: Vec2,
+    /// How far the run has reached
+    to: Vec2,
+    /// The last segment joined, whose attributes the next one takes
+    like: Line,
+    /// Whether any segment of this run was emitted yet
+    emitted: bool,
+}
+
+impl Run {
+    /// Emits what is left of the run
+    fn finish(self, lines: &mut Vec<Line>) {
+        if !self.emitted || (self.to - self.from).magnitude() > 0.0 {
+            lines.push(joined(self.from, self.to, &self.like));
+        }
+    }
+}
+
+/// A segment from `from` to `to` drawn like `like`
+fn joined(from: Vec2, to: Vec2, like: &Line) -> Line {
+    Line {
+        position: (from + to) / 2.0,
+        dir: to - from,
+        ..*like
+    }
+}
+
+fn endpoints(line: &Line) -> (Vec2, Vec2) {
+    (
+        line.position - line.dir / 2.0,
+        line.position + line.dir / 2.0,
+    )
+}
+
+fn mean_length(lines: &[Line]) -> f32 {
+    if lines.is_empty() {
+        return 0.0;
+    }
+
+    lines.iter().map(|l| l.dir.magnitude()).sum::<f32>() / lines.len() as f32
+}
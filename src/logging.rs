@@ -0,0 +1,266 @@
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::config::LoggingConfig;
+use crate::paths;
+
+/// Name of the log file being written; rotated ones get `.1`, `.2`, ... appended
+pub const LOG_FILE_NAME: &str = "scribble-vk.log";
+
+/// The log file, once `[logging] file` opened it
+static FILE: Mutex<Option<RotatingFile>> = Mutex::new(None);
+
+/// Log lines kept in memory for crash reports
+const RECENT_LINES: usize = 200;
+
+/// Least detailed level kept for crash reports, whatever the terminal and file log
+const RECENT_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// The latest lines logged up to [`RECENT_LEVEL`], oldest first
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Level the terminal logs up to, from `RUST_LOG`
+static CONSOLE_LEVEL: OnceLock<LevelFilter> = OnceLock::new();
+
+/// Logs to the terminal as `RUST_LOG` asks, and to the log file if there is one
+struct Logger {
+    console: Box<dyn Log>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.console.enabled(metadata) || RECENT_LEVEL.max(file_level()) >= metadata.level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.console.enabled(record.metadata()) {
+            self.console.log(record);
+        }
+        let mut file = FILE.lock().unwrap_or_else(|e| e.into_inner());
+        let to_file = file.as_ref().is_some_and(|f| f.level >= record.level());
+        if !to_file && RECENT_LEVEL < record.level() {
+            return;
+        }
+
+        let line = format_line(record);
+        if let Some(file) = file.as_mut().filter(|_| to_file) {
+            file.write_line(&line, record.level());
+        }
+        if RECENT_LEVEL >= record.level() {
+            let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+            if recent.len() == RECENT_LINES {
+                recent.pop_front();
+            }
+            recent.push_back(line);
+        }
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+        if let Some(file) = FILE.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            let _ = file.out.flush();
+        }
+    }
+}
+
+/// Level the log file records up to, `Off` without one
+fn file_level() -> LevelFilter {
+    FILE.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map_or(LevelFilter::Off, |f| f.level)
+}
+
+/// Installs the logger, which prints to the terminal like `pretty_env_logger`
+///
+/// Call [`configure`] once the configuration is loaded to also write a log file.
+pub fn init() {
+    let mut builder = pretty_env_logger::formatted_builder();
+    if let Ok(filters) = std::env::var("RUST_LOG") {
+        builder.parse_filters(&filters);
+    }
+    let console = builder.build();
+    let console_level = *CONSOLE_LEVEL.get_or_init(|| console.filter());
+
+    let logger = Logger {
+        console: Box::new(console),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(console_level.max(RECENT_LEVEL));
+    }
+}
+
+/// Opens, reopens or closes the log file as `[logging]` says
+///
+/// Without a `directory` the file goes in [`paths::log_dir`]. Errors leave
+/// the terminal logging as it was.
+pub fn configure(config: &LoggingConfig) -> Result<()> {
+    let console_level = CONSOLE_LEVEL.get().copied().unwrap_or(LevelFilter::Off);
+    let mut file = FILE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(file) = file.as_mut() {
+        let _ = file.out.flush();
+    }
+    *file = None;
+    log::set_max_level(console_level.max(RECENT_LEVEL));
+    if !config.file {
+        return Ok(());
+    }
+
+    let directory = match &config.directory {
+        Some(directory) => directory.clone(),
+        None => paths::log_dir().context("No data directory to keep logs in")?,
+    };
+    let opened = RotatingFile::open(&directory, config)?;
+    log::set_max_level(console_level.max(RECENT_LEVEL).max(opened.level));
+    *file = Some(opened);
+    Ok(())
+}
+
+/// The latest log lines, for crash reports, whether or not a log file is written
+pub fn recent_lines() -> Vec<String> {
+    let recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    recent.iter().cloned().collect()
+}
+
+/// `record` as a line of the log file, with a UTC timestamp
+fn format_line(record: &Record) -> String {
+    format!(
+        "{} {:<5} {}: {}\n",
+        timestamp(SystemTime::now()),
+        record.level(),
+        record.target(),
+        record.args()
+    )
+}
+
+/// A log file that is moved aside once it grows past a size
+///
+/// Rotating renames `scribble-vk.log` to `scribble-vk.log.1`, shifting
+/// older ones up to `keep` and deleting the one past it, and starts a new file.
+pub struct RotatingFile {
+    path: PathBuf,
+    out: BufWriter<File>,
+    /// Bytes in the current file
+    written: u64,
+    max_bytes: u64,
+    keep: usize,
+    level: LevelFilter,
+}
+
+impl RotatingFile {
+    /// Appends to the log file in `directory`, creating the directory if needed
+    pub fn open(directory: &Path, config: &LoggingConfig) -> Result<Self> {
+        fs::create_dir_all(directory)
+            .with_context(|| format!("Failed to create `{}`", directory.display()))?;
+        let path = directory.join(LOG_FILE_NAME);
+        let file = open_append(&path)?;
+        let written = file.metadata().map(|m| m.len()).unwrap_or(0);
+
+        Ok(Self {
+            path,
+            out: BufWriter::new(file),
+            written,
+            max_bytes: config.max_bytes(),
+            keep: config.keep,
+            level: config.level.filter(),
+        })
+    }
+
+    /// Path of the file being written
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes a line for `record`, rotating first if it would overflow the file
+    pub fn write(&mut self, record: &Record) {
+        self.write_line(&format_line(record), record.level());
+    }
+
+    fn write_line(&mut self, line: &str, level: log::Level) {
+        if self.written > 0 && self.written + line.len() as u64 > self.max_bytes {
+            if let Err(e) = self.rotate() {
+                // The logger can't log its own failure, so the terminal has to do
+                eprintln!("Failed to rotate `{}`: {:#}", self.path.display(), e);
+            }
+        }
+        if self.out.write_all(line.as_bytes()).is_ok() {
+            self.written += line.len() as u64;
+        }
+        // Warnings and errors are the lines most wanted after a crash
+        if level <= log::Level::Warn {
+            let _ = self.out.flush();
+        }
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        self.out.flush()?;
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let _ = fs::remove_file(rotated(self.keep));
+            for n in (1..self.keep).rev() {
+                let _ = fs::rename(rotated(n), rotated(n + 1));
+            }
+            fs::rename(&self.path, rotated(1))?;
+        }
+        self.out = BufWriter::new(open_append(&self.path)?);
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Drop for RotatingFile {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open `{}`", path.display()))
+}
+
+/// `time` in UTC as `2024-05-01T12:34:56.789Z`
+pub fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, day_seconds) = (seconds / 86_400, seconds % 86_400);
+
+    // Howard Hinnant's days-to-civil, with eras of 400 years starting in March
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        day_seconds / 3600,
+        day_seconds / 60 % 60,
+        day_seconds % 60,
+        since_epoch.subsec_millis()
+    )
+}
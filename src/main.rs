@@ -1,141 +1,405 @@
 // SPDX-License-Identifier: Apache-2.0
 
-#![allow(
-    dead_code,
-    unsafe_op_in_unsafe_fn,
-    unused_variables,
-    clippy::manual_slice_size_calculation,
-    clippy::too_many_arguments,
-    clippy::unnecessary_wraps
-)]
-
-mod app;
-mod config;
-mod types;
-mod vulkan;
+mod cli;
 
 use anyhow::Result;
+use clap::Parser;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
-use winit::dpi::LogicalSize;
-use winit::event::{ElementState, Event, MouseButton, WindowEvent};
-use winit::event_loop::{ControlFlow, EventLoop};
-use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
-use winit::window::WindowBuilder;
+use winit::dpi::{LogicalSize, PhysicalPosition};
+use winit::event::{ElementState, Event, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoopBuilder, EventLoopWindowTarget};
+use winit::keyboard::{ModifiersState, PhysicalKey};
+use winit::monitor::MonitorHandle;
+use winit::window::{CursorIcon, Fullscreen, Window, WindowBuilder, WindowLevel};
 
-use app::App;
-use types::Vec2;
+use cli::{Args, Command};
+use scribble_vk::config::{watch_config, FrameRate, MonitorChoice, WindowConfig};
+use scribble_vk::crash;
+use scribble_vk::export::export_png;
+use scribble_vk::input::{PointerEvent, TouchTracker};
+use scribble_vk::keybindings::{Action, ButtonBinding, GlobalHotkeys};
+use scribble_vk::logging;
+use scribble_vk::net::client::CollabClient;
+use scribble_vk::net::host::run_host;
+use scribble_vk::paths;
+use scribble_vk::scripting::export_script;
+#[cfg(feature = "tray")]
+use scribble_vk::tray::{Tray, TrayCommand};
+use scribble_vk::types::Vec2;
+use scribble_vk::vulkan::info::print_info;
+use scribble_vk::{App, Document};
 
-const FRAME_TIME: Duration = Duration::from_micros(16_667);
+/// Refresh rate assumed when the monitor does not report one
+const FALLBACK_REFRESH_MILLIHERTZ: u32 = 60_000;
+
+/// Pixels of smooth (touchpad) scrolling that count as one scroll wheel line
+const PIXELS_PER_SCROLL_LINE: f64 = 40.0;
+
+/// Why the event loop was woken from another thread
+#[derive(Debug)]
+enum Wake {
+    /// The collaboration peer sent edits
+    Peer,
+    /// The configuration file changed on disk
+    Config,
+    /// A global hotkey was pressed, while another window may have focus
+    Hotkey(Action),
+    /// An entry of the tray menu was picked
+    #[cfg(feature = "tray")]
+    Tray(TrayCommand),
+}
+
+/// Where the save action writes when no document was opened
+const DEFAULT_DOCUMENT: &str = "drawing.json";
 
-#[rustfmt::skip]
 fn main() -> Result<()> {
-    pretty_env_logger::init();
+    logging::init();
+    crash::install();
+
+    let result = run();
+    if let Err(e) = &result {
+        crash::report(&format!("{:?}", e));
+    }
+    result
+}
+
+#[rustfmt::skip]
+fn run() -> Result<()> {
+    #[cfg(feature = "tracy")]
+    if let Err(e) = scribble_vk::profiling::install() {
+        log::warn!("Not profiling with Tracy: {:#}", e);
+    }
+
+    let args = Args::parse();
+    let config = args.config()?;
+    crash::set_config(&config);
+    if let Err(e) = logging::configure(&config.logging) {
+        log::warn!("Not logging to a file: {:#}", e);
+    }
+
+    // Headless modes
+
+    if let Some(Command::Info) = args.command {
+        // A hidden window, only for its surface; without a display the devices are still listed
+        let event_loop = EventLoopBuilder::new().build().map_err(|e| log::warn!("Failed to create an event loop: {}", e)).ok();
+        let window = event_loop.as_ref().and_then(|event_loop| {
+            WindowBuilder::new().with_visible(false).build(event_loop).map_err(|e| log::warn!("Failed to open a window: {}", e)).ok()
+        });
+        return unsafe { print_info(window.as_ref(), &config) };
+    }
+
+    if let Some(Command::ExportScript { input, output }) = &args.command {
+        return export_script(&Document::load(input)?, output);
+    }
+
+    if let Some(port) = args.host {
+        let document = match &args.open {
+            Some(path) => Document::load(path)?,
+            None => Document::default(),
+        };
+        return run_host(port, document);
+    }
+
+    if let Some(paths) = &args.headless_export {
+        let document = Document::load(&paths[0])?;
+        let canvas = config.window.canvas_size.unwrap_or([config.window.width, config.window.height]);
+        return unsafe { export_png(&config, &document, canvas, &paths[1]) };
+    }
+
+    // Collaboration
+
+    let event_loop = EventLoopBuilder::<Wake>::with_user_event().build()?;
+    let proxy = event_loop.create_proxy();
+    let wake = move || { let _ = proxy.send_event(Wake::Peer); };
+    let mut peer = match (&args.connect, args.listen) {
+        (Some(address), _) => Some(CollabClient::connect(address, wake)?),
+        (None, Some(port)) => Some(CollabClient::listen(port, wake)?),
+        (None, None) => None,
+    };
+
+    // Reload the configuration when it changes
+    let proxy = event_loop.create_proxy();
+    watch_config(args.config_path(), move || { let _ = proxy.send_event(Wake::Config); });
 
     // Window
 
-    let event_loop = EventLoop::new()?;
-    let window = WindowBuilder::new()
-        .with_title("Scribble")
-        .with_inner_size(LogicalSize::new(1024, 768))
-        .build(&event_loop)?;
+    let monitor = pick_monitor(&event_loop, config.window.monitor.as_ref());
+    let mut window = WindowBuilder::new()
+        .with_title(&config.window.title)
+        .with_inner_size(LogicalSize::new(config.window.width, config.window.height))
+        .with_fullscreen(config.window.fullscreen.then(|| fullscreen(&config.window, monitor.clone())))
+        .with_transparent(config.window.is_transparent());
+    if let Some(monitor) = monitor.as_ref().filter(|_| config.window.monitor.is_some() || config.window.position.is_some()) {
+        window = window.with_position(window_position(&config.window, monitor));
+    }
+    if config.window.overlay {
+        // Sized to the monitor rather than fullscreen, which compositors may draw unblended
+        window = window.with_decorations(false).with_window_level(WindowLevel::AlwaysOnTop);
+        if let Some(monitor) = &monitor {
+            window = window.with_position(monitor.position()).with_inner_size(monitor.size());
+        }
+    }
+    let window = window.build(&event_loop)?;
+
+    // Kept registered for as long as the event loop runs
+    let _hotkeys = if config.window.overlay {
+        let proxy = event_loop.create_proxy();
+        let hotkeys = GlobalHotkeys::register(&config.keybindings, move |action| { let _ = proxy.send_event(Wake::Hotkey(action)); });
+        hotkeys.map_err(|e| log::warn!("Global hotkeys are unavailable: {:#}", e)).ok()
+    } else {
+        None
+    };
 
     // App
 
-    let mut app = unsafe { App::create(&window)? };
+    let mut app = unsafe { App::create_with_config(&window, config)? };
+    if let Some(path) = &args.open {
+        unsafe { app.load_document(&Document::load(path)?)? };
+        app.add_recent_file(path);
+    }
+    let document_path = args.open.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_DOCUMENT));
+    app.set_recording(peer.is_some());
+    if let Some(peer) = peer.as_mut().filter(|_| args.spectate) {
+        peer.spectate();
+        app.set_read_only(true);
+    }
     let mut minimized = false;
-    let mut left_mouse_down = false;
+    let mut cursor = Vec2::new(0.0, 0.0);
     let mut last_frame = Instant::now();
     let mut needs_redraw = true;
     let mut modifiers = ModifiersState::empty();
+    let mut touches = TouchTracker::default();
+    let mut pass_through = false;
+    #[cfg(feature = "tray")]
+    let (mut _tray, proxy) = (None, event_loop.create_proxy());
+    let (mut frame_time, mut animation_frame_time) = frame_intervals(&app.config().window, &window);
+    let mut refresh = frame_interval(FrameRate::Monitor, &window);
 
+    // Borrowed so the window outlives the app, which drops with the closure and destroys its surface first
+    let window = &window;
     event_loop.run(move |event, elwt| {
         match event {
-            // Request a redraw when needed and enough time has passed.
+            // The tray icon can only be added once the loop runs
+            #[cfg(feature = "tray")]
+            Event::NewEvents(winit::event::StartCause::Init) => {
+                let proxy = proxy.clone();
+                let chosen = move |command| { let _ = proxy.send_event(Wake::Tray(command)); };
+                _tray = Tray::create(app.config().window.overlay, chosen).map_err(|e| log::warn!("Failed to add the tray icon: {:#}", e)).ok();
+            }
+            // Request a redraw when needed and enough time has passed, otherwise sleep until the next event.
             Event::AboutToWait => {
-                if needs_redraw {
-                    let now = Instant::now();
-                    let next_frame_time = last_frame + FRAME_TIME;
+                if let Some(peer) = &mut peer {
+                    for edit in app.take_recorded_edits() {
+                        peer.send(&edit);
+                    }
+                }
+
+                // Input redraws at the frame rate, as close to the display's vblank as
+                // low-latency pacing can start them, UI animations and timers at the animation rate
+                let next_frame_time = if needs_redraw {
+                    Some(unsafe { app.frame_start(last_frame + frame_time, refresh) })
+                } else {
+                    app.repaint_at().map(|at| at.max(last_frame + animation_frame_time))
+                };
 
-                    if now >= next_frame_time {
+                match next_frame_time {
+                    Some(at) if Instant::now() >= at => {
                         window.request_redraw();
                         needs_redraw = false;
-                    } else {
-                        elwt.set_control_flow(ControlFlow::WaitUntil(next_frame_time));
+                        elwt.set_control_flow(ControlFlow::Wait);
                     }
-                } else {
-                    elwt.set_control_flow(ControlFlow::Wait);
+                    Some(at) => elwt.set_control_flow(ControlFlow::WaitUntil(at)),
+                    None => elwt.set_control_flow(ControlFlow::Wait),
                 }
             }
-            Event::WindowEvent { event, .. } => match event {
-                // Render a frame if our Vulkan app is not being destroyed.
-                WindowEvent::RedrawRequested if !elwt.exiting() && !minimized => {
-                    unsafe { app.render(&window) }.unwrap();
-                    last_frame = Instant::now();
-                    needs_redraw = false;
-                },
-                // Mark the window as having been resized.
-                WindowEvent::Resized(size) => {
-                    if size.width == 0 || size.height == 0 {
-                        minimized = true;
-                    } else {
-                        minimized = false;
-                        app.resized = true;
+            // Apply edits from the peer.
+            Event::UserEvent(Wake::Peer) => {
+                if let Some(peer) = &mut peer {
+                    for edit in peer.receive() {
+                        if let Err(e) = unsafe { app.apply_remote(edit) } {
+                            log::error!("Failed to apply an edit from {}: {:#}", peer.peer(), e);
+                        }
                         needs_redraw = true;
                     }
+                    peer.resync(|| app.document());
                 }
-                // Destroy our Vulkan app.
-                WindowEvent::CloseRequested => {
-                    elwt.exit();
-                    unsafe { app.destroy(); }
+            }
+            // Apply the new configuration, keeping the old one if it doesn't parse.
+            Event::UserEvent(Wake::Config) => match args.config() {
+                Ok(config) => {
+                    app.apply_config(config);
+                    (frame_time, animation_frame_time) = frame_intervals(&app.config().window, window);
+                    needs_redraw = true;
                 }
-                // Track modifier state
-                WindowEvent::ModifiersChanged(new_modifiers) => {
-                    modifiers = new_modifiers.state();
+                Err(e) => log::error!("Failed to reload the configuration: {:#}", e),
+            },
+            // Global hotkeys work like key bindings, also while clicks go through the overlay.
+            Event::UserEvent(Wake::Hotkey(action)) => match action {
+                Action::Save => save_document(&mut app, &document_path),
+                Action::ToggleFullscreen => toggle_fullscreen(window, &app.config().window),
+                Action::TogglePassThrough => pass_through = set_pass_through(window, !pass_through),
+                action => needs_redraw |= unsafe { app.perform(&action) }.unwrap(),
+            },
+            #[cfg(feature = "tray")]
+            Event::UserEvent(Wake::Tray(command)) => match command {
+                TrayCommand::ToggleWindow => {
+                    let visible = !window.is_visible().unwrap_or(true);
+                    window.set_visible(visible);
+                    if visible {
+                        window.focus_window();
+                        needs_redraw = true;
+                    }
                 }
-                // Handle keyboard events
-                WindowEvent::KeyboardInput { event, .. } => {
-                    if event.state == ElementState::Pressed {
-                        match event.physical_key {
-                            // Ctrl+Z for undo
-                            PhysicalKey::Code(KeyCode::KeyZ) if modifiers.control_key() => {
-                                app.undo();
-                                needs_redraw = true;
+                TrayCommand::TogglePassThrough if app.config().window.overlay => pass_through = set_pass_through(window, !pass_through),
+                TrayCommand::TogglePassThrough => {}
+                TrayCommand::Clear => needs_redraw |= unsafe { app.perform(&Action::Clear) }.unwrap(),
+                TrayCommand::Quit => {
+                    save_settings_on_exit(&mut app, window);
+                    elwt.exit();
+                }
+            },
+            Event::WindowEvent { event, .. } => {
+                // The UI sees every event first and may claim it
+                let ui = app.window_event(window, &event);
+                needs_redraw |= ui.repaint;
+
+                match event {
+                    // Render a frame if our Vulkan app is not being destroyed.
+                    WindowEvent::RedrawRequested if !elwt.exiting() && !minimized => {
+                        // A hung or lost GPU won't draw again, so keep the drawing and close
+                        if let Err(e) = unsafe { app.render(window) } {
+                            log::error!("Failed to render: {:#}", e);
+                            app.save_emergency();
+                            crash::report(&format!("{:?}", e));
+                            elwt.exit();
+                        }
+                        last_frame = Instant::now();
+                    },
+                    // The window may have moved to a monitor with another refresh rate
+                    WindowEvent::Moved(_) => {
+                        (frame_time, animation_frame_time) = frame_intervals(&app.config().window, window);
+                        refresh = frame_interval(FrameRate::Monitor, window);
+                    }
+                    // Mark the window as having been resized.
+                    WindowEvent::Resized(size) => {
+                        if size.width == 0 || size.height == 0 {
+                            minimized = true;
+                        } else {
+                            minimized = false;
+                            app.resized = true;
+                            needs_redraw = true;
+                        }
+                    }
+                    // Moving to a monitor with another scale changes the window's size in pixels
+                    WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                        app.scale_factor_changed(scale_factor);
+                        needs_redraw = true;
+                    }
+                    // Stop the loop; the app is destroyed when it drops with this closure.
+                    WindowEvent::CloseRequested => {
+                        save_settings_on_exit(&mut app, window);
+                        elwt.exit();
+                    }
+                    // Focusing a click-through overlay (e.g. with Alt+Tab) makes it draw again
+                    WindowEvent::Focused(true) if pass_through => {
+                        pass_through = set_pass_through(window, false);
+                    }
+                    // Track modifier state, which also switches angle snapping
+                    WindowEvent::ModifiersChanged(new_modifiers) => {
+                        modifiers = new_modifiers.state();
+                        needs_redraw |= unsafe { app.modifiers_changed(modifiers) }.unwrap();
+                    }
+                    // Handle keyboard events
+                    WindowEvent::KeyboardInput { event, .. } => {
+                        if let PhysicalKey::Code(code) = event.physical_key {
+                            if event.state == ElementState::Pressed {
+                                // Bound chords trigger their action, other keys go to scripts and the active tool
+                                if !ui.consumed {
+                                    match app.action_for(code, modifiers) {
+                                        Some(Action::HoldToPan) => {
+                                            needs_redraw |= unsafe { app.hold_pan(code) }.unwrap();
+                                            window.set_cursor_icon(CursorIcon::Grab);
+                                        }
+                                        Some(action) => needs_redraw |= run_action(&mut app, action, window, &document_path, &mut pass_through),
+                                        None => needs_redraw |= unsafe { app.key_pressed(code, modifiers) }.unwrap(),
+                                    }
+                                }
+                            } else if app.key_released(code) {
+                                // Releasing the key held to pan hands the left button back to the tool, even over the UI.
+                                window.set_cursor_icon(CursorIcon::Default);
+                            }
+                        }
+                    }
+                    // Track mouse button state
+                    WindowEvent::MouseInput { state, button, .. } => {
+                        if button == MouseButton::Left {
+                            // Presses go to the active tool unless they hit the UI, releases
+                            // always go through so a stroke can end over the UI.
+                            if state == ElementState::Released {
+                                needs_redraw |= unsafe { app.pointer_up(cursor) }.unwrap();
+                            } else if !ui.consumed {
+                                needs_redraw |= unsafe { app.pointer_down(cursor) }.unwrap();
+                            }
+                        } else if state == ElementState::Released {
+                            match app.button_for(button) {
+                                Some(ButtonBinding::Pan) => app.end_pan(),
+                                Some(ButtonBinding::Tool(_)) => needs_redraw |= unsafe { app.release_tool(cursor) }.unwrap(),
+                                _ => {}
                             }
-                            // U for undo
-                            PhysicalKey::Code(KeyCode::KeyU) => {
-                                app.undo();
-                                needs_redraw = true;
+                        } else if !ui.consumed {
+                            // Other mouse and pen buttons pan, open the menu, run an action or hold a tool, as `[keybindings.buttons]` says
+                            match app.button_for(button) {
+                                Some(ButtonBinding::Pan) => app.begin_pan(cursor),
+                                Some(ButtonBinding::Menu) => {
+                                    app.open_context_menu();
+                                    needs_redraw = true;
+                                }
+                                Some(ButtonBinding::Tool(name)) => needs_redraw |= unsafe { app.hold_tool(&name, cursor) }.unwrap(),
+                                Some(ButtonBinding::Action(action)) => needs_redraw |= run_action(&mut app, action, window, &document_path, &mut pass_through),
+                                None => {}
                             }
-                            _ => { }
                         }
                     }
-                }
-                // Track mouse button state
-                WindowEvent::MouseInput { state, button, .. } => {
-                    if button == MouseButton::Left {
-                        left_mouse_down = state == ElementState::Pressed;
-                        if !left_mouse_down {
-                            unsafe { app.commit_new_line().unwrap() };
+                    // Scrolling zooms around the cursor
+                    WindowEvent::MouseWheel { delta, .. } if !ui.consumed => {
+                        let lines = match delta {
+                            MouseScrollDelta::LineDelta(_, y) => y,
+                            MouseScrollDelta::PixelDelta(position) => (position.y / PIXELS_PER_SCROLL_LINE) as f32,
+                        };
+                        app.scroll_zoom(lines);
+                        needs_redraw = true;
+                    }
+                    // Track the cursor and let the active tool react to it
+                    WindowEvent::CursorMoved { position, .. } => {
+                        cursor = app.to_ndc(position);
+                        needs_redraw |= unsafe { app.pointer_moved(cursor) }.unwrap();
+                    }
+                    // One finger draws like the left button, two fingers pan and zoom the canvas
+                    WindowEvent::Touch(touch) => {
+                        let position = app.to_ndc(touch.location);
+                        let starts_on_ui = touch.phase == TouchPhase::Started && ui.consumed;
+                        if !starts_on_ui {
+                            needs_redraw |= match touches.update(touch.id, touch.phase, position) {
+                                Some(PointerEvent::Down(p)) => unsafe { app.pointer_down(p) }.unwrap(),
+                                Some(PointerEvent::Moved(p)) => unsafe { app.pointer_moved(p) }.unwrap(),
+                                Some(PointerEvent::Up(p)) => unsafe { app.pointer_up(p) }.unwrap(),
+                                Some(PointerEvent::Cancel) => unsafe { app.pointer_cancel() }.unwrap(),
+                                Some(PointerEvent::Pinch { from, to, scale }) => {
+                                    app.set_camera(app.camera().zoomed_around(from, scale).panned(to - from));
+                                    true
+                                }
+                                None => false,
+                            };
                         }
+                    }
+                    // Trackpad pinch (macOS) zooms around the cursor
+                    WindowEvent::TouchpadMagnify { delta, .. } if !ui.consumed => {
+                        app.set_camera(app.camera().zoomed_around(cursor, 1.0 + delta as f32));
                         needs_redraw = true;
                     }
+                    _ => {}
                 }
-                // Record position only when left button is down
-                WindowEvent::CursorMoved { position, .. } if left_mouse_down => {
-                    let window_size = window.inner_size();
-
-                    // Convert pixel coordinates to NDC (-1 to 1)
-                    let ndc_x = (position.x as f32 / window_size.width as f32) * 2.0 - 1.0;
-                    let ndc_y = (position.y as f32 / window_size.height as f32) * 2.0 - 1.0;
-
-                    // Create a vertex at the mouse position
-                    let vertex = Vec2::new(ndc_x, ndc_y);
-
-                    // Append it to your vertex list
-                    unsafe { app.append_vertex(vertex) }.unwrap();
-                    needs_redraw = true;
-                }
-                _ => {}
             }
             _ => {}
         }
@@ -143,3 +407,177 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Shortest time between frames after input and while only animating, on the window's current monitor
+fn frame_intervals(config: &WindowConfig, window: &Window) -> (Duration, Duration) {
+    let input = frame_interval(config.frame_rate, window);
+    let animation = config
+        .animation_frame_rate
+        .map_or(input, |rate| frame_interval(rate, window));
+    (input, animation)
+}
+
+/// Shortest time between two frames at `rate` on the window's current monitor
+fn frame_interval(rate: FrameRate, window: &Window) -> Duration {
+    let millihertz = match rate {
+        FrameRate::Fps(fps) => fps.saturating_mul(1000),
+        FrameRate::Uncapped => return Duration::ZERO,
+        FrameRate::Monitor => window
+            .current_monitor()
+            .and_then(|m| m.refresh_rate_millihertz())
+            .unwrap_or(FALLBACK_REFRESH_MILLIHERTZ),
+    };
+    Duration::from_secs_f64(1000.0 / millihertz.max(1) as f64)
+}
+
+/// The monitor `[window] monitor` picks, else the primary one
+fn pick_monitor(
+    event_loop: &EventLoopWindowTarget<Wake>,
+    choice: Option<&MonitorChoice>,
+) -> Option<MonitorHandle> {
+    if let Some(choice) = choice {
+        let found = event_loop
+            .available_monitors()
+            .enumerate()
+            .find(|(index, monitor)| choice.matches(*index, monitor.name().as_deref()));
+        if let Some((_, monitor)) = found {
+            return Some(monitor);
+        }
+
+        let available: Vec<String> = event_loop
+            .available_monitors()
+            .enumerate()
+            .map(|(index, monitor)| format!("{} `{}`", index, monitor.name().unwrap_or_default()))
+            .collect();
+        log::warn!(
+            "No monitor matches {:?}, opening on the primary one (available: {}).",
+            choice,
+            available.join(", ")
+        );
+    }
+
+    event_loop
+        .primary_monitor()
+        .or_else(|| event_loop.available_monitors().next())
+}
+
+/// Where the window's top left corner goes on `monitor`: `[window] position`
+/// from the monitor's corner, or centered if that is unset or off the monitor
+fn window_position(config: &WindowConfig, monitor: &MonitorHandle) -> PhysicalPosition<i32> {
+    let (origin, bounds) = (monitor.position(), monitor.size());
+    let on_monitor = |&[x, y]: &[i32; 2]| {
+        (0..bounds.width as i32).contains(&x) && (0..bounds.height as i32).contains(&y)
+    };
+    let [x, y] = config.position.filter(on_monitor).unwrap_or_else(|| {
+        let size = LogicalSize::new(config.width, config.height)
+            .to_physical::<i32>(monitor.scale_factor());
+        [
+            (bounds.width as i32 - size.width) / 2,
+            (bounds.height as i32 - size.height) / 2,
+        ]
+    });
+    PhysicalPosition::new(origin.x + x, origin.y + y)
+}
+
+/// Fullscreen on `monitor`, in its native video mode with the highest refresh
+/// rate if `exclusive_fullscreen` is set and the monitor lists one
+fn fullscreen(config: &WindowConfig, monitor: Option<MonitorHandle>) -> Fullscreen {
+    let mode = monitor
+        .as_ref()
+        .filter(|_| config.exclusive_fullscreen)
+        .and_then(|monitor| {
+            monitor
+                .video_modes()
+                .filter(|mode| mode.size() == monitor.size())
+                .max_by_key(|mode| (mode.refresh_rate_millihertz(), mode.bit_depth()))
+        });
+    match mode {
+        Some(mode) => Fullscreen::Exclusive(mode),
+        None => Fullscreen::Borderless(monitor),
+    }
+}
+
+/// Leaves fullscreen, or enters it on the monitor the window is on
+///
+/// The resize that follows recreates the swapchain.
+fn toggle_fullscreen(window: &Window, config: &WindowConfig) {
+    let fullscreen = match window.fullscreen() {
+        Some(_) => None,
+        None => Some(fullscreen(config, window.current_monitor())),
+    };
+    window.set_fullscreen(fullscreen);
+}
+
+/// Writes the settings changed at runtime to the per-user config file if
+/// `session.save_on_exit` is set
+///
+/// Never the file that was loaded, which may be the `config.toml` of a
+/// checkout that is also embedded as the defaults.
+fn save_settings_on_exit(app: &mut App, window: &Window) {
+    if !app.config().session.save_on_exit {
+        return;
+    }
+    let Some(path) = paths::user_config_file() else {
+        log::warn!("No config directory to save the settings to.");
+        return;
+    };
+    if let Err(e) = app.save_settings(window, &path) {
+        log::error!("Failed to save the settings: {:#}", e);
+    }
+}
+
+/// Carries out a bound action, including the ones `App::perform` leaves to
+/// the event loop, returning whether to redraw
+fn run_action(
+    app: &mut App,
+    action: Action,
+    window: &Window,
+    document_path: &Path,
+    pass_through: &mut bool,
+) -> bool {
+    match action {
+        Action::Save => save_document(app, document_path),
+        Action::ToggleFullscreen => toggle_fullscreen(window, &app.config().window),
+        Action::TogglePassThrough if app.config().window.overlay => {
+            *pass_through = set_pass_through(window, !*pass_through);
+        }
+        action => return unsafe { app.perform(&action) }.unwrap(),
+    }
+    false
+}
+
+/// Writes the drawing to `path` and remembers it as a recent file
+fn save_document(app: &mut App, path: &Path) {
+    match app.document().save(path) {
+        Ok(()) => {
+            log::info!("Saved the drawing to `{}`.", path.display());
+            app.add_recent_file(path);
+        }
+        Err(e) => log::error!("{:#}", e),
+    }
+}
+
+/// Lets clicks through the overlay to the windows below, or catches them again
+///
+/// Returns whether clicks now go through, which stays as it was on platforms
+/// that can't change it.
+fn set_pass_through(window: &Window, pass_through: bool) -> bool {
+    match window.set_cursor_hittest(!pass_through) {
+        Ok(()) if pass_through => {
+            log::info!("Clicks now go through the overlay, focus it again to draw.");
+            true
+        }
+        Ok(()) => {
+            // A hotkey may switch back while another window has focus
+            window.focus_window();
+            false
+        }
+        Err(e) => {
+            log::error!(
+                "Failed to change whether clicks go through the overlay: {}",
+                e
+            );
+            !pass_through
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+use log::*;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+
+use super::{spawn_reader, write_message, Authority, Connection, Envelope, NetMessage};
+use crate::document::Document;
+use crate::edit::EditCommand;
+
+/// A connection to one collaborating peer
+///
+/// Incoming messages are read on a background thread and queued until
+/// [`CollabClient::receive`] collects them; `wake` is called after each one
+/// so an idle event loop knows to come back for them.
+pub struct CollabClient {
+    writer: Option<Box<dyn Write + Send>>,
+    incoming: Receiver<Envelope>,
+    peer: String,
+    spectating: bool,
+    /// Messages collected from the peer so far
+    received: u64,
+    /// Set on the side whose board orders the edits of both
+    authority: Option<Authority>,
+    /// Whether the peer needs a snapshot after edits of theirs were dropped or reordered
+    resync: bool,
+}
+
+impl CollabClient {
+    /// Connects to a peer at `address` (e.g. `"192.168.0.2:7878"`)
+    pub fn connect(address: &str, wake: impl Fn() + Send + 'static) -> Result<Self> {
+        let stream = TcpStream::connect(address)
+            .with_context(|| format!("Failed to connect to `{}`", address))?;
+        Self::start(Connection::tcp(stream)?, wake)
+    }
+
+    /// Waits on `port` until a single peer connects, and keeps the
+    /// authoritative board for the two
+    pub fn listen(port: u16, wake: impl Fn() + Send + 'static) -> Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .with_context(|| format!("Failed to listen on port {}", port))?;
+
+        info!("Waiting for a peer on port {}.", port);
+        let (stream, _) = listener.accept()?;
+        Ok(Self::start(Connection::tcp(stream)?, wake)?.with_authority())
+    }
+
+    /// Collaborates over an already open connection, whatever its transport
+    pub fn start(connection: Connection, wake: impl Fn() + Send + 'static) -> Result<Self> {
+        let Connection {
+            reader,
+            writer,
+            peer,
+        } = connection;
+        let (sender, incoming) = mpsc::channel();
+
+        spawn_reader(reader, peer.clone(), move |message| match message {
+            Some(message) => {
+                let open = sender.send(message).is_ok();
+                wake();
+                open
+            }
+            None => false,
+        });
+
+        info!("Collaborating with {}.", peer);
+
+        Ok(Self {
+            writer: Some(writer),
+            incoming,
+            peer,
+            spectating: false,
+            received: 0,
+            authority: None,
+            resync: false,
+        })
+    }
+
+    /// Makes this side's board the one both sides' edits are ordered by (see
+    /// [`Authority`]); exactly one side of a connection should be
+    pub fn with_authority(mut self) -> Self {
+        self.authority = Some(Authority::default());
+        self
+    }
+
+    pub fn peer(&self) -> &str {
+        &self.peer
+    }
+
+    /// Tells the peer this side only watches, and stops sending edits
+    pub fn spectate(&mut self) {
+        self.write(NetMessage::Spectate);
+        self.spectating = true;
+        info!("Spectating {}.", self.peer);
+    }
+
+    /// Sends a locally committed edit to the peer, unless spectating
+    ///
+    /// A failed send closes the connection; drawing carries on locally.
+    pub fn send(&mut self, edit: &EditCommand) {
+        if self.spectating {
+            return;
+        }
+        if let Some(message) = NetMessage::from_edit(edit) {
+            self.write(message);
+        }
+    }
+
+    fn write(&mut self, message: NetMessage) {
+        let Some(writer) = &mut self.writer else {
+            return;
+        };
+
+        if let Some(authority) = &mut self.authority {
+            match message {
+                NetMessage::Snapshot(_) => authority.synced(),
+                _ => authority.sent(),
+            }
+        }
+        let envelope = Envelope {
+            message,
+            seen: self.received,
+        };
+        if let Err(e) = write_message(writer, &envelope) {
+            warn!("Lost connection to {}: {:#}", self.peer, e);
+            self.writer = None;
+        }
+    }
+
+    /// Edits received from the peer since the last call
+    ///
+    /// On the authoritative side, edits the peer made before it saw ours are
+    /// left out (see [`Authority`]); [`CollabClient::resync`] then sends it
+    /// the board.
+    pub fn receive(&mut self) -> Vec<EditCommand> {
+        let envelopes: Vec<Envelope> = self.incoming.try_iter().collect();
+        let mut edits = Vec::new();
+        for envelope in envelopes {
+            self.received += 1;
+            if let Some(authority) = &self.authority {
+                let resolution = authority.resolve(&envelope);
+                self.resync |= resolution.resync;
+                if resolution.echo {
+                    self.write(envelope.message.clone());
+                }
+                if !resolution.apply {
+                    warn!(
+                        "Dropping an edit {} made before it saw the latest board.",
+                        self.peer
+                    );
+                    continue;
+                }
+            }
+            edits.extend(envelope.message.to_edits());
+        }
+        edits
+    }
+
+    /// Sends the peer a snapshot of `board` if [`CollabClient::receive`]
+    /// dropped or reordered edits of theirs, replacing what they have
+    ///
+    /// `board` is only called then, once the received edits are applied.
+    pub fn resync(&mut self, board: impl FnOnce() -> Document) {
+        if std::mem::take(&mut self.resync) {
+            info!("Sending {} the board again.", self.peer);
+            self.write(NetMessage::Snapshot(board()));
+        }
+    }
+}
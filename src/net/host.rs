@@ -0,0 +1,265 @@
+use anyhow::{anyhow, Context, Result};
+use log::*;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use super::{spawn_reader, write_message, Authority, Connection, Envelope, NetMessage};
+use crate::document::{Document, Stroke};
+use crate::types::Transform;
+
+/// Something that happened on one of the host's connections
+enum HostEvent {
+    Joined(usize, Box<dyn Write + Send>),
+    Message(usize, Envelope),
+    Left(usize),
+}
+
+/// The authoritative copy of a shared board
+///
+/// Applies the same edits as the clients' canvases, so a late joiner's
+/// snapshot matches what everyone else sees. Undo and redo arrive as the
+/// edits they resolved to on the client that made them.
+#[derive(Default)]
+pub struct Board {
+    strokes: Vec<Stroke>,
+}
+
+impl Board {
+    pub fn new(document: Document) -> Self {
+        Self {
+            strokes: document.strokes,
+        }
+    }
+
+    pub fn apply(&mut self, message: &NetMessage) {
+        match message {
+            NetMessage::Stroke(stroke) => {
+                // Fading strokes are only relayed, since they are gone before anyone joins
+                if stroke.lines().is_empty() || stroke.fade_after.is_some() {
+                    return;
+                }
+                self.strokes.push(stroke.clone());
+            }
+            NetMessage::Remove { strokes } => {
+                for i in self.existing(strokes).into_iter().rev() {
+                    self.strokes.remove(i);
+                }
+            }
+            NetMessage::Transform { strokes, transform } => {
+                let indices = self.existing(strokes);
+                self.transform(&indices, transform);
+            }
+            NetMessage::Tag { strokes, tag } => {
+                let indices = self.existing(strokes);
+                self.tag(&indices, tag);
+            }
+            NetMessage::Insert { strokes } => {
+                for (i, stroke) in strokes {
+                    if *i <= self.strokes.len() && !stroke.lines().is_empty() {
+                        self.strokes.insert(*i, stroke.clone());
+                    }
+                }
+            }
+            NetMessage::Replace { strokes } => {
+                for (i, stroke) in strokes {
+                    if *i < self.strokes.len() && !stroke.lines().is_empty() {
+                        self.strokes[*i] = stroke.clone();
+                    }
+                }
+            }
+            NetMessage::Clear => self.strokes.clear(),
+            NetMessage::Snapshot(_) => {
+                warn!("Ignoring a snapshot sent by a client.");
+            }
+            NetMessage::Spectate => {}
+        }
+    }
+
+    /// The indices of strokes that exist, sorted and without duplicates
+    fn existing(&self, strokes: &[usize]) -> Vec<usize> {
+        let mut indices: Vec<usize> = strokes
+            .iter()
+            .copied()
+            .filter(|&i| i < self.strokes.len())
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
+    fn tag(&mut self, indices: &[usize], tag: &Option<String>) {
+        for &i in indices {
+            self.strokes[i].tag = tag.clone();
+        }
+    }
+
+    fn transform(&mut self, indices: &[usize], transform: &Transform) {
+        for &i in indices {
+            self.strokes[i] = self.strokes[i].transformed(transform);
+        }
+    }
+
+    pub fn snapshot(&self) -> Document {
+        Document {
+            strokes: self.strokes.clone(),
+        }
+    }
+}
+
+/// A client's end of the board
+struct Client {
+    writer: Box<dyn Write + Send>,
+    /// Messages the client sent, stamped on ours as how many we had seen
+    received: u64,
+    authority: Authority,
+}
+
+impl Client {
+    /// Sends a message, returning `false` once the connection is gone
+    fn send(&mut self, message: NetMessage) -> bool {
+        let snapshot = matches!(message, NetMessage::Snapshot(_));
+        let envelope = Envelope {
+            message,
+            seen: self.received,
+        };
+        if write_message(&mut self.writer, &envelope).is_err() {
+            return false;
+        }
+        if snapshot {
+            self.authority.synced();
+        } else {
+            self.authority.sent();
+        }
+        true
+    }
+}
+
+/// Serves a shared board on `port` until the process is stopped
+///
+/// Every edit a client sends is applied to the board and relayed to all
+/// other clients; clients that join later start from a snapshot. The board
+/// is the [`Authority`]: an edit a client made before seeing everyone
+/// else's is dropped (or kept, if it only adds a stroke) and the client
+/// gets a fresh snapshot. Clients that sent [`NetMessage::Spectate`] only receive.
+pub fn run_host(port: u16, document: Document) -> Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .with_context(|| format!("Failed to listen on port {}", port))?;
+    info!("Hosting a board on port {}.", port);
+
+    let (events, incoming) = mpsc::channel();
+    thread::spawn(move || accept_clients(listener, events));
+
+    let mut board = Board::new(document);
+    let mut clients = HashMap::new();
+    let mut spectators = HashSet::new();
+
+    for event in incoming {
+        match event {
+            HostEvent::Joined(id, writer) => {
+                let mut client = Client {
+                    writer,
+                    received: 0,
+                    authority: Authority::default(),
+                };
+                if client.send(NetMessage::Snapshot(board.snapshot())) {
+                    clients.insert(id, client);
+                } else {
+                    warn!("Failed to send the board to a new client.");
+                }
+                info!("{} client(s) connected.", clients.len());
+            }
+            HostEvent::Message(id, envelope) => {
+                let Some(client) = clients.get_mut(&id) else {
+                    continue;
+                };
+                client.received += 1;
+                if let NetMessage::Spectate = envelope.message {
+                    spectators.insert(id);
+                    info!("Client {} is spectating.", id);
+                    continue;
+                }
+                if spectators.contains(&id) {
+                    warn!("Ignoring an edit from spectating client {}.", id);
+                    continue;
+                }
+
+                let resolution = client.authority.resolve(&envelope);
+                if resolution.apply {
+                    board.apply(&envelope.message);
+                    clients.retain(|&other, client| {
+                        other == id || client.send(envelope.message.clone())
+                    });
+                } else {
+                    warn!(
+                        "Dropping an edit client {} made before it saw the latest board.",
+                        id
+                    );
+                }
+
+                let Some(client) = clients.get_mut(&id) else {
+                    continue;
+                };
+                let open = if resolution.resync {
+                    client.send(NetMessage::Snapshot(board.snapshot()))
+                } else if resolution.echo {
+                    client.send(envelope.message)
+                } else {
+                    true
+                };
+                if !open {
+                    clients.remove(&id);
+                }
+            }
+            HostEvent::Left(id) => {
+                clients.remove(&id);
+                spectators.remove(&id);
+                info!("{} client(s) connected.", clients.len());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn accept_clients(listener: TcpListener, events: Sender<HostEvent>) {
+    for (id, stream) in listener.incoming().enumerate() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Failed to accept a client: {:#}", e);
+                continue;
+            }
+        };
+
+        if let Err(e) = add_client(id, stream, &events) {
+            warn!("Failed to set up a client: {:#}", e);
+        }
+    }
+}
+
+fn add_client(id: usize, stream: TcpStream, events: &Sender<HostEvent>) -> Result<()> {
+    let Connection {
+        reader,
+        writer,
+        peer,
+    } = Connection::tcp(stream)?;
+    info!("Client {} joined.", peer);
+
+    // Queue the join first so the snapshot goes out before any relayed edits
+    events
+        .send(HostEvent::Joined(id, writer))
+        .map_err(|_| anyhow!("Host has shut down"))?;
+
+    let sender = events.clone();
+    spawn_reader(reader, peer, move |message| {
+        let event = match message {
+            Some(envelope) => HostEvent::Message(id, envelope),
+            None => HostEvent::Left(id),
+        };
+        sender.send(event).is_ok()
+    });
+    Ok(())
+}
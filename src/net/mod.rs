@@ -0,0 +1,282 @@
+pub mod client;
+pub mod host;
+
+use anyhow::Result;
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+
+use crate::document::{Document, Stroke};
+use crate::edit::EditCommand;
+use crate::types::Transform;
+
+//================================================
+// Protocol
+//================================================
+
+/// A message exchanged between collaborating canvases
+///
+/// Messages are sent as one JSON object per line, with strokes in the same
+/// encoding as saved documents. Only committed edits travel over the wire;
+/// in-progress strokes stay local until they are finished. Undo and redo
+/// travel as the edits they resolved to, since every canvas keeps its own history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NetMessage {
+    Stroke(Stroke),
+    /// Strokes removed by index in drawing order, e.g. by the eraser
+    Remove {
+        strokes: Vec<usize>,
+    },
+    /// Strokes moved, scaled or rotated, by index in drawing order
+    Transform {
+        strokes: Vec<usize>,
+        transform: Transform,
+    },
+    /// Strokes filed under a tag, or untagged, by index in drawing order
+    Tag {
+        strokes: Vec<usize>,
+        tag: Option<String>,
+    },
+    /// Strokes put back at their indices in drawing order, ascending, e.g. by undoing a removal
+    Insert {
+        strokes: Vec<(usize, Stroke)>,
+    },
+    /// Strokes swapped for other versions by index in drawing order, e.g. by
+    /// undoing a transform
+    Replace {
+        strokes: Vec<(usize, Stroke)>,
+    },
+    Clear,
+    /// The whole board, sent by a host to clients when they join
+    Snapshot(Document),
+    /// Sent by a client that only watches; a host ignores its edits from then on
+    Spectate,
+}
+
+impl NetMessage {
+    /// The message announcing a committed edit, `None` for local-only edits
+    pub fn from_edit(edit: &EditCommand) -> Option<Self> {
+        match edit {
+            EditCommand::AddStroke(stroke) => Some(NetMessage::Stroke(stroke.clone())),
+            EditCommand::RemoveStrokes(indices) => Some(NetMessage::Remove {
+                strokes: indices.clone(),
+            }),
+            EditCommand::TransformStrokes(indices, transform) => Some(NetMessage::Transform {
+                strokes: indices.clone(),
+                transform: *transform,
+            }),
+            EditCommand::TagStrokes(indices, tag) => Some(NetMessage::Tag {
+                strokes: indices.clone(),
+                tag: tag.clone(),
+            }),
+            EditCommand::InsertStrokes(strokes) => Some(NetMessage::Insert {
+                strokes: strokes.clone(),
+            }),
+            EditCommand::ReplaceStrokes(strokes) => Some(NetMessage::Replace {
+                strokes: strokes.clone(),
+            }),
+            EditCommand::Clear => Some(NetMessage::Clear),
+            // Only the authoritative side sends snapshots, and it does so itself
+            EditCommand::ReplaceAll(_) => None,
+            // Erasing, cropping, moving the selection, undo and redo are sent
+            // as the removal, transform or other edit they resolved to;
+            // selection and the canvas bounds stay local
+            EditCommand::StrokeTo(_)
+            | EditCommand::SetStroke(_)
+            | EditCommand::CommitStroke
+            | EditCommand::Erase(_)
+            | EditCommand::Crop(_)
+            | EditCommand::Select(_)
+            | EditCommand::TransformSelection(_)
+            | EditCommand::CommitTransform
+            | EditCommand::Undo
+            | EditCommand::Redo => None,
+        }
+    }
+
+    /// The edits to apply when this message arrives from a peer
+    pub fn to_edits(&self) -> Vec<EditCommand> {
+        match self {
+            NetMessage::Stroke(stroke) => vec![EditCommand::AddStroke(stroke.clone())],
+            NetMessage::Remove { strokes } => vec![EditCommand::RemoveStrokes(strokes.clone())],
+            NetMessage::Transform { strokes, transform } => {
+                vec![EditCommand::TransformStrokes(strokes.clone(), *transform)]
+            }
+            NetMessage::Tag { strokes, tag } => {
+                vec![EditCommand::TagStrokes(strokes.clone(), tag.clone())]
+            }
+            NetMessage::Insert { strokes } => vec![EditCommand::InsertStrokes(strokes.clone())],
+            NetMessage::Replace { strokes } => vec![EditCommand::ReplaceStrokes(strokes.clone())],
+            NetMessage::Clear => vec![EditCommand::Clear],
+            NetMessage::Snapshot(document) => {
+                vec![EditCommand::ReplaceAll(document.strokes.clone())]
+            }
+            NetMessage::Spectate => Vec::new(),
+        }
+    }
+
+    /// Whether the message does the same whatever the board it arrives at,
+    /// like a fading stroke, which never takes a stroke index
+    fn is_unordered(&self) -> bool {
+        match self {
+            NetMessage::Stroke(stroke) => stroke.fade_after.is_some(),
+            NetMessage::Spectate => true,
+            _ => false,
+        }
+    }
+}
+
+/// A message as it goes over the wire, with how many messages its sender
+/// had received on the same connection by then
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    #[serde(flatten)]
+    pub message: NetMessage,
+    #[serde(default)]
+    pub seen: u64,
+}
+
+/// Writes one message as a line of JSON
+pub fn write_message(writer: &mut impl Write, envelope: &Envelope) -> Result<()> {
+    let mut line = serde_json::to_string(envelope)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads the next message, `None` once the peer has closed the connection
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Envelope>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+//================================================
+// Ordering
+//================================================
+
+/// Keeps the order of edits on the authoritative side of a connection
+///
+/// Edits name strokes by index, so an edit only means the same on both
+/// sides if it was made on the same board. The host (or the side that
+/// listened for a peer) is authoritative: an edit its peer sent before
+/// seeing all of the authoritative side's messages was made on a board
+/// that no longer exists, so it is dropped, and the peer gets a snapshot
+/// replacing whatever it had. New strokes don't name an index and are kept,
+/// only their order may differ, which the snapshot fixes.
+#[derive(Debug, Default)]
+pub struct Authority {
+    /// Messages sent to the peer
+    sent: u64,
+    /// `sent` right after the latest snapshot, which the peer's board is
+    /// replaced with as soon as it arrives
+    synced: u64,
+}
+
+/// What the authoritative side does with an edit from its peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolution {
+    /// Apply the edit (and relay it, on a host)
+    pub apply: bool,
+    /// Send the peer a snapshot of the board once the edit is applied
+    pub resync: bool,
+    /// Send the edit back to the peer, whose board a snapshot on its way
+    /// will replace without it
+    pub echo: bool,
+}
+
+impl Authority {
+    /// Counts a message sent to the peer
+    pub fn sent(&mut self) {
+        self.sent += 1;
+    }
+
+    /// Counts a snapshot sent to the peer
+    pub fn synced(&mut self) {
+        self.sent();
+        self.synced = self.sent;
+    }
+
+    /// Decides what to do with an edit the peer sent
+    pub fn resolve(&self, envelope: &Envelope) -> Resolution {
+        if envelope.seen >= self.sent || envelope.message.is_unordered() {
+            return Resolution {
+                apply: true,
+                resync: false,
+                echo: false,
+            };
+        }
+
+        let appends = matches!(envelope.message, NetMessage::Stroke(_));
+        let snapshot_pending = envelope.seen < self.synced;
+        Resolution {
+            apply: appends,
+            resync: !snapshot_pending,
+            echo: appends && snapshot_pending,
+        }
+    }
+}
+
+//================================================
+// Transport
+//================================================
+
+/// A byte stream to a peer that messages travel over, split into its two directions
+///
+/// The protocol only needs bytes in order, so any transport that can
+/// provide both halves works; TCP is the one built in.
+pub struct Connection {
+    pub reader: Box<dyn Read + Send>,
+    pub writer: Box<dyn Write + Send>,
+    /// Who is on the other end, for log messages
+    pub peer: String,
+}
+
+impl Connection {
+    pub fn tcp(stream: TcpStream) -> Result<Self> {
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            peer: stream.peer_addr()?.to_string(),
+            reader: Box::new(stream.try_clone()?),
+            writer: Box::new(stream),
+        })
+    }
+}
+
+/// Reads messages from `reader` on a background thread
+///
+/// `handle` gets every message, then `None` once the connection is gone, and
+/// can return `false` to stop reading early.
+fn spawn_reader(
+    reader: Box<dyn Read + Send>,
+    peer: String,
+    mut handle: impl FnMut(Option<Envelope>) -> bool + Send + 'static,
+) {
+    let mut reader = BufReader::new(reader);
+
+    thread::spawn(move || loop {
+        let message = match read_message(&mut reader) {
+            Ok(Some(message)) => Some(message),
+            Ok(None) => {
+                info!("Peer {} disconnected.", peer);
+                None
+            }
+            Err(e) => {
+                warn!("Dropping connection to {}: {:#}", peer, e);
+                None
+            }
+        };
+
+        let closed = message.is_none();
+        if !handle(message) || closed {
+            break;
+        }
+    });
+}
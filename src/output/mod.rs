@@ -0,0 +1,98 @@
+pub mod ndi;
+
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use vulkanalia::prelude::v1_0::*;
+
+use crate::camera::Camera;
+use crate::config::{Config, OutputBackend};
+use crate::types::{Color, StrokeStyle};
+use crate::vulkan::context::VulkanContext;
+use crate::vulkan::offscreen::OffscreenRenderer;
+
+//================================================
+// Frame Sinks
+//================================================
+
+/// Somewhere published frames go, like an NDI sender
+pub trait FrameSink {
+    /// Sends one frame of tightly packed RGBA8 rows
+    fn send(&mut self, width: u32, height: u32, pixels: &[u8]) -> Result<()>;
+}
+
+//================================================
+// Video Output
+//================================================
+
+/// Publishes the canvas as a video source, see `[output]`
+///
+/// After a window frame [`VideoOutput::publish`] renders the same strokes
+/// offscreen, without the UI, reads the image back into host memory and
+/// hands it to the sink, at most `frame_rate` times a second. Frames are
+/// only published when the window redraws, so an idle canvas sends nothing
+/// and receivers keep showing the last frame.
+pub struct VideoOutput {
+    sink: Box<dyn FrameSink>,
+    /// Created on first use and whenever the window size changes
+    offscreen: Option<OffscreenRenderer>,
+    interval: Duration,
+    last_sent: Option<Instant>,
+}
+
+impl VideoOutput {
+    /// Opens the configured backend
+    pub fn create(config: &Config) -> Result<Self> {
+        let output = &config.output;
+        let sink: Box<dyn FrameSink> = match output.backend {
+            OutputBackend::Ndi => Box::new(ndi::NdiSender::create(output)?),
+        };
+
+        Ok(Self {
+            sink,
+            offscreen: None,
+            interval: Duration::from_secs(1) / output.frame_rate.max(1),
+            last_sent: None,
+        })
+    }
+
+    /// Whether enough time has passed since the last frame to send another
+    pub fn is_due(&self) -> bool {
+        self.last_sent.is_none_or(|t| t.elapsed() >= self.interval)
+    }
+
+    /// Renders `batches` through `camera` at `extent` and sends the image
+    pub unsafe fn publish(
+        &mut self,
+        context: &VulkanContext,
+        config: &Config,
+        extent: vk::Extent2D,
+        background: Color,
+        camera: &Camera,
+        style: &StrokeStyle,
+        (rect_buffer, index_buffer): (vk::Buffer, vk::Buffer),
+        batches: &[(vk::Buffer, Range<u32>)],
+    ) -> Result<()> {
+        let offscreen = match &mut self.offscreen {
+            Some(offscreen) if offscreen.extent == extent => offscreen,
+            slot => {
+                // The old target goes first, so both never take up memory at once
+                *slot = None;
+                slot.insert(OffscreenRenderer::create(
+                    context,
+                    config,
+                    extent.width,
+                    extent.height,
+                    false,
+                )?)
+            }
+        };
+        offscreen.background = background;
+        let pixels =
+            offscreen.render_view(context, rect_buffer, index_buffer, camera, style, batches)?;
+        self.last_sent = Some(Instant::now());
+
+        self.sink.send(extent.width, extent.height, &pixels)
+    }
+}
@@ -0,0 +1,194 @@
+use std::ffi::{c_char, c_int, c_void, CString};
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use libloading::Library;
+use log::*;
+
+use super::FrameSink;
+use crate::config::OutputConfig;
+
+/// `NDIlib_send_create_t`
+#[repr(C)]
+struct SendCreate {
+    name: *const c_char,
+    groups: *const c_char,
+    clock_video: bool,
+    clock_audio: bool,
+}
+
+/// `NDIlib_video_frame_v2_t`
+#[repr(C)]
+struct VideoFrame {
+    xres: c_int,
+    yres: c_int,
+    four_cc: u32,
+    frame_rate_n: c_int,
+    frame_rate_d: c_int,
+    picture_aspect_ratio: f32,
+    frame_format_type: c_int,
+    timecode: i64,
+    data: *const u8,
+    line_stride_in_bytes: c_int,
+    metadata: *const c_char,
+    timestamp: i64,
+}
+
+/// `NDIlib_FourCC_video_type_RGBX`: 8-bit RGBA rows with the alpha ignored
+const FOURCC_RGBX: u32 = u32::from_le_bytes(*b"RGBX");
+
+/// `NDIlib_frame_format_type_progressive`
+const FRAME_FORMAT_PROGRESSIVE: c_int = 1;
+
+/// `NDIlib_send_timecode_synthesize`, letting NDI timestamp frames itself
+const TIMECODE_SYNTHESIZE: i64 = i64::MAX;
+
+type Initialize = unsafe extern "C" fn() -> bool;
+type Destroy = unsafe extern "C" fn();
+type SendCreateFn = unsafe extern "C" fn(*const SendCreate) -> *mut c_void;
+type SendDestroy = unsafe extern "C" fn(*mut c_void);
+type SendVideo = unsafe extern "C" fn(*mut c_void, *const VideoFrame);
+
+/// An NDI source publishing the canvas, using the NDI runtime library
+/// loaded at startup so the app builds and runs without it
+pub struct NdiSender {
+    instance: *mut c_void,
+    send_video: SendVideo,
+    send_destroy: SendDestroy,
+    destroy: Destroy,
+    frame_rate: u32,
+    /// Declared last, since the function pointers above point into it
+    _library: Library,
+}
+
+impl NdiSender {
+    pub fn create(config: &OutputConfig) -> Result<Self> {
+        let library = load_library(config)?;
+        let name = CString::new(config.name.as_str()).context("NDI source name contains a NUL")?;
+
+        unsafe {
+            let initialize = *library.get::<Initialize>(b"NDIlib_initialize\0")?;
+            let destroy = *library.get::<Destroy>(b"NDIlib_destroy\0")?;
+            let send_create = *library.get::<SendCreateFn>(b"NDIlib_send_create\0")?;
+            let send_destroy = *library.get::<SendDestroy>(b"NDIlib_send_destroy\0")?;
+            let send_video = *library.get::<SendVideo>(b"NDIlib_send_send_video_v2\0")?;
+
+            if !initialize() {
+                return Err(anyhow!("The NDI runtime does not support this CPU."));
+            }
+
+            // Frames go out as the canvas is redrawn, not paced by NDI
+            let settings = SendCreate {
+                name: name.as_ptr(),
+                groups: std::ptr::null(),
+                clock_video: false,
+                clock_audio: false,
+            };
+            let instance = send_create(&settings);
+            if instance.is_null() {
+                destroy();
+                return Err(anyhow!(
+                    "Failed to create the NDI source `{}`.",
+                    config.name
+                ));
+            }
+
+            info!("Publishing the canvas as NDI source `{}`.", config.name);
+            Ok(Self {
+                instance,
+                send_video,
+                send_destroy,
+                destroy,
+                frame_rate: config.frame_rate,
+                _library: library,
+            })
+        }
+    }
+}
+
+impl FrameSink for NdiSender {
+    fn send(&mut self, width: u32, height: u32, pixels: &[u8]) -> Result<()> {
+        if pixels.len() != (width * height * 4) as usize {
+            return Err(anyhow!(
+                "Frame of {}x{} has {} bytes.",
+                width,
+                height,
+                pixels.len()
+            ));
+        }
+
+        let frame = VideoFrame {
+            xres: width as c_int,
+            yres: height as c_int,
+            four_cc: FOURCC_RGBX,
+            frame_rate_n: self.frame_rate as c_int,
+            frame_rate_d: 1,
+            picture_aspect_ratio: width as f32 / height.max(1) as f32,
+            frame_format_type: FRAME_FORMAT_PROGRESSIVE,
+            timecode: TIMECODE_SYNTHESIZE,
+            data: pixels.as_ptr(),
+            line_stride_in_bytes: (width * 4) as c_int,
+            metadata: std::ptr::null(),
+            timestamp: 0,
+        };
+        // The synchronous send is done with the pixels when it returns
+        unsafe { (self.send_video)(self.instance, &frame) };
+
+        Ok(())
+    }
+}
+
+impl Drop for NdiSender {
+    fn drop(&mut self) {
+        unsafe {
+            (self.send_destroy)(self.instance);
+            (self.destroy)();
+        }
+    }
+}
+
+/// Opens `[output] library`, or the NDI runtime wherever it is usually installed
+fn load_library(config: &OutputConfig) -> Result<Library> {
+    let candidates = match &config.library {
+        Some(path) => vec![path.clone()],
+        None => default_library_paths(),
+    };
+
+    for path in &candidates {
+        match unsafe { Library::new(path) } {
+            Ok(library) => return Ok(library),
+            Err(e) => debug!("Could not load `{}`: {}", path.display(), e),
+        }
+    }
+
+    Err(anyhow!(
+        "Could not find the NDI runtime (tried {}); install it or set `output.library`.",
+        candidates
+            .iter()
+            .map(|p| format!("`{}`", p.display()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+fn default_library_paths() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        let file = "Processing.NDI.Lib.x64.dll";
+        ["NDI_RUNTIME_DIR_V6", "NDI_RUNTIME_DIR_V5"]
+            .iter()
+            .filter_map(std::env::var_os)
+            .map(|dir| PathBuf::from(dir).join(file))
+            .chain([PathBuf::from(file)])
+            .collect()
+    } else if cfg!(target_os = "macos") {
+        ["libndi.dylib", "/usr/local/lib/libndi.dylib"]
+            .iter()
+            .map(PathBuf::from)
+            .collect()
+    } else {
+        ["libndi.so.6", "libndi.so.5", "libndi.so"]
+            .iter()
+            .map(PathBuf::from)
+            .collect()
+    }
+}
@@ -0,0 +1,69 @@
+use directories::ProjectDirs;
+use std::path::{Path, PathBuf};
+
+/// File name of the configuration, in the working directory or the config directory
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "scribble-vk")
+}
+
+/// The configuration file to use when none is given on the command line
+///
+/// A `config.toml` in the working directory wins, so running from a checkout
+/// keeps working; otherwise it is the one in [`config_dir`], whether or not
+/// it exists yet.
+pub fn config_file() -> PathBuf {
+    let local = Path::new(CONFIG_FILE_NAME);
+    if local.exists() {
+        return local.to_path_buf();
+    }
+
+    match config_dir() {
+        Some(dir) => dir.join(CONFIG_FILE_NAME),
+        None => local.to_path_buf(),
+    }
+}
+
+/// The per-user configuration file in [`config_dir`], where settings are
+/// saved on exit whichever file was loaded
+pub fn user_config_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(CONFIG_FILE_NAME))
+}
+
+/// Per-user configuration, e.g. `$XDG_CONFIG_HOME/scribble-vk` on Linux,
+/// `~/Library/Application Support/scribble-vk` on macOS and
+/// `%APPDATA%\scribble-vk\config` on Windows
+pub fn config_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+/// Per-user data such as autosaved drawings, e.g. `$XDG_DATA_HOME/scribble-vk`
+pub fn data_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+/// Where autosaved drawings go
+pub fn autosave_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("autosave"))
+}
+
+/// Where the drawing is saved when the app panics, offered back on the next launch
+pub fn emergency_file() -> Option<PathBuf> {
+    autosave_dir().map(|dir| dir.join("emergency.json"))
+}
+
+/// Where log files go
+pub fn log_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.data_local_dir().join("logs"))
+}
+
+/// Files that can be rebuilt at any time, e.g. `$XDG_CACHE_HOME/scribble-vk`
+pub fn cache_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.cache_dir().to_path_buf())
+}
+
+/// Where the Vulkan pipeline cache is kept between runs
+pub fn pipeline_cache_file() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("pipeline_cache.bin"))
+}
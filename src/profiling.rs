@@ -0,0 +1,177 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_char, c_int, CString};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use libloading::Library;
+use log::*;
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Metadata, Subscriber};
+
+/// Name of the span whose end is marked as the end of a frame
+pub const FRAME_SPAN: &str = "frame";
+
+/// `___tracy_source_location_data`
+#[repr(C)]
+struct SourceLocation {
+    name: *const c_char,
+    function: *const c_char,
+    file: *const c_char,
+    line: u32,
+    color: u32,
+}
+
+/// `___tracy_c_zone_context`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ZoneContext {
+    id: u32,
+    active: c_int,
+}
+
+type ZoneBegin = unsafe extern "C" fn(*const SourceLocation, c_int) -> ZoneContext;
+type ZoneEnd = unsafe extern "C" fn(ZoneContext);
+type FrameMark = unsafe extern "C" fn(*const c_char);
+
+thread_local! {
+    /// Zones entered on this thread and not left yet, innermost last
+    static ZONES: RefCell<Vec<ZoneContext>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A `tracing` subscriber that turns spans into Tracy zones
+///
+/// The Tracy client is loaded at runtime from a `TracyClient` shared library
+/// (built with `TRACY_ENABLE`), like the NDI runtime, so the feature adds no
+/// build dependencies. Every entered span becomes a zone named after it, and
+/// leaving the [`FRAME_SPAN`] span marks the end of a frame.
+///
+/// Span ids are the addresses of their callsite's source location, which
+/// lives as long as the program, so entering a span needs no lookup.
+pub struct TracySubscriber {
+    zone_begin: ZoneBegin,
+    zone_end: ZoneEnd,
+    frame_mark: FrameMark,
+    /// Source location address for each registered callsite's metadata address
+    locations: Mutex<HashMap<usize, usize>>,
+    /// Address of the `frame` span's source location, once registered
+    frame: Mutex<Option<u64>>,
+    /// Declared last, since the function pointers above point into it
+    _library: Library,
+}
+
+impl TracySubscriber {
+    pub fn create() -> Result<Self> {
+        let library = load_library()?;
+        unsafe {
+            let zone_begin = *library.get::<ZoneBegin>(b"___tracy_emit_zone_begin\0")?;
+            let zone_end = *library.get::<ZoneEnd>(b"___tracy_emit_zone_end\0")?;
+            let frame_mark = *library.get::<FrameMark>(b"___tracy_emit_frame_mark\0")?;
+            Ok(Self {
+                zone_begin,
+                zone_end,
+                frame_mark,
+                locations: Mutex::new(HashMap::new()),
+                frame: Mutex::new(None),
+                _library: library,
+            })
+        }
+    }
+
+    /// The source location of a callsite, leaked on first use since Tracy keeps pointers to it
+    fn location(&self, metadata: &'static Metadata<'static>) -> u64 {
+        let mut locations = self.locations.lock().unwrap();
+        let key = metadata as *const _ as usize;
+        let address = *locations.entry(key).or_insert_with(|| {
+            let text = |s: &str| CString::new(s).unwrap_or_default().into_raw() as *const c_char;
+            let location = Box::new(SourceLocation {
+                name: text(metadata.name()),
+                function: text(metadata.module_path().unwrap_or(metadata.target())),
+                file: text(metadata.file().unwrap_or("")),
+                line: metadata.line().unwrap_or(0),
+                color: 0,
+            });
+            Box::into_raw(location) as usize
+        });
+        if metadata.name() == FRAME_SPAN {
+            *self.frame.lock().unwrap() = Some(address as u64);
+        }
+        address as u64
+    }
+}
+
+impl Subscriber for TracySubscriber {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if metadata.is_span() {
+            self.location(metadata);
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_span()
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        Id::from_u64(self.location(span.metadata()))
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, span: &Id) {
+        let location = span.into_u64() as *const SourceLocation;
+        let zone = unsafe { (self.zone_begin)(location, 1) };
+        ZONES.with(|zones| zones.borrow_mut().push(zone));
+    }
+
+    fn exit(&self, span: &Id) {
+        if let Some(zone) = ZONES.with(|zones| zones.borrow_mut().pop()) {
+            unsafe { (self.zone_end)(zone) };
+        }
+        if *self.frame.lock().unwrap() == Some(span.into_u64()) {
+            unsafe { (self.frame_mark)(std::ptr::null()) };
+        }
+    }
+}
+
+/// Sends spans to Tracy from now on, if its client library can be loaded
+pub fn install() -> Result<()> {
+    let subscriber = TracySubscriber::create()?;
+    tracing::subscriber::set_global_default(subscriber)?;
+    info!("Profiling with Tracy.");
+    Ok(())
+}
+
+/// Opens the Tracy client from the library search path
+fn load_library() -> Result<Library> {
+    let candidates: &[&str] = if cfg!(windows) {
+        &["TracyClient.dll"]
+    } else if cfg!(target_os = "macos") {
+        &["libTracyClient.dylib"]
+    } else {
+        &["libTracyClient.so"]
+    };
+
+    for name in candidates {
+        match unsafe { Library::new(name) } {
+            Ok(library) => return Ok(library),
+            Err(e) => debug!("Could not load `{}`: {}", name, e),
+        }
+    }
+
+    Err(anyhow!(
+        "Could not find the Tracy client (tried {}); build it with TRACY_ENABLE and put it on the library path.",
+        candidates
+            .iter()
+            .map(|name| format!("`{}`", name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
@@ -0,0 +1,410 @@
+use anyhow::{anyhow, Context, Result};
+use log::*;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use winit::keyboard::KeyCode;
+
+use crate::config::ScriptConfig;
+use crate::document::{Document, Stroke};
+use crate::edit::EditCommand;
+use crate::input::parse_key_code;
+use crate::turtle::Turtle;
+use crate::types::{Color, Vec2};
+
+/// Most Rhai operations one run may take, so a runaway loop fails the
+/// script instead of freezing the window
+pub const MAX_SCRIPT_OPERATIONS: u64 = 10_000_000;
+
+/// A canvas operation requested by a script, applied by `App` once the script finishes
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptCommand {
+    /// A canvas edit, applied like any other
+    Edit(EditCommand),
+    /// Writes the canvas to a PNG file
+    Export(PathBuf),
+}
+
+/// Rhai engine exposing canvas operations, plus the key bindings that run scripts
+///
+/// Scripts can call `stroke([[x, y], ...])` (or `polyline`), `line(x1, y1, x2, y2)`,
+/// `circle(x, y, radius)`, `color("#rrggbb")`, `clear()`, `undo()`, `redo()`
+/// and `save_png("file.png")`. Strokes start out in the brush color.
+///
+/// `stroke`, `polyline` and `circle` also take a style map as their last
+/// argument, e.g. `#{ color: "#ff8000", fade: 3.0, tag: "grid", brush: "neon" }`,
+/// which applies to that stroke only.
+///
+/// Each run also gets a [`Turtle`]: `forward(d)`, `back(d)`, `left(degrees)`,
+/// `right(degrees)`, `go_to(x, y)`, `set_heading(degrees)`, `pen_up()` and
+/// `pen_down()`. Connected moves with the pen down make up one stroke.
+pub struct ScriptEngine {
+    engine: Engine,
+    commands: Rc<RefCell<Vec<ScriptCommand>>>,
+    color: Rc<Cell<Color>>,
+    turtle: Rc<RefCell<TurtleState>>,
+    directory: PathBuf,
+    bindings: HashMap<KeyCode, PathBuf>,
+}
+
+/// The running script's turtle and the stroke its current path is drawn into
+#[derive(Default)]
+struct TurtleState {
+    turtle: Turtle,
+    /// Index of the path's [`EditCommand::AddStroke`] in the queued commands
+    stroke: Option<usize>,
+}
+
+impl TurtleState {
+    /// Queues a segment the turtle drew, extending its path if nothing else
+    /// was queued since and the path ends where the segment starts
+    fn draw(
+        &mut self,
+        commands: &mut Vec<ScriptCommand>,
+        segment: Option<(Vec2, Vec2)>,
+        color: Color,
+    ) {
+        let Some((from, to)) = segment else {
+            return;
+        };
+        let path = self
+            .stroke
+            .filter(|&i| i + 1 == commands.len())
+            .and_then(|i| match &mut commands[i] {
+                ScriptCommand::Edit(EditCommand::AddStroke(stroke)) => Some(stroke),
+                _ => None,
+            })
+            .filter(|s| s.color == color && s.points.last() == Some(&[from.x, from.y]));
+
+        match path {
+            Some(stroke) => stroke.points.push([to.x, to.y]),
+            None => {
+                self.stroke = Some(commands.len());
+                commands.push(ScriptCommand::Edit(EditCommand::AddStroke(
+                    Stroke::from_points(&[from, to], color),
+                )));
+            }
+        }
+    }
+}
+
+impl ScriptEngine {
+    pub fn new(config: &ScriptConfig) -> Self {
+        let commands = Rc::new(RefCell::new(Vec::new()));
+        let color = Rc::new(Cell::new(Color::WHITE));
+        let turtle = Rc::new(RefCell::new(TurtleState::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_SCRIPT_OPERATIONS);
+
+        for name in ["stroke", "polyline"] {
+            let (queue, current) = (commands.clone(), color.clone());
+            engine.register_fn(
+                name,
+                move |points: Array| -> Result<(), Box<EvalAltResult>> {
+                    let points = to_points(&points)?;
+                    queue
+                        .borrow_mut()
+                        .push(ScriptCommand::Edit(EditCommand::AddStroke(
+                            Stroke::from_points(&points, current.get()),
+                        )));
+                    Ok(())
+                },
+            );
+
+            let (queue, current) = (commands.clone(), color.clone());
+            engine.register_fn(
+                name,
+                move |points: Array, style: Map| -> Result<(), Box<EvalAltResult>> {
+                    let stroke = Stroke::from_points(&to_points(&points)?, current.get());
+                    queue
+                        .borrow_mut()
+                        .push(ScriptCommand::Edit(EditCommand::AddStroke(styled(
+                            stroke, &style,
+                        )?)));
+                    Ok(())
+                },
+            );
+        }
+
+        let (queue, current) = (commands.clone(), color.clone());
+        engine.register_fn(
+            "circle",
+            move |x: Dynamic, y: Dynamic, radius: Dynamic| -> Result<(), Box<EvalAltResult>> {
+                let center = Vec2::new(to_f32(&x)?, to_f32(&y)?);
+                queue
+                    .borrow_mut()
+                    .push(ScriptCommand::Edit(EditCommand::AddStroke(Stroke::circle(
+                        center,
+                        to_f32(&radius)?,
+                        current.get(),
+                    ))));
+                Ok(())
+            },
+        );
+
+        let (queue, current) = (commands.clone(), color.clone());
+        engine.register_fn(
+            "circle",
+            move |x: Dynamic,
+                  y: Dynamic,
+                  radius: Dynamic,
+                  style: Map|
+                  -> Result<(), Box<EvalAltResult>> {
+                let center = Vec2::new(to_f32(&x)?, to_f32(&y)?);
+                let stroke = Stroke::circle(center, to_f32(&radius)?, current.get());
+                queue
+                    .borrow_mut()
+                    .push(ScriptCommand::Edit(EditCommand::AddStroke(styled(
+                        stroke, &style,
+                    )?)));
+                Ok(())
+            },
+        );
+
+        let (queue, current) = (commands.clone(), color.clone());
+        engine.register_fn(
+            "line",
+            move |x1: Dynamic,
+                  y1: Dynamic,
+                  x2: Dynamic,
+                  y2: Dynamic|
+                  -> Result<(), Box<EvalAltResult>> {
+                let from = Vec2::new(to_f32(&x1)?, to_f32(&y1)?);
+                let to = Vec2::new(to_f32(&x2)?, to_f32(&y2)?);
+                queue
+                    .borrow_mut()
+                    .push(ScriptCommand::Edit(EditCommand::AddStroke(
+                        Stroke::from_points(&[from, to], current.get()),
+                    )));
+                Ok(())
+            },
+        );
+
+        let current = color.clone();
+        engine.register_fn(
+            "color",
+            move |hex: &str| -> Result<(), Box<EvalAltResult>> {
+                let color = Color::from_hex(hex)
+                    .ok_or_else(|| format!("invalid color `{}`, expected \"#rrggbb\"", hex))?;
+                current.set(color);
+                Ok(())
+            },
+        );
+
+        let queue = commands.clone();
+        engine.register_fn("clear", move || {
+            queue
+                .borrow_mut()
+                .push(ScriptCommand::Edit(EditCommand::Clear));
+        });
+
+        let queue = commands.clone();
+        engine.register_fn("undo", move || {
+            queue
+                .borrow_mut()
+                .push(ScriptCommand::Edit(EditCommand::Undo));
+        });
+
+        let queue = commands.clone();
+        engine.register_fn("redo", move || {
+            queue
+                .borrow_mut()
+                .push(ScriptCommand::Edit(EditCommand::Redo));
+        });
+
+        for (name, sign) in [("forward", 1.0), ("back", -1.0)] {
+            let (queue, current, turtle) = (commands.clone(), color.clone(), turtle.clone());
+            engine.register_fn(
+                name,
+                move |distance: Dynamic| -> Result<(), Box<EvalAltResult>> {
+                    let mut turtle = turtle.borrow_mut();
+                    let segment = turtle.turtle.forward(sign * to_f32(&distance)?);
+                    turtle.draw(&mut queue.borrow_mut(), segment, current.get());
+                    Ok(())
+                },
+            );
+        }
+
+        for (name, sign) in [("right", 1.0), ("left", -1.0)] {
+            let turtle = turtle.clone();
+            engine.register_fn(
+                name,
+                move |degrees: Dynamic| -> Result<(), Box<EvalAltResult>> {
+                    turtle.borrow_mut().turtle.turn(sign * to_f32(&degrees)?);
+                    Ok(())
+                },
+            );
+        }
+
+        let (queue, current, state) = (commands.clone(), color.clone(), turtle.clone());
+        engine.register_fn(
+            "go_to",
+            move |x: Dynamic, y: Dynamic| -> Result<(), Box<EvalAltResult>> {
+                let mut turtle = state.borrow_mut();
+                let segment = turtle.turtle.go_to(Vec2::new(to_f32(&x)?, to_f32(&y)?));
+                turtle.draw(&mut queue.borrow_mut(), segment, current.get());
+                Ok(())
+            },
+        );
+
+        let state = turtle.clone();
+        engine.register_fn(
+            "set_heading",
+            move |degrees: Dynamic| -> Result<(), Box<EvalAltResult>> {
+                state.borrow_mut().turtle.set_heading(to_f32(&degrees)?);
+                Ok(())
+            },
+        );
+
+        let state = turtle.clone();
+        engine.register_fn("pen_up", move || state.borrow_mut().turtle.pen_up());
+
+        let state = turtle.clone();
+        engine.register_fn("pen_down", move || state.borrow_mut().turtle.pen_down());
+
+        let queue = commands.clone();
+        engine.register_fn("save_png", move |path: &str| {
+            queue
+                .borrow_mut()
+                .push(ScriptCommand::Export(PathBuf::from(path)));
+        });
+
+        let mut bindings = HashMap::new();
+        for (key, script) in &config.bindings {
+            match parse_key_code(key) {
+                Some(code) => {
+                    bindings.insert(code, script.clone());
+                }
+                None => warn!("Ignoring script binding for unknown key `{}`.", key),
+            }
+        }
+
+        Self {
+            engine,
+            commands,
+            color,
+            turtle,
+            directory: config.directory.clone(),
+            bindings,
+        }
+    }
+
+    /// Path of the script bound to `key`, resolved against the scripts directory
+    pub fn script_for(&self, key: KeyCode) -> Option<PathBuf> {
+        self.bindings.get(&key).map(|s| self.directory.join(s))
+    }
+
+    /// Runs a script file and returns the canvas commands it issued
+    pub fn run_file(&self, path: &Path, brush_color: Color) -> Result<Vec<ScriptCommand>> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read script `{}`", path.display()))?;
+
+        self.run(&source, brush_color)
+    }
+
+    /// Runs script source and returns the canvas commands it issued
+    pub fn run(&self, source: &str, brush_color: Color) -> Result<Vec<ScriptCommand>> {
+        self.commands.borrow_mut().clear();
+        self.color.set(brush_color);
+        *self.turtle.borrow_mut() = TurtleState::default();
+
+        let result = self.engine.run(source);
+        let commands = std::mem::take(&mut *self.commands.borrow_mut());
+
+        result.map_err(|e| anyhow!("{}", e))?;
+        Ok(commands)
+    }
+}
+
+/// A script that draws `document` again, one `stroke` call per stroke
+///
+/// Colors, tags and brushes are kept in each call's style map, so running the script
+/// on an empty canvas gives back the same strokes, and the script can be
+/// edited as text in between.
+pub fn document_script(document: &Document) -> String {
+    let mut script = format!(
+        "// {} strokes exported by scribble-vk; run this script to draw them again.\n\n",
+        document.strokes.len()
+    );
+    for stroke in &document.strokes {
+        let points: Vec<String> = stroke
+            .points
+            .iter()
+            .map(|[x, y]| format!("[{}, {}]", x, y))
+            .collect();
+        let mut style = format!("color: {:?}", String::from(stroke.color));
+        if let Some(tag) = &stroke.tag {
+            style.push_str(&format!(", tag: {:?}", tag));
+        }
+        if let Some(brush) = &stroke.brush {
+            style.push_str(&format!(", brush: {:?}", brush));
+        }
+        script.push_str(&format!(
+            "stroke([{}], #{{ {} }});\n",
+            points.join(", "),
+            style
+        ));
+    }
+    script
+}
+
+/// Writes [`document_script`] for `document` to `path`
+pub fn export_script(document: &Document, path: &Path) -> Result<()> {
+    std::fs::write(path, document_script(document))
+        .with_context(|| format!("Failed to write script `{}`", path.display()))
+}
+
+fn to_f32(value: &Dynamic) -> Result<f32, Box<EvalAltResult>> {
+    let number = if let Ok(float) = value.as_float() {
+        float as f32
+    } else if let Ok(int) = value.as_int() {
+        int as f32
+    } else {
+        return Err(format!("expected a number, got {}", value.type_name()).into());
+    };
+    if !number.is_finite() {
+        return Err(format!("expected a finite number, got {}", value).into());
+    }
+    Ok(number)
+}
+
+fn to_points(points: &Array) -> Result<Vec<Vec2>, Box<EvalAltResult>> {
+    points.iter().map(to_point).collect()
+}
+
+/// `stroke` with the `color`, `fade` (seconds), `tag` and `brush` of a style map applied
+fn styled(mut stroke: Stroke, style: &Map) -> Result<Stroke, Box<EvalAltResult>> {
+    for (key, value) in style {
+        match key.as_str() {
+            "color" => {
+                let hex = value.clone().into_string()?;
+                stroke.color = Color::from_hex(&hex)
+                    .ok_or_else(|| format!("invalid color `{}`, expected \"#rrggbb\"", hex))?;
+            }
+            "fade" => stroke.fade_after = Some(to_f32(value)?),
+            "tag" => stroke.tag = Some(value.clone().into_string()?),
+            "brush" => stroke.brush = Some(value.clone().into_string()?),
+            other => {
+                return Err(format!(
+                    "unknown style `{}`, expected `color`, `fade`, `tag` or `brush`",
+                    other
+                )
+                .into())
+            }
+        }
+    }
+
+    Ok(stroke)
+}
+
+fn to_point(value: &Dynamic) -> Result<Vec2, Box<EvalAltResult>> {
+    let point = value
+        .clone()
+        .try_cast::<Array>()
+        .filter(|p| p.len() == 2)
+        .ok_or_else(|| format!("expected a point [x, y], got {}", value.type_name()))?;
+
+    Ok(Vec2::new(to_f32(&point[0])?, to_f32(&point[1])?))
+}
@@ -0,0 +1,221 @@
+use std::ops::Range;
+
+use crate::strokes::{StoredStroke, StrokeStore};
+use crate::types::{Rect, Vec2};
+
+/// Strokes a node holds before it is split into quadrants
+const MAX_NODE_STROKES: usize = 8;
+
+/// Side length below which nodes are no longer split, so piles of tiny strokes can't recurse forever
+const MIN_NODE_SIZE: f32 = 1.0 / 1024.0;
+
+/// A quadtree over the bounds of committed strokes
+///
+/// Answers "which strokes could touch this area" without looking at every
+/// stroke, for erasing, picking, selection and culling the view. Queries
+/// only compare bounds, so callers still test the segments of each
+/// candidate. Strokes are identified by their index in drawing order and
+/// also remember where their segments are in the vertex buffer, both as
+/// the [`StrokeStore`] had them.
+///
+/// Appending a stroke is cheap; anything that shifts indices (removing,
+/// undoing, clearing) rebuilds the tree with [`StrokeIndex::new`].
+#[derive(Debug, Clone)]
+pub struct StrokeIndex {
+    strokes: Vec<IndexedStroke>,
+    root: Node,
+}
+
+#[derive(Debug, Clone)]
+struct IndexedStroke {
+    /// Bounds of the segments' centerlines, `None` for an empty stroke
+    bounds: Option<Rect>,
+    lines: Range<u32>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    bounds: Rect,
+    /// Strokes that don't fit entirely inside one quadrant
+    strokes: Vec<usize>,
+    children: Option<Box<[Node; 4]>>,
+}
+
+/// Farthest a stroke may reach from the origin and still be indexed, well
+/// inside what doubling the root can cover in `f32`
+const MAX_INDEXED_COORDINATE: f32 = f32::MAX / 8.0;
+
+fn indexable(bounds: &Rect) -> bool {
+    bounds.is_finite()
+        && [bounds.min.x, bounds.min.y, bounds.max.x, bounds.max.y]
+            .iter()
+            .all(|c| c.abs() <= MAX_INDEXED_COORDINATE)
+}
+
+impl Default for StrokeIndex {
+    fn default() -> Self {
+        Self {
+            strokes: Vec::new(),
+            // The default view; grows as strokes land outside it
+            root: Node::new(Rect::around_point(Vec2::new(0.0, 0.0), 1.0)),
+        }
+    }
+}
+
+impl StrokeIndex {
+    pub fn new(strokes: &StrokeStore) -> Self {
+        let mut index = Self::default();
+        for stroke in strokes {
+            index.push(stroke);
+        }
+        index
+    }
+
+    /// Adds a stroke after all others
+    pub fn push(&mut self, stroke: &StoredStroke) {
+        let id = self.strokes.len();
+        let bounds = stroke.bounds;
+        self.strokes.push(IndexedStroke {
+            bounds,
+            lines: stroke.range.clone(),
+        });
+
+        // The root could never grow to cover bounds that aren't finite, or
+        // reach that far without overflowing; such strokes are drawn but can't be found
+        if let Some(bounds) = bounds.filter(indexable) {
+            while !self.root.bounds.contains(&bounds) {
+                self.grow_towards(&bounds);
+            }
+            self.root.insert(id, &bounds, &self.strokes);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.strokes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strokes.is_empty()
+    }
+
+    /// Indices, in drawing order, of the strokes whose bounds intersect `area`
+    pub fn query(&self, area: &Rect) -> Vec<usize> {
+        let mut found = Vec::new();
+        self.root.query(area, &self.strokes, &mut found);
+        found.sort_unstable();
+        found
+    }
+
+    /// Vertex buffer ranges holding the strokes that intersect `area`, in drawing order
+    ///
+    /// Strokes that sit next to each other in the buffer are merged into one range.
+    pub fn line_ranges(&self, area: &Rect) -> Vec<Range<u32>> {
+        let mut ranges: Vec<Range<u32>> = Vec::new();
+        for id in self.query(area) {
+            let lines = self.strokes[id].lines.clone();
+            match ranges.last_mut() {
+                Some(last) if last.end == lines.start => last.end = lines.end,
+                _ => ranges.push(lines),
+            }
+        }
+        ranges
+    }
+
+    /// Doubles the root, keeping the old root as the quadrant away from `bounds`
+    fn grow_towards(&mut self, bounds: &Rect) {
+        let old = self.root.bounds;
+        let size = old.max - old.min;
+        let min = Vec2::new(
+            if bounds.min.x < old.min.x {
+                old.min.x - size.x
+            } else {
+                old.min.x
+            },
+            if bounds.min.y < old.min.y {
+                old.min.y - size.y
+            } else {
+                old.min.y
+            },
+        );
+
+        let mut root = Node::new(Rect {
+            min,
+            max: min + size * 2.0,
+        });
+        let mut children = root.quadrants().map(Node::new);
+        let quadrant = root.quadrant_of(old.min + size / 2.0);
+        children[quadrant] = std::mem::replace(&mut self.root, Node::new(old));
+        root.children = Some(Box::new(children));
+        self.root = root;
+    }
+}
+
+impl Node {
+    fn new(bounds: Rect) -> Self {
+        Self {
+            bounds,
+            strokes: Vec::new(),
+            children: None,
+        }
+    }
+
+    fn insert(&mut self, id: usize, bounds: &Rect, strokes: &[IndexedStroke]) {
+        if let Some(children) = &mut self.children {
+            if let Some(child) = children.iter_mut().find(|c| c.bounds.contains(bounds)) {
+                child.insert(id, bounds, strokes);
+                return;
+            }
+        }
+
+        self.strokes.push(id);
+        if self.children.is_none()
+            && self.strokes.len() > MAX_NODE_STROKES
+            && self.bounds.max.x - self.bounds.min.x > MIN_NODE_SIZE
+        {
+            self.split(strokes);
+        }
+    }
+
+    /// Creates the four quadrants and moves down every stroke that fits in one
+    fn split(&mut self, strokes: &[IndexedStroke]) {
+        self.children = Some(Box::new(self.quadrants().map(Node::new)));
+        for id in std::mem::take(&mut self.strokes) {
+            if let Some(bounds) = strokes[id].bounds {
+                self.insert(id, &bounds, strokes);
+            }
+        }
+    }
+
+    fn quadrants(&self) -> [Rect; 4] {
+        let Rect { min, max } = self.bounds;
+        let center = (min + max) / 2.0;
+        [
+            Rect { min, max: center },
+            Rect::from_corners(Vec2::new(center.x, min.y), Vec2::new(max.x, center.y)),
+            Rect::from_corners(Vec2::new(min.x, center.y), Vec2::new(center.x, max.y)),
+            Rect { min: center, max },
+        ]
+    }
+
+    /// Index in [`Node::quadrants`] of the quadrant containing `point`
+    fn quadrant_of(&self, point: Vec2) -> usize {
+        let center = (self.bounds.min + self.bounds.max) / 2.0;
+        usize::from(point.x >= center.x) + 2 * usize::from(point.y >= center.y)
+    }
+
+    fn query(&self, area: &Rect, strokes: &[IndexedStroke], found: &mut Vec<usize>) {
+        if !self.bounds.intersects(area) {
+            return;
+        }
+
+        found.extend(self.strokes.iter().copied().filter(|&id| {
+            strokes[id]
+                .bounds
+                .is_some_and(|bounds| bounds.intersects(area))
+        }));
+
+        for child in self.children.iter().flat_map(|c| c.iter()) {
+            child.query(area, strokes, found);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::config::StatsFormat;
+
+/// Timings of one rendered frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameRecord {
+    /// When the frame started, counted from the app's start
+    pub at: Duration,
+    /// CPU time from starting the frame until it was presented, waits included
+    pub cpu: Duration,
+    /// GPU time of the latest finished frame, `None` without timestamp queries
+    pub gpu: Option<Duration>,
+    /// Waiting on fences for an earlier frame to finish
+    pub fence: Duration,
+    /// Acquiring the swapchain image
+    pub acquire: Duration,
+    /// Queueing the present
+    pub present: Duration,
+    /// Bytes of line data uploaded, committed strokes and the one being drawn
+    pub upload_bytes: usize,
+}
+
+/// The latest frames' timings, kept for a report when something stutters
+///
+/// Recording is a push into a ring buffer, so it stays on in release builds;
+/// [`FrameStats::save`] writes what it holds as CSV (one row per frame) or
+/// JSON (a summary followed by the frames).
+#[derive(Debug)]
+pub struct FrameStats {
+    /// Oldest first
+    frames: VecDeque<FrameRecord>,
+    capacity: usize,
+}
+
+/// Milliseconds, which reports use throughout
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[derive(Serialize)]
+struct JsonReport {
+    frames: usize,
+    cpu_ms: Summary,
+    gpu_ms: Option<Summary>,
+    stalls_ms: Summary,
+    records: Vec<JsonFrame>,
+}
+
+#[derive(Serialize)]
+struct JsonFrame {
+    at_ms: f64,
+    cpu_ms: f64,
+    gpu_ms: Option<f64>,
+    fence_ms: f64,
+    acquire_ms: f64,
+    present_ms: f64,
+    upload_bytes: usize,
+}
+
+/// Distribution of one timing over the recorded frames
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct Summary {
+    pub mean: f64,
+    pub median: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    /// `None` for no values
+    pub fn of(values: impl IntoIterator<Item = Duration>) -> Option<Self> {
+        let mut values: Vec<f64> = values.into_iter().map(ms).collect();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        let at = |quantile: f64| values[((values.len() - 1) as f64 * quantile).round() as usize];
+        Some(Self {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            median: at(0.5),
+            p99: at(0.99),
+            max: values[values.len() - 1],
+        })
+    }
+}
+
+impl FrameStats {
+    /// Keeps up to `capacity` frames, at least one
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(capacity.max(1)),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Changes how many frames are kept, dropping the oldest if there are too many
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.frames.len() > self.capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Recorded frames, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &FrameRecord> {
+        self.frames.iter()
+    }
+
+    /// Records a frame, dropping the oldest once full
+    pub fn push(&mut self, frame: FrameRecord) {
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    /// Writes one header row and a row per frame, timings in milliseconds
+    ///
+    /// The GPU column is empty for frames without a GPU time.
+    pub fn write_csv(&self, mut out: impl Write) -> Result<()> {
+        writeln!(
+            out,
+            "at_ms,cpu_ms,gpu_ms,fence_ms,acquire_ms,present_ms,upload_bytes"
+        )?;
+        for frame in &self.frames {
+            let gpu = frame
+                .gpu
+                .map(|g| format!("{:.3}", ms(g)))
+                .unwrap_or_default();
+            writeln!(
+                out,
+                "{:.3},{:.3},{},{:.3},{:.3},{:.3},{}",
+                ms(frame.at),
+                ms(frame.cpu),
+                gpu,
+                ms(frame.fence),
+                ms(frame.acquire),
+                ms(frame.present),
+                frame.upload_bytes
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes a summary of CPU, GPU and stall times followed by every frame
+    ///
+    /// Stalls are the fence, acquire and present waits added up.
+    pub fn write_json(&self, out: impl Write) -> Result<()> {
+        let stall = |f: &FrameRecord| f.fence + f.acquire + f.present;
+        let report = JsonReport {
+            frames: self.frames.len(),
+            cpu_ms: Summary::of(self.frames.iter().map(|f| f.cpu)).unwrap_or_default(),
+            gpu_ms: Summary::of(self.frames.iter().filter_map(|f| f.gpu)),
+            stalls_ms: Summary::of(self.frames.iter().map(stall)).unwrap_or_default(),
+            records: self
+                .frames
+                .iter()
+                .map(|f| JsonFrame {
+                    at_ms: ms(f.at),
+                    cpu_ms: ms(f.cpu),
+                    gpu_ms: f.gpu.map(ms),
+                    fence_ms: ms(f.fence),
+                    acquire_ms: ms(f.acquire),
+                    present_ms: ms(f.present),
+                    upload_bytes: f.upload_bytes,
+                })
+                .collect(),
+        };
+        serde_json::to_writer_pretty(out, &report)?;
+        Ok(())
+    }
+
+    /// Writes a report into `directory`, named after the current time, and returns its path
+    pub fn save(&self, directory: &Path, format: StatsFormat) -> Result<PathBuf> {
+        fs::create_dir_all(directory)
+            .with_context(|| format!("Failed to create `{}`", directory.display()))?;
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = directory.join(format!("frame-stats-{}.{}", seconds, format.extension()));
+
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create `{}`", path.display()))?;
+        let mut out = BufWriter::new(file);
+        match format {
+            StatsFormat::Csv => self.write_csv(&mut out)?,
+            StatsFormat::Json => self.write_json(&mut out)?,
+        }
+        out.flush()?;
+        Ok(path)
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::ops::{Index, Range};
+
+use cgmath::AbsDiffEq;
+
+use crate::types::{Color, Line, Rect, Vec2, POINT_EPSILON};
+
+/// Handle of a committed stroke that stays valid while strokes before it
+/// are removed, reordered or brought back
+///
+/// Ids are local to one run: peers and documents still refer to strokes by
+/// their index in drawing order, which every participant agrees on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StrokeId(u64);
+
+/// A committed stroke and where its segments are drawn from
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StoredStroke {
+    pub id: StrokeId,
+    pub lines: Vec<Line>,
+    /// Bounds of the segments' centerlines, `None` for an empty stroke
+    pub bounds: Option<Rect>,
+    /// Tag bit shared by the stroke's segments, 0 when untagged
+    pub tag: u32,
+    /// Segments of the vertex buffer holding `lines`, kept up to date by the store
+    pub range: Range<u32>,
+}
+
+impl StoredStroke {
+    /// The stroke's bounds grown by `margin`, e.g. to cover its width
+    pub fn bounds_with(&self, margin: f32) -> Option<Rect> {
+        self.bounds.map(|bounds| bounds.grown(margin))
+    }
+}
+
+/// The committed strokes in drawing order, which is also their order in the vertex buffer
+///
+/// Strokes can be looked up by index (how edits and peers name them) or by
+/// [`StrokeId`]. Removing or inserting a stroke shifts the indices and
+/// buffer ranges of everything after it, but never an id: a stroke that is
+/// removed and brought back by undo keeps the id it had.
+#[derive(Debug, Default)]
+pub struct StrokeStore {
+    strokes: Vec<StoredStroke>,
+    positions: HashMap<StrokeId, usize>,
+    next_id: u64,
+    line_count: u32,
+}
+
+impl StrokeStore {
+    pub fn len(&self) -> usize {
+        self.strokes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strokes.is_empty()
+    }
+
+    /// Segments of all strokes together, the used part of the vertex buffer
+    pub fn line_count(&self) -> usize {
+        self.line_count as usize
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, StoredStroke> {
+        self.strokes.iter()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&StoredStroke> {
+        self.strokes.get(index)
+    }
+
+    /// Index in drawing order of the stroke with `id`, `None` once it is gone
+    pub fn index_of(&self, id: StrokeId) -> Option<usize> {
+        self.positions.get(&id).copied()
+    }
+
+    pub fn by_id(&self, id: StrokeId) -> Option<&StoredStroke> {
+        self.index_of(id).map(|index| &self.strokes[index])
+    }
+
+    /// Adds a stroke on top of all others, returning its new id
+    pub fn push(&mut self, lines: Vec<Line>) -> StrokeId {
+        self.insert_lines(self.strokes.len(), lines)
+    }
+
+    /// Adds a new stroke at `index`, moving the ones after it up, and returns its id
+    pub fn insert_lines(&mut self, index: usize, lines: Vec<Line>) -> StrokeId {
+        let stroke = self.new_stroke(lines);
+        let id = stroke.id;
+        self.insert(index, stroke);
+        id
+    }
+
+    /// Adds a stroke on top of all others if the store stays within
+    /// `max_lines` segments, returning its new id, else leaves the store alone
+    pub fn push_within(&mut self, lines: Vec<Line>, max_lines: usize) -> Option<StrokeId> {
+        (self.line_count() + lines.len() <= max_lines).then(|| self.push(lines))
+    }
+
+    /// Puts a stroke taken out with [`StrokeStore::remove`] (or a new one) at `index`
+    pub fn insert(&mut self, index: usize, stroke: StoredStroke) {
+        self.line_count += stroke.lines.len() as u32;
+        self.strokes.insert(index, stroke);
+        self.reindex_from(index);
+    }
+
+    /// Takes out the stroke at `index`, moving the ones after it down
+    pub fn remove(&mut self, index: usize) -> StoredStroke {
+        let stroke = self.strokes.remove(index);
+        self.positions.remove(&stroke.id);
+        self.line_count -= stroke.lines.len() as u32;
+        self.reindex_from(index);
+        stroke
+    }
+
+    /// Takes out the topmost stroke
+    pub fn pop(&mut self) -> Option<StoredStroke> {
+        (!self.strokes.is_empty()).then(|| self.remove(self.strokes.len() - 1))
+    }
+
+    /// Swaps in new segments for the stroke at `index`, as many as it had,
+    /// so no other stroke moves in the buffer
+    pub fn replace_lines(&mut self, index: usize, lines: Vec<Line>) {
+        let stroke = &mut self.strokes[index];
+        debug_assert_eq!(stroke.lines.len(), lines.len());
+        stroke.bounds = Rect::around_lines(&lines, 0.0);
+        stroke.tag = lines.first().map_or(0, |l| l.tag);
+        stroke.lines = lines;
+    }
+
+    /// Takes out every stroke, e.g. to keep them for undoing a clear
+    pub fn take_all(&mut self) -> Vec<StoredStroke> {
+        self.positions.clear();
+        self.line_count = 0;
+        std::mem::take(&mut self.strokes)
+    }
+
+    /// Puts strokes taken out by [`StrokeStore::take_all`] back, under any added since
+    pub fn restore_all(&mut self, strokes: Vec<StoredStroke>) {
+        self.strokes.splice(0..0, strokes);
+        self.reindex_from(0);
+        self.line_count = self.strokes.last().map_or(0, |s| s.range.end);
+    }
+
+    /// Segments of the strokes from `index` on, and where in the buffer they start
+    pub fn lines_from(&self, index: usize) -> (usize, Vec<Line>) {
+        let start = self.strokes[..index].last().map_or(0, |s| s.range.end);
+        let lines = self.strokes[index..]
+            .iter()
+            .flat_map(|s| s.lines.iter().copied())
+            .collect();
+        (start as usize, lines)
+    }
+
+    /// A stroke that isn't in the store yet, with a fresh id
+    fn new_stroke(&mut self, lines: Vec<Line>) -> StoredStroke {
+        let id = StrokeId(self.next_id);
+        self.next_id += 1;
+        StoredStroke {
+            id,
+            bounds: Rect::around_lines(&lines, 0.0),
+            tag: lines.first().map_or(0, |l| l.tag),
+            range: 0..0,
+            lines,
+        }
+    }
+
+    /// Refreshes the positions and buffer ranges of the strokes from `index` on
+    fn reindex_from(&mut self, index: usize) {
+        let mut start = self.strokes[..index].last().map_or(0, |s| s.range.end);
+        for (position, stroke) in self.strokes.iter_mut().enumerate().skip(index) {
+            let end = start + stroke.lines.len() as u32;
+            stroke.range = start..end;
+            start = end;
+            self.positions.insert(stroke.id, position);
+        }
+    }
+}
+
+impl Index<usize> for StrokeStore {
+    type Output = StoredStroke;
+
+    fn index(&self, index: usize) -> &StoredStroke {
+        &self.strokes[index]
+    }
+}
+
+impl<'a> IntoIterator for &'a StrokeStore {
+    type Item = &'a StoredStroke;
+    type IntoIter = std::slice::Iter<'a, StoredStroke>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.strokes.iter()
+    }
+}
+
+/// The stroke being drawn, before it is committed to the [`StrokeStore`]
+///
+/// Its segments are drawn straight from a staging buffer of `capacity`
+/// segments, which [`StrokeBuilder::extend_to`] never grows it past: a full
+/// stroke has to be committed (and continued with
+/// [`StrokeBuilder::start_at`]) first, which [`StrokeBuilder::take_full`]
+/// does in one go. Only [`StrokeBuilder::replace`] can make it longer, and
+/// such a stroke is still committed whole.
+#[derive(Debug)]
+pub struct StrokeBuilder {
+    lines: Vec<Line>,
+    /// The first point, while no segment has been drawn from it yet
+    start: Option<Vec2>,
+    capacity: usize,
+    /// [`Line::tilt`] of segments added from now on
+    tilt: Vec2,
+    /// [`Line::brush`] of segments added from now on
+    brush: u32,
+}
+
+impl StrokeBuilder {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: Vec::new(),
+            start: None,
+            capacity: capacity.max(1),
+            tilt: Vec2::new(0.0, 0.0),
+            brush: 0,
+        }
+    }
+
+    /// Sets the pen tilt (or nib) the next segments are drawn with
+    pub fn set_tilt(&mut self, tilt: Vec2) {
+        self.tilt = tilt;
+    }
+
+    /// Sets the brush shader the next segments are drawn with
+    pub fn set_brush(&mut self, brush: u32) {
+        self.brush = brush;
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Segments the staging buffer holds, and so the most a committed piece has
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.lines.len() >= self.capacity
+    }
+
+    /// Where the next segment starts: the end of the last one, else the first point
+    pub fn end(&self) -> Option<Vec2> {
+        match self.lines.last() {
+            Some(last) => Some(last.position + last.dir / 2.0),
+            None => self.start,
+        }
+    }
+
+    /// Extends the stroke to `point`, returning the new segment
+    ///
+    /// The first point only marks where the stroke starts. Points too close
+    /// to the end add nothing, and neither does anything once the stroke is full.
+    pub fn extend_to(&mut self, point: Vec2, color: Color) -> Option<Line> {
+        let Some(end) = self.end() else {
+            self.start = Some(point);
+            return None;
+        };
+        if self.is_full() || end.abs_diff_eq(&point, POINT_EPSILON) {
+            return None;
+        }
+
+        let mut line = Line {
+            tilt: self.tilt,
+            ..Line::new(end, point, color)
+        };
+        line.set_brush(self.brush);
+        self.lines.push(line);
+        Some(line)
+    }
+
+    /// Starts the next stroke at `point` without a segment, e.g. where a committed piece ended
+    pub fn start_at(&mut self, point: Option<Vec2>) {
+        self.start = point;
+    }
+
+    /// Takes out the segments to commit them, leaving an empty stroke with no start
+    pub fn take(&mut self) -> Vec<Line> {
+        self.start = None;
+        std::mem::take(&mut self.lines)
+    }
+
+    /// Takes out the segments of a full stroke to commit them, going on from
+    /// where they end, so a long stroke has no gap between its pieces
+    pub fn take_full(&mut self) -> Option<Vec<Line>> {
+        if !self.is_full() {
+            return None;
+        }
+        let end = self.end();
+        let lines = self.take();
+        self.start_at(end);
+        Some(lines)
+    }
+
+    /// Swaps in `lines` starting at `start`, e.g. a polyline from a tool or script
+    pub fn replace(&mut self, lines: Vec<Line>, start: Option<Vec2>) {
+        self.lines = lines;
+        self.start = start;
+    }
+
+    pub fn clear(&mut self) {
+        self.take();
+    }
+}
@@ -0,0 +1,69 @@
+use cgmath::AbsDiffEq;
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use super::Tool;
+use crate::edit::EditCommand;
+use crate::types::{Rect, Vec2, POINT_EPSILON};
+
+/// Crops the canvas to a rectangle dragged out over it
+///
+/// Letting go sends [`EditCommand::Crop`], which removes the strokes
+/// outside the rectangle and makes it the canvas. Escape drops the drag.
+#[derive(Debug, Default)]
+pub struct CropTool {
+    /// Corner where the drag started and the one following the pointer
+    drag: Option<(Vec2, Vec2)>,
+}
+
+impl Tool for CropTool {
+    fn name(&self) -> &str {
+        "crop"
+    }
+
+    fn pointer_down(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.drag = Some((position, position));
+        vec![]
+    }
+
+    fn pointer_moved(&mut self, position: Vec2) -> Vec<EditCommand> {
+        if let Some((_, end)) = &mut self.drag {
+            *end = position;
+        }
+        vec![]
+    }
+
+    fn pointer_up(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.pointer_moved(position);
+        let area = self.marquee();
+        self.drag = None;
+        area.map(EditCommand::Crop).into_iter().collect()
+    }
+
+    fn key_pressed(&mut self, key: KeyCode, modifiers: ModifiersState) -> Vec<EditCommand> {
+        if key == KeyCode::Escape {
+            self.drag = None;
+        }
+        vec![]
+    }
+
+    fn deactivate(&mut self) -> Vec<EditCommand> {
+        self.drag = None;
+        vec![]
+    }
+
+    fn cancel(&mut self) -> Vec<EditCommand> {
+        self.deactivate()
+    }
+
+    fn marquee(&self) -> Option<Rect> {
+        match self.drag {
+            Some((start, end))
+                if !start.x.abs_diff_eq(&end.x, POINT_EPSILON)
+                    && !start.y.abs_diff_eq(&end.y, POINT_EPSILON) =>
+            {
+                Some(Rect::from_corners(start, end))
+            }
+            _ => None,
+        }
+    }
+}
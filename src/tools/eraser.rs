@@ -0,0 +1,43 @@
+use super::Tool;
+use crate::edit::EditCommand;
+use crate::types::Vec2;
+
+/// Removes whole strokes the pointer passes over while pressed
+#[derive(Debug, Default)]
+pub struct EraserTool {
+    erasing: bool,
+}
+
+impl Tool for EraserTool {
+    fn name(&self) -> &str {
+        "eraser"
+    }
+
+    fn pointer_down(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.erasing = true;
+        vec![EditCommand::Erase(position)]
+    }
+
+    fn pointer_moved(&mut self, position: Vec2) -> Vec<EditCommand> {
+        if self.erasing {
+            vec![EditCommand::Erase(position)]
+        } else {
+            vec![]
+        }
+    }
+
+    fn pointer_up(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.erasing = false;
+        vec![]
+    }
+
+    fn deactivate(&mut self) -> Vec<EditCommand> {
+        self.erasing = false;
+        vec![]
+    }
+
+    fn cancel(&mut self) -> Vec<EditCommand> {
+        self.erasing = false;
+        vec![]
+    }
+}
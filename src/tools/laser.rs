@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+
+use super::Tool;
+use crate::edit::EditCommand;
+use crate::types::Vec2;
+
+/// Number of recent pointer positions the laser trail keeps
+const TRAIL_LENGTH: usize = 24;
+
+/// Pointer for presenting: a short trail follows the cursor while pressed
+/// and vanishes on release, without ever committing a stroke
+#[derive(Debug, Default)]
+pub struct LaserTool {
+    trail: Option<VecDeque<Vec2>>,
+}
+
+impl LaserTool {
+    fn trail_to(&mut self, position: Vec2) -> Vec<EditCommand> {
+        let Some(trail) = &mut self.trail else {
+            return vec![];
+        };
+
+        if trail.len() == TRAIL_LENGTH {
+            trail.pop_front();
+        }
+        trail.push_back(position);
+
+        vec![EditCommand::SetStroke(trail.iter().copied().collect())]
+    }
+}
+
+impl Tool for LaserTool {
+    fn name(&self) -> &str {
+        "laser"
+    }
+
+    fn pointer_down(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.trail = Some(VecDeque::with_capacity(TRAIL_LENGTH));
+        self.trail_to(position)
+    }
+
+    fn pointer_moved(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.trail_to(position)
+    }
+
+    fn pointer_up(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.deactivate()
+    }
+
+    fn deactivate(&mut self) -> Vec<EditCommand> {
+        match self.trail.take() {
+            Some(_) => vec![EditCommand::SetStroke(vec![])],
+            None => vec![],
+        }
+    }
+
+    fn cancel(&mut self) -> Vec<EditCommand> {
+        self.trail = None;
+        vec![]
+    }
+}
@@ -0,0 +1,73 @@
+use super::{snap_angle, Tool};
+use crate::edit::EditCommand;
+use crate::types::Vec2;
+
+/// Straight line from where the pointer was pressed to where it is released
+///
+/// With angle snapping on, the line's direction snaps to the configured step.
+#[derive(Debug, Default)]
+pub struct LineTool {
+    /// Where the line starts and where the pointer last was
+    drag: Option<(Vec2, Vec2)>,
+    snap: Option<f32>,
+}
+
+impl LineTool {
+    fn points(&self, start: Vec2, end: Vec2) -> Vec<Vec2> {
+        match self.snap {
+            Some(step) => vec![start, snap_angle(start, end, step)],
+            None => vec![start, end],
+        }
+    }
+}
+
+impl Tool for LineTool {
+    fn name(&self) -> &str {
+        "line"
+    }
+
+    fn pointer_down(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.drag = Some((position, position));
+        vec![EditCommand::SetStroke(vec![position])]
+    }
+
+    fn pointer_moved(&mut self, position: Vec2) -> Vec<EditCommand> {
+        match self.drag {
+            Some((start, _)) => {
+                self.drag = Some((start, position));
+                vec![EditCommand::SetStroke(self.points(start, position))]
+            }
+            None => vec![],
+        }
+    }
+
+    fn pointer_up(&mut self, position: Vec2) -> Vec<EditCommand> {
+        match self.drag.take() {
+            Some((start, _)) => vec![
+                EditCommand::SetStroke(self.points(start, position)),
+                EditCommand::CommitStroke,
+            ],
+            None => vec![],
+        }
+    }
+
+    fn deactivate(&mut self) -> Vec<EditCommand> {
+        match self.drag.take() {
+            Some(_) => vec![EditCommand::CommitStroke],
+            None => vec![],
+        }
+    }
+
+    fn cancel(&mut self) -> Vec<EditCommand> {
+        self.drag = None;
+        vec![]
+    }
+
+    fn angle_snap_changed(&mut self, step: Option<f32>) -> Vec<EditCommand> {
+        self.snap = step;
+        match self.drag {
+            Some((start, end)) => vec![EditCommand::SetStroke(self.points(start, end))],
+            None => vec![],
+        }
+    }
+}
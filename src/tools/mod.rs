@@ -0,0 +1,190 @@
+pub mod crop;
+pub mod eraser;
+pub mod laser;
+pub mod line;
+pub mod pen;
+pub mod pixel_eraser;
+pub mod protractor;
+pub mod ruler;
+pub mod select;
+pub mod shape;
+
+use cgmath::InnerSpace;
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use crate::edit::EditCommand;
+use crate::types::{Rect, Vec2};
+
+//================================================
+// Tool Interface
+//================================================
+
+/// Interactive input handler that turns pointer and key events into canvas edits
+///
+/// Every method has a default doing nothing, so a tool only implements the
+/// events it cares about.
+pub trait Tool {
+    /// Display name, also used to select the tool
+    fn name(&self) -> &str;
+
+    fn pointer_down(&mut self, position: Vec2) -> Vec<EditCommand> {
+        vec![]
+    }
+
+    /// Called for every cursor move, whether or not the pointer is down
+    fn pointer_moved(&mut self, position: Vec2) -> Vec<EditCommand> {
+        vec![]
+    }
+
+    fn pointer_up(&mut self, position: Vec2) -> Vec<EditCommand> {
+        vec![]
+    }
+
+    fn key_pressed(&mut self, key: KeyCode, modifiers: ModifiersState) -> Vec<EditCommand> {
+        vec![]
+    }
+
+    /// Called when another tool becomes active, so unfinished work can be flushed
+    fn deactivate(&mut self) -> Vec<EditCommand> {
+        vec![]
+    }
+
+    /// Called when the press is taken over (e.g. by a touch gesture) and should be forgotten
+    ///
+    /// `App` discards the in-progress stroke afterwards, so tools only need to
+    /// reset their own state.
+    fn cancel(&mut self) -> Vec<EditCommand> {
+        vec![]
+    }
+
+    /// Rectangle the tool is dragging out (e.g. a selection), outlined by the UI layer
+    fn marquee(&self) -> Option<Rect> {
+        None
+    }
+
+    /// What the tool is measuring, drawn with its reading by the UI layer
+    fn measurement(&self) -> Option<Measure> {
+        None
+    }
+
+    /// Points (in canvas units) the tool lets you grab, drawn as handles by the UI layer
+    fn handles(&self) -> Vec<Vec2> {
+        vec![]
+    }
+
+    /// Called with the step (in radians) directions snap to when the tool
+    /// becomes active and whenever snapping is switched, `None` while off
+    ///
+    /// Tools drawing constrained strokes redraw their preview with it.
+    fn angle_snap_changed(&mut self, step: Option<f32>) -> Vec<EditCommand> {
+        vec![]
+    }
+
+    /// Called with the bounds (in canvas units) of the selected strokes when
+    /// the tool becomes active and whenever they change, `None` once nothing
+    /// is selected
+    fn selection_changed(&mut self, bounds: Option<Rect>) {}
+
+    /// Whether the strokes the tool draws erase the pixels under them
+    /// rather than adding color (see [`PIXEL_ERASER_BRUSH`](crate::types::PIXEL_ERASER_BRUSH))
+    fn erases_pixels(&self) -> bool {
+        false
+    }
+}
+
+/// `to` moved around `from` onto the nearest direction that is a multiple of
+/// `step` radians, keeping its distance
+pub fn snap_angle(from: Vec2, to: Vec2, step: f32) -> Vec2 {
+    let offset = to - from;
+    let angle = (offset.y.atan2(offset.x) / step).round() * step;
+    from + Vec2::new(angle.cos(), angle.sin()) * offset.magnitude()
+}
+
+/// A reading a measuring tool shows over the canvas, in canvas units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Measure {
+    /// The distance between two points
+    Distance(Vec2, Vec2),
+    /// The angle at `vertex` between the rays through `first` and `second`,
+    /// just the first ray until the second is placed
+    Angle {
+        vertex: Vec2,
+        first: Vec2,
+        second: Option<Vec2>,
+    },
+}
+
+//================================================
+// Tool Registry
+//================================================
+
+/// The set of available tools and which one receives input
+pub struct ToolRegistry {
+    tools: Vec<Box<dyn Tool>>,
+    active: usize,
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            tools: Vec::new(),
+            active: 0,
+        };
+        registry.register(Box::new(pen::PenTool::default()));
+        registry.register(Box::new(line::LineTool::default()));
+        registry.register(Box::new(shape::ShapeTool::new(shape::Shape::Rectangle)));
+        registry.register(Box::new(shape::ShapeTool::new(shape::Shape::Ellipse)));
+        registry.register(Box::new(eraser::EraserTool::default()));
+        registry.register(Box::new(select::SelectTool::default()));
+        registry.register(Box::new(laser::LaserTool::default()));
+        registry.register(Box::new(ruler::RulerTool::default()));
+        registry.register(Box::new(protractor::ProtractorTool::default()));
+        registry.register(Box::new(pixel_eraser::PixelEraserTool::default()));
+        registry.register(Box::new(crop::CropTool::default()));
+        registry
+    }
+}
+
+impl ToolRegistry {
+    /// Adds a tool, returning its index
+    pub fn register(&mut self, tool: Box<dyn Tool>) -> usize {
+        self.tools.push(tool);
+        self.tools.len() - 1
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.tools.iter().map(|t| t.name())
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn active(&mut self) -> Option<&mut Box<dyn Tool>> {
+        self.tools.get_mut(self.active)
+    }
+
+    /// Whether the active tool's strokes erase pixels, see [`Tool::erases_pixels`]
+    pub fn erases_pixels(&self) -> bool {
+        self.tools
+            .get(self.active)
+            .is_some_and(|t| t.erases_pixels())
+    }
+
+    /// Makes the tool at `index` active, returning the previous tool's flush edits
+    pub fn select(&mut self, index: usize) -> Option<Vec<EditCommand>> {
+        if index >= self.tools.len() || index == self.active {
+            return None;
+        }
+
+        let edits = self.tools[self.active].deactivate();
+        self.active = index;
+        Some(edits)
+    }
+
+    /// Makes the tool called `name` active
+    pub fn select_by_name(&mut self, name: &str) -> Option<Vec<EditCommand>> {
+        let index = self.tools.iter().position(|t| t.name() == name)?;
+        self.select(index)
+    }
+}
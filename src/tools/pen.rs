@@ -0,0 +1,50 @@
+use super::Tool;
+use crate::edit::EditCommand;
+use crate::types::Vec2;
+
+/// Freehand drawing following the pointer
+#[derive(Debug, Default)]
+pub struct PenTool {
+    drawing: bool,
+}
+
+impl Tool for PenTool {
+    fn name(&self) -> &str {
+        "pen"
+    }
+
+    fn pointer_down(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.drawing = true;
+        vec![EditCommand::StrokeTo(position)]
+    }
+
+    fn pointer_moved(&mut self, position: Vec2) -> Vec<EditCommand> {
+        if self.drawing {
+            vec![EditCommand::StrokeTo(position)]
+        } else {
+            vec![]
+        }
+    }
+
+    fn pointer_up(&mut self, position: Vec2) -> Vec<EditCommand> {
+        if !self.drawing {
+            return vec![];
+        }
+
+        self.drawing = false;
+        vec![EditCommand::StrokeTo(position), EditCommand::CommitStroke]
+    }
+
+    fn deactivate(&mut self) -> Vec<EditCommand> {
+        if std::mem::take(&mut self.drawing) {
+            vec![EditCommand::CommitStroke]
+        } else {
+            vec![]
+        }
+    }
+
+    fn cancel(&mut self) -> Vec<EditCommand> {
+        self.drawing = false;
+        vec![]
+    }
+}
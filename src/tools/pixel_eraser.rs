@@ -0,0 +1,58 @@
+use super::Tool;
+use crate::edit::EditCommand;
+use crate::types::Vec2;
+
+/// Rubs out the pixels of strokes under the pointer while pressed, unlike
+/// the eraser, which removes whole strokes
+///
+/// Draws strokes like the pen, which [`Tool::erases_pixels`] has `App` give
+/// the pixel eraser brush instead of the current one.
+#[derive(Debug, Default)]
+pub struct PixelEraserTool {
+    erasing: bool,
+}
+
+impl Tool for PixelEraserTool {
+    fn name(&self) -> &str {
+        "pixel_eraser"
+    }
+
+    fn pointer_down(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.erasing = true;
+        vec![EditCommand::StrokeTo(position)]
+    }
+
+    fn pointer_moved(&mut self, position: Vec2) -> Vec<EditCommand> {
+        if self.erasing {
+            vec![EditCommand::StrokeTo(position)]
+        } else {
+            vec![]
+        }
+    }
+
+    fn pointer_up(&mut self, position: Vec2) -> Vec<EditCommand> {
+        if !self.erasing {
+            return vec![];
+        }
+
+        self.erasing = false;
+        vec![EditCommand::StrokeTo(position), EditCommand::CommitStroke]
+    }
+
+    fn deactivate(&mut self) -> Vec<EditCommand> {
+        if std::mem::take(&mut self.erasing) {
+            vec![EditCommand::CommitStroke]
+        } else {
+            vec![]
+        }
+    }
+
+    fn cancel(&mut self) -> Vec<EditCommand> {
+        self.erasing = false;
+        vec![]
+    }
+
+    fn erases_pixels(&self) -> bool {
+        true
+    }
+}
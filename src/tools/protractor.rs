@@ -0,0 +1,64 @@
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use super::{Measure, Tool};
+use crate::edit::EditCommand;
+use crate::types::Vec2;
+
+/// Measures angles: click the vertex, then a point on each ray
+///
+/// The next ray follows the pointer until it is placed, so the angle reads
+/// live. A finished angle stays until the next click starts a new one or
+/// Escape clears it; nothing is ever committed as a stroke.
+#[derive(Debug, Default)]
+pub struct ProtractorTool {
+    /// The vertex, then the points placed on the first and second ray
+    placed: Vec<Vec2>,
+    /// Latest pointer position, previewing the next point
+    hover: Option<Vec2>,
+}
+
+impl Tool for ProtractorTool {
+    fn name(&self) -> &str {
+        "protractor"
+    }
+
+    fn pointer_down(&mut self, position: Vec2) -> Vec<EditCommand> {
+        if self.placed.len() == 3 {
+            self.placed.clear();
+        }
+        self.placed.push(position);
+        vec![]
+    }
+
+    fn pointer_moved(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.hover = Some(position);
+        vec![]
+    }
+
+    fn key_pressed(&mut self, key: KeyCode, modifiers: ModifiersState) -> Vec<EditCommand> {
+        if key == KeyCode::Escape {
+            self.placed.clear();
+        }
+        vec![]
+    }
+
+    fn deactivate(&mut self) -> Vec<EditCommand> {
+        self.placed.clear();
+        vec![]
+    }
+
+    fn cancel(&mut self) -> Vec<EditCommand> {
+        self.deactivate()
+    }
+
+    fn measurement(&self) -> Option<Measure> {
+        let (&vertex, placed) = self.placed.split_first()?;
+        let preview = self.hover.filter(|_| placed.len() < 2);
+        let mut rays = placed.iter().copied().chain(preview);
+        Some(Measure::Angle {
+            vertex,
+            first: rays.next()?,
+            second: rays.next(),
+        })
+    }
+}
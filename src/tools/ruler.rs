@@ -0,0 +1,62 @@
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use super::{Measure, Tool};
+use crate::edit::EditCommand;
+use crate::types::Vec2;
+
+/// Measures distances: dragging draws a line labeled with its length, which
+/// stays until the next drag or Escape and is never committed as a stroke
+#[derive(Debug, Default)]
+pub struct RulerTool {
+    /// Where the drag started and where it is now (or ended)
+    measurement: Option<(Vec2, Vec2)>,
+    dragging: bool,
+}
+
+impl Tool for RulerTool {
+    fn name(&self) -> &str {
+        "ruler"
+    }
+
+    fn pointer_down(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.measurement = Some((position, position));
+        self.dragging = true;
+        vec![]
+    }
+
+    fn pointer_moved(&mut self, position: Vec2) -> Vec<EditCommand> {
+        match &mut self.measurement {
+            Some((_, end)) if self.dragging => *end = position,
+            _ => {}
+        }
+        vec![]
+    }
+
+    fn pointer_up(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.pointer_moved(position);
+        self.dragging = false;
+        vec![]
+    }
+
+    fn key_pressed(&mut self, key: KeyCode, modifiers: ModifiersState) -> Vec<EditCommand> {
+        if key == KeyCode::Escape {
+            self.measurement = None;
+        }
+        vec![]
+    }
+
+    fn deactivate(&mut self) -> Vec<EditCommand> {
+        self.measurement = None;
+        self.dragging = false;
+        vec![]
+    }
+
+    fn cancel(&mut self) -> Vec<EditCommand> {
+        self.deactivate()
+    }
+
+    fn measurement(&self) -> Option<Measure> {
+        self.measurement
+            .map(|(from, to)| Measure::Distance(from, to))
+    }
+}
@@ -0,0 +1,241 @@
+use std::f32::consts::PI;
+
+use cgmath::{AbsDiffEq, InnerSpace};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use super::Tool;
+use crate::edit::EditCommand;
+use crate::types::{Rect, Transform, Vec2, POINT_EPSILON};
+
+/// How close (in canvas units) a click has to be to a stroke to select it
+pub const PICK_RADIUS: f32 = 0.015;
+
+/// How far (in canvas units) above the selection the rotation handle sits
+const ROTATE_HANDLE_DISTANCE: f32 = 0.06;
+
+/// Distance (in canvas units) an arrow key moves the selection, ten times that with Shift
+const NUDGE_STEP: f32 = 0.01;
+
+/// Factor `=` and `-` scale the selection by
+const SCALE_STEP: f32 = 1.1;
+
+/// Angle `]` and `[` rotate the selection by
+const ROTATE_STEP: f32 = PI / 12.0;
+
+/// Selects strokes by clicking on them or dragging a rectangle over them
+///
+/// Dragging inside the selection moves it, dragging a corner handle scales it
+/// around the opposite corner and dragging the handle above it rotates it
+/// around its center. Arrow keys nudge the selection, `=`/`-` scale it and
+/// `]`/`[` rotate it.
+#[derive(Debug, Default)]
+pub struct SelectTool {
+    /// Bounds of the selected strokes, kept up to date by `App`
+    selection: Option<Rect>,
+    drag: Option<Drag>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Drag {
+    /// Dragging out a marquee between two corners
+    Marquee(Vec2, Vec2),
+    /// Dragging the selection or one of its handles, from `start` and last seen at `last`
+    Transform {
+        handle: Handle,
+        start: Vec2,
+        last: Vec2,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Handle {
+    Move,
+    /// A corner, scaling around the opposite one
+    Scale {
+        pivot: Vec2,
+    },
+    /// Above the selection, rotating around where its center was when grabbed
+    Rotate {
+        pivot: Vec2,
+    },
+}
+
+impl Handle {
+    /// The change dragging this handle from `from` to `to` makes, `None` while
+    /// the pointer is too close to the pivot to tell
+    fn transform(&self, from: Vec2, to: Vec2) -> Option<Transform> {
+        match *self {
+            Handle::Move => Some(Transform::translation(to - from)),
+            Handle::Scale { pivot } => {
+                let (from, to) = ((from - pivot).magnitude(), (to - pivot).magnitude());
+                (from > POINT_EPSILON && to > POINT_EPSILON)
+                    .then(|| Transform::scaling_around(pivot, to / from))
+            }
+            Handle::Rotate { pivot } => {
+                let (from, to) = (from - pivot, to - pivot);
+                (from.magnitude() > POINT_EPSILON && to.magnitude() > POINT_EPSILON).then(|| {
+                    Transform::rotation_around(pivot, to.y.atan2(to.x) - from.y.atan2(from.x))
+                })
+            }
+        }
+    }
+}
+
+impl SelectTool {
+    /// The handles around `bounds`: the four corners, then the rotation handle
+    fn handles_around(bounds: &Rect) -> [(Vec2, Handle); 5] {
+        let Rect { min, max } = *bounds;
+        let center = (min + max) / 2.0;
+        let corner = |at: Vec2, opposite: Vec2| (at, Handle::Scale { pivot: opposite });
+        [
+            corner(min, max),
+            corner(Vec2::new(max.x, min.y), Vec2::new(min.x, max.y)),
+            corner(max, min),
+            corner(Vec2::new(min.x, max.y), Vec2::new(max.x, min.y)),
+            (
+                Vec2::new(center.x, min.y - ROTATE_HANDLE_DISTANCE),
+                Handle::Rotate { pivot: center },
+            ),
+        ]
+    }
+
+    /// What pressing at `position` grabs, if anything
+    fn grab(&self, position: Vec2) -> Option<Handle> {
+        let bounds = self.selection?;
+        let handle = Self::handles_around(&bounds)
+            .into_iter()
+            .find(|(at, _)| at.abs_diff_eq(&position, PICK_RADIUS));
+        if let Some((_, handle)) = handle {
+            return Some(handle);
+        }
+
+        Rect::around_point(position, PICK_RADIUS)
+            .intersects(&bounds)
+            .then_some(Handle::Move)
+    }
+
+    fn end_transform(&mut self) -> Vec<EditCommand> {
+        match self.drag.take() {
+            Some(Drag::Transform { .. }) => vec![EditCommand::CommitTransform],
+            _ => vec![],
+        }
+    }
+}
+
+impl Tool for SelectTool {
+    fn name(&self) -> &str {
+        "select"
+    }
+
+    fn pointer_down(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.drag = Some(match self.grab(position) {
+            Some(handle) => Drag::Transform {
+                handle,
+                start: position,
+                last: position,
+            },
+            None => Drag::Marquee(position, position),
+        });
+        vec![]
+    }
+
+    fn pointer_moved(&mut self, position: Vec2) -> Vec<EditCommand> {
+        match &mut self.drag {
+            Some(Drag::Marquee(_, end)) => *end = position,
+            Some(Drag::Transform { handle, last, .. }) => {
+                if let Some(transform) = handle.transform(*last, position) {
+                    *last = position;
+                    return vec![EditCommand::TransformSelection(transform)];
+                }
+            }
+            None => {}
+        }
+        vec![]
+    }
+
+    fn pointer_up(&mut self, position: Vec2) -> Vec<EditCommand> {
+        let mut edits = self.pointer_moved(position);
+        match self.drag.take() {
+            Some(Drag::Marquee(start, _)) => {
+                let area = if start.abs_diff_eq(&position, POINT_EPSILON) {
+                    Rect::around_point(position, PICK_RADIUS)
+                } else {
+                    Rect::from_corners(start, position)
+                };
+                edits.push(EditCommand::Select(area));
+            }
+            Some(Drag::Transform { handle, start, .. }) => {
+                edits.push(EditCommand::CommitTransform);
+                // A click inside the selection without dragging picks the stroke under it
+                if handle == Handle::Move && start.abs_diff_eq(&position, POINT_EPSILON) {
+                    edits.push(EditCommand::Select(Rect::around_point(
+                        position,
+                        PICK_RADIUS,
+                    )));
+                }
+            }
+            None => {}
+        }
+        edits
+    }
+
+    fn key_pressed(&mut self, key: KeyCode, modifiers: ModifiersState) -> Vec<EditCommand> {
+        let Some(bounds) = self.selection else {
+            return vec![];
+        };
+
+        let step = if modifiers.shift_key() {
+            NUDGE_STEP * 10.0
+        } else {
+            NUDGE_STEP
+        };
+        let center = (bounds.min + bounds.max) / 2.0;
+        let transform = match key {
+            KeyCode::ArrowLeft => Transform::translation(Vec2::new(-step, 0.0)),
+            KeyCode::ArrowRight => Transform::translation(Vec2::new(step, 0.0)),
+            KeyCode::ArrowUp => Transform::translation(Vec2::new(0.0, -step)),
+            KeyCode::ArrowDown => Transform::translation(Vec2::new(0.0, step)),
+            KeyCode::Equal => Transform::scaling_around(center, SCALE_STEP),
+            KeyCode::Minus => Transform::scaling_around(center, 1.0 / SCALE_STEP),
+            KeyCode::BracketRight => Transform::rotation_around(center, ROTATE_STEP),
+            KeyCode::BracketLeft => Transform::rotation_around(center, -ROTATE_STEP),
+            _ => return vec![],
+        };
+        vec![
+            EditCommand::TransformSelection(transform),
+            EditCommand::CommitTransform,
+        ]
+    }
+
+    fn deactivate(&mut self) -> Vec<EditCommand> {
+        self.end_transform()
+    }
+
+    /// Keeps what a transform drag did so far, since it is already on the canvas
+    fn cancel(&mut self) -> Vec<EditCommand> {
+        self.end_transform()
+    }
+
+    fn marquee(&self) -> Option<Rect> {
+        match self.drag {
+            Some(Drag::Marquee(start, end)) if !start.abs_diff_eq(&end, POINT_EPSILON) => {
+                Some(Rect::from_corners(start, end))
+            }
+            _ => None,
+        }
+    }
+
+    fn handles(&self) -> Vec<Vec2> {
+        match &self.selection {
+            Some(bounds) => Self::handles_around(bounds)
+                .into_iter()
+                .map(|(at, _)| at)
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn selection_changed(&mut self, bounds: Option<Rect>) {
+        self.selection = bounds;
+    }
+}
@@ -0,0 +1,117 @@
+use std::f32::consts::TAU;
+
+use super::{snap_angle, Tool};
+use crate::edit::EditCommand;
+use crate::types::Vec2;
+
+/// Segments used to approximate an ellipse
+const ELLIPSE_SEGMENTS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Rectangle,
+    /// Ellipse inscribed in the dragged rectangle
+    Ellipse,
+}
+
+impl Shape {
+    /// Closed outline of the shape spanning the corners `a` and `b`
+    pub fn outline(self, a: Vec2, b: Vec2) -> Vec<Vec2> {
+        match self {
+            Shape::Rectangle => vec![a, Vec2::new(b.x, a.y), b, Vec2::new(a.x, b.y), a],
+            Shape::Ellipse => {
+                let center = (a + b) / 2.;
+                let radius = (b - a) / 2.;
+                (0..=ELLIPSE_SEGMENTS)
+                    .map(|i| {
+                        let angle = i as f32 / ELLIPSE_SEGMENTS as f32 * TAU;
+                        center + Vec2::new(radius.x * angle.cos(), radius.y * angle.sin())
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Outlined shape dragged from one corner to the opposite one
+///
+/// With angle snapping on, the diagonal snaps to the configured step, so at
+/// 45° (or 15°) steps the shape is a square or circle.
+#[derive(Debug)]
+pub struct ShapeTool {
+    shape: Shape,
+    /// The corner the drag started at and where the pointer last was
+    drag: Option<(Vec2, Vec2)>,
+    snap: Option<f32>,
+}
+
+impl ShapeTool {
+    pub fn new(shape: Shape) -> Self {
+        Self {
+            shape,
+            drag: None,
+            snap: None,
+        }
+    }
+
+    fn outline(&self, start: Vec2, end: Vec2) -> Vec<Vec2> {
+        match self.snap {
+            Some(step) => self.shape.outline(start, snap_angle(start, end, step)),
+            None => self.shape.outline(start, end),
+        }
+    }
+}
+
+impl Tool for ShapeTool {
+    fn name(&self) -> &str {
+        match self.shape {
+            Shape::Rectangle => "rectangle",
+            Shape::Ellipse => "ellipse",
+        }
+    }
+
+    fn pointer_down(&mut self, position: Vec2) -> Vec<EditCommand> {
+        self.drag = Some((position, position));
+        vec![EditCommand::SetStroke(vec![position])]
+    }
+
+    fn pointer_moved(&mut self, position: Vec2) -> Vec<EditCommand> {
+        match self.drag {
+            Some((start, _)) => {
+                self.drag = Some((start, position));
+                vec![EditCommand::SetStroke(self.outline(start, position))]
+            }
+            None => vec![],
+        }
+    }
+
+    fn pointer_up(&mut self, position: Vec2) -> Vec<EditCommand> {
+        match self.drag.take() {
+            Some((start, _)) => vec![
+                EditCommand::SetStroke(self.outline(start, position)),
+                EditCommand::CommitStroke,
+            ],
+            None => vec![],
+        }
+    }
+
+    fn deactivate(&mut self) -> Vec<EditCommand> {
+        match self.drag.take() {
+            Some(_) => vec![EditCommand::CommitStroke],
+            None => vec![],
+        }
+    }
+
+    fn cancel(&mut self) -> Vec<EditCommand> {
+        self.drag = None;
+        vec![]
+    }
+
+    fn angle_snap_changed(&mut self, step: Option<f32>) -> Vec<EditCommand> {
+        self.snap = step;
+        match self.drag {
+            Some((start, end)) => vec![EditCommand::SetStroke(self.outline(start, end))],
+            None => vec![],
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::Result;
+use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+/// Side of the generated tray icon in pixels
+const ICON_SIZE: u32 = 32;
+
+/// What an entry of the tray menu asks the app to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayCommand {
+    /// Shows the window, or hides it to live in the tray
+    ToggleWindow,
+    /// Switches the overlay between drawing and letting clicks through
+    TogglePassThrough,
+    Clear,
+    Quit,
+}
+
+/// Menu entries by id, in the order they are listed
+const ENTRIES: &[(&str, &str, TrayCommand)] = &[
+    ("show", "Show / Hide", TrayCommand::ToggleWindow),
+    (
+        "pass_through",
+        "Draw on Screen / Click Through",
+        TrayCommand::TogglePassThrough,
+    ),
+    ("clear", "Clear Canvas", TrayCommand::Clear),
+    ("quit", "Quit", TrayCommand::Quit),
+];
+
+/// The icon in the system tray, removed when dropped
+///
+/// On Linux the icon belongs to a thread running a GTK main loop, as the
+/// tray needs one and winit doesn't run it, and stays until the app exits.
+pub struct Tray {
+    _icon: Option<TrayIcon>,
+}
+
+impl Tray {
+    /// Adds the icon to the tray, calling `chosen` with each menu entry picked
+    ///
+    /// Must be called on the event loop's thread once it runs (macOS needs
+    /// the loop to have started). The click-through entry is only enabled
+    /// for the `overlay`. On Linux, failures past starting the thread are logged.
+    pub fn create(
+        overlay: bool,
+        chosen: impl Fn(TrayCommand) + Send + Sync + 'static,
+    ) -> Result<Self> {
+        MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+            if let Some(&(_, _, command)) = ENTRIES.iter().find(|(id, _, _)| event.id == *id) {
+                chosen(command);
+            }
+        }));
+
+        #[cfg(target_os = "linux")]
+        {
+            std::thread::Builder::new()
+                .name("tray".to_string())
+                .spawn(move || {
+                    if let Err(e) = gtk::init() {
+                        log::error!("Failed to start GTK for the tray icon: {}", e);
+                        return;
+                    }
+                    match create_icon(overlay) {
+                        Ok(icon) => {
+                            gtk::main();
+                            drop(icon);
+                        }
+                        Err(e) => log::error!("Failed to create the tray icon: {:#}", e),
+                    }
+                })?;
+            Ok(Self { _icon: None })
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        Ok(Self {
+            _icon: Some(create_icon(overlay)?),
+        })
+    }
+}
+
+impl Drop for Tray {
+    fn drop(&mut self) {
+        MenuEvent::set_event_handler(None::<fn(MenuEvent)>);
+    }
+}
+
+fn create_icon(overlay: bool) -> Result<TrayIcon> {
+    let menu = Menu::new();
+    for &(id, text, command) in ENTRIES {
+        if command == TrayCommand::Quit {
+            menu.append(&PredefinedMenuItem::separator())?;
+        }
+        let enabled = overlay || command != TrayCommand::TogglePassThrough;
+        menu.append(&MenuItem::with_id(id, text, enabled, None))?;
+    }
+
+    let icon = TrayIconBuilder::new()
+        .with_menu(Box::new(menu))
+        .with_tooltip("Scribble")
+        .with_icon(Icon::from_rgba(icon_pixels(), ICON_SIZE, ICON_SIZE)?)
+        .build()?;
+    Ok(icon)
+}
+
+/// A white stroke across a dark disc, as RGBA8
+fn icon_pixels() -> Vec<u8> {
+    let center = (ICON_SIZE as f32 - 1.0) / 2.0;
+    let mut pixels = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let (dx, dy) = (x as f32 - center, y as f32 - center);
+            let on_disc = dx * dx + dy * dy <= center * center;
+            // Distance from the diagonal running from bottom left to top right
+            let on_stroke = (dx + dy).abs() < 3.0 && dx.abs() < center * 0.6;
+            pixels.extend_from_slice(match (on_disc, on_stroke) {
+                (true, true) => &[255, 255, 255, 255],
+                (true, false) => &[32, 32, 32, 255],
+                (false, _) => &[0, 0, 0, 0],
+            });
+        }
+    }
+    pixels
+}
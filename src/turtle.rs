@@ -0,0 +1,70 @@
+use crate::types::Vec2;
+
+/// A Logo-style turtle that walks the canvas and draws where it goes
+///
+/// Positions are in canvas units. The turtle starts at the origin facing up
+/// with its pen down. Headings are degrees clockwise from up, which is
+/// towards negative y since canvas y grows downwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Turtle {
+    position: Vec2,
+    heading: f32,
+    pen_down: bool,
+}
+
+impl Default for Turtle {
+    fn default() -> Self {
+        Self {
+            position: Vec2::new(0.0, 0.0),
+            heading: 0.0,
+            pen_down: true,
+        }
+    }
+}
+
+impl Turtle {
+    pub fn position(&self) -> Vec2 {
+        self.position
+    }
+
+    /// Degrees clockwise from up, between 0 and 360
+    pub fn heading(&self) -> f32 {
+        self.heading
+    }
+
+    pub fn is_pen_down(&self) -> bool {
+        self.pen_down
+    }
+
+    /// Moves `distance` along the heading (backwards if negative), returning
+    /// the segment drawn if the pen is down
+    pub fn forward(&mut self, distance: f32) -> Option<(Vec2, Vec2)> {
+        let radians = self.heading.to_radians();
+        let to = self.position + Vec2::new(radians.sin(), -radians.cos()) * distance;
+        self.go_to(to)
+    }
+
+    /// Moves straight to `position` without turning, returning the segment
+    /// drawn if the pen is down
+    pub fn go_to(&mut self, position: Vec2) -> Option<(Vec2, Vec2)> {
+        let from = std::mem::replace(&mut self.position, position);
+        self.pen_down.then_some((from, position))
+    }
+
+    /// Turns clockwise by `degrees`, or counterclockwise if negative
+    pub fn turn(&mut self, degrees: f32) {
+        self.set_heading(self.heading + degrees);
+    }
+
+    pub fn set_heading(&mut self, degrees: f32) {
+        self.heading = degrees.rem_euclid(360.0);
+    }
+
+    pub fn pen_up(&mut self) {
+        self.pen_down = false;
+    }
+
+    pub fn pen_down(&mut self) {
+        self.pen_down = true;
+    }
+}
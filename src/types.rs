@@ -1,11 +1,146 @@
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::mem::size_of;
 
 use vulkanalia::prelude::v1_0::*;
 
+use crate::config::VertexFormat;
+
 pub type Vec2 = cgmath::Vector2<f32>;
 pub type Vec3 = cgmath::Vector3<f32>;
+pub type Vec4 = cgmath::Vector4<f32>;
 pub type Mat4 = cgmath::Matrix4<f32>;
 
+/// Stroke points closer than this (per axis, in canvas units) are merged
+pub const POINT_EPSILON: f32 = 1e-3;
+
+/// Half the width of a stroke in canvas units
+pub const STROKE_THICKNESS: f32 = 0.004;
+
+/// How strokes are drawn this frame, independent of the strokes themselves
+///
+/// Sent to the stroke shaders as push constants next to the camera transform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokeStyle {
+    /// Half the stroke width in canvas units
+    pub thickness: f32,
+    /// Colors darker than this relative luminance are lightened up to it
+    pub min_luminance: f32,
+    /// Seconds since the app started, which [`Line::fade`] counts against
+    pub time: f32,
+    /// Tag bits whose segments are not drawn, see [`Line::tag`]
+    pub hidden_tags: u32,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self {
+            thickness: STROKE_THICKNESS,
+            min_luminance: 0.0,
+            time: 0.0,
+            hidden_tags: 0,
+        }
+    }
+}
+
+/// An sRGB color with straight alpha, as stored per segment in the vertex buffer
+///
+/// Written as `"#rrggbb"` or `"#rrggbbaa"` in config files and documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+#[repr(C)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const TRANSPARENT: Color = Color {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 0,
+    };
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 255 }
+    }
+
+    /// Parses `#rrggbb` or `#rrggbbaa` (the `#` is optional)
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+            return None;
+        }
+
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        Some(Color {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+            a: if hex.len() == 8 { channel(6)? } else { 255 },
+        })
+    }
+
+    /// Linear RGBA, e.g. for clearing an sRGB attachment
+    pub fn to_linear(self) -> [f32; 4] {
+        let linear = |c: u8| {
+            let c = c as f32 / 255.0;
+            if c < 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        [
+            linear(self.r),
+            linear(self.g),
+            linear(self.b),
+            self.a as f32 / 255.0,
+        ]
+    }
+
+    /// sRGB RGBA from 0 to 1, e.g. for clearing an attachment that doesn't encode sRGB
+    pub fn to_srgb(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a].map(|c| c as f32 / 255.0)
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Color::WHITE
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if self.a != 255 {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, Self::Error> {
+        Color::from_hex(&hex).ok_or_else(|| format!("invalid color `{}`, expected #rrggbb", hex))
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
 #[rustfmt::skip]
 pub const RECT: &[f32] = &[
     0., 0., 
@@ -23,7 +158,206 @@ pub const RECT_INDICES: &[u16] = &[
     0, 4, 1
 ];
 
+/// Axis-aligned rectangle in normalized device coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rect {
+    #[cfg_attr(feature = "serde", serde(with = "vec2_serde"))]
+    pub min: Vec2,
+    #[cfg_attr(feature = "serde", serde(with = "vec2_serde"))]
+    pub max: Vec2,
+}
+
+impl Rect {
+    /// Rectangle spanned by two opposite corners, in any order
+    pub fn from_corners(a: Vec2, b: Vec2) -> Self {
+        Rect {
+            min: Vec2::new(a.x.min(b.x), a.y.min(b.y)),
+            max: Vec2::new(a.x.max(b.x), a.y.max(b.y)),
+        }
+    }
+
+    /// Square of side `2 * radius` centered on `center`
+    pub fn around_point(center: Vec2, radius: f32) -> Self {
+        Rect {
+            min: center - Vec2::new(radius, radius),
+            max: center + Vec2::new(radius, radius),
+        }
+    }
+
+    /// Bounding rectangle of a line segment, grown by `margin` plus the
+    /// segment's own [`Line::extra_width`] on every side, both stretched by its tilt.
+    pub fn around_line(line: &Line, margin: f32) -> Self {
+        let margin = margin * line.stretch() + line.extra_width();
+        let a = line.position - line.dir / 2.;
+        let b = line.position + line.dir / 2.;
+        Rect {
+            min: Vec2::new(a.x.min(b.x) - margin, a.y.min(b.y) - margin),
+            max: Vec2::new(a.x.max(b.x) + margin, a.y.max(b.y) + margin),
+        }
+    }
+
+    /// Bounding rectangle of a set of segments, `None` when there are none
+    pub fn around_lines(lines: &[Line], margin: f32) -> Option<Self> {
+        lines
+            .iter()
+            .map(|line| Rect::around_line(line, margin))
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// This rectangle with `margin` added on every side
+    pub fn grown(&self, margin: f32) -> Self {
+        Rect {
+            min: self.min - Vec2::new(margin, margin),
+            max: self.max + Vec2::new(margin, margin),
+        }
+    }
+
+    /// Smallest rectangle containing both `self` and `other`.
+    pub fn union(&self, other: &Rect) -> Self {
+        Rect {
+            min: Vec2::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: Vec2::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
+
+    /// Whether every corner is a finite point
+    pub fn is_finite(&self) -> bool {
+        [self.min.x, self.min.y, self.max.x, self.max.y]
+            .iter()
+            .all(|c| c.is_finite())
+    }
+
+    /// Whether the two rectangles overlap or touch
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+
+    /// Whether `other` lies entirely inside this rectangle
+    pub fn contains(&self, other: &Rect) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && other.max.x <= self.max.x
+            && other.max.y <= self.max.y
+    }
+
+    /// Whether any part of the segment lies inside the rectangle
+    pub fn intersects_line(&self, line: &Line) -> bool {
+        // Liang-Barsky: clip the segment's parameter range against each edge
+        let start = line.position - line.dir / 2.;
+        let (mut t0, mut t1) = (0f32, 1f32);
+
+        for (p, q) in [
+            (-line.dir.x, start.x - self.min.x),
+            (line.dir.x, self.max.x - start.x),
+            (-line.dir.y, start.y - self.min.y),
+            (line.dir.y, self.max.y - start.y),
+        ] {
+            if p == 0. {
+                if q < 0. {
+                    return false;
+                }
+            } else if p < 0. {
+                t0 = t0.max(q / p);
+            } else {
+                t1 = t1.min(q / p);
+            }
+
+            if t0 > t1 {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// A uniform scale and rotation around the origin followed by a move, in canvas units
+///
+/// Used to move, scale and rotate selected strokes. Stored with plain arrays
+/// so it can travel over the network like stroke points do.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub scale: f32,
+    /// Angle in radians, turning +x towards +y
+    pub rotation: f32,
+    pub offset: [f32; 2],
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            scale: 1.,
+            rotation: 0.,
+            offset: [0., 0.],
+        }
+    }
+}
+
+impl Transform {
+    pub fn translation(offset: Vec2) -> Self {
+        Transform {
+            offset: offset.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Scales by `factor`, keeping `pivot` in place
+    pub fn scaling_around(pivot: Vec2, factor: f32) -> Self {
+        Transform {
+            scale: factor,
+            offset: (pivot - pivot * factor).into(),
+            ..Default::default()
+        }
+    }
+
+    /// Rotates by `angle` radians, keeping `pivot` in place
+    pub fn rotation_around(pivot: Vec2, angle: f32) -> Self {
+        let rotation = Transform {
+            rotation: angle,
+            ..Default::default()
+        };
+        Transform {
+            offset: (pivot - rotation.apply_linear(pivot)).into(),
+            ..rotation
+        }
+    }
+
+    /// This transform followed by `next`
+    pub fn then(&self, next: &Transform) -> Self {
+        Transform {
+            scale: self.scale * next.scale,
+            rotation: self.rotation + next.rotation,
+            offset: next.apply(self.offset.into()).into(),
+        }
+    }
+
+    pub fn apply(&self, point: Vec2) -> Vec2 {
+        self.apply_linear(point) + Vec2::from(self.offset)
+    }
+
+    /// The segment with both ends transformed, keeping its color and fade
+    pub fn apply_line(&self, line: &Line) -> Line {
+        Line {
+            position: self.apply(line.position),
+            dir: self.apply_linear(line.dir),
+            ..*line
+        }
+    }
+
+    /// Scale and rotation only, for directions
+    fn apply_linear(&self, v: Vec2) -> Vec2 {
+        let (sin, cos) = self.rotation.sin_cos();
+        Vec2::new(cos * v.x - sin * v.y, sin * v.x + cos * v.y) * self.scale
+    }
+}
+
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Lines(Vec<Line>);
 
 impl Lines {
@@ -44,26 +378,252 @@ impl Lines {
     }
 }
 
+/// [`Line::style`] bit drawing the segment as dashes three half widths long
+pub const LINE_STYLE_DASHED: u32 = 1;
+
+/// [`Line::style`] bits from this one up hold the segment's brush: which of
+/// the stroke fragment shaders draws it, 0 for `[shaders] fragment`
+pub const LINE_STYLE_BRUSH_SHIFT: u32 = 8;
+
+/// Brush of pixel eraser strokes, the highest the style bits hold: no stroke
+/// pipeline draws them, the erase layer punches them out of the strokes
+pub const PIXEL_ERASER_BRUSH: u32 = u32::MAX >> LINE_STYLE_BRUSH_SHIFT;
+
+/// One segment of a stroke, drawn as an instanced quad by `shader.vert`
+///
+/// The layout is read straight from the vertex buffer, so it must match the
+/// attribute descriptions below, which the assertions after this check.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(C)]
 pub struct Line {
+    #[cfg_attr(feature = "serde", serde(with = "vec2_serde"))]
     pub position: Vec2,
+    #[cfg_attr(feature = "serde", serde(with = "vec2_serde"))]
     pub dir: Vec2,
+    pub color: Color,
+    /// When (in [`StrokeStyle::time`]) the segment starts and finishes fading
+    /// out; segments with both at zero never fade
+    #[cfg_attr(feature = "serde", serde(with = "vec2_serde"))]
+    pub fade: Vec2,
+    /// Bit of the stroke's tag, hidden when set in [`StrokeStyle::hidden_tags`];
+    /// zero for untagged strokes
+    pub tag: u32,
+    /// Half the segment's width in canvas units, 0 for [`StrokeStyle::thickness`]
+    pub width: f32,
+    /// Pen pressure from 0 to 1, scaling the width
+    pub pressure: f32,
+    /// `LINE_STYLE_*` bits, 0 for a solid line
+    pub style: u32,
+    /// Which way and how far the pen leans (or a calligraphy nib points):
+    /// the footprint is stretched along it by its length, zero for round
+    #[cfg_attr(feature = "serde", serde(with = "vec2_serde"))]
+    pub tilt: Vec2,
 }
 
+// `shader.vert` expects exactly this layout
+const _: () = {
+    use std::mem::offset_of;
+    assert!(size_of::<Line>() == 52);
+    assert!(offset_of!(Line, position) == 0);
+    assert!(offset_of!(Line, dir) == 8);
+    assert!(offset_of!(Line, color) == 16);
+    assert!(offset_of!(Line, fade) == 20);
+    assert!(offset_of!(Line, tag) == 28);
+    assert!(offset_of!(Line, width) == 32);
+    assert!(offset_of!(Line, pressure) == 36);
+    assert!(offset_of!(Line, style) == 40);
+    assert!(offset_of!(Line, tilt) == 44);
+};
+
 impl Line {
-    pub fn new(from: Vec2, to: Vec2) -> Self {
+    pub fn new(from: Vec2, to: Vec2, color: Color) -> Self {
         let dir = to - from;
         Line {
             position: (from + to) / 2.,
             dir,
+            color,
+            fade: Vec2::new(0., 0.),
+            tag: 0,
+            width: 0.,
+            pressure: 1.,
+            style: 0,
+            tilt: Vec2::new(0., 0.),
+        }
+    }
+
+    /// How far the segment reaches beyond its centerline, 0 for the default width
+    pub fn extra_width(&self) -> f32 {
+        self.width * self.pressure.clamp(0., 1.) * self.stretch()
+    }
+
+    /// The most [`Line::tilt`] widens the footprint by, 1 for a round one
+    pub fn stretch(&self) -> f32 {
+        1. + self.tilt.magnitude()
+    }
+
+    /// Index of the fragment shader drawing the segment, see [`LINE_STYLE_BRUSH_SHIFT`]
+    pub fn brush(&self) -> u32 {
+        self.style >> LINE_STYLE_BRUSH_SHIFT
+    }
+
+    /// Draws the segment with the fragment shader at `brush`, keeping the other style bits
+    pub fn set_brush(&mut self, brush: u32) {
+        let others = self.style & ((1 << LINE_STYLE_BRUSH_SHIFT) - 1);
+        self.style = others | brush << LINE_STYLE_BRUSH_SHIFT;
+    }
+
+    /// Shortest distance from `point` to the segment
+    pub fn distance_to(&self, point: Vec2) -> f32 {
+        let start = self.position - self.dir / 2.;
+        let length_squared = self.dir.dot(self.dir);
+        let t = if length_squared > 0. {
+            ((point - start).dot(self.dir) / length_squared).clamp(0., 1.)
+        } else {
+            0.
+        };
+        (start + self.dir * t - point).magnitude()
+    }
+
+    /// Bytes per segment in vertex buffers of `format`
+    pub fn stride(format: VertexFormat) -> usize {
+        match format {
+            VertexFormat::F32 => size_of::<Line>(),
+            VertexFormat::F16 => size_of::<HalfLine>(),
+        }
+    }
+
+    /// Writes `lines` to `destination` as `format`, converting to half floats if asked
+    ///
+    /// `destination` must have room for `lines.len() * Line::stride(format)` bytes.
+    pub unsafe fn write(format: VertexFormat, lines: &[Line], destination: *mut u8) {
+        match format {
+            VertexFormat::F32 => {
+                std::ptr::copy_nonoverlapping(lines.as_ptr(), destination.cast(), lines.len())
+            }
+            VertexFormat::F16 => {
+                let destination = destination.cast::<HalfLine>();
+                for (i, line) in lines.iter().enumerate() {
+                    destination.add(i).write_unaligned(HalfLine::from(line));
+                }
+            }
         }
     }
-    pub fn binding_description() -> vk::VertexInputBindingDescription {
+
+    pub fn binding_description(format: VertexFormat) -> vk::VertexInputBindingDescription {
         vk::VertexInputBindingDescription::builder()
             .binding(1)
-            .stride(size_of::<Line>() as u32)
+            .stride(Line::stride(format) as u32)
             .input_rate(vk::VertexInputRate::INSTANCE)
             .build()
     }
+
+    /// The per-instance inputs of `shader.vert`, at locations 1 to 9 of binding 1
+    ///
+    /// Both formats read as the same floats in the shader, so it needs no variant.
+    pub fn attribute_descriptions(
+        format: VertexFormat,
+    ) -> [vk::VertexInputAttributeDescription; 9] {
+        let attribute = |location: u32, format: vk::Format, offset: usize| {
+            vk::VertexInputAttributeDescription::builder()
+                .binding(1)
+                .location(location)
+                .format(format)
+                .offset(offset as u32)
+                .build()
+        };
+        use std::mem::offset_of;
+        match format {
+            VertexFormat::F32 => [
+                attribute(1, vk::Format::R32G32_SFLOAT, offset_of!(Line, dir)),
+                attribute(2, vk::Format::R32G32_SFLOAT, offset_of!(Line, position)),
+                attribute(3, vk::Format::R8G8B8A8_UNORM, offset_of!(Line, color)),
+                attribute(4, vk::Format::R32G32_SFLOAT, offset_of!(Line, fade)),
+                attribute(5, vk::Format::R32_UINT, offset_of!(Line, tag)),
+                attribute(6, vk::Format::R32_SFLOAT, offset_of!(Line, width)),
+                attribute(7, vk::Format::R32_SFLOAT, offset_of!(Line, pressure)),
+                attribute(8, vk::Format::R32_UINT, offset_of!(Line, style)),
+                attribute(9, vk::Format::R32G32_SFLOAT, offset_of!(Line, tilt)),
+            ],
+            VertexFormat::F16 => [
+                attribute(1, vk::Format::R16G16_SFLOAT, offset_of!(HalfLine, dir)),
+                attribute(2, vk::Format::R16G16_SFLOAT, offset_of!(HalfLine, position)),
+                attribute(3, vk::Format::R8G8B8A8_UNORM, offset_of!(HalfLine, color)),
+                attribute(4, vk::Format::R32G32_SFLOAT, offset_of!(HalfLine, fade)),
+                attribute(5, vk::Format::R32_UINT, offset_of!(HalfLine, tag)),
+                attribute(6, vk::Format::R16_SFLOAT, offset_of!(HalfLine, width)),
+                attribute(7, vk::Format::R16_SFLOAT, offset_of!(HalfLine, pressure)),
+                attribute(8, vk::Format::R32_UINT, offset_of!(HalfLine, style)),
+                attribute(9, vk::Format::R16G16_SFLOAT, offset_of!(HalfLine, tilt)),
+            ],
+        }
+    }
+}
+
+/// A [`Line`] as stored in vertex buffers with `[vulkan] vertex_format = "f16"`
+///
+/// Geometry shrinks to half floats; fade times stay 32-bit floats, since
+/// seconds since startup soon outgrow half precision, and tag and style bits
+/// stay whole.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct HalfLine {
+    position: [u16; 2],
+    dir: [u16; 2],
+    color: Color,
+    fade: [f32; 2],
+    tag: u32,
+    width: u16,
+    pressure: u16,
+    style: u32,
+    tilt: [u16; 2],
+}
+
+const _: () = {
+    use std::mem::offset_of;
+    assert!(size_of::<HalfLine>() == 36);
+    assert!(offset_of!(HalfLine, position) == 0);
+    assert!(offset_of!(HalfLine, dir) == 4);
+    assert!(offset_of!(HalfLine, color) == 8);
+    assert!(offset_of!(HalfLine, fade) == 12);
+    assert!(offset_of!(HalfLine, tag) == 20);
+    assert!(offset_of!(HalfLine, width) == 24);
+    assert!(offset_of!(HalfLine, pressure) == 26);
+    assert!(offset_of!(HalfLine, style) == 28);
+    assert!(offset_of!(HalfLine, tilt) == 32);
+};
+
+impl From<&Line> for HalfLine {
+    fn from(line: &Line) -> Self {
+        let half = |value: f32| half::f16::from_f32(value).to_bits();
+        HalfLine {
+            position: [half(line.position.x), half(line.position.y)],
+            dir: [half(line.dir.x), half(line.dir.y)],
+            color: line.color,
+            fade: [line.fade.x, line.fade.y],
+            tag: line.tag,
+            width: half(line.width),
+            pressure: half(line.pressure),
+            style: line.style,
+            tilt: [half(line.tilt.x), half(line.tilt.y)],
+        }
+    }
+}
+
+/// Writes a [`Vec2`] as `[x, y]`, like stroke points in documents and on the
+/// network, for `#[serde(with = "vec2_serde")]` on the `serde` feature's types
+#[cfg(feature = "serde")]
+pub mod vec2_serde {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Vec2;
+
+    pub fn serialize<S: Serializer>(v: &Vec2, serializer: S) -> Result<S::Ok, S::Error> {
+        [v.x, v.y].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec2, D::Error> {
+        let [x, y] = <[f32; 2]>::deserialize(deserializer)?;
+        Ok(Vec2::new(x, y))
+    }
 }
@@ -0,0 +1,1149 @@
+use std::f32::consts::FRAC_PI_2;
+use std::time::{Duration, Instant};
+
+use egui::{Align2, ClippedPrimitive, Color32, Stroke, TexturesDelta, ViewportId};
+use egui_winit::EventResponse;
+use winit::event::WindowEvent;
+use winit::window::Window;
+
+use crate::config::Theme;
+use crate::keybindings::Action;
+use crate::types::{Color, Rect, Vec2};
+use crate::vulkan::memory::{HeapUsage, MIB};
+
+/// Outline color for selected strokes and the selection marquee
+const SELECTION_COLOR: Color32 = Color32::from_rgb(80, 160, 255);
+
+/// Side length in points of the squares drawn for tool handles
+const HANDLE_SIZE: f32 = 7.0;
+
+/// Thickness in points of the rulers along the top and left edges
+const RULER_SIZE: f32 = 18.0;
+/// Closest that labeled ruler ticks get, in points
+const RULER_MIN_SPACING: f32 = 64.0;
+
+/// Size in points of the minimap in the bottom right corner
+pub const MINIMAP_SIZE: [f32; 2] = [192.0, 128.0];
+
+/// The parts of the app state the UI shows, gathered by `App` each frame
+pub struct UiState<'a> {
+    /// Names of the registered tools, in registration order
+    pub tools: Vec<&'a str>,
+    pub active_tool: usize,
+    pub color: Color,
+    /// Swatches shown under the color picker
+    pub palette: &'a [Color],
+    /// Half the stroke width in NDC
+    pub thickness: f32,
+    /// Bounds (in NDC) of each selected stroke
+    pub selection: Vec<Rect>,
+    /// Rectangle (in NDC) the active tool is dragging out
+    pub marquee: Option<Rect>,
+    /// Points (in NDC) the active tool lets you grab, e.g. to scale or rotate the selection
+    pub handles: Vec<Vec2>,
+    /// Line the active tool is measuring, labeled with its length
+    pub measurement: Option<Measurement>,
+    /// Pointer position in canvas coordinates, once it has moved over the window
+    pub cursor: Option<Vec2>,
+    pub zoom: f32,
+    /// Canvas rectangle the window shows, for the rulers and the minimap
+    pub visible: Rect,
+    /// Seconds new strokes stay before fading, `None` while they are permanent
+    pub fade_after: Option<f32>,
+    /// Recent and undone edits, oldest first, while the history window is open
+    pub history: Vec<HistoryEntry>,
+    /// Edits currently applied, which the entry with this `applied` count is the last of
+    pub history_applied: usize,
+    /// Tags strokes are filed under, with whether each one is shown
+    pub tags: Vec<(&'a str, bool)>,
+    /// Tag new strokes are filed under
+    pub brush_tag: Option<&'a str>,
+    /// `[shaders.brushes]` entry new strokes are drawn with, `None` for the default shader
+    pub brush_shader: Option<&'a str>,
+    /// Whether hidden tags are shown for now
+    pub tags_revealed: bool,
+    /// Spectating a session, so local edits are ignored
+    pub read_only: bool,
+    pub stroke_count: usize,
+    /// Committed segments in the vertex buffer
+    pub segment_count: usize,
+    /// Segments of the stroke being drawn
+    pub pending_segment_count: usize,
+    /// Capacity of the vertex buffer in segments
+    pub max_vertices: usize,
+    /// Smoothed CPU time per frame
+    pub frame_time: Duration,
+    /// GPU time of the last finished frame, if the device supports timestamps
+    pub gpu_time: Option<Duration>,
+    /// Usage of each GPU memory heap
+    pub memory: &'a [HeapUsage],
+    /// Bound key chords and their actions, for the shortcut window
+    pub shortcuts: &'a [(String, Action)],
+    /// Whether there are copied strokes to paste
+    pub can_paste: bool,
+    pub high_contrast: bool,
+    /// Where (in NDC) to draw the large accessibility crosshair, if at all
+    pub crosshair: Option<Vec2>,
+    /// Arm length of the crosshair in points
+    pub crosshair_size: f32,
+    /// Names of the configured themes, in the order they are offered
+    pub themes: Vec<&'a str>,
+    pub theme_name: &'a str,
+    pub theme: &'a Theme,
+    /// Whether a drawing saved when an earlier run crashed is waiting to be restored
+    pub recovered: bool,
+}
+
+/// What a measuring tool shows over the canvas, see [`Tool::measurement`](crate::tools::Tool::measurement)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    /// Segments (in NDC) to draw, with a dot at each end
+    pub lines: Vec<(Vec2, Vec2)>,
+    /// The reading, e.g. a length or an angle; nothing is shown while empty
+    pub label: String,
+    /// Point (in NDC) the label sits above
+    pub anchor: Vec2,
+}
+
+/// An edit listed in the history window
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// What the edit did, e.g. "Erase 3 strokes"
+    pub label: String,
+    /// Edits left applied after rolling back (or forward) to this one, itself included
+    pub applied: usize,
+    /// Whether the edit was undone, so rolling forward to it redoes it
+    pub undone: bool,
+}
+
+/// Something the user asked for through the UI, carried out by `App` after the frame
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiAction {
+    SelectTool(usize),
+    SetColor(Color),
+    /// Adds a color to the palette for the rest of the session
+    AddSwatch(Color),
+    /// Anything a key chord could trigger
+    Perform(Action),
+    /// Pastes the copied strokes centered on a canvas point
+    PasteAt(Vec2),
+    /// Removes the stroke at this index
+    DeleteStroke(usize),
+    /// Undoes or redoes edits until this many are applied
+    RollBack(usize),
+    /// Restores (`true`) or discards the drawing saved when an earlier run crashed
+    Recover(bool),
+    /// Moves the view so this canvas point is in the middle of the window
+    CenterOn(Vec2),
+    /// Switches to the theme with this name
+    SetTheme(String),
+    /// Shows or hides the strokes filed under a tag
+    SetTagVisible(String, bool),
+    /// Files strokes drawn from now on under a tag, or under none
+    SetBrushTag(Option<String>),
+    /// Files the selected strokes under a tag, or untags them
+    TagSelection(Option<String>),
+}
+
+/// Where the right-click menu was opened and what it applies to
+#[derive(Debug, Clone, Copy)]
+struct ContextMenu {
+    /// Position in screen NDC
+    screen: Vec2,
+    /// The same point in canvas coordinates
+    canvas: Vec2,
+    /// The topmost stroke under it
+    stroke: Option<usize>,
+}
+
+/// What the UI produced for one frame, ready for `UiRenderer`
+pub struct UiFrame {
+    pub primitives: Vec<ClippedPrimitive>,
+    pub textures_delta: TexturesDelta,
+    pub pixels_per_point: f32,
+    /// Region (in NDC) covered by the UI this frame, `None` if nothing was drawn
+    pub bounds: Option<Rect>,
+    pub actions: Vec<UiAction>,
+}
+
+/// The egui layer drawn over the canvas
+///
+/// Owns the egui context and its winit integration. Window events go through
+/// [`UiLayer::on_window_event`] before the canvas sees them, and
+/// [`UiLayer::run`] builds the UI once per rendered frame.
+pub struct UiLayer {
+    context: egui::Context,
+    state: egui_winit::State,
+    /// When egui wants another frame without new input, e.g. to animate or show a delayed tooltip
+    repaint_at: Option<Instant>,
+
+    /// Whether the keyboard shortcut window is open
+    pub show_help: bool,
+    /// Whether the color picker is open
+    pub show_colors: bool,
+    /// Whether the frame time and buffer usage overlay is shown
+    pub show_stats: bool,
+    /// Whether the tag window is open
+    pub show_tags: bool,
+    /// Whether rulers in canvas units run along the top and left edges
+    pub show_rulers: bool,
+    /// Whether the minimap is shown
+    pub show_minimap: bool,
+    /// Whether the history window is open
+    pub show_history: bool,
+    /// The whole drawing rendered small, and the canvas rectangle it shows
+    minimap: Option<(egui::TextureHandle, Rect)>,
+    /// Name typed into the tag window for a new tag
+    new_tag: String,
+    context_menu: Option<ContextMenu>,
+    /// High-contrast mode and theme the egui visuals were last built for
+    visuals: Option<(bool, Theme)>,
+}
+
+impl UiLayer {
+    pub fn new(window: &Window, max_texture_side: usize) -> Self {
+        let context = egui::Context::default();
+        let state = egui_winit::State::new(
+            context.clone(),
+            ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            Some(max_texture_side),
+        );
+
+        Self {
+            context,
+            state,
+            repaint_at: Some(Instant::now()),
+            show_help: false,
+            show_colors: false,
+            show_stats: false,
+            show_tags: false,
+            show_rulers: false,
+            show_minimap: false,
+            show_history: false,
+            minimap: None,
+            new_tag: String::new(),
+            context_menu: None,
+            visuals: None,
+        }
+    }
+
+    /// Feeds a window event to egui
+    ///
+    /// `consumed` is set when egui used the event (e.g. a click on a button)
+    /// and the canvas should ignore it.
+    pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> EventResponse {
+        let response = self.state.on_window_event(window, event);
+        if response.repaint {
+            self.repaint_at = Some(Instant::now());
+        }
+        response
+    }
+
+    /// Opens the right-click menu at `screen` (in NDC), acting on `canvas` and the stroke there
+    pub fn open_context_menu(&mut self, screen: Vec2, canvas: Vec2, stroke: Option<usize>) {
+        self.context_menu = Some(ContextMenu {
+            screen,
+            canvas,
+            stroke,
+        });
+        self.repaint_at = Some(Instant::now());
+    }
+
+    /// Shows `pixels` (tightly packed RGBA8 rows of `size`) as the minimap of
+    /// the canvas rectangle `shows`, or no minimap for an empty drawing
+    pub fn set_minimap(&mut self, image: Option<([usize; 2], &[u8], Rect)>) {
+        let Some((size, pixels, shows)) = image else {
+            self.minimap = None;
+            return;
+        };
+        let image = egui::ColorImage::from_rgba_unmultiplied(size, pixels);
+        match &mut self.minimap {
+            Some((texture, rect)) => {
+                texture.set(image, egui::TextureOptions::LINEAR);
+                *rect = shows;
+            }
+            None => {
+                let texture =
+                    self.context
+                        .load_texture("minimap", image, egui::TextureOptions::LINEAR);
+                self.minimap = Some((texture, shows));
+            }
+        }
+        self.repaint_at = Some(Instant::now());
+    }
+
+    /// Whether the UI has changed (or is animating) and wants another frame now
+    pub fn needs_repaint(&self) -> bool {
+        self.repaint_at.is_some_and(|at| at <= Instant::now())
+    }
+
+    /// When the UI next wants a frame, `None` if it can wait for input
+    pub fn repaint_at(&self) -> Option<Instant> {
+        self.repaint_at
+    }
+
+    /// Builds the UI for the next frame, which is shown in `frame` (in
+    /// physical pixels) of the window
+    ///
+    /// With a fixed canvas size the frame is letterboxed, so egui gets a
+    /// screen of the frame's size and pointer positions relative to it.
+    pub fn run(&mut self, window: &Window, frame: egui::Rect, state: &UiState) -> UiFrame {
+        let applied = (state.high_contrast, state.theme.clone());
+        if self.visuals.as_ref() != Some(&applied) {
+            self.context
+                .set_visuals(visuals(state.high_contrast, state.theme));
+            self.visuals = Some(applied);
+        }
+
+        let mut input = self.state.take_egui_input(window);
+        let pixels_per_point = egui_winit::pixels_per_point(&self.context, window);
+        letterbox_input(&mut input, frame, pixels_per_point);
+        let context = self.context.clone();
+        let mut actions = Vec::new();
+        let output = context.run(input, |ctx| self.show(ctx, state, &mut actions));
+
+        self.state
+            .handle_platform_output(window, output.platform_output);
+        self.repaint_at = output
+            .viewport_output
+            .get(&ViewportId::ROOT)
+            .and_then(|v| Instant::now().checked_add(v.repaint_delay));
+
+        let pixels_per_point = output.pixels_per_point;
+        let primitives = self.context.tessellate(output.shapes, pixels_per_point);
+        let bounds =
+            primitives_bounds(&primitives, pixels_per_point, frame.width(), frame.height());
+
+        UiFrame {
+            primitives,
+            textures_delta: output.textures_delta,
+            pixels_per_point,
+            bounds,
+            actions,
+        }
+    }
+
+    fn show(&mut self, ctx: &egui::Context, state: &UiState, actions: &mut Vec<UiAction>) {
+        status_bar(ctx, state);
+        if self.show_stats {
+            stats_overlay(ctx, state);
+        }
+        toolbar(ctx, state, &mut self.show_colors, actions);
+        selection_outlines(ctx, state);
+        measurement(ctx, state);
+        if self.show_rulers {
+            rulers(ctx, state);
+        }
+        if let Some((texture, shows)) = self.minimap.as_ref().filter(|_| self.show_minimap) {
+            minimap(ctx, state, texture, shows, actions);
+        }
+
+        if let Some(menu) = self.context_menu {
+            if !context_menu(ctx, state, &menu, &mut self.show_colors, actions) {
+                self.context_menu = None;
+            }
+        }
+
+        egui::Window::new("Color")
+            .open(&mut self.show_colors)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| color_picker(ui, state, actions));
+
+        egui::Window::new("Tags")
+            .open(&mut self.show_tags)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| tag_list(ui, state, &mut self.new_tag, actions));
+
+        egui::Window::new("History")
+            .open(&mut self.show_history)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| history(ui, state, actions));
+
+        if state.recovered {
+            recovery(ctx, actions);
+        }
+
+        crosshair(ctx, state);
+
+        egui::Window::new("Shortcuts")
+            .open(&mut self.show_help)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("shortcuts").striped(true).show(ui, |ui| {
+                    for (keys, action) in state.shortcuts {
+                        ui.label(keys);
+                        ui.label(action.label());
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+}
+
+/// Offers the drawing saved when an earlier run crashed back, in the middle of the window
+fn recovery(ctx: &egui::Context, actions: &mut Vec<UiAction>) {
+    egui::Window::new("Recover drawing")
+        .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label("scribble-vk closed unexpectedly last time, and saved the drawing.");
+            ui.horizontal(|ui| {
+                if ui.button("Restore").clicked() {
+                    actions.push(UiAction::Recover(true));
+                }
+                if ui.button("Discard").clicked() {
+                    actions.push(UiAction::Recover(false));
+                }
+            });
+        });
+}
+
+/// Tool buttons plus the current stroke color and width, pinned to the top left
+fn toolbar(
+    ctx: &egui::Context,
+    state: &UiState,
+    show_colors: &mut bool,
+    actions: &mut Vec<UiAction>,
+) {
+    let screen = ctx.screen_rect();
+
+    egui::Area::new(egui::Id::new("toolbar"))
+        .anchor(Align2::LEFT_TOP, [8.0, 8.0])
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for (i, name) in state.tools.iter().enumerate() {
+                        let mut button = ui.selectable_label(i == state.active_tool, *name);
+                        let shortcut = state.shortcuts.iter().find(|(_, action)| {
+                            matches!(action, Action::SelectTool(tool) if tool == name)
+                        });
+                        if let Some((keys, _)) = shortcut {
+                            button = button.on_hover_text(keys);
+                        }
+                        if button.clicked() {
+                            actions.push(UiAction::SelectTool(i));
+                        }
+                    }
+
+                    ui.separator();
+                    let swatch = swatch_button(ui, state.color).on_hover_text("Pick a color");
+                    if swatch.clicked() {
+                        *show_colors = !*show_colors;
+                    }
+                    // Full width in points: twice the half width, over half the screen per NDC unit
+                    ui.label(format!("{:.1} pt", state.thickness * screen.height()));
+
+                    if state.themes.len() > 1 {
+                        ui.separator();
+                        egui::ComboBox::from_id_source("theme")
+                            .selected_text(state.theme_name)
+                            .show_ui(ui, |ui| {
+                                for &name in &state.themes {
+                                    if ui
+                                        .selectable_label(name == state.theme_name, name)
+                                        .clicked()
+                                    {
+                                        actions.push(UiAction::SetTheme(name.to_string()));
+                                    }
+                                }
+                            })
+                            .response
+                            .on_hover_text("Theme");
+                    }
+                });
+            });
+        });
+}
+
+/// Quick actions at the point that was right-clicked, returning whether the menu stays open
+fn context_menu(
+    ctx: &egui::Context,
+    state: &UiState,
+    menu: &ContextMenu,
+    show_colors: &mut bool,
+    actions: &mut Vec<UiAction>,
+) -> bool {
+    let screen = ctx.screen_rect();
+    let position = egui::pos2(
+        (menu.screen.x + 1.0) / 2.0 * screen.width(),
+        (menu.screen.y + 1.0) / 2.0 * screen.height(),
+    );
+    let mut chosen = None;
+    let mut more_colors = false;
+
+    let area = egui::Area::new(egui::Id::new("context_menu"))
+        .fixed_pos(position)
+        .order(egui::Order::Foreground)
+        .constrain(true)
+        .show(ctx, |ui| {
+            egui::Frame::menu(ui.style()).show(ui, |ui| {
+                ui.set_min_width(140.0);
+
+                ui.menu_button("Color", |ui| {
+                    ui.horizontal_wrapped(|ui| {
+                        for &swatch in state.palette {
+                            if swatch_button(ui, swatch)
+                                .on_hover_text(swatch.to_string())
+                                .clicked()
+                            {
+                                chosen = Some(UiAction::SetColor(swatch));
+                            }
+                        }
+                    });
+                    more_colors = ui.button("More...").clicked();
+                });
+                ui.menu_button("Tool", |ui| {
+                    for (i, name) in state.tools.iter().enumerate() {
+                        if ui.selectable_label(i == state.active_tool, *name).clicked() {
+                            chosen = Some(UiAction::SelectTool(i));
+                        }
+                    }
+                });
+                ui.separator();
+
+                let copy = egui::Button::new("Copy");
+                if ui.add_enabled(!state.selection.is_empty(), copy).clicked() {
+                    chosen = Some(UiAction::Perform(Action::Copy));
+                }
+                let duplicate = egui::Button::new("Duplicate");
+                if ui
+                    .add_enabled(!state.selection.is_empty(), duplicate)
+                    .clicked()
+                {
+                    chosen = Some(UiAction::Perform(Action::Duplicate));
+                }
+                if ui
+                    .add_enabled(state.can_paste, egui::Button::new("Paste"))
+                    .clicked()
+                {
+                    chosen = Some(UiAction::PasteAt(menu.canvas));
+                }
+                let delete = egui::Button::new("Delete stroke");
+                if ui.add_enabled(menu.stroke.is_some(), delete).clicked() {
+                    chosen = menu.stroke.map(UiAction::DeleteStroke);
+                }
+                ui.separator();
+
+                if ui.button("Clear page").clicked() {
+                    chosen = Some(UiAction::Perform(Action::Clear));
+                }
+            });
+        });
+
+    *show_colors |= more_colors;
+    let dismissed =
+        area.response.clicked_elsewhere() || ctx.input(|i| i.key_pressed(egui::Key::Escape));
+    match chosen {
+        Some(action) => {
+            actions.push(action);
+            false
+        }
+        None => !dismissed && !more_colors,
+    }
+}
+
+/// Cursor position and document info along the bottom edge
+fn status_bar(ctx: &egui::Context, state: &UiState) {
+    egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            match state.cursor {
+                Some(cursor) => ui.monospace(format!("{:>7.3}, {:>7.3}", cursor.x, cursor.y)),
+                None => ui.monospace("      -,       -"),
+            };
+            ui.separator();
+            ui.label(format!("{:.0}%", state.zoom * 100.0));
+            ui.separator();
+            if let Some(tool) = state.tools.get(state.active_tool) {
+                ui.label(*tool);
+                ui.separator();
+            }
+            if let Some(seconds) = state.fade_after {
+                ui.label(format!("fades after {}s", seconds));
+                ui.separator();
+            }
+            if let Some(tag) = state.brush_tag {
+                ui.label(format!("tagged {}", tag));
+                ui.separator();
+            }
+            if let Some(brush) = state.brush_shader {
+                ui.label(format!("{} brush", brush));
+                ui.separator();
+            }
+            if state.tags_revealed {
+                ui.label("hidden tags revealed");
+                ui.separator();
+            }
+            if state.read_only {
+                ui.label("spectating");
+                ui.separator();
+            }
+            ui.label(match state.stroke_count {
+                1 => "1 stroke".to_string(),
+                n => format!("{} strokes", n),
+            });
+        });
+    });
+}
+
+/// Frame times, vertex buffer and GPU memory usage, pinned to the top right
+fn stats_overlay(ctx: &egui::Context, state: &UiState) {
+    let millis = |d: Duration| format!("{:.2} ms", d.as_secs_f64() * 1000.0);
+
+    egui::Area::new(egui::Id::new("stats"))
+        .anchor(Align2::RIGHT_TOP, [-8.0, 8.0])
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                egui::Grid::new("stats_grid").show(ui, |ui| {
+                    ui.label("Frame (CPU)");
+                    ui.monospace(millis(state.frame_time));
+                    ui.end_row();
+
+                    ui.label("GPU");
+                    ui.monospace(state.gpu_time.map_or("n/a".to_string(), millis));
+                    ui.end_row();
+
+                    ui.label("Strokes");
+                    ui.monospace(state.stroke_count.to_string());
+                    ui.end_row();
+
+                    ui.label("Segments");
+                    ui.monospace(format!(
+                        "{} (+{} drawing)",
+                        state.segment_count, state.pending_segment_count
+                    ));
+                    ui.end_row();
+
+                    ui.label("Vertex buffer");
+                    let usage = state.segment_count as f32 / state.max_vertices.max(1) as f32;
+                    ui.add(
+                        egui::ProgressBar::new(usage)
+                            .desired_width(140.0)
+                            .text(format!("{} / {}", state.segment_count, state.max_vertices)),
+                    );
+                    ui.end_row();
+
+                    // System memory heaps are shared with everything else, so only GPU ones are shown
+                    for heap in state.memory.iter().filter(|h| h.device_local) {
+                        let mib = |bytes: u64| bytes as f64 / MIB;
+                        ui.label("GPU memory");
+                        ui.add(
+                            egui::ProgressBar::new(heap.used() as f32 / heap.budget.max(1) as f32)
+                                .desired_width(140.0)
+                                .text(format!(
+                                    "{:.0} / {:.0} MiB ({:.0} ours)",
+                                    mib(heap.used()),
+                                    mib(heap.budget),
+                                    mib(heap.allocated)
+                                )),
+                        )
+                        .on_hover_text(match heap.used {
+                            Some(_) => "Used by all apps, against the driver's budget",
+                            None => "Allocated by this app; the driver doesn't report a budget",
+                        });
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+}
+
+/// HSV picker for the brush color with the palette below it
+fn color_picker(ui: &mut egui::Ui, state: &UiState, actions: &mut Vec<UiAction>) {
+    let mut color = to_color32(state.color);
+    if egui::color_picker::color_picker_color32(ui, &mut color, egui::color_picker::Alpha::Opaque) {
+        actions.push(UiAction::SetColor(from_color32(color)));
+    }
+
+    ui.separator();
+    ui.horizontal_wrapped(|ui| {
+        for &swatch in state.palette {
+            if swatch_button(ui, swatch)
+                .on_hover_text(swatch.to_string())
+                .clicked()
+            {
+                actions.push(UiAction::SetColor(swatch));
+            }
+        }
+
+        if ui.button("+").on_hover_text("Add to palette").clicked() {
+            actions.push(UiAction::AddSwatch(state.color));
+        }
+    });
+}
+
+/// Each tag with its visibility, whether new strokes get it and a button
+/// filing the selection under it, then a field for adding a tag
+fn tag_list(ui: &mut egui::Ui, state: &UiState, new_tag: &mut String, actions: &mut Vec<UiAction>) {
+    let has_selection = !state.selection.is_empty();
+
+    if !state.tags.is_empty() {
+        egui::Grid::new("tags").striped(true).show(ui, |ui| {
+            for &(name, visible) in &state.tags {
+                let mut shown = visible;
+                if ui.checkbox(&mut shown, name).changed() {
+                    actions.push(UiAction::SetTagVisible(name.to_string(), shown));
+                }
+
+                let drawing = state.brush_tag == Some(name);
+                if ui
+                    .selectable_label(drawing, "Draw")
+                    .on_hover_text("File new strokes under this tag")
+                    .clicked()
+                {
+                    actions.push(UiAction::SetBrushTag((!drawing).then(|| name.to_string())));
+                }
+
+                let tag = egui::Button::new("Tag selection");
+                if ui.add_enabled(has_selection, tag).clicked() {
+                    actions.push(UiAction::TagSelection(Some(name.to_string())));
+                }
+                ui.end_row();
+            }
+        });
+        ui.separator();
+    }
+
+    ui.horizontal(|ui| {
+        let field = ui.add(egui::TextEdit::singleline(new_tag).hint_text("New tag"));
+        let entered = field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        let name = new_tag.trim();
+        if (ui.button("Add").clicked() || entered) && !name.is_empty() {
+            // A new tag is drawn with right away, since it has no strokes yet
+            actions.push(UiAction::SetBrushTag(Some(name.to_string())));
+            new_tag.clear();
+        }
+    });
+
+    let untag = egui::Button::new("Untag selection");
+    if ui.add_enabled(has_selection, untag).clicked() {
+        actions.push(UiAction::TagSelection(None));
+    }
+    if state.tags_revealed {
+        ui.label("Hidden tags are revealed for now.");
+    }
+}
+
+/// Recent edits, newest first, below the ones undone since; clicking one
+/// undoes or redoes edits until it is the last one applied
+fn history(ui: &mut egui::Ui, state: &UiState, actions: &mut Vec<UiAction>) {
+    if state.history.is_empty() {
+        ui.label("Nothing to undo yet.");
+        return;
+    }
+
+    egui::ScrollArea::vertical()
+        .max_height(320.0)
+        .show(ui, |ui| {
+            for entry in state.history.iter().rev() {
+                let current = entry.applied == state.history_applied;
+                let label = if entry.undone {
+                    egui::RichText::new(&entry.label).weak()
+                } else {
+                    egui::RichText::new(&entry.label)
+                };
+                if ui.selectable_label(current, label).clicked() && !current {
+                    actions.push(UiAction::RollBack(entry.applied));
+                }
+            }
+        });
+}
+
+fn swatch_button(ui: &mut egui::Ui, color: Color) -> egui::Response {
+    ui.add(
+        egui::Button::new("")
+            .fill(to_color32(color))
+            .min_size(egui::vec2(18.0, 18.0)),
+    )
+}
+
+fn to_color32(color: Color) -> Color32 {
+    Color32::from_rgba_unmultiplied(color.r, color.g, color.b, color.a)
+}
+
+fn from_color32(color: Color32) -> Color {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    Color { r, g, b, a }
+}
+
+/// egui's light or dark visuals recolored by `theme`
+///
+/// High-contrast mode ignores the theme for dark panels with white text and
+/// outlines.
+fn visuals(high_contrast: bool, theme: &Theme) -> egui::Visuals {
+    if high_contrast {
+        let mut visuals = egui::Visuals::dark();
+        visuals.override_text_color = Some(Color32::WHITE);
+        visuals.widgets.noninteractive.bg_stroke = Stroke::new(1.0, Color32::WHITE);
+        visuals.window_stroke = Stroke::new(2.0, Color32::WHITE);
+        return visuals;
+    }
+
+    let mut visuals = if theme.light {
+        egui::Visuals::light()
+    } else {
+        egui::Visuals::dark()
+    };
+    if let Some(panel) = theme.panel.map(to_color32) {
+        visuals.panel_fill = panel;
+        visuals.window_fill = panel;
+        visuals.extreme_bg_color = panel;
+    }
+    if let Some(text) = theme.text.map(to_color32) {
+        visuals.override_text_color = Some(text);
+    }
+    if let Some(accent) = theme.accent.map(to_color32) {
+        visuals.selection.bg_fill = accent;
+        visuals.hyperlink_color = accent;
+        visuals.widgets.hovered.bg_stroke = Stroke::new(1.0, accent);
+        visuals.widgets.active.bg_fill = accent;
+    }
+    visuals
+}
+
+/// A large outlined crosshair at the cursor that stays visible on any stroke color
+fn crosshair(ctx: &egui::Context, state: &UiState) {
+    let Some(center) = state.crosshair else {
+        return;
+    };
+
+    let screen = ctx.screen_rect();
+    let center = egui::pos2(
+        (center.x + 1.0) / 2.0 * screen.width(),
+        (center.y + 1.0) / 2.0 * screen.height(),
+    );
+    let size = state.crosshair_size;
+    let arms = [
+        [
+            center - egui::vec2(size, 0.0),
+            center + egui::vec2(size, 0.0),
+        ],
+        [
+            center - egui::vec2(0.0, size),
+            center + egui::vec2(0.0, size),
+        ],
+    ];
+
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Tooltip,
+        egui::Id::new("crosshair"),
+    ));
+    for arm in arms {
+        painter.line_segment(arm, Stroke::new(5.0, Color32::BLACK));
+    }
+    for arm in arms {
+        painter.line_segment(arm, Stroke::new(2.0, Color32::WHITE));
+    }
+}
+
+/// Outlines the selected strokes and the marquee being dragged, and draws
+/// the active tool's handles, under any windows
+fn selection_outlines(ctx: &egui::Context, state: &UiState) {
+    let screen = ctx.screen_rect();
+    let to_point = |p: Vec2| {
+        egui::pos2(
+            (p.x + 1.0) / 2.0 * screen.width(),
+            (p.y + 1.0) / 2.0 * screen.height(),
+        )
+    };
+    let to_points = |rect: &Rect| egui::Rect::from_min_max(to_point(rect.min), to_point(rect.max));
+
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    for rect in &state.selection {
+        painter.rect_stroke(to_points(rect), 0.0, Stroke::new(1.0, SELECTION_COLOR));
+    }
+    if let Some(marquee) = &state.marquee {
+        painter.rect(
+            to_points(marquee),
+            0.0,
+            SELECTION_COLOR.gamma_multiply(0.15),
+            Stroke::new(1.0, SELECTION_COLOR),
+        );
+    }
+    for &handle in &state.handles {
+        painter.rect(
+            egui::Rect::from_center_size(to_point(handle), egui::vec2(HANDLE_SIZE, HANDLE_SIZE)),
+            0.0,
+            Color32::WHITE,
+            Stroke::new(1.0, SELECTION_COLOR),
+        );
+    }
+}
+
+/// The measuring tool's lines with its reading on top
+fn measurement(ctx: &egui::Context, state: &UiState) {
+    let Some(measurement) = &state.measurement else {
+        return;
+    };
+
+    let screen = ctx.screen_rect();
+    let to_point = |p: Vec2| {
+        egui::pos2(
+            (p.x + 1.0) / 2.0 * screen.width(),
+            (p.y + 1.0) / 2.0 * screen.height(),
+        )
+    };
+    let stroke = Stroke::new(1.5, SELECTION_COLOR);
+
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    for &(from, to) in &measurement.lines {
+        let (from, to) = (to_point(from), to_point(to));
+        painter.line_segment([from, to], stroke);
+        for end in [from, to] {
+            painter.circle_filled(end, 2.5, SELECTION_COLOR);
+        }
+    }
+
+    if measurement.label.is_empty() {
+        return;
+    }
+    let galley = painter.layout_no_wrap(
+        measurement.label.clone(),
+        egui::FontId::monospace(12.0),
+        Color32::WHITE,
+    );
+    let above = to_point(measurement.anchor) - egui::vec2(0.0, HANDLE_SIZE);
+    let label = Align2::CENTER_BOTTOM
+        .anchor_size(above, galley.size())
+        .expand(3.0);
+    painter.rect_filled(label, 3.0, Color32::from_black_alpha(200));
+    painter.galley(label.shrink(3.0).min, galley, Color32::WHITE);
+}
+
+/// Rulers marked in canvas units along the top and left edges, following
+/// pan and zoom, with the cursor's position marked on both
+fn rulers(ctx: &egui::Context, state: &UiState) {
+    let screen = ctx.screen_rect();
+    let visible = state.visible;
+    let size = visible.max - visible.min;
+    if size.x <= 0.0 || size.y <= 0.0 {
+        return;
+    }
+    let visuals = ctx.style().visuals.clone();
+    let text = visuals.text_color();
+    let font = egui::FontId::monospace(10.0);
+    let painter = ctx.layer_painter(egui::LayerId::background());
+
+    let top = egui::Rect::from_min_max(
+        screen.min,
+        egui::pos2(screen.max.x, screen.min.y + RULER_SIZE),
+    );
+    let left = egui::Rect::from_min_max(
+        egui::pos2(screen.min.x, screen.min.y + RULER_SIZE),
+        egui::pos2(screen.min.x + RULER_SIZE, screen.max.y),
+    );
+    for rect in [top, left] {
+        painter.rect_filled(rect, 0.0, visuals.extreme_bg_color.gamma_multiply(0.85));
+    }
+    let edge = Stroke::new(1.0, visuals.weak_text_color());
+    painter.line_segment([top.left_bottom(), top.right_bottom()], edge);
+    painter.line_segment([left.right_top(), left.right_bottom()], edge);
+
+    // Canvas units per point are the same on both axes
+    let step = ruler_step(size.x / screen.width());
+    let (minor, per_major) = minor_ticks(step);
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    let label = |value: f32| {
+        // Rounding shouldn't show a tick at the origin as -0
+        let value = if value.abs() < minor / 2.0 {
+            0.0
+        } else {
+            value
+        };
+        format!("{:.*}", decimals, value)
+    };
+
+    let x_of = |x: f32| screen.min.x + (x - visible.min.x) / size.x * screen.width();
+    let y_of = |y: f32| screen.min.y + (y - visible.min.y) / size.y * screen.height();
+    let ticks = |min: f32, max: f32| {
+        let first = (min / minor).ceil() as i64;
+        let last = (max / minor).floor() as i64;
+        (first..=last).map(|i| (i as f32 * minor, i.rem_euclid(per_major) == 0))
+    };
+
+    for (x, major) in ticks(visible.min.x, visible.max.x) {
+        let at = x_of(x);
+        if at < left.max.x {
+            continue;
+        }
+        let length = if major { RULER_SIZE } else { RULER_SIZE / 3.0 };
+        painter.line_segment(
+            [
+                egui::pos2(at, top.max.y - length),
+                egui::pos2(at, top.max.y),
+            ],
+            edge,
+        );
+        if major {
+            painter.text(
+                egui::pos2(at + 2.0, top.min.y + 1.0),
+                Align2::LEFT_TOP,
+                label(x),
+                font.clone(),
+                text,
+            );
+        }
+    }
+    for (y, major) in ticks(visible.min.y, visible.max.y) {
+        let at = y_of(y);
+        if at < top.max.y {
+            continue;
+        }
+        let length = if major { RULER_SIZE } else { RULER_SIZE / 3.0 };
+        painter.line_segment(
+            [
+                egui::pos2(left.max.x - length, at),
+                egui::pos2(left.max.x, at),
+            ],
+            edge,
+        );
+        if major {
+            // Read bottom to top, like a ruler turned on its side
+            let galley = painter.layout_no_wrap(label(y), font.clone(), text);
+            let origin = egui::pos2(left.min.x + 1.0, at - 2.0);
+            painter.add(egui::epaint::TextShape::new(origin, galley, text).with_angle(-FRAC_PI_2));
+        }
+    }
+
+    if let Some(cursor) = state.cursor {
+        let marker = Stroke::new(1.0, SELECTION_COLOR);
+        let (x, y) = (x_of(cursor.x), y_of(cursor.y));
+        if x >= left.max.x {
+            painter.line_segment([egui::pos2(x, top.min.y), egui::pos2(x, top.max.y)], marker);
+        }
+        if y >= top.max.y {
+            painter.line_segment(
+                [egui::pos2(left.min.x, y), egui::pos2(left.max.x, y)],
+                marker,
+            );
+        }
+    }
+}
+
+/// The drawing in the bottom right corner with the part the window shows
+/// outlined; clicking or dragging in it centers the view there
+fn minimap(
+    ctx: &egui::Context,
+    state: &UiState,
+    texture: &egui::TextureHandle,
+    shows: &Rect,
+    actions: &mut Vec<UiAction>,
+) {
+    egui::Area::new(egui::Id::new("minimap"))
+        .anchor(Align2::RIGHT_BOTTOM, [-8.0, -8.0])
+        // Above the status bar
+        .constrain_to(ctx.available_rect())
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style())
+                .inner_margin(0.0)
+                .show(ui, |ui| {
+                    let size = egui::Vec2::from(MINIMAP_SIZE);
+                    let response = ui.add(
+                        egui::Image::new((texture.id(), size)).sense(egui::Sense::click_and_drag()),
+                    );
+                    let rect = response.rect;
+                    let shown = shows.max - shows.min;
+                    let to_point = |p: Vec2| {
+                        egui::pos2(
+                            rect.min.x + (p.x - shows.min.x) / shown.x * rect.width(),
+                            rect.min.y + (p.y - shows.min.y) / shown.y * rect.height(),
+                        )
+                    };
+
+                    let view = egui::Rect::from_min_max(
+                        to_point(state.visible.min),
+                        to_point(state.visible.max),
+                    );
+                    ui.painter_at(rect)
+                        .rect_stroke(view, 0.0, Stroke::new(1.5, SELECTION_COLOR));
+
+                    if response.is_pointer_button_down_on() {
+                        if let Some(pointer) = response.interact_pointer_pos() {
+                            let pointer = rect.clamp(pointer);
+                            actions.push(UiAction::CenterOn(Vec2::new(
+                                shows.min.x + (pointer.x - rect.min.x) / rect.width() * shown.x,
+                                shows.min.y + (pointer.y - rect.min.y) / rect.height() * shown.y,
+                            )));
+                        }
+                    }
+                });
+        });
+}
+
+/// The distance in canvas units between labeled ruler ticks: 1, 2 or 5
+/// times a power of ten, at least [`RULER_MIN_SPACING`] points apart
+fn ruler_step(units_per_point: f32) -> f32 {
+    let min_step = units_per_point * RULER_MIN_SPACING;
+    let magnitude = 10f32.powf(min_step.log10().floor());
+    [1.0, 2.0, 5.0]
+        .into_iter()
+        .map(|mantissa| mantissa * magnitude)
+        .find(|&step| step >= min_step)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// Spacing of the unlabeled ticks for labeled ones `step` apart, and how many
+/// make up one step
+fn minor_ticks(step: f32) -> (f32, i64) {
+    let mantissa = step / 10f32.powf(step.log10().floor());
+    let per_major = if (mantissa - 2.0).abs() < 0.5 { 4 } else { 5 };
+    (step / per_major as f32, per_major)
+}
+
+/// Moves egui's screen and pointer positions from the window into `frame`
+/// (in physical pixels), leaving them as they are when the frame fills the window
+fn letterbox_input(input: &mut egui::RawInput, frame: egui::Rect, pixels_per_point: f32) {
+    let screen = egui::Rect::from_min_size(egui::Pos2::ZERO, frame.size() / pixels_per_point);
+    if input.screen_rect.is_none_or(|rect| rect == screen) {
+        return;
+    }
+
+    input.screen_rect = Some(screen);
+    let offset = frame.min.to_vec2() / pixels_per_point;
+    for event in &mut input.events {
+        match event {
+            egui::Event::PointerMoved(pos)
+            | egui::Event::PointerButton { pos, .. }
+            | egui::Event::Touch { pos, .. } => *pos -= offset,
+            _ => {}
+        }
+    }
+}
+
+/// NDC bounds of everything the primitives draw, clipped to their clip rectangles,
+/// on a frame of `width` x `height` pixels
+fn primitives_bounds(
+    primitives: &[ClippedPrimitive],
+    pixels_per_point: f32,
+    width: f32,
+    height: f32,
+) -> Option<Rect> {
+    let to_ndc = |p: egui::Pos2| {
+        Vec2::new(
+            p.x * pixels_per_point / width * 2.0 - 1.0,
+            p.y * pixels_per_point / height * 2.0 - 1.0,
+        )
+    };
+
+    primitives
+        .iter()
+        .filter_map(|p| {
+            let bounds = match &p.primitive {
+                egui::epaint::Primitive::Mesh(mesh) => mesh.calc_bounds(),
+                egui::epaint::Primitive::Callback(_) => p.clip_rect,
+            };
+            let bounds = bounds.intersect(p.clip_rect);
+            (bounds.is_positive()).then(|| Rect {
+                min: to_ndc(bounds.min),
+                max: to_ndc(bounds.max),
+            })
+        })
+        .reduce(|a, b| a.union(&b))
+}
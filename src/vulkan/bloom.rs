@@ -0,0 +1,467 @@
+use anyhow::{Context, Result};
+use std::mem::size_of;
+use vulkanalia::prelude::v1_0::*;
+
+use super::context::VulkanContext;
+use super::image::{create_image, create_image_view};
+use super::owned::{guard, Destroy};
+use super::pipeline::{
+    create_framebuffers, create_fullscreen_pipeline, create_sampled_render_pass, PipelineRegistry,
+};
+use crate::config::{BloomConfig, ShaderConfig};
+use crate::types::Vec2;
+
+/// Format strokes are drawn in before the glow is added, linear and above 1.0 where colors add up
+pub const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// Push constants of the bloom passes, laid out like the block in
+/// `bloom_blur.frag` and `bloom_composite.frag`
+#[repr(C)]
+struct BloomConstants {
+    /// One texel of the source along the blur direction, in UV units
+    step: Vec2,
+    /// Luminance the blur leaves out, 0 after the first pass
+    threshold: f32,
+    /// How strongly the composite adds the glow
+    intensity: f32,
+}
+
+/// The images of one swapchain image's frames
+struct BloomTargets {
+    /// Strokes, at the render extent
+    scene: (vk::Image, vk::DeviceMemory),
+    /// The glow at half the render extent, blurred back and forth between the two
+    blur: [(vk::Image, vk::DeviceMemory); 2],
+    views: [vk::ImageView; 3],
+    /// Scene, then the two blur images
+    framebuffers: Vec<vk::Framebuffer>,
+    /// Sampling the scene, either blur image, and the scene with the glow for the composite
+    descriptor_sets: [vk::DescriptorSet; 4],
+}
+
+// Indices of the images' framebuffers and the descriptor sets sampling them
+const SCENE: usize = 0;
+const BLUR_A: usize = 1;
+const BLUR_B: usize = 2;
+const COMPOSITE: usize = 3;
+
+impl Destroy for BloomTargets {
+    unsafe fn destroy(&mut self, device: &Device) {
+        self.framebuffers.destroy(device);
+        self.views.iter_mut().for_each(|v| v.destroy(device));
+        self.blur.iter_mut().for_each(|b| b.destroy(device));
+        self.scene.destroy(device);
+    }
+}
+
+/// Draws strokes into a floating point image, blurs its bright parts and
+/// adds them back as a glow while drawing the frame
+///
+/// The strokes' render pass is replaced by [`Bloom::cmd_glow`] followed by
+/// [`Bloom::cmd_composite`] inside the frame's own render pass, which then
+/// draws the UI on top as usual. Every frame is drawn in full: the glow
+/// reaches past any damaged region.
+pub struct Bloom {
+    settings: BloomConfig,
+    extent: vk::Extent2D,
+    blur_extent: vk::Extent2D,
+    scene_render_pass: vk::RenderPass,
+    blur_render_pass: vk::RenderPass,
+    /// Stroke and background pipelines drawing into the scene image
+    pub pipelines: PipelineRegistry,
+    blur_pipeline: (vk::Pipeline, vk::PipelineLayout),
+    composite_pipeline: (vk::Pipeline, vk::PipelineLayout),
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    sampler: vk::Sampler,
+    targets: Vec<BloomTargets>,
+}
+
+impl Bloom {
+    /// Creates `count` sets of images of `extent`, and the pipelines to
+    /// composite them in `render_pass` drawing `color_format`
+    ///
+    /// `background` names the `[shaders.backgrounds]` entry drawn under the strokes.
+    pub unsafe fn create(
+        context: &VulkanContext,
+        frame_layout: vk::DescriptorSetLayout,
+        settings: &BloomConfig,
+        shader_config: &ShaderConfig,
+        background: Option<&str>,
+        render_pass: vk::RenderPass,
+        color_format: vk::Format,
+        extent: vk::Extent2D,
+        transparent: bool,
+        count: usize,
+    ) -> Result<Self> {
+        let device = &context.device;
+        let blur_extent = vk::Extent2D {
+            width: (extent.width / 2).max(1),
+            height: (extent.height / 2).max(1),
+        };
+
+        let scene_render_pass = guard(
+            device,
+            create_sampled_render_pass(device, HDR_FORMAT, vk::AttachmentLoadOp::CLEAR)?,
+        );
+        let blur_render_pass = guard(
+            device,
+            create_sampled_render_pass(device, HDR_FORMAT, vk::AttachmentLoadOp::DONT_CARE)?,
+        );
+
+        let pipelines = guard(
+            device,
+            PipelineRegistry::create(
+                device,
+                context.pipeline_cache,
+                extent,
+                *scene_render_pass,
+                frame_layout,
+                shader_config,
+                background,
+                transparent,
+                context.vertex_format,
+                HDR_FORMAT,
+            )?,
+        );
+
+        let bindings = [0, 1].map(|binding| {
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build()
+        });
+        let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+        let descriptor_set_layout =
+            guard(device, device.create_descriptor_set_layout(&info, None)?);
+
+        let sets = (count * 4) as u32;
+        let pool_size = vk::DescriptorPoolSize::builder()
+            .type_(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(sets * 2);
+        let pool_sizes = &[pool_size];
+        let info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(pool_sizes)
+            .max_sets(sets);
+        let descriptor_pool = guard(device, device.create_descriptor_pool(&info, None)?);
+
+        let info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE);
+        let sampler = guard(device, device.create_sampler(&info, None)?);
+
+        let fullscreen = |render_pass, fragment: &std::path::Path, color_format| {
+            create_fullscreen_pipeline(
+                device,
+                context.pipeline_cache,
+                render_pass,
+                &[*descriptor_set_layout],
+                &shader_config.fullscreen_vertex,
+                fragment,
+                size_of::<BloomConstants>() as u32,
+                color_format,
+                None,
+            )
+            .with_context(|| format!("Failed to create the pipeline for `{}`", fragment.display()))
+        };
+        let blur_pipeline = guard(
+            device,
+            fullscreen(*blur_render_pass, &shader_config.bloom_blur, HDR_FORMAT)?,
+        );
+        let composite_pipeline = guard(
+            device,
+            fullscreen(render_pass, &shader_config.bloom_composite, color_format)?,
+        );
+
+        let mut targets = guard(device, Vec::new());
+        for _ in 0..count {
+            let image = |extent: vk::Extent2D| {
+                create_image(
+                    &context.instance,
+                    device,
+                    context.physical_device,
+                    extent.width,
+                    extent.height,
+                    1,
+                    vk::SampleCountFlags::_1,
+                    HDR_FORMAT,
+                    vk::ImageTiling::OPTIMAL,
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                )
+            };
+            let scene = guard(device, image(extent)?);
+            let blur_a = guard(device, image(blur_extent)?);
+            let blur_b = guard(device, image(blur_extent)?);
+
+            let mut views = guard(device, Vec::new());
+            for image in [scene.0, blur_a.0, blur_b.0] {
+                views.push(create_image_view(
+                    device,
+                    image,
+                    HDR_FORMAT,
+                    vk::ImageAspectFlags::COLOR,
+                )?);
+            }
+
+            let mut framebuffers = guard(
+                device,
+                create_framebuffers(device, &views[..1], extent, *scene_render_pass)?,
+            );
+            framebuffers.extend(create_framebuffers(
+                device,
+                &views[1..],
+                blur_extent,
+                *blur_render_pass,
+            )?);
+
+            let set_layouts = [*descriptor_set_layout; 4];
+            let info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(*descriptor_pool)
+                .set_layouts(&set_layouts);
+            let allocated = device.allocate_descriptor_sets(&info)?;
+            let descriptor_sets = [allocated[0], allocated[1], allocated[2], allocated[3]];
+
+            // What each set samples at bindings 0 and 1
+            let sources = [
+                (views[0], views[0]),
+                (views[1], views[1]),
+                (views[2], views[2]),
+                (views[0], views[2]),
+            ];
+            let image_infos = sources.map(|(first, second)| {
+                [first, second].map(|view| {
+                    vk::DescriptorImageInfo::builder()
+                        .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                        .image_view(view)
+                        .sampler(*sampler)
+                        .build()
+                })
+            });
+            let writes = descriptor_sets
+                .iter()
+                .zip(&image_infos)
+                .map(|(&set, infos)| {
+                    vk::WriteDescriptorSet::builder()
+                        .dst_set(set)
+                        .dst_binding(0)
+                        .dst_array_element(0)
+                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                        .image_info(infos)
+                        .build()
+                })
+                .collect::<Vec<_>>();
+            device.update_descriptor_sets(&writes, &[] as &[vk::CopyDescriptorSet]);
+
+            let views = views.release();
+            targets.push(BloomTargets {
+                framebuffers: framebuffers.release(),
+                views: [views[0], views[1], views[2]],
+                blur: [blur_a.release(), blur_b.release()],
+                scene: scene.release(),
+                descriptor_sets,
+            });
+        }
+
+        Ok(Self {
+            settings: settings.clone(),
+            extent,
+            blur_extent,
+            scene_render_pass: scene_render_pass.release(),
+            blur_render_pass: blur_render_pass.release(),
+            pipelines: pipelines.release(),
+            blur_pipeline: blur_pipeline.release(),
+            composite_pipeline: composite_pipeline.release(),
+            descriptor_set_layout: descriptor_set_layout.release(),
+            descriptor_pool: descriptor_pool.release(),
+            sampler: sampler.release(),
+            targets: targets.release(),
+        })
+    }
+
+    /// The render pass drawing strokes into the scene images
+    pub fn scene_render_pass(&self) -> vk::RenderPass {
+        self.scene_render_pass
+    }
+
+    /// Begins the render pass drawing strokes into target `index`'s scene
+    /// image, cleared to `clear` (linear and premultiplied)
+    ///
+    /// Draw with [`Bloom::pipelines`] and end the render pass before [`Bloom::cmd_glow`].
+    pub unsafe fn cmd_begin_scene(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        index: usize,
+        clear: [f32; 4],
+    ) {
+        let clear_values = &[vk::ClearValue {
+            color: vk::ClearColorValue { float32: clear },
+        }];
+        let info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.scene_render_pass)
+            .framebuffer(self.targets[index].framebuffers[SCENE])
+            .render_area(full(self.extent))
+            .clear_values(clear_values);
+        device.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+    }
+
+    /// Blurs the parts of target `index`'s scene brighter than the threshold,
+    /// or `least_threshold` if that is higher, into its glow image
+    pub unsafe fn cmd_glow(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        index: usize,
+        least_threshold: f32,
+    ) {
+        let target = &self.targets[index];
+        let texel = Vec2::new(
+            1.0 / self.blur_extent.width as f32,
+            1.0 / self.blur_extent.height as f32,
+        );
+        let threshold = self.settings.threshold.max(least_threshold);
+
+        for pass in 0..self.settings.passes {
+            // Across into A, from the scene at first and the last pass's B later
+            let (source, threshold) = if pass == 0 {
+                (SCENE, threshold)
+            } else {
+                (BLUR_B, 0.0)
+            };
+            let horizontal = BloomConstants {
+                step: Vec2::new(texel.x, 0.0),
+                threshold,
+                intensity: self.settings.intensity,
+            };
+            self.cmd_blur(device, command_buffer, target, BLUR_A, source, &horizontal);
+
+            // Then down into B
+            let vertical = BloomConstants {
+                step: Vec2::new(0.0, texel.y),
+                threshold: 0.0,
+                intensity: self.settings.intensity,
+            };
+            self.cmd_blur(device, command_buffer, target, BLUR_B, BLUR_A, &vertical);
+        }
+    }
+
+    /// Draws the blur pipeline into `target`'s framebuffer `framebuffer`
+    /// sampling descriptor set `source`
+    unsafe fn cmd_blur(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        target: &BloomTargets,
+        framebuffer: usize,
+        source: usize,
+        constants: &BloomConstants,
+    ) {
+        let info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.blur_render_pass)
+            .framebuffer(target.framebuffers[framebuffer])
+            .render_area(full(self.blur_extent));
+        device.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+        self.cmd_fullscreen(
+            device,
+            command_buffer,
+            self.blur_pipeline,
+            target.descriptor_sets[source],
+            self.blur_extent,
+            constants,
+        );
+        device.cmd_end_render_pass(command_buffer);
+    }
+
+    /// Draws target `index`'s scene with the glow added over the whole of the
+    /// frame's render pass, which the scene and glow images are the size of
+    pub unsafe fn cmd_composite(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        index: usize,
+    ) {
+        let constants = BloomConstants {
+            step: Vec2::new(0.0, 0.0),
+            threshold: 0.0,
+            intensity: self.settings.intensity,
+        };
+        self.cmd_fullscreen(
+            device,
+            command_buffer,
+            self.composite_pipeline,
+            self.targets[index].descriptor_sets[COMPOSITE],
+            self.extent,
+            &constants,
+        );
+    }
+
+    unsafe fn cmd_fullscreen(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        (pipeline, pipeline_layout): (vk::Pipeline, vk::PipelineLayout),
+        descriptor_set: vk::DescriptorSet,
+        extent: vk::Extent2D,
+        constants: &BloomConstants,
+    ) {
+        device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+        let viewport = vk::Viewport::builder()
+            .x(0.0)
+            .y(0.0)
+            .width(extent.width as f32)
+            .height(extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0);
+        device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        device.cmd_set_scissor(command_buffer, 0, &[full(extent)]);
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline_layout,
+            0,
+            &[descriptor_set],
+            &[],
+        );
+        let bytes = std::slice::from_raw_parts(
+            constants as *const BloomConstants as *const u8,
+            size_of::<BloomConstants>(),
+        );
+        device.cmd_push_constants(
+            command_buffer,
+            pipeline_layout,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            bytes,
+        );
+        device.cmd_draw(command_buffer, 3, 1, 0, 0);
+    }
+}
+
+fn full(extent: vk::Extent2D) -> vk::Rect2D {
+    vk::Rect2D {
+        offset: vk::Offset2D::default(),
+        extent,
+    }
+}
+
+impl Destroy for Bloom {
+    unsafe fn destroy(&mut self, device: &Device) {
+        // Descriptor sets go with the pool
+        self.targets.destroy(device);
+        self.composite_pipeline.destroy(device);
+        self.blur_pipeline.destroy(device);
+        self.pipelines.destroy(device);
+        self.sampler.destroy(device);
+        self.descriptor_pool.destroy(device);
+        self.descriptor_set_layout.destroy(device);
+        self.blur_render_pass.destroy(device);
+        self.scene_render_pass.destroy(device);
+    }
+}
@@ -4,13 +4,16 @@ use anyhow::Result;
 use vulkanalia::prelude::v1_0::*;
 
 use super::helpers::{begin_single_time_commands, end_single_time_commands, get_memory_type_index};
+use super::memory::allocate_memory;
+use super::owned::guard;
+use crate::config::VertexFormat;
 use crate::types::{Line, RECT, RECT_INDICES};
 
 //================================================
 // Generic Buffer Creation
 //================================================
 
-unsafe fn create_buffer(
+pub unsafe fn create_buffer(
     instance: &Instance,
     device: &Device,
     physical_device: vk::PhysicalDevice,
@@ -24,10 +27,10 @@ unsafe fn create_buffer(
         .usage(usage)
         .sharing_mode(vk::SharingMode::EXCLUSIVE);
 
-    let buffer = device.create_buffer(&buffer_info, None)?;
+    let buffer = guard(device, device.create_buffer(&buffer_info, None)?);
 
     // Memory
-    let requirements = device.get_buffer_memory_requirements(buffer);
+    let requirements = device.get_buffer_memory_requirements(*buffer);
 
     let memory_info = vk::MemoryAllocateInfo::builder()
         .allocation_size(requirements.size)
@@ -38,11 +41,14 @@ unsafe fn create_buffer(
             requirements,
         )?);
 
-    let buffer_memory = device.allocate_memory(&memory_info, None)?;
+    let buffer_memory = guard(
+        device,
+        allocate_memory(instance, device, physical_device, &memory_info)?,
+    );
 
-    device.bind_buffer_memory(buffer, buffer_memory, 0)?;
+    device.bind_buffer_memory(*buffer, *buffer_memory, 0)?;
 
-    Ok((buffer, buffer_memory))
+    Ok((buffer.release(), buffer_memory.release()))
 }
 
 pub unsafe fn copy_buffer(
@@ -64,6 +70,41 @@ pub unsafe fn copy_buffer(
     Ok(())
 }
 
+/// Uploads `lines` into `destination` starting at line index `first_line`
+///
+/// Data goes through the persistently mapped `staging_ptr` in chunks of at
+/// most `staging_capacity` lines, written as `vertex_format`.
+pub unsafe fn upload_lines(
+    device: &Device,
+    graphics_queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    staging_buffer: vk::Buffer,
+    staging_ptr: *mut u8,
+    staging_capacity: usize,
+    vertex_format: VertexFormat,
+    destination: vk::Buffer,
+    first_line: usize,
+    lines: &[Line],
+) -> Result<()> {
+    let stride = Line::stride(vertex_format);
+    for (i, chunk) in lines.chunks(staging_capacity.max(1)).enumerate() {
+        Line::write(vertex_format, chunk, staging_ptr);
+
+        let dst_line = first_line + i * staging_capacity;
+        copy_buffer(
+            device,
+            graphics_queue,
+            command_pool,
+            staging_buffer,
+            destination,
+            (stride * dst_line) as u64,
+            (stride * chunk.len()) as u64,
+        )?;
+    }
+
+    Ok(())
+}
+
 //================================================
 // Create Scribble Buffers
 //================================================
@@ -76,6 +117,7 @@ pub unsafe fn create_buffers(
     command_pool: vk::CommandPool,
     max_vertices: u32,
     staging_buffer_vertex_count: u32,
+    vertex_format: VertexFormat,
 ) -> Result<(
     vk::Buffer,
     vk::DeviceMemory,
@@ -94,16 +136,22 @@ pub unsafe fn create_buffers(
             physical_device,
             max_vertices,
             staging_buffer_vertex_count,
+            vertex_format,
         )?;
+    let vertex = guard(device, (vertex_buffer, vertex_buffer_memory));
+    let staging = guard(device, (staging_buffer, staging_buffer_memory));
 
     // Create instance buffer
-    let (instance_buffer, instance_buffer_memory) = create_instance_buffers(
-        instance,
+    let instance_buffer = guard(
         device,
-        physical_device,
-        graphics_queue,
-        command_pool,
-    )?;
+        create_instance_buffers(
+            instance,
+            device,
+            physical_device,
+            graphics_queue,
+            command_pool,
+        )?,
+    );
 
     // Create index buffer
     let (instance_index_buffer, instance_index_buffer_memory) = create_index_buffers(
@@ -114,6 +162,9 @@ pub unsafe fn create_buffers(
         command_pool,
     )?;
 
+    let (vertex_buffer, vertex_buffer_memory) = vertex.release();
+    let (staging_buffer, staging_buffer_memory) = staging.release();
+    let (instance_buffer, instance_buffer_memory) = instance_buffer.release();
     Ok((
         vertex_buffer,
         vertex_buffer_memory,
@@ -132,19 +183,24 @@ pub unsafe fn create_vertex_buffers(
     physical_device: vk::PhysicalDevice,
     max_vertices: u32,
     staging_buffer_vertex_count: u32,
+    vertex_format: VertexFormat,
 ) -> Result<(vk::Buffer, vk::DeviceMemory, vk::Buffer, vk::DeviceMemory)> {
-    let vertex_buffer_size = (size_of::<Line>() * max_vertices as usize) as u64;
-    let staging_buffer_size = (size_of::<Line>() * staging_buffer_vertex_count as usize) as u64;
+    let stride = Line::stride(vertex_format);
+    let vertex_buffer_size = (stride * max_vertices as usize) as u64;
+    let staging_buffer_size = (stride * staging_buffer_vertex_count as usize) as u64;
 
     // Create staging buffer
-    let (staging_buffer, staging_buffer_memory) = create_buffer(
-        instance,
+    let staging = guard(
         device,
-        physical_device,
-        staging_buffer_size,
-        vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::VERTEX_BUFFER,
-        vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
-    )?;
+        create_buffer(
+            instance,
+            device,
+            physical_device,
+            staging_buffer_size,
+            vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::VERTEX_BUFFER,
+            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+        )?,
+    );
 
     // Create vertex buffer
     let (vertex_buffer, vertex_buffer_memory) = create_buffer(
@@ -156,6 +212,7 @@ pub unsafe fn create_vertex_buffers(
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
     )?;
 
+    let (staging_buffer, staging_buffer_memory) = staging.release();
     Ok((
         vertex_buffer,
         vertex_buffer_memory,
@@ -173,25 +230,33 @@ pub unsafe fn create_instance_buffers(
 ) -> Result<(vk::Buffer, vk::DeviceMemory)> {
     let buffer_size = (size_of::<f32>() * RECT.len()) as u64;
 
-    // Create staging buffer
-    let (staging_buffer, staging_buffer_memory) = create_buffer(
-        instance,
+    // Create staging buffer, destroyed when the guard goes out of scope
+    let staging = guard(
         device,
-        physical_device,
-        buffer_size,
-        vk::BufferUsageFlags::TRANSFER_SRC,
-        vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
-    )?;
+        create_buffer(
+            instance,
+            device,
+            physical_device,
+            buffer_size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+        )?,
+    );
+    let (staging_buffer, staging_buffer_memory) = *staging;
 
     // Create vertex buffer
-    let (vertex_buffer, vertex_buffer_memory) = create_buffer(
-        instance,
+    let vertex = guard(
         device,
-        physical_device,
-        buffer_size,
-        vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER,
-        vk::MemoryPropertyFlags::DEVICE_LOCAL,
-    )?;
+        create_buffer(
+            instance,
+            device,
+            physical_device,
+            buffer_size,
+            vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?,
+    );
+    let (vertex_buffer, vertex_buffer_memory) = *vertex;
 
     copy_buffer(
         device,
@@ -221,10 +286,8 @@ pub unsafe fn create_instance_buffers(
         0,
         buffer_size,
     )?;
-    device.destroy_buffer(staging_buffer, None);
-    device.free_memory(staging_buffer_memory, None);
 
-    Ok((vertex_buffer, vertex_buffer_memory))
+    Ok(vertex.release())
 }
 
 pub unsafe fn create_index_buffers(
@@ -236,25 +299,33 @@ pub unsafe fn create_index_buffers(
 ) -> Result<(vk::Buffer, vk::DeviceMemory)> {
     let buffer_size = (size_of::<u16>() * RECT_INDICES.len()) as u64;
 
-    // Create staging buffer
-    let (staging_buffer, staging_buffer_memory) = create_buffer(
-        instance,
+    // Create staging buffer, destroyed when the guard goes out of scope
+    let staging = guard(
         device,
-        physical_device,
-        buffer_size,
-        vk::BufferUsageFlags::TRANSFER_SRC,
-        vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
-    )?;
+        create_buffer(
+            instance,
+            device,
+            physical_device,
+            buffer_size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+        )?,
+    );
+    let (staging_buffer, staging_buffer_memory) = *staging;
 
     // Create index buffer
-    let (index_buffer, index_buffer_memory) = create_buffer(
-        instance,
+    let index = guard(
         device,
-        physical_device,
-        buffer_size,
-        vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER,
-        vk::MemoryPropertyFlags::DEVICE_LOCAL,
-    )?;
+        create_buffer(
+            instance,
+            device,
+            physical_device,
+            buffer_size,
+            vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?,
+    );
+    let (index_buffer, index_buffer_memory) = *index;
 
     let memory = device.map_memory(
         staging_buffer_memory,
@@ -275,8 +346,6 @@ pub unsafe fn create_index_buffers(
         0,
         buffer_size,
     )?;
-    device.destroy_buffer(staging_buffer, None);
-    device.free_memory(staging_buffer_memory, None);
 
-    Ok((index_buffer, index_buffer_memory))
+    Ok(index.release())
 }
@@ -2,6 +2,7 @@ use anyhow::Result;
 use vulkanalia::prelude::v1_0::*;
 
 use super::device::QueueFamilyIndices;
+use super::owned::guard;
 
 //================================================
 // Command Pools
@@ -33,13 +34,13 @@ pub unsafe fn create_command_pools(
     swapchain_image_count: usize,
 ) -> Result<Vec<vk::CommandPool>> {
     // Per-framebuffer command pools
-    let mut command_pools = Vec::new();
+    let mut command_pools = guard(device, Vec::new());
     for _ in 0..swapchain_image_count {
         let pool = create_command_pool(instance, device, surface, physical_device)?;
         command_pools.push(pool);
     }
 
-    Ok(command_pools)
+    Ok(command_pools.release())
 }
 
 //================================================
@@ -1,14 +1,18 @@
 use anyhow::Result;
+use log::*;
 use vulkanalia::loader::{LIBRARY, LibloadingLoader};
 use vulkanalia::prelude::v1_0::*;
 use vulkanalia::vk::ExtDebugUtilsExtensionInstanceCommands;
 use vulkanalia::vk::KhrSurfaceExtensionInstanceCommands;
 use winit::window::Window;
 
+use super::info::describe_context;
 use super::instance::create_instance;
 use super::logical_device::create_logical_device;
 use super::physical_device::pick_physical_device;
-use crate::config::Config;
+use super::pipeline::{create_pipeline_cache, save_pipeline_cache};
+use crate::config::{Config, VertexFormat};
+use crate::crash;
 
 /// Core Vulkan objects that live for the entire application lifetime
 pub struct VulkanContext {
@@ -21,6 +25,10 @@ pub struct VulkanContext {
     pub present_queue: vk::Queue,
     pub messenger: vk::DebugUtilsMessengerEXT,
     pub command_pool: vk::CommandPool,
+    pub pipeline_cache: vk::PipelineCache,
+    /// How segments are laid out in every stroke vertex buffer, `[vulkan]
+    /// vertex_format` if the device can read it
+    pub vertex_format: VertexFormat,
 }
 
 impl VulkanContext {
@@ -29,14 +37,18 @@ impl VulkanContext {
         let loader = LibloadingLoader::new(LIBRARY)?;
         let entry = vulkanalia::Entry::new(loader).map_err(|b| anyhow::anyhow!("{}", b))?;
 
-        let (instance, messenger) = create_instance(window, &entry, &config.window)?;
+        let (instance, messenger) = create_instance(Some(window), &entry, &config.window)?;
         let surface = vulkanalia::window::create_surface(&instance, window, window)?;
-        let physical_device = pick_physical_device(&instance, surface)?;
+        let physical_device = pick_physical_device(&instance, surface, config.vulkan.gpu)?;
         let (device, graphics_queue, present_queue) =
             create_logical_device(&entry, &instance, surface, physical_device)?;
+        crash::set_gpu(describe_context(&instance, physical_device, &device));
 
         let command_pool =
             super::command::create_command_pool(&instance, &device, surface, physical_device)?;
+        let pipeline_cache = create_pipeline_cache(&device)?;
+        let vertex_format =
+            supported_vertex_format(&instance, physical_device, config.vulkan.vertex_format);
 
         Ok(Self {
             entry,
@@ -48,20 +60,96 @@ impl VulkanContext {
             present_queue,
             messenger,
             command_pool,
+            pipeline_cache,
+            vertex_format,
         })
     }
 
-    /// Destroys the Vulkan context
-    pub unsafe fn destroy(&self) {
-        self.device.destroy_command_pool(self.command_pool, None);
-        self.device.destroy_device(None);
-        self.instance.destroy_surface_khr(self.surface, None);
+    /// Creates a Vulkan context without a window or surface, for offscreen rendering
+    pub unsafe fn create_headless(config: &Config) -> Result<Self> {
+        let loader = LibloadingLoader::new(LIBRARY)?;
+        let entry = vulkanalia::Entry::new(loader).map_err(|b| anyhow::anyhow!("{}", b))?;
 
-        if !self.messenger.is_null() {
-            self.instance
-                .destroy_debug_utils_messenger_ext(self.messenger, None);
-        }
+        let (instance, messenger) = create_instance(None, &entry, &config.window)?;
+        let surface = vk::SurfaceKHR::null();
+        let physical_device = pick_physical_device(&instance, surface, config.vulkan.gpu)?;
+        let (device, graphics_queue, present_queue) =
+            create_logical_device(&entry, &instance, surface, physical_device)?;
+        crash::set_gpu(describe_context(&instance, physical_device, &device));
+
+        let command_pool =
+            super::command::create_command_pool(&instance, &device, surface, physical_device)?;
+        let pipeline_cache = create_pipeline_cache(&device)?;
+        let vertex_format =
+            supported_vertex_format(&instance, physical_device, config.vulkan.vertex_format);
+
+        Ok(Self {
+            entry,
+            instance,
+            device,
+            physical_device,
+            surface,
+            graphics_queue,
+            present_queue,
+            messenger,
+            command_pool,
+            pipeline_cache,
+            vertex_format,
+        })
+    }
+}
+
+/// `format`, or `F32` with a warning when the device can't read half floats as vertex attributes
+unsafe fn supported_vertex_format(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    format: VertexFormat,
+) -> VertexFormat {
+    if format == VertexFormat::F32 {
+        return format;
+    }
 
-        self.instance.destroy_instance(None);
+    let readable = [vk::Format::R16G16_SFLOAT, vk::Format::R16_SFLOAT]
+        .into_iter()
+        .all(|format| {
+            instance
+                .get_physical_device_format_properties(physical_device, format)
+                .buffer_features
+                .contains(vk::FormatFeatureFlags::VERTEX_BUFFER)
+        });
+    if readable {
+        format
+    } else {
+        warn!("Storing segments as 32-bit floats, the device can't read half floats from vertex buffers.");
+        VertexFormat::F32
+    }
+}
+
+/// Destroys the Vulkan context, after everything created from it has been dropped
+impl Drop for VulkanContext {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(e) = self.device.device_wait_idle() {
+                warn!("Failed to wait for the GPU before shutting down: {}", e);
+            }
+
+            if let Err(e) = save_pipeline_cache(&self.device, self.pipeline_cache) {
+                warn!("Failed to save the pipeline cache: {:#}", e);
+            }
+            self.device.destroy_pipeline_cache(self.pipeline_cache, None);
+            self.device.destroy_command_pool(self.command_pool, None);
+            self.device.destroy_device(None);
+
+            if !self.surface.is_null() {
+                self.instance.destroy_surface_khr(self.surface, None);
+            }
+
+            if !self.messenger.is_null() {
+                self.instance
+                    .destroy_debug_utils_messenger_ext(self.messenger, None);
+            }
+
+            self.instance.destroy_instance(None);
+        }
     }
 }
@@ -14,6 +14,8 @@ pub struct QueueFamilyIndices {
 }
 
 impl QueueFamilyIndices {
+    /// Finds the graphics and present queue families. Without a surface
+    /// (headless rendering) the graphics family doubles as present family.
     pub unsafe fn get(
         instance: &Instance,
         surface: vk::SurfaceKHR,
@@ -27,14 +29,18 @@ impl QueueFamilyIndices {
             .map(|i| i as u32);
 
         let mut present = None;
-        for (index, _properties) in properties.iter().enumerate() {
-            if instance.get_physical_device_surface_support_khr(
-                physical_device,
-                index as u32,
-                surface,
-            )? {
-                present = Some(index as u32);
-                break;
+        if surface.is_null() {
+            present = graphics;
+        } else {
+            for (index, _properties) in properties.iter().enumerate() {
+                if instance.get_physical_device_surface_support_khr(
+                    physical_device,
+                    index as u32,
+                    surface,
+                )? {
+                    present = Some(index as u32);
+                    break;
+                }
             }
         }
 
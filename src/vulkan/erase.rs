@@ -0,0 +1,328 @@
+use anyhow::{Context, Result};
+use std::mem::size_of;
+use std::ops::Range;
+use vulkanalia::prelude::v1_0::*;
+
+use super::context::VulkanContext;
+use super::image::{create_image, create_image_view};
+use super::owned::{guard, Destroy};
+use super::pipeline::{
+    create_erase_mask_pipeline, create_framebuffers, create_fullscreen_pipeline,
+    create_sampled_render_pass,
+};
+use super::renderer::cmd_draw_brush;
+use super::uniforms::FrameBinding;
+use crate::camera::Camera;
+use crate::config::ShaderConfig;
+use crate::types::{StrokeStyle, PIXEL_ERASER_BRUSH};
+
+/// Format of the mask, how much of each pixel the pixel erasers cover
+pub const MASK_FORMAT: vk::Format = vk::Format::R8_UNORM;
+
+/// The mask of one swapchain image's frames
+struct EraseTarget {
+    mask: (vk::Image, vk::DeviceMemory),
+    view: vk::ImageView,
+    framebuffer: vk::Framebuffer,
+    /// Sampling the mask for the composite
+    descriptor_set: vk::DescriptorSet,
+}
+
+impl Destroy for EraseTarget {
+    unsafe fn destroy(&mut self, device: &Device) {
+        self.framebuffer.destroy(device);
+        self.view.destroy(device);
+        self.mask.destroy(device);
+    }
+}
+
+/// A raster layer over the strokes where pixel eraser strokes punch holes
+///
+/// [`EraseLayer::cmd_mask`] draws the strokes with [`PIXEL_ERASER_BRUSH`]
+/// into a mask before the frame's render pass; no stroke pipeline draws
+/// them. Once the strokes are drawn, [`EraseLayer::cmd_composite`] blends
+/// the canvas background back over them as far as the mask covers them.
+/// The holes go through every stroke, later ones too, for as long as the
+/// eraser strokes stay, and show the plain background color rather than
+/// an animated one.
+#[derive(Default)]
+pub struct EraseLayer {
+    extent: vk::Extent2D,
+    mask_render_pass: vk::RenderPass,
+    mask_pipeline: (vk::Pipeline, vk::PipelineLayout),
+    composite_pipeline: (vk::Pipeline, vk::PipelineLayout),
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    sampler: vk::Sampler,
+    targets: Vec<EraseTarget>,
+}
+
+impl EraseLayer {
+    /// Creates `count` masks of `extent`, and the pipelines to composite
+    /// them in `render_pass` drawing `color_format`
+    pub unsafe fn create(
+        context: &VulkanContext,
+        frame_layout: vk::DescriptorSetLayout,
+        shader_config: &ShaderConfig,
+        render_pass: vk::RenderPass,
+        color_format: vk::Format,
+        extent: vk::Extent2D,
+        count: usize,
+    ) -> Result<Self> {
+        let device = &context.device;
+
+        let mask_render_pass = guard(
+            device,
+            create_sampled_render_pass(device, MASK_FORMAT, vk::AttachmentLoadOp::CLEAR)?,
+        );
+        let mask_pipeline = guard(
+            device,
+            create_erase_mask_pipeline(
+                device,
+                context.pipeline_cache,
+                extent,
+                *mask_render_pass,
+                frame_layout,
+                shader_config,
+                context.vertex_format,
+                MASK_FORMAT,
+            )?,
+        );
+
+        let binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT);
+        let bindings = &[binding];
+        let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
+        let descriptor_set_layout =
+            guard(device, device.create_descriptor_set_layout(&info, None)?);
+
+        let pool_size = vk::DescriptorPoolSize::builder()
+            .type_(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(count as u32);
+        let pool_sizes = &[pool_size];
+        let info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(pool_sizes)
+            .max_sets(count as u32);
+        let descriptor_pool = guard(device, device.create_descriptor_pool(&info, None)?);
+
+        // The mask is the size of the frame, so texels map to pixels
+        let info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE);
+        let sampler = guard(device, device.create_sampler(&info, None)?);
+
+        // Colors give way to the background's by the mask, and alpha to the
+        // background's alpha, which comes in through the blend constants
+        let blend = vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::all())
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::ONE)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::CONSTANT_ALPHA)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .build();
+        let fragment = &shader_config.erase_composite;
+        let composite_pipeline = guard(
+            device,
+            create_fullscreen_pipeline(
+                device,
+                context.pipeline_cache,
+                render_pass,
+                &[*descriptor_set_layout],
+                &shader_config.fullscreen_vertex,
+                fragment,
+                size_of::<[f32; 4]>() as u32,
+                color_format,
+                Some(blend),
+            )
+            .with_context(|| {
+                format!("Failed to create the pipeline for `{}`", fragment.display())
+            })?,
+        );
+
+        let mut targets = guard(device, Vec::new());
+        for _ in 0..count {
+            let mask = guard(
+                device,
+                create_image(
+                    &context.instance,
+                    device,
+                    context.physical_device,
+                    extent.width,
+                    extent.height,
+                    1,
+                    vk::SampleCountFlags::_1,
+                    MASK_FORMAT,
+                    vk::ImageTiling::OPTIMAL,
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                )?,
+            );
+            let view = guard(
+                device,
+                create_image_view(device, mask.0, MASK_FORMAT, vk::ImageAspectFlags::COLOR)?,
+            );
+            let framebuffer = guard(
+                device,
+                create_framebuffers(device, &[*view], extent, *mask_render_pass)?[0],
+            );
+
+            let set_layouts = &[*descriptor_set_layout];
+            let info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(*descriptor_pool)
+                .set_layouts(set_layouts);
+            let descriptor_set = device.allocate_descriptor_sets(&info)?[0];
+
+            let image_info = vk::DescriptorImageInfo::builder()
+                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .image_view(*view)
+                .sampler(*sampler);
+            let image_infos = &[image_info];
+            let write = vk::WriteDescriptorSet::builder()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(image_infos);
+            device.update_descriptor_sets(&[write], &[] as &[vk::CopyDescriptorSet]);
+
+            targets.push(EraseTarget {
+                framebuffer: framebuffer.release(),
+                view: view.release(),
+                mask: mask.release(),
+                descriptor_set,
+            });
+        }
+
+        Ok(Self {
+            extent,
+            mask_render_pass: mask_render_pass.release(),
+            mask_pipeline: mask_pipeline.release(),
+            composite_pipeline: composite_pipeline.release(),
+            descriptor_set_layout: descriptor_set_layout.release(),
+            descriptor_pool: descriptor_pool.release(),
+            sampler: sampler.release(),
+            targets: targets.release(),
+        })
+    }
+
+    /// Draws the pixel eraser segments of `batches` into target `index`'s
+    /// mask, outside any render pass and before [`EraseLayer::cmd_composite`]
+    ///
+    /// The whole mask is drawn every time, since the composite may cover any
+    /// part of the frame.
+    pub unsafe fn cmd_mask(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        index: usize,
+        frame: FrameBinding,
+        camera: &Camera,
+        style: &StrokeStyle,
+        rect_buffer: vk::Buffer,
+        index_buffer: vk::Buffer,
+        batches: &[(vk::Buffer, Range<u32>)],
+    ) {
+        let render_area = full(self.extent);
+        let clear_values = &[vk::ClearValue {
+            color: vk::ClearColorValue { float32: [0.0; 4] },
+        }];
+        let info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.mask_render_pass)
+            .framebuffer(self.targets[index].framebuffer)
+            .render_area(render_area)
+            .clear_values(clear_values);
+        device.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+        // The erasers read the stroke shader's parameters, in block 0
+        cmd_draw_brush(
+            device,
+            command_buffer,
+            self.mask_pipeline,
+            frame,
+            0,
+            PIXEL_ERASER_BRUSH,
+            render_area,
+            camera,
+            style,
+            rect_buffer,
+            index_buffer,
+            batches,
+        );
+        device.cmd_end_render_pass(command_buffer);
+    }
+
+    /// Blends `background` over what is drawn within `scissor` as far as
+    /// target `index`'s mask covers it, inside the render pass the layer was
+    /// created for and after the strokes
+    ///
+    /// `background` is the canvas color in linear light, premultiplied where
+    /// the clear color is.
+    pub unsafe fn cmd_composite(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        index: usize,
+        scissor: vk::Rect2D,
+        background: [f32; 4],
+    ) {
+        let (pipeline, pipeline_layout) = self.composite_pipeline;
+        device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+        let viewport = vk::Viewport::builder()
+            .x(0.0)
+            .y(0.0)
+            .width(self.extent.width as f32)
+            .height(self.extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0);
+        device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+        device.cmd_set_blend_constants(command_buffer, [0.0, 0.0, 0.0, background[3]]);
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline_layout,
+            0,
+            &[self.targets[index].descriptor_set],
+            &[],
+        );
+        let bytes =
+            std::slice::from_raw_parts(background.as_ptr() as *const u8, size_of::<[f32; 4]>());
+        device.cmd_push_constants(
+            command_buffer,
+            pipeline_layout,
+            vk::ShaderStageFlags::FRAGMENT,
+            0,
+            bytes,
+        );
+        device.cmd_draw(command_buffer, 3, 1, 0, 0);
+    }
+}
+
+fn full(extent: vk::Extent2D) -> vk::Rect2D {
+    vk::Rect2D {
+        offset: vk::Offset2D::default(),
+        extent,
+    }
+}
+
+impl Destroy for EraseLayer {
+    unsafe fn destroy(&mut self, device: &Device) {
+        // Descriptor sets go with the pool
+        self.targets.destroy(device);
+        self.composite_pipeline.destroy(device);
+        self.mask_pipeline.destroy(device);
+        self.sampler.destroy(device);
+        self.descriptor_pool.destroy(device);
+        self.descriptor_set_layout.destroy(device);
+        self.mask_render_pass.destroy(device);
+    }
+}
@@ -2,6 +2,8 @@ use anyhow::{Result, anyhow};
 use vulkanalia::prelude::v1_0::*;
 
 use super::helpers::{begin_single_time_commands, end_single_time_commands, get_memory_type_index};
+use super::memory::allocate_memory;
+use super::owned::guard;
 
 pub unsafe fn create_image(
     instance: &Instance,
@@ -32,8 +34,8 @@ pub unsafe fn create_image(
         .sharing_mode(vk::SharingMode::EXCLUSIVE)
         .samples(samples);
 
-    let image = device.create_image(&info, None)?;
-    let requirements = device.get_image_memory_requirements(image);
+    let image = guard(device, device.create_image(&info, None)?);
+    let requirements = device.get_image_memory_requirements(*image);
 
     let info = vk::MemoryAllocateInfo::builder()
         .allocation_size(requirements.size)
@@ -44,10 +46,10 @@ pub unsafe fn create_image(
             requirements,
         )?);
 
-    let image_memory = device.allocate_memory(&info, None)?;
-    device.bind_image_memory(image, image_memory, 0)?;
+    let image_memory = guard(device, allocate_memory(instance, device, physical_device, &info)?);
+    device.bind_image_memory(*image, *image_memory, 0)?;
 
-    Ok((image, image_memory))
+    Ok((image.release(), image_memory.release()))
 }
 
 pub unsafe fn create_image_view(
@@ -173,6 +175,67 @@ pub unsafe fn copy_buffer_to_image(
     Ok(())
 }
 
+pub unsafe fn copy_image_to_buffer(
+    device: &Device,
+    graphics_queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    image: vk::Image,
+    buffer: vk::Buffer,
+    width: u32,
+    height: u32,
+) -> Result<()> {
+    let command_buffer = begin_single_time_commands(device, command_pool)?;
+
+    let subresource = vk::ImageSubresourceLayers::builder()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .mip_level(0)
+        .base_array_layer(0)
+        .layer_count(1);
+
+    let region = vk::BufferImageCopy::builder()
+        .buffer_offset(0)
+        .buffer_row_length(0)
+        .buffer_image_height(0)
+        .image_subresource(subresource)
+        .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+        .image_extent(vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        });
+
+    device.cmd_copy_image_to_buffer(
+        command_buffer,
+        image,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        buffer,
+        &[region],
+    );
+
+    // Make the copied pixels visible to the host
+    let barrier = vk::BufferMemoryBarrier::builder()
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::HOST_READ)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .buffer(buffer)
+        .offset(0)
+        .size(vk::WHOLE_SIZE);
+
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::HOST,
+        vk::DependencyFlags::empty(),
+        &[] as &[vk::MemoryBarrier],
+        &[barrier],
+        &[] as &[vk::ImageMemoryBarrier],
+    );
+
+    end_single_time_commands(device, graphics_queue, command_pool, command_buffer)?;
+    Ok(())
+}
+
 pub unsafe fn generate_mipmaps(
     instance: &Instance,
     device: &Device,
@@ -0,0 +1,232 @@
+use anyhow::Result;
+use vulkanalia::loader::{LibloadingLoader, LIBRARY};
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::{ExtDebugUtilsExtensionInstanceCommands, KhrSurfaceExtensionInstanceCommands};
+use winit::window::Window;
+
+use super::device::SwapchainSupport;
+use super::instance::create_instance;
+use super::physical_device::{check_physical_device, pick_physical_device};
+use crate::config::Config;
+
+/// Prints every physical device with its queue families, memory heaps, the
+/// limits the renderer depends on and, given a `window`, what its surface
+/// supports, for `scribble-vk info`
+///
+/// Creates only an instance (and a surface), so it also works with devices
+/// the app would refuse to render on. The device the app would pick is marked.
+pub unsafe fn print_info(window: Option<&Window>, config: &Config) -> Result<()> {
+    let loader = LibloadingLoader::new(LIBRARY)?;
+    let entry = vulkanalia::Entry::new(loader).map_err(|b| anyhow::anyhow!("{}", b))?;
+
+    let (instance, messenger) = create_instance(window, &entry, &config.window)?;
+    let surface = match window {
+        Some(window) => vulkanalia::window::create_surface(&instance, window, window)?,
+        None => vk::SurfaceKHR::null(),
+    };
+
+    let result = print_devices(&instance, surface, config);
+
+    if !surface.is_null() {
+        instance.destroy_surface_khr(surface, None);
+    }
+    if !messenger.is_null() {
+        instance.destroy_debug_utils_messenger_ext(messenger, None);
+    }
+    instance.destroy_instance(None);
+
+    result
+}
+
+unsafe fn print_devices(
+    instance: &Instance,
+    surface: vk::SurfaceKHR,
+    config: &Config,
+) -> Result<()> {
+    let physical_devices = instance.enumerate_physical_devices()?;
+    let selected = pick_physical_device(instance, surface, config.vulkan.gpu).ok();
+
+    if surface.is_null() {
+        println!("No window could be opened, so surface support is not listed.");
+    }
+    println!("Vulkan devices ({}):", physical_devices.len());
+
+    for (index, &physical_device) in physical_devices.iter().enumerate() {
+        let properties = instance.get_physical_device_properties(physical_device);
+        println!();
+        println!(
+            "[{}] {} ({:?}){}",
+            index,
+            properties.device_name,
+            properties.device_type,
+            if selected == Some(physical_device) {
+                ", selected"
+            } else {
+                ""
+            }
+        );
+        println!(
+            "    API {}, driver {:#x}, vendor {:#06x}, device {:#06x}",
+            format_version(properties.api_version),
+            properties.driver_version,
+            properties.vendor_id,
+            properties.device_id
+        );
+        match check_physical_device(instance, surface, physical_device) {
+            Ok(()) => println!("    Suitable: yes"),
+            Err(e) => println!("    Suitable: no, {}", e),
+        }
+
+        let memory = instance.get_physical_device_memory_properties(physical_device);
+        let heaps: Vec<String> = memory.memory_heaps[..memory.memory_heap_count as usize]
+            .iter()
+            .map(|heap| {
+                let local = heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL);
+                format!(
+                    "{} MiB{}",
+                    heap.size / (1024 * 1024),
+                    if local { " (device local)" } else { "" }
+                )
+            })
+            .collect();
+        println!("    Memory heaps: {}", heaps.join(", "));
+
+        println!("    Queue families:");
+        let families = instance.get_physical_device_queue_family_properties(physical_device);
+        for (family, properties) in families.iter().enumerate() {
+            let present = !surface.is_null()
+                && instance.get_physical_device_surface_support_khr(
+                    physical_device,
+                    family as u32,
+                    surface,
+                )?;
+            println!(
+                "      {}: {} queues, {:?}{}",
+                family,
+                properties.queue_count,
+                properties.queue_flags,
+                if present { ", present" } else { "" }
+            );
+        }
+
+        // Reported even for unsuitable devices, which may lack the swapchain extension
+        if !surface.is_null() {
+            match SwapchainSupport::get(instance, surface, physical_device) {
+                Ok(support) => print_surface_support(&support),
+                Err(e) => println!("    Surface: not supported ({})", e),
+            }
+
+            let extensions: Vec<_> = instance
+                .enumerate_device_extension_properties(physical_device, None)?
+                .iter()
+                .map(|e| e.extension_name)
+                .collect();
+            let timing: Vec<&str> = [
+                (vk::KHR_PRESENT_WAIT_EXTENSION.name, "present wait"),
+                (vk::GOOGLE_DISPLAY_TIMING_EXTENSION.name, "display timing"),
+            ]
+            .into_iter()
+            .filter(|(name, _)| extensions.contains(name))
+            .map(|(_, label)| label)
+            .collect();
+            println!(
+                "    Present timing: {}",
+                if timing.is_empty() {
+                    "none (no low-latency pacing)".to_string()
+                } else {
+                    timing.join(", ")
+                }
+            );
+        }
+
+        let limits = properties.limits;
+        println!("    Limits:");
+        println!(
+            "      max memory allocations: {}",
+            limits.max_memory_allocation_count
+        );
+        println!("      max 2D image size: {}", limits.max_image_dimension_2d);
+        println!(
+            "      max push constants: {} bytes",
+            limits.max_push_constants_size
+        );
+        println!(
+            "      max vertex input attributes: {}",
+            limits.max_vertex_input_attributes
+        );
+        println!(
+            "      line width range: {} to {} (granularity {})",
+            limits.line_width_range[0], limits.line_width_range[1], limits.line_width_granularity
+        );
+        println!(
+            "      timestamp period: {} ns{}",
+            limits.timestamp_period,
+            if limits.timestamp_compute_and_graphics == vk::TRUE {
+                ""
+            } else {
+                " (no timestamps on every queue)"
+            }
+        );
+    }
+
+    Ok(())
+}
+
+fn print_surface_support(support: &SwapchainSupport) {
+    let formats: Vec<String> = support
+        .formats
+        .iter()
+        .map(|f| format!("{:?}/{:?}", f.format, f.color_space))
+        .collect();
+    let capabilities = &support.capabilities;
+    let max_images = match capabilities.max_image_count {
+        0 => "unlimited".to_string(),
+        count => count.to_string(),
+    };
+
+    println!("    Surface formats: {}", formats.join(", "));
+    println!("    Present modes: {:?}", support.present_modes);
+    println!(
+        "    Composite alpha: {:?}",
+        capabilities.supported_composite_alpha
+    );
+    println!(
+        "    Swapchain images: {} to {}",
+        capabilities.min_image_count, max_images
+    );
+}
+
+/// The device in use and the extensions enabled on it and its instance, for crash reports
+pub unsafe fn describe_context(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    device: &Device,
+) -> String {
+    let properties = instance.get_physical_device_properties(physical_device);
+    let sorted = |names: Vec<String>| {
+        let mut names = names;
+        names.sort();
+        names.join(", ")
+    };
+    format!(
+        "Device: {} ({:?})\nAPI {}, driver {:#x}, vendor {:#06x}, device {:#06x}\nInstance extensions: {}\nDevice extensions: {}\n",
+        properties.device_name,
+        properties.device_type,
+        format_version(properties.api_version),
+        properties.driver_version,
+        properties.vendor_id,
+        properties.device_id,
+        sorted(instance.extensions().iter().map(|e| e.to_string()).collect()),
+        sorted(device.extensions().iter().map(|e| e.to_string()).collect()),
+    )
+}
+
+/// `major.minor.patch` of a packed Vulkan version
+fn format_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        vk::version_major(version),
+        vk::version_minor(version),
+        vk::version_patch(version)
+    )
+}
@@ -7,7 +7,6 @@ use log::*;
 use vulkanalia::prelude::v1_0::*;
 use vulkanalia::window as vk_window;
 use vulkanalia::vk::ExtDebugUtilsExtensionInstanceCommands;
-use vulkanalia::Version;
 use winit::window::Window;
 
 use crate::config::WindowConfig;
@@ -17,15 +16,14 @@ const VALIDATION_ENABLED: bool = cfg!(debug_assertions);
 /// The name of the validation layers.
 const VALIDATION_LAYER: vk::ExtensionName =
     vk::ExtensionName::from_bytes(b"VK_LAYER_KHRONOS_validation");
-/// The Vulkan SDK version that started requiring the portability subset extension for macOS.
-const PORTABILITY_MACOS_VERSION: Version = Version::new(1, 3, 216);
 
 //================================================
 // Instance Creation
 //================================================
 
+/// Creates the instance, with the surface extensions `window` needs when given
 pub unsafe fn create_instance(
-    window: &Window,
+    window: Option<&Window>,
     entry: &Entry,
     config: &WindowConfig,
 ) -> Result<(Instance, vk::DebugUtilsMessengerEXT)> {
@@ -56,22 +54,46 @@ pub unsafe fn create_instance(
     };
 
     // Extensions
-    let mut extensions = vk_window::get_required_instance_extensions(window)
+    let available_extensions = entry
+        .enumerate_instance_extension_properties(None)?
+        .iter()
+        .map(|e| e.extension_name)
+        .collect::<HashSet<_>>();
+
+    let required_extensions = match window {
+        Some(window) => vk_window::get_required_instance_extensions(window),
+        None => &[],
+    };
+
+    let mut extensions = required_extensions
         .iter()
         .map(|e| e.as_ptr())
         .collect::<Vec<_>>();
 
-    // Required by Vulkan SDK on macOS since 1.3.216.
-    let flags = if cfg!(target_os = "macos") && entry.version()? >= PORTABILITY_MACOS_VERSION {
-        info!("Enabling extensions for macOS portability.");
+    // Lets devices report their memory budget (VK_EXT_memory_budget) on Vulkan 1.0,
+    // and is needed by the device-side portability subset extension.
+    if available_extensions.contains(&vk::KHR_GET_PHYSICAL_DEVICE_PROPERTIES2_EXTENSION.name) {
         extensions.push(
             vk::KHR_GET_PHYSICAL_DEVICE_PROPERTIES2_EXTENSION
                 .name
                 .as_ptr(),
         );
+    }
+
+    // Since loader 1.3.216 MoltenVK devices are only enumerated when the
+    // instance opts into portability enumeration, so enable it whenever the
+    // loader advertises it rather than guessing from the SDK version.
+    let portability = cfg!(target_os = "macos")
+        && available_extensions.contains(&vk::KHR_PORTABILITY_ENUMERATION_EXTENSION.name);
+
+    let flags = if portability {
+        info!("Enabling extensions for macOS portability.");
         extensions.push(vk::KHR_PORTABILITY_ENUMERATION_EXTENSION.name.as_ptr());
         vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR
     } else {
+        if cfg!(target_os = "macos") {
+            warn!("Portability enumeration is not available, MoltenVK devices may be hidden.");
+        }
         vk::InstanceCreateFlags::empty()
     };
 
@@ -1,8 +1,9 @@
 use super::device::QueueFamilyIndices;
 use anyhow::Result;
 use std::collections::HashSet;
-use vulkanalia::Version;
 use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::KhrGetPhysicalDeviceProperties2ExtensionInstanceCommands;
+use vulkanalia::Version;
 
 const DEVICE_EXTENSIONS: &[vk::ExtensionName] = &[vk::KHR_SWAPCHAIN_EXTENSION.name];
 const PORTABILITY_MACOS_VERSION: Version = Version::new(1, 3, 216);
@@ -36,22 +37,71 @@ pub unsafe fn create_logical_device(
         vec![]
     };
 
-    let mut extensions = DEVICE_EXTENSIONS
+    // Headless devices never present
+    let device_extensions = if surface.is_null() {
+        &[]
+    } else {
+        DEVICE_EXTENSIONS
+    };
+
+    let mut extensions = device_extensions
         .iter()
         .map(|n| n.as_ptr())
         .collect::<Vec<_>>();
 
+    let available = instance
+        .enumerate_device_extension_properties(physical_device, None)?
+        .iter()
+        .map(|e| e.extension_name)
+        .collect::<HashSet<_>>();
+
+    // Optional: lets the stats overlay show the driver's memory budget
+    let properties2 = instance
+        .extensions()
+        .contains(&vk::KHR_GET_PHYSICAL_DEVICE_PROPERTIES2_EXTENSION.name);
+    if properties2 && available.contains(&vk::EXT_MEMORY_BUDGET_EXTENSION.name) {
+        extensions.push(vk::EXT_MEMORY_BUDGET_EXTENSION.name.as_ptr());
+    }
+
+    // Optional: tells the renderer when frames reach the screen, for low-latency pacing
+    let mut present_id_features = vk::PhysicalDevicePresentIdFeaturesKHR::builder();
+    let mut present_wait_features = vk::PhysicalDevicePresentWaitFeaturesKHR::builder();
+    let present_wait = !surface.is_null()
+        && properties2
+        && available.contains(&vk::KHR_PRESENT_ID_EXTENSION.name)
+        && available.contains(&vk::KHR_PRESENT_WAIT_EXTENSION.name)
+        && {
+            let mut features = vk::PhysicalDeviceFeatures2::builder()
+                .push_next(&mut present_id_features)
+                .push_next(&mut present_wait_features);
+            instance.get_physical_device_features2_khr(physical_device, &mut features);
+            present_id_features.present_id == vk::TRUE
+                && present_wait_features.present_wait == vk::TRUE
+        };
+    if present_wait {
+        extensions.push(vk::KHR_PRESENT_ID_EXTENSION.name.as_ptr());
+        extensions.push(vk::KHR_PRESENT_WAIT_EXTENSION.name.as_ptr());
+    }
+    if !surface.is_null() && available.contains(&vk::GOOGLE_DISPLAY_TIMING_EXTENSION.name) {
+        extensions.push(vk::GOOGLE_DISPLAY_TIMING_EXTENSION.name.as_ptr());
+    }
+
     if cfg!(target_os = "macos") && entry.version()? >= PORTABILITY_MACOS_VERSION {
         extensions.push(vk::KHR_PORTABILITY_SUBSET_EXTENSION.name.as_ptr());
     }
 
     let features = vk::PhysicalDeviceFeatures::builder();
 
-    let info = vk::DeviceCreateInfo::builder()
+    let mut info = vk::DeviceCreateInfo::builder()
         .queue_create_infos(&queue_infos)
         .enabled_layer_names(&layers)
         .enabled_extension_names(&extensions)
         .enabled_features(&features);
+    if present_wait {
+        info = info
+            .push_next(&mut present_id_features)
+            .push_next(&mut present_wait_features);
+    }
 
     let device = instance.create_device(physical_device, &info, None)?;
 
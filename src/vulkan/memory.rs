@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use log::*;
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::{Handle, KhrGetPhysicalDeviceProperties2ExtensionInstanceCommands};
+
+/// Share of a heap's budget past which allocations and the stats poll log a warning
+pub const BUDGET_WARNING_FRACTION: f64 = 0.9;
+
+pub const MIB: f64 = 1024.0 * 1024.0;
+
+/// Size and heap of every live allocation made through [`allocate_memory`], by handle
+static ALLOCATIONS: Mutex<BTreeMap<u64, Allocation>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Copy)]
+struct Allocation {
+    heap: u32,
+    size: vk::DeviceSize,
+}
+
+/// How full one memory heap is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeapUsage {
+    /// Whether this is GPU memory rather than system memory visible to the GPU
+    pub device_local: bool,
+    /// Bytes allocated by this app
+    pub allocated: vk::DeviceSize,
+    /// Bytes in use by every process, if the driver reports a budget
+    pub used: Option<vk::DeviceSize>,
+    /// Bytes this process can use before allocations may fail: the driver's
+    /// budget with `VK_EXT_memory_budget`, otherwise the heap size
+    pub budget: vk::DeviceSize,
+}
+
+impl HeapUsage {
+    /// Bytes in use as far as we know, counting other processes when the driver tells us
+    pub fn used(&self) -> vk::DeviceSize {
+        self.used.unwrap_or(self.allocated)
+    }
+
+    /// Whether usage is past [`BUDGET_WARNING_FRACTION`] of the budget
+    pub fn is_nearly_full(&self) -> bool {
+        self.used() as f64 > self.budget as f64 * BUDGET_WARNING_FRACTION
+    }
+}
+
+/// Whether `device` was created with `VK_EXT_memory_budget`
+pub fn has_memory_budget(device: &Device) -> bool {
+    device
+        .extensions()
+        .contains(&vk::EXT_MEMORY_BUDGET_EXTENSION.name)
+}
+
+/// Usage of every memory heap of `physical_device`
+pub unsafe fn heap_usage(
+    instance: &Instance,
+    device: &Device,
+    physical_device: vk::PhysicalDevice,
+) -> Vec<HeapUsage> {
+    let mut budget = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+    let memory = if has_memory_budget(device) {
+        let mut properties = vk::PhysicalDeviceMemoryProperties2::builder().push_next(&mut budget);
+        instance.get_physical_device_memory_properties2_khr(physical_device, &mut properties);
+        properties.memory_properties
+    } else {
+        instance.get_physical_device_memory_properties(physical_device)
+    };
+
+    let mut allocated = vec![0; memory.memory_heap_count as usize];
+    for allocation in ALLOCATIONS.lock().unwrap().values() {
+        if let Some(bytes) = allocated.get_mut(allocation.heap as usize) {
+            *bytes += allocation.size;
+        }
+    }
+
+    let budget_known = has_memory_budget(device);
+    memory.memory_heaps[..memory.memory_heap_count as usize]
+        .iter()
+        .enumerate()
+        .map(|(i, heap)| HeapUsage {
+            device_local: heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+            allocated: allocated[i],
+            used: budget_known.then_some(budget.heap_usage[i]),
+            budget: if budget_known && budget.heap_budget[i] > 0 {
+                budget.heap_budget[i]
+            } else {
+                heap.size
+            },
+        })
+        .collect()
+}
+
+/// Allocates device memory and keeps track of it until [`free_memory`]
+///
+/// Warns when the allocation takes its heap close to the budget, and names
+/// the heap and how full it was when the allocation fails.
+pub unsafe fn allocate_memory(
+    instance: &Instance,
+    device: &Device,
+    physical_device: vk::PhysicalDevice,
+    info: &vk::MemoryAllocateInfo,
+) -> Result<vk::DeviceMemory> {
+    let properties = instance.get_physical_device_memory_properties(physical_device);
+    let heap = properties.memory_types[info.memory_type_index as usize].heap_index;
+    let usage = heap_usage(instance, device, physical_device)[heap as usize];
+    let size = info.allocation_size;
+
+    let projected = usage.used() + size;
+    if projected as f64 > usage.budget as f64 * BUDGET_WARNING_FRACTION {
+        warn!(
+            "Allocating {:.1} MiB brings GPU memory heap {} to {:.1} of {:.1} MiB.",
+            size as f64 / MIB,
+            heap,
+            projected as f64 / MIB,
+            usage.budget as f64 / MIB,
+        );
+    }
+
+    let memory = device.allocate_memory(info, None).with_context(|| {
+        format!(
+            "Failed to allocate {:.1} MiB of GPU memory (heap {} at {:.1} of {:.1} MiB)",
+            size as f64 / MIB,
+            heap,
+            usage.used() as f64 / MIB,
+            usage.budget as f64 / MIB,
+        )
+    })?;
+
+    ALLOCATIONS
+        .lock()
+        .unwrap()
+        .insert(memory.as_raw(), Allocation { heap, size });
+
+    Ok(memory)
+}
+
+/// Frees memory from [`allocate_memory`]
+pub unsafe fn free_memory(device: &Device, memory: vk::DeviceMemory) {
+    ALLOCATIONS.lock().unwrap().remove(&memory.as_raw());
+    device.free_memory(memory, None);
+}
@@ -1,13 +1,26 @@
+pub mod bloom;
 pub mod buffer;
 pub mod command;
 pub mod context;
 pub mod device;
+pub mod erase;
 pub mod helpers;
 pub mod image;
+pub mod info;
 pub mod instance;
 pub mod logical_device;
+pub mod memory;
+pub mod offscreen;
+pub mod owned;
+pub mod pacing;
 pub mod physical_device;
 pub mod pipeline;
 pub mod renderer;
+pub mod scaled;
 pub mod swapchain;
 pub mod sync;
+pub mod timer;
+pub mod ui;
+pub mod uniforms;
+pub mod upload;
+pub mod watchdog;
@@ -0,0 +1,331 @@
+use std::ops::Range;
+
+use anyhow::{bail, Result};
+use vulkanalia::prelude::v1_0::*;
+
+use super::buffer::create_buffer;
+use super::context::VulkanContext;
+use super::erase::EraseLayer;
+use super::helpers::{begin_single_time_commands, end_single_time_commands};
+use super::image::{copy_image_to_buffer, create_image, create_image_view};
+use super::owned::{guard, Destroy};
+use super::pipeline::{create_framebuffers, create_offscreen_render_pass, PipelineRegistry};
+use super::renderer::cmd_draw_lines;
+use super::uniforms::{FrameUniformBuffers, FrameUniforms, ParamBlock};
+use crate::camera::Camera;
+use crate::config::Config;
+use crate::types::{Color, StrokeStyle};
+
+/// Pixel format of offscreen targets, matching the sRGB swapchain format
+const OFFSCREEN_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+/// Renders strokes into an image instead of a swapchain and reads the pixels back
+pub struct OffscreenRenderer {
+    pub extent: vk::Extent2D,
+    /// Canvas color the image is cleared to
+    pub background: Color,
+    /// Cleared to transparent black instead of `background`, with strokes
+    /// blended like in a transparent window
+    pub transparent: bool,
+
+    // Color target
+    pub image: vk::Image,
+    pub image_memory: vk::DeviceMemory,
+    pub image_view: vk::ImageView,
+
+    // Pipeline
+    pub render_pass: vk::RenderPass,
+    /// One stroke pipeline per brush, like the window's, and no background
+    pub pipelines: PipelineRegistry,
+    /// Where pixel eraser strokes punch holes into the strokes
+    erase: EraseLayer,
+    /// What the shaders read as the frame, which is never animated here
+    frame_uniforms: FrameUniformBuffers,
+    pub framebuffer: vk::Framebuffer,
+
+    // Host-visible copy of the color target
+    pub readback_buffer: vk::Buffer,
+    pub readback_buffer_memory: vk::DeviceMemory,
+
+    // Kept to destroy everything above on drop
+    device: Device,
+}
+
+impl OffscreenRenderer {
+    /// Creates an offscreen target of `width` x `height` pixels
+    ///
+    /// A `transparent` target reads back premultiplied colors, see
+    /// [`crate::export::unpremultiply`].
+    pub unsafe fn create(
+        context: &VulkanContext,
+        config: &Config,
+        width: u32,
+        height: u32,
+        transparent: bool,
+    ) -> Result<Self> {
+        let extent = vk::Extent2D { width, height };
+        let device = &context.device;
+
+        let max_size = context
+            .instance
+            .get_physical_device_properties(context.physical_device)
+            .limits
+            .max_image_dimension_2d;
+        if width.max(height) > max_size {
+            bail!(
+                "{}x{} exceeds the device's {} pixel limit",
+                width,
+                height,
+                max_size
+            );
+        }
+
+        let image = guard(
+            device,
+            create_image(
+                &context.instance,
+                device,
+                context.physical_device,
+                width,
+                height,
+                1,
+                vk::SampleCountFlags::_1,
+                OFFSCREEN_FORMAT,
+                vk::ImageTiling::OPTIMAL,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?,
+        );
+
+        let image_view = guard(
+            device,
+            create_image_view(
+                device,
+                image.0,
+                OFFSCREEN_FORMAT,
+                vk::ImageAspectFlags::COLOR,
+            )?,
+        );
+
+        let render_pass = guard(
+            device,
+            create_offscreen_render_pass(device, OFFSCREEN_FORMAT)?,
+        );
+
+        let uniforms = FrameUniformBuffers::create(context, 1, config.shaders.param_block_count())?;
+        let mut frame_uniforms = guard(device, uniforms);
+        frame_uniforms.write(0, &FrameUniforms::still(extent));
+
+        let pipelines = guard(
+            device,
+            PipelineRegistry::create(
+                device,
+                context.pipeline_cache,
+                extent,
+                *render_pass,
+                frame_uniforms.layout,
+                &config.shaders,
+                None,
+                transparent,
+                context.vertex_format,
+                OFFSCREEN_FORMAT,
+            )?,
+        );
+
+        let erase = guard(
+            device,
+            EraseLayer::create(
+                context,
+                frame_uniforms.layout,
+                &config.shaders,
+                *render_pass,
+                OFFSCREEN_FORMAT,
+                extent,
+                1,
+            )?,
+        );
+
+        let framebuffer = guard(
+            device,
+            create_framebuffers(device, &[*image_view], extent, *render_pass)?[0],
+        );
+
+        let readback_buffer = guard(
+            device,
+            create_buffer(
+                &context.instance,
+                device,
+                context.physical_device,
+                width as u64 * height as u64 * 4,
+                vk::BufferUsageFlags::TRANSFER_DST,
+                vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+            )?,
+        );
+
+        let (image, image_memory) = image.release();
+        let (readback_buffer, readback_buffer_memory) = readback_buffer.release();
+        let mut renderer = Self {
+            extent,
+            background: config.theme.active().background,
+            transparent,
+            image,
+            image_memory,
+            image_view: image_view.release(),
+            render_pass: render_pass.release(),
+            pipelines: pipelines.release(),
+            erase: erase.release(),
+            frame_uniforms: frame_uniforms.release(),
+            framebuffer: framebuffer.release(),
+            readback_buffer,
+            readback_buffer_memory,
+            device: device.clone(),
+        };
+        renderer.set_shader_params(config);
+        Ok(renderer)
+    }
+
+    /// Hands the brushes `config`'s `[shader_params]`, e.g. after a reload
+    pub unsafe fn set_shader_params(&mut self, config: &Config) {
+        let params = config.shader_params.blocks(&config.shaders);
+        for (block, params) in params.enumerate() {
+            self.frame_uniforms
+                .write_params(0, block, &ParamBlock::new(params));
+        }
+    }
+
+    /// Draws `line_count` lines from `line_buffer` and returns the image as tightly packed RGBA8 rows
+    pub unsafe fn render(
+        &self,
+        context: &VulkanContext,
+        rect_buffer: vk::Buffer,
+        line_buffer: vk::Buffer,
+        index_buffer: vk::Buffer,
+        line_count: u32,
+    ) -> Result<Vec<u8>> {
+        self.render_view(
+            context,
+            rect_buffer,
+            index_buffer,
+            &Camera::default().with_viewport(self.extent.width, self.extent.height),
+            &StrokeStyle::default(),
+            &[(line_buffer, 0..line_count)],
+        )
+    }
+
+    /// Draws `batches` as seen through `camera`, like a frame of the window
+    /// without the UI, and returns the image as tightly packed RGBA8 rows
+    pub unsafe fn render_view(
+        &self,
+        context: &VulkanContext,
+        rect_buffer: vk::Buffer,
+        index_buffer: vk::Buffer,
+        camera: &Camera,
+        style: &StrokeStyle,
+        batches: &[(vk::Buffer, Range<u32>)],
+    ) -> Result<Vec<u8>> {
+        let device = &context.device;
+        let command_buffer = begin_single_time_commands(device, context.command_pool)?;
+
+        let render_area = vk::Rect2D::builder()
+            .offset(vk::Offset2D::default())
+            .extent(self.extent)
+            .build();
+
+        let background = if self.transparent {
+            [0.0; 4]
+        } else {
+            self.background.to_linear()
+        };
+        let color_clear_value = vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: background,
+            },
+        };
+
+        // Drawn whether or not there are pixel erasers, renders here are one-offs
+        let frame = self.frame_uniforms.binding(0);
+        self.erase.cmd_mask(
+            device,
+            command_buffer,
+            0,
+            frame,
+            camera,
+            style,
+            rect_buffer,
+            index_buffer,
+            batches,
+        );
+
+        let clear_values = &[color_clear_value];
+        let info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass)
+            .framebuffer(self.framebuffer)
+            .render_area(render_area)
+            .clear_values(clear_values);
+
+        device.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+
+        cmd_draw_lines(
+            device,
+            command_buffer,
+            &self.pipelines,
+            frame,
+            render_area,
+            camera,
+            style,
+            rect_buffer,
+            index_buffer,
+            batches,
+        );
+        self.erase
+            .cmd_composite(device, command_buffer, 0, render_area, background);
+
+        device.cmd_end_render_pass(command_buffer);
+        end_single_time_commands(
+            device,
+            context.graphics_queue,
+            context.command_pool,
+            command_buffer,
+        )?;
+
+        copy_image_to_buffer(
+            device,
+            context.graphics_queue,
+            context.command_pool,
+            self.image,
+            self.readback_buffer,
+            self.extent.width,
+            self.extent.height,
+        )?;
+
+        let size = self.extent.width as usize * self.extent.height as usize * 4;
+        let memory = device.map_memory(
+            self.readback_buffer_memory,
+            0,
+            size as u64,
+            vk::MemoryMapFlags::empty(),
+        )?;
+
+        let mut pixels = vec![0u8; size];
+        std::ptr::copy_nonoverlapping(memory.cast::<u8>(), pixels.as_mut_ptr(), size);
+        device.unmap_memory(self.readback_buffer_memory);
+
+        Ok(pixels)
+    }
+}
+
+impl Drop for OffscreenRenderer {
+    fn drop(&mut self) {
+        let device = &self.device;
+        unsafe {
+            (self.readback_buffer, self.readback_buffer_memory).destroy(device);
+            self.framebuffer.destroy(device);
+            self.erase.destroy(device);
+            self.pipelines.destroy(device);
+            self.frame_uniforms.destroy(device);
+            self.render_pass.destroy(device);
+            self.image_view.destroy(device);
+            (self.image, self.image_memory).destroy(device);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::KhrSwapchainExtensionDeviceCommands;
+
+use super::memory::free_memory;
+
+/// Something created from a `Device` that must be destroyed before the device is
+///
+/// Destroying a null handle does nothing, so owners can null out handles
+/// they already destroyed and still destroy everything again safely.
+pub trait Destroy {
+    unsafe fn destroy(&mut self, device: &Device);
+}
+
+macro_rules! impl_destroy {
+    ($($handle:ty => $method:ident),* $(,)?) => {
+        $(
+            impl Destroy for $handle {
+                unsafe fn destroy(&mut self, device: &Device) {
+                    device.$method(*self, None);
+                }
+            }
+        )*
+    };
+}
+
+impl_destroy!(
+    vk::Buffer => destroy_buffer,
+    vk::Image => destroy_image,
+    vk::ImageView => destroy_image_view,
+    vk::Sampler => destroy_sampler,
+    vk::ShaderModule => destroy_shader_module,
+    vk::RenderPass => destroy_render_pass,
+    vk::Framebuffer => destroy_framebuffer,
+    vk::Pipeline => destroy_pipeline,
+    vk::PipelineLayout => destroy_pipeline_layout,
+    vk::DescriptorPool => destroy_descriptor_pool,
+    vk::DescriptorSetLayout => destroy_descriptor_set_layout,
+    vk::CommandPool => destroy_command_pool,
+    vk::QueryPool => destroy_query_pool,
+    vk::Semaphore => destroy_semaphore,
+    vk::Fence => destroy_fence,
+    vk::SwapchainKHR => destroy_swapchain_khr,
+);
+
+impl Destroy for vk::DeviceMemory {
+    unsafe fn destroy(&mut self, device: &Device) {
+        free_memory(device, *self);
+    }
+}
+
+/// An object and what it depends on, like a buffer and its memory, destroyed in that order
+impl<A: Destroy, B: Destroy> Destroy for (A, B) {
+    unsafe fn destroy(&mut self, device: &Device) {
+        self.0.destroy(device);
+        self.1.destroy(device);
+    }
+}
+
+impl<T: Destroy> Destroy for Vec<T> {
+    unsafe fn destroy(&mut self, device: &Device) {
+        self.iter_mut().for_each(|o| o.destroy(device));
+    }
+}
+
+impl<T: Destroy> Destroy for Option<T> {
+    unsafe fn destroy(&mut self, device: &Device) {
+        if let Some(object) = self {
+            object.destroy(device);
+        }
+    }
+}
+
+/// Destroys what it holds when dropped, unless it was [`Guard::release`]d
+///
+/// Constructors wrap each object they create in a guard and release them
+/// into the finished owner at the end, so returning early with `?` or
+/// panicking halfway destroys whatever was already created.
+pub struct Guard<'a, T: Destroy> {
+    device: &'a Device,
+    object: ManuallyDrop<T>,
+}
+
+pub fn guard<T: Destroy>(device: &Device, object: T) -> Guard<'_, T> {
+    Guard {
+        device,
+        object: ManuallyDrop::new(object),
+    }
+}
+
+impl<T: Destroy> Guard<'_, T> {
+    /// Hands the object over to a new owner
+    pub fn release(mut self) -> T {
+        let object = unsafe { ManuallyDrop::take(&mut self.object) };
+        std::mem::forget(self);
+        object
+    }
+}
+
+impl<T: Destroy> Deref for Guard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.object
+    }
+}
+
+impl<T: Destroy> DerefMut for Guard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.object
+    }
+}
+
+impl<T: Destroy> Drop for Guard<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            self.object.destroy(self.device);
+            ManuallyDrop::drop(&mut self.object);
+        }
+    }
+}
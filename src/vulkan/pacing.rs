@@ -0,0 +1,206 @@
+use log::*;
+use std::time::{Duration, Instant};
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::{
+    GoogleDisplayTimingExtensionDeviceCommands, KhrPresentWaitExtensionDeviceCommands,
+};
+
+/// Time kept free between a frame's expected end and the vblank it aims for
+const SAFETY_MARGIN: Duration = Duration::from_millis(2);
+
+/// Share of the longest recent frame time the estimate keeps per frame, so
+/// one slow frame raises it right away but only fades out slowly
+const RENDER_TIME_DECAY: f32 = 0.95;
+
+/// How the device reports when presented frames reached the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentTiming {
+    /// `VK_KHR_present_wait`: waiting on a present id returns once it is shown
+    PresentWait,
+    /// `VK_GOOGLE_display_timing`: past presents carry the time they were shown
+    DisplayTiming,
+    /// Neither, so frames can't be paced against the display
+    Unavailable,
+}
+
+impl PresentTiming {
+    /// The best source `device` was created with
+    pub fn of(device: &Device) -> Self {
+        let extensions = device.extensions();
+        if extensions.contains(&vk::KHR_PRESENT_WAIT_EXTENSION.name) {
+            PresentTiming::PresentWait
+        } else if extensions.contains(&vk::GOOGLE_DISPLAY_TIMING_EXTENSION.name) {
+            PresentTiming::DisplayTiming
+        } else {
+            PresentTiming::Unavailable
+        }
+    }
+}
+
+/// Starts frames as late before a vblank as they can, to keep pen-to-pixel latency low
+///
+/// Every present is tagged with an id. Before the next frame, [`FramePacer::frame_start`]
+/// learns when the last one reached the screen, which puts the vblanks on a
+/// grid of refresh periods, and picks the latest start that still leaves
+/// the recent frame time (CPU and GPU) plus [`SAFETY_MARGIN`] before the
+/// next one. Input arriving meanwhile then makes it into that frame.
+pub struct FramePacer {
+    pub timing: PresentTiming,
+    /// Id of the latest present, counting up from 1 across swapchains
+    present_id: u64,
+    /// Latest present id known to be shown or given up on
+    settled_id: u64,
+    /// When the latest frame known to be shown reached the screen
+    last_vblank: Option<Instant>,
+    /// Refresh period the display reported, with display timing
+    refresh: Option<Duration>,
+    /// Recent longest time from starting a frame until the GPU finished it
+    render_time: Duration,
+}
+
+impl FramePacer {
+    pub fn new(device: &Device) -> Self {
+        let timing = PresentTiming::of(device);
+        match timing {
+            PresentTiming::PresentWait => info!("Pacing frames with VK_KHR_present_wait."),
+            PresentTiming::DisplayTiming => info!("Pacing frames with VK_GOOGLE_display_timing."),
+            PresentTiming::Unavailable => {
+                info!("No present timing extension, low-latency pacing is unavailable.")
+            }
+        }
+
+        Self {
+            timing,
+            present_id: 0,
+            settled_id: 0,
+            last_vblank: None,
+            refresh: None,
+            render_time: Duration::ZERO,
+        }
+    }
+
+    /// Id of the latest present queued, 0 before the first
+    pub fn present_id(&self) -> u64 {
+        self.present_id
+    }
+
+    /// Id for the present about to be queued
+    pub fn next_present_id(&mut self) -> u64 {
+        self.present_id += 1;
+        self.present_id
+    }
+
+    /// Forgets presents to a swapchain that is being replaced, which will never be waited for
+    pub fn swapchain_replaced(&mut self) {
+        self.settled_id = self.present_id;
+        self.refresh = None;
+    }
+
+    /// Notes how long the latest frame took, CPU and GPU together
+    pub fn frame_rendered(&mut self, time: Duration) {
+        self.render_time = time.max(self.render_time.mul_f32(RENDER_TIME_DECAY));
+    }
+
+    /// Latest time, no earlier than `earliest`, to start the next frame so it
+    /// is shown at the coming vblank
+    ///
+    /// `refresh` is the monitor's refresh period, used unless the display
+    /// reports its own. With present wait this may block for up to a refresh
+    /// period until the previous frame is shown.
+    pub unsafe fn frame_start(
+        &mut self,
+        device: &Device,
+        swapchain: vk::SwapchainKHR,
+        earliest: Instant,
+        refresh: Duration,
+    ) -> Instant {
+        self.update_vblank(device, swapchain, refresh);
+        let Some(vblank) = self.last_vblank else {
+            return earliest;
+        };
+        let refresh = self.refresh.unwrap_or(refresh);
+        if refresh.is_zero() {
+            return earliest;
+        }
+
+        // The first vblank the frame can still make if started at `earliest`
+        let lead = self.render_time + SAFETY_MARGIN;
+        let ready = earliest + lead;
+        let periods = if ready > vblank {
+            ((ready - vblank).as_secs_f64() / refresh.as_secs_f64()).ceil() as u32
+        } else {
+            0
+        };
+        let target = vblank + refresh * periods;
+
+        target.checked_sub(lead).unwrap_or(earliest).max(earliest)
+    }
+
+    /// Learns when the latest settled present reached the screen
+    unsafe fn update_vblank(
+        &mut self,
+        device: &Device,
+        swapchain: vk::SwapchainKHR,
+        refresh: Duration,
+    ) {
+        match self.timing {
+            PresentTiming::PresentWait => {
+                if self.settled_id == self.present_id {
+                    return;
+                }
+                let id = self.present_id;
+                self.settled_id = id;
+                let timeout = refresh.as_nanos().min(u64::MAX as u128) as u64;
+                match device.wait_for_present_khr(swapchain, id, timeout) {
+                    Ok(vk::SuccessCode::SUCCESS) => self.last_vblank = Some(Instant::now()),
+                    // Not shown within a refresh period, e.g. while the window is hidden
+                    Ok(_) => self.last_vblank = None,
+                    Err(e) => {
+                        warn!(
+                            "Waiting for a present failed ({}), no longer pacing frames.",
+                            e
+                        );
+                        self.timing = PresentTiming::Unavailable;
+                        self.last_vblank = None;
+                    }
+                }
+            }
+            PresentTiming::DisplayTiming => {
+                if self.refresh.is_none() {
+                    self.refresh = device
+                        .get_refresh_cycle_duration_google(swapchain)
+                        .ok()
+                        .map(|r| Duration::from_nanos(r.refresh_duration));
+                }
+                let timings = device
+                    .get_past_presentation_timing_google(swapchain)
+                    .unwrap_or_default();
+                let latest = timings.iter().max_by_key(|t| t.present_id);
+                if let (Some(latest), Some(now)) = (latest, monotonic_nanos()) {
+                    let ago = Duration::from_nanos(now.saturating_sub(latest.actual_present_time));
+                    self.last_vblank = Instant::now().checked_sub(ago);
+                }
+            }
+            PresentTiming::Unavailable => {}
+        }
+    }
+}
+
+/// `CLOCK_MONOTONIC`, the clock display timing reports in, in nanoseconds
+#[cfg(unix)]
+fn monotonic_nanos() -> Option<u64> {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) } != 0 {
+        return None;
+    }
+    Some(now.tv_sec as u64 * 1_000_000_000 + now.tv_nsec as u64)
+}
+
+/// Display timing is only found on platforms with `CLOCK_MONOTONIC`
+#[cfg(not(unix))]
+fn monotonic_nanos() -> Option<u64> {
+    None
+}
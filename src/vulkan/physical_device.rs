@@ -9,8 +9,34 @@ const DEVICE_EXTENSIONS: &[vk::ExtensionName] = &[vk::KHR_SWAPCHAIN_EXTENSION.na
 pub unsafe fn pick_physical_device(
     instance: &Instance,
     surface: vk::SurfaceKHR,
+    gpu: Option<usize>,
 ) -> Result<vk::PhysicalDevice> {
-    for physical_device in instance.enumerate_physical_devices()? {
+    let physical_devices = instance.enumerate_physical_devices()?;
+
+    // Explicitly requested GPU
+    if let Some(index) = gpu {
+        let physical_device = *physical_devices.get(index).ok_or_else(|| {
+            anyhow!(
+                "GPU index {} out of range ({} devices available).",
+                index,
+                physical_devices.len()
+            )
+        })?;
+        let properties = instance.get_physical_device_properties(physical_device);
+
+        check_physical_device(instance, surface, physical_device).map_err(|error| {
+            anyhow!(
+                "Requested physical device (`{}`) is not suitable: {}",
+                properties.device_name,
+                error
+            )
+        })?;
+
+        info!("Selected physical device (`{}`).", properties.device_name);
+        return Ok(physical_device);
+    }
+
+    for physical_device in physical_devices {
         let properties = instance.get_physical_device_properties(physical_device);
 
         if let Err(error) = check_physical_device(instance, surface, physical_device) {
@@ -27,12 +53,18 @@ pub unsafe fn pick_physical_device(
     Err(anyhow!("Failed to find suitable physical device."))
 }
 
-unsafe fn check_physical_device(
+pub unsafe fn check_physical_device(
     instance: &Instance,
     surface: vk::SurfaceKHR,
     physical_device: vk::PhysicalDevice,
 ) -> Result<()> {
     QueueFamilyIndices::get(instance, surface, physical_device)?;
+
+    // Headless rendering needs no swapchain
+    if surface.is_null() {
+        return Ok(());
+    }
+
     check_physical_device_extensions(instance, physical_device)?;
 
     let support = SwapchainSupport::get(instance, surface, physical_device)?;
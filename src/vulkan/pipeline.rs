@@ -1,22 +1,152 @@
-use crate::config::ShaderConfig;
-use crate::types::{Line, Vec2, RECT};
-use anyhow::Result;
+use super::bloom::HDR_FORMAT;
+use super::owned::{guard, Destroy};
+use super::renderer::{BackgroundConstants, StrokeConstants};
+use super::swapchain::is_srgb_format;
+use crate::config::{ShaderConfig, VertexFormat};
+use crate::paths;
+use crate::types::{Line, Vec2};
+use anyhow::{Context, Result};
+use log::*;
+use std::fs;
+use std::path::Path;
 use vulkanalia::bytecode::Bytecode;
 use vulkanalia::prelude::v1_0::*;
 
+/// Creates the stroke render pass.
+///
+/// With `preserve_contents` the previously presented image is loaded instead
+/// of cleared, so only a damaged region has to be redrawn. Both variants are
+/// render pass compatible and can share framebuffers and pipelines. The image
+/// is left in `layout` (`PRESENT_SRC_KHR` for swapchain images), which is
+/// also the layout a preserved image is loaded from.
 pub unsafe fn create_render_pass(
     device: &Device,
     swapchain_format: vk::Format,
+    preserve_contents: bool,
+    layout: vk::ImageLayout,
 ) -> Result<vk::RenderPass> {
+    let (load_op, initial_layout) = if preserve_contents {
+        (vk::AttachmentLoadOp::LOAD, layout)
+    } else {
+        (vk::AttachmentLoadOp::CLEAR, vk::ImageLayout::UNDEFINED)
+    };
+
     let color_attachment = vk::AttachmentDescription::builder()
         .format(swapchain_format)
         .samples(vk::SampleCountFlags::_1)
+        .load_op(load_op)
+        .store_op(vk::AttachmentStoreOp::STORE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(initial_layout)
+        .final_layout(layout);
+
+    let color_attachment_ref = vk::AttachmentReference::builder()
+        .attachment(0)
+        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+
+    let color_attachments = &[color_attachment_ref];
+    let subpass = vk::SubpassDescription::builder()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(color_attachments);
+
+    let dependency = vk::SubpassDependency::builder()
+        .src_subpass(vk::SUBPASS_EXTERNAL)
+        .dst_subpass(0)
+        .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+        .src_access_mask(vk::AccessFlags::empty())
+        .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+        .dst_access_mask(
+            vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        );
+
+    let attachments = &[color_attachment];
+    let subpasses = &[subpass];
+    let dependencies = &[dependency];
+    let info = vk::RenderPassCreateInfo::builder()
+        .attachments(attachments)
+        .subpasses(subpasses)
+        .dependencies(dependencies);
+
+    Ok(device.create_render_pass(&info, None)?)
+}
+
+/// Creates a render pass drawing into an image a later pass samples, as the
+/// bloom passes do
+///
+/// Its old contents are discarded, and it ends up in `SHADER_READ_ONLY_OPTIMAL`.
+pub unsafe fn create_sampled_render_pass(
+    device: &Device,
+    format: vk::Format,
+    load_op: vk::AttachmentLoadOp,
+) -> Result<vk::RenderPass> {
+    let color_attachment = vk::AttachmentDescription::builder()
+        .format(format)
+        .samples(vk::SampleCountFlags::_1)
+        .load_op(load_op)
+        .store_op(vk::AttachmentStoreOp::STORE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+    let color_attachment_ref = vk::AttachmentReference::builder()
+        .attachment(0)
+        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+
+    let color_attachments = &[color_attachment_ref];
+    let subpass = vk::SubpassDescription::builder()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(color_attachments);
+
+    // Wait for the last pass sampling the image before overwriting it
+    let dependency = vk::SubpassDependency::builder()
+        .src_subpass(vk::SUBPASS_EXTERNAL)
+        .dst_subpass(0)
+        .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+        .src_access_mask(vk::AccessFlags::empty())
+        .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+        .dst_access_mask(
+            vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        );
+
+    // Make the rendered pixels visible to the passes sampling them
+    let sampled_dependency = vk::SubpassDependency::builder()
+        .src_subpass(0)
+        .dst_subpass(vk::SUBPASS_EXTERNAL)
+        .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ);
+
+    let attachments = &[color_attachment];
+    let subpasses = &[subpass];
+    let dependencies = &[dependency, sampled_dependency];
+    let info = vk::RenderPassCreateInfo::builder()
+        .attachments(attachments)
+        .subpasses(subpasses)
+        .dependencies(dependencies);
+
+    Ok(device.create_render_pass(&info, None)?)
+}
+
+/// Creates the render pass for offscreen rendering.
+///
+/// The color target ends up in `TRANSFER_SRC_OPTIMAL` so its pixels can be
+/// copied into a readback buffer right after the pass.
+pub unsafe fn create_offscreen_render_pass(
+    device: &Device,
+    format: vk::Format,
+) -> Result<vk::RenderPass> {
+    let color_attachment = vk::AttachmentDescription::builder()
+        .format(format)
+        .samples(vk::SampleCountFlags::_1)
         .load_op(vk::AttachmentLoadOp::CLEAR)
         .store_op(vk::AttachmentStoreOp::STORE)
         .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
         .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
         .initial_layout(vk::ImageLayout::UNDEFINED)
-        .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+        .final_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
 
     let color_attachment_ref = vk::AttachmentReference::builder()
         .attachment(0)
@@ -35,9 +165,18 @@ pub unsafe fn create_render_pass(
         .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
         .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE);
 
+    // Make the rendered pixels visible to the readback copy
+    let readback_dependency = vk::SubpassDependency::builder()
+        .src_subpass(0)
+        .dst_subpass(vk::SUBPASS_EXTERNAL)
+        .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .dst_stage_mask(vk::PipelineStageFlags::TRANSFER)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_READ);
+
     let attachments = &[color_attachment];
     let subpasses = &[subpass];
-    let dependencies = &[dependency];
+    let dependencies = &[dependency, readback_dependency];
     let info = vk::RenderPassCreateInfo::builder()
         .attachments(attachments)
         .subpasses(subpasses)
@@ -46,27 +185,206 @@ pub unsafe fn create_render_pass(
     Ok(device.create_render_pass(&info, None)?)
 }
 
-pub unsafe fn create_pipeline(
+/// The kinds of primitive frames are drawn from, each with its own vertex
+/// input, shaders and draw call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primitive {
+    /// Instanced quads, one per stroke segment ([`Line`]), with a pipeline per brush
+    Lines,
+    /// A triangle covering the target, drawing `[background] shader`
+    Background,
+}
+
+/// The pipelines drawing each [`Primitive`] into one render pass, created
+/// together whenever the render pass is
+///
+/// The UI is drawn by [`UiRenderer`](super::ui::UiRenderer), which keeps
+/// its pipeline next to the textures it samples.
+#[derive(Debug, Default)]
+pub struct PipelineRegistry {
+    lines: Vec<(vk::Pipeline, vk::PipelineLayout)>,
+    background: Option<(vk::Pipeline, vk::PipelineLayout)>,
+    /// `[shader_params]` block of the background's shader
+    background_params: u32,
+}
+
+impl PipelineRegistry {
+    /// Creates the pipelines for `render_pass` drawing `color_format` at
+    /// `extent`, with the frame uniforms of `frame_layout` at set 0
+    ///
+    /// `background` names the `[shaders.backgrounds]` entry to draw, if any.
+    pub unsafe fn create(
+        device: &Device,
+        pipeline_cache: vk::PipelineCache,
+        extent: vk::Extent2D,
+        render_pass: vk::RenderPass,
+        frame_layout: vk::DescriptorSetLayout,
+        shader_config: &ShaderConfig,
+        background: Option<&str>,
+        transparent: bool,
+        vertex_format: VertexFormat,
+        color_format: vk::Format,
+    ) -> Result<Self> {
+        let lines = guard(
+            device,
+            create_stroke_pipelines(
+                device,
+                pipeline_cache,
+                extent,
+                render_pass,
+                frame_layout,
+                shader_config,
+                transparent,
+                vertex_format,
+                color_format,
+            )?,
+        );
+        let background_pipeline = create_background_pipeline(
+            device,
+            pipeline_cache,
+            render_pass,
+            frame_layout,
+            shader_config,
+            background,
+            color_format,
+        )?;
+        let background_params =
+            background.and_then(|name| shader_config.background_param_block(name));
+        Ok(Self {
+            lines: lines.release(),
+            background: background_pipeline,
+            background_params: background_params.unwrap_or(0),
+        })
+    }
+
+    /// The pipelines drawing `primitive`, empty when there are none: one per
+    /// brush for [`Primitive::Lines`], at most one for [`Primitive::Background`]
+    pub fn get(&self, primitive: Primitive) -> &[(vk::Pipeline, vk::PipelineLayout)] {
+        match primitive {
+            Primitive::Lines => &self.lines,
+            Primitive::Background => self.background.as_slice(),
+        }
+    }
+
+    /// The `[shader_params]` block the shader of `primitive`'s pipeline `index` reads
+    pub fn param_block(&self, primitive: Primitive, index: usize) -> u32 {
+        match primitive {
+            Primitive::Lines => index as u32,
+            Primitive::Background => self.background_params,
+        }
+    }
+}
+
+impl Destroy for PipelineRegistry {
+    unsafe fn destroy(&mut self, device: &Device) {
+        self.background.destroy(device);
+        self.lines.destroy(device);
+    }
+}
+
+/// Creates the stroke pipelines, one per [`ShaderConfig::stroke_fragments`]
+///
+/// They share the vertex shader and differ only in the fragment shader, so a
+/// segment's brush index picks the pipeline that draws it. Set 0 is the
+/// [`FrameUniforms`](super::uniforms::FrameUniforms) of `frame_layout`.
+unsafe fn create_stroke_pipelines(
     device: &Device,
+    pipeline_cache: vk::PipelineCache,
     swapchain_extent: vk::Extent2D,
     render_pass: vk::RenderPass,
+    frame_layout: vk::DescriptorSetLayout,
     shader_config: &ShaderConfig,
+    transparent: bool,
+    vertex_format: VertexFormat,
+    color_format: vk::Format,
+) -> Result<Vec<(vk::Pipeline, vk::PipelineLayout)>> {
+    let mut pipelines = guard(device, Vec::new());
+    for fragment in shader_config.stroke_fragments() {
+        let pipeline = create_pipeline(
+            device,
+            pipeline_cache,
+            swapchain_extent,
+            render_pass,
+            frame_layout,
+            &shader_config.vertex,
+            fragment,
+            transparent,
+            vertex_format,
+            color_format,
+        )
+        .with_context(|| format!("Failed to create the pipeline for `{}`", fragment.display()))?;
+        pipelines.push(pipeline);
+    }
+    Ok(pipelines.release())
+}
+
+/// Creates the pipeline drawing pixel eraser strokes into the erase layer's
+/// mask of `mask_format`, [`ShaderConfig::erase_mask`] after the stroke
+/// vertex shader
+///
+/// Its blending keeps the highest coverage, like the stroke pipelines do
+/// outside transparent windows.
+pub unsafe fn create_erase_mask_pipeline(
+    device: &Device,
+    pipeline_cache: vk::PipelineCache,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    frame_layout: vk::DescriptorSetLayout,
+    shader_config: &ShaderConfig,
+    vertex_format: VertexFormat,
+    mask_format: vk::Format,
 ) -> Result<(vk::Pipeline, vk::PipelineLayout)> {
-    let vert = std::fs::read(&shader_config.vertex)?;
-    let frag = std::fs::read(&shader_config.fragment)?;
+    let fragment = &shader_config.erase_mask;
+    create_pipeline(
+        device,
+        pipeline_cache,
+        extent,
+        render_pass,
+        frame_layout,
+        &shader_config.vertex,
+        fragment,
+        false,
+        vertex_format,
+        mask_format,
+    )
+    .with_context(|| format!("Failed to create the pipeline for `{}`", fragment.display()))
+}
 
-    let vert_shader_module = create_shader_module(device, &vert)?;
-    let frag_shader_module = create_shader_module(device, &frag)?;
+unsafe fn create_pipeline(
+    device: &Device,
+    pipeline_cache: vk::PipelineCache,
+    swapchain_extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    frame_layout: vk::DescriptorSetLayout,
+    vertex: &Path,
+    fragment: &Path,
+    transparent: bool,
+    vertex_format: VertexFormat,
+    color_format: vk::Format,
+) -> Result<(vk::Pipeline, vk::PipelineLayout)> {
+    let vert = std::fs::read(vertex)?;
+    let frag = std::fs::read(fragment)?;
+
+    // Only needed until the pipeline is built, so the guards destroy them on every return
+    let vert_shader_module = guard(device, create_shader_module(device, &vert)?);
+    let frag_shader_module = guard(device, create_shader_module(device, &frag)?);
 
     let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
         .stage(vk::ShaderStageFlags::VERTEX)
-        .module(vert_shader_module)
+        .module(*vert_shader_module)
         .name(b"main\0");
 
+    let encode_srgb = encode_srgb_constant(color_format);
+    let map_entries = &[ENCODE_SRGB_ENTRY];
+    let specialization = vk::SpecializationInfo::builder()
+        .map_entries(map_entries)
+        .data(&encode_srgb);
+
     let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
         .stage(vk::ShaderStageFlags::FRAGMENT)
-        .module(frag_shader_module)
-        .name(b"main\0");
+        .module(*frag_shader_module)
+        .name(b"main\0")
+        .specialization_info(&specialization);
 
     let rect_binding = vk::VertexInputBindingDescription::builder()
         .binding(0)
@@ -74,11 +392,7 @@ pub unsafe fn create_pipeline(
         .input_rate(vk::VertexInputRate::VERTEX)
         .build();
 
-    let line_binding = vk::VertexInputBindingDescription::builder()
-        .binding(1)
-        .stride(size_of::<Line>() as u32)
-        .input_rate(vk::VertexInputRate::INSTANCE)
-        .build();
+    let line_binding = Line::binding_description(vertex_format);
 
     let rect_vertex_attribute_description = vk::VertexInputAttributeDescription::builder()
         .binding(0)
@@ -87,30 +401,14 @@ pub unsafe fn create_pipeline(
         .offset(0)
         .build();
 
-    let direction_attribute_description = vk::VertexInputAttributeDescription::builder()
-        .binding(1)
-        .location(1)
-        .format(vk::Format::R32G32_SFLOAT)
-        .offset(8)
-        .build();
-
-    let position_attribute_description = vk::VertexInputAttributeDescription::builder()
-        .binding(1)
-        .location(2)
-        .format(vk::Format::R32G32_SFLOAT)
-        .offset(0)
-        .build();
-
     let binding_descriptions = &[rect_binding, line_binding];
-    let attribute_descriptions = &[
-        rect_vertex_attribute_description,
-        position_attribute_description,
-        direction_attribute_description,
-    ];
+    let attribute_descriptions: Vec<_> = std::iter::once(rect_vertex_attribute_description)
+        .chain(Line::attribute_descriptions(vertex_format))
+        .collect();
 
     let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
         .vertex_binding_descriptions(binding_descriptions)
-        .vertex_attribute_descriptions(attribute_descriptions);
+        .vertex_attribute_descriptions(&attribute_descriptions);
 
     let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
         .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
@@ -147,33 +445,202 @@ pub unsafe fn create_pipeline(
         .sample_shading_enable(false)
         .rasterization_samples(vk::SampleCountFlags::_1);
 
-    let attachment = vk::PipelineColorBlendAttachmentState::builder()
-        .color_write_mask(vk::ColorComponentFlags::all())
-        .blend_enable(true)
-        .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
-        .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
-        .color_blend_op(vk::BlendOp::MAX)
-        .src_alpha_blend_factor(vk::BlendFactor::ONE)
-        .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
-        .alpha_blend_op(vk::BlendOp::MIN);
+    let attachment = if transparent {
+        // Over a transparent clear this leaves premultiplied colors and the
+        // strokes' coverage in alpha, for the compositor to blend
+        vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::all())
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(vk::BlendOp::ADD)
+    } else {
+        vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::all())
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::MAX)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+            .alpha_blend_op(vk::BlendOp::MIN)
+    };
 
     let attachments = &[attachment];
     let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
         .logic_op_enable(false)
         .attachments(attachments);
 
+    // Scissor is set per frame to the damaged region
+    let dynamic_states = &[vk::DynamicState::SCISSOR];
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(dynamic_states);
+
     let vert_push_constant_range = vk::PushConstantRange::builder()
         .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
         .offset(0)
-        .size(12);
+        .size(size_of::<StrokeConstants>() as u32);
 
-    let set_layouts = &[];
+    let set_layouts = &[frame_layout];
     let push_constant_range = &[vert_push_constant_range];
     let layout_info = vk::PipelineLayoutCreateInfo::builder()
         .set_layouts(set_layouts)
         .push_constant_ranges(push_constant_range);
 
-    let pipeline_layout = device.create_pipeline_layout(&layout_info, None)?;
+    let pipeline_layout = guard(device, device.create_pipeline_layout(&layout_info, None)?);
+
+    let stages = &[vert_stage, frag_stage];
+    let info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(stages)
+        .vertex_input_state(&vertex_input_state)
+        .input_assembly_state(&input_assembly_state)
+        .viewport_state(&viewport_state)
+        .rasterization_state(&rasterization_state)
+        .multisample_state(&multisample_state)
+        .color_blend_state(&color_blend_state)
+        .dynamic_state(&dynamic_state)
+        .layout(*pipeline_layout)
+        .render_pass(render_pass)
+        .subpass(0);
+
+    let pipeline = device
+        .create_graphics_pipelines(pipeline_cache, &[info], None)?
+        .0[0];
+
+    Ok((pipeline, pipeline_layout.release()))
+}
+
+/// Creates the pipeline drawing egui meshes over the strokes.
+///
+/// Vertices are `egui::epaint::Vertex` (position and UV in points, sRGB
+/// color) with one combined image sampler per texture. Viewport and scissor
+/// are dynamic since every mesh has its own clip rectangle.
+pub unsafe fn create_ui_pipeline(
+    device: &Device,
+    pipeline_cache: vk::PipelineCache,
+    render_pass: vk::RenderPass,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    shader_config: &ShaderConfig,
+    color_format: vk::Format,
+) -> Result<(vk::Pipeline, vk::PipelineLayout)> {
+    let vert = std::fs::read(&shader_config.ui_vertex)?;
+    let frag = std::fs::read(&shader_config.ui_fragment)?;
+
+    // Only needed until the pipeline is built, so the guards destroy them on every return
+    let vert_shader_module = guard(device, create_shader_module(device, &vert)?);
+    let frag_shader_module = guard(device, create_shader_module(device, &frag)?);
+
+    let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::VERTEX)
+        .module(*vert_shader_module)
+        .name(b"main\0");
+
+    let encode_srgb = encode_srgb_constant(color_format);
+    let map_entries = &[ENCODE_SRGB_ENTRY];
+    let specialization = vk::SpecializationInfo::builder()
+        .map_entries(map_entries)
+        .data(&encode_srgb);
+
+    let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::FRAGMENT)
+        .module(*frag_shader_module)
+        .name(b"main\0")
+        .specialization_info(&specialization);
+
+    let vertex_binding = vk::VertexInputBindingDescription::builder()
+        .binding(0)
+        .stride(size_of::<egui::epaint::Vertex>() as u32)
+        .input_rate(vk::VertexInputRate::VERTEX)
+        .build();
+
+    let position_attribute_description = vk::VertexInputAttributeDescription::builder()
+        .binding(0)
+        .location(0)
+        .format(vk::Format::R32G32_SFLOAT)
+        .offset(0)
+        .build();
+
+    let uv_attribute_description = vk::VertexInputAttributeDescription::builder()
+        .binding(0)
+        .location(1)
+        .format(vk::Format::R32G32_SFLOAT)
+        .offset(8)
+        .build();
+
+    let color_attribute_description = vk::VertexInputAttributeDescription::builder()
+        .binding(0)
+        .location(2)
+        .format(vk::Format::R8G8B8A8_UNORM)
+        .offset(16)
+        .build();
+
+    let binding_descriptions = &[vertex_binding];
+    let attribute_descriptions = &[
+        position_attribute_description,
+        uv_attribute_description,
+        color_attribute_description,
+    ];
+
+    let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+        .vertex_binding_descriptions(binding_descriptions)
+        .vertex_attribute_descriptions(attribute_descriptions);
+
+    let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+        .primitive_restart_enable(false);
+
+    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+        .depth_clamp_enable(false)
+        .rasterizer_discard_enable(false)
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.0)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .depth_bias_enable(false);
+
+    let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+        .sample_shading_enable(false)
+        .rasterization_samples(vk::SampleCountFlags::_1);
+
+    // egui outputs premultiplied alpha
+    let attachment = vk::PipelineColorBlendAttachmentState::builder()
+        .color_write_mask(vk::ColorComponentFlags::all())
+        .blend_enable(true)
+        .src_color_blend_factor(vk::BlendFactor::ONE)
+        .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+        .color_blend_op(vk::BlendOp::ADD)
+        .src_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_DST_ALPHA)
+        .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+        .alpha_blend_op(vk::BlendOp::ADD);
+
+    let attachments = &[attachment];
+    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+        .logic_op_enable(false)
+        .attachments(attachments);
+
+    let dynamic_states = &[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(dynamic_states);
+
+    let push_constant_range = vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::VERTEX)
+        .offset(0)
+        .size(size_of::<Vec2>() as u32);
+
+    let set_layouts = &[descriptor_set_layout];
+    let push_constant_ranges = &[push_constant_range];
+    let layout_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(set_layouts)
+        .push_constant_ranges(push_constant_ranges);
+
+    let pipeline_layout = guard(device, device.create_pipeline_layout(&layout_info, None)?);
 
     let stages = &[vert_stage, frag_stage];
     let info = vk::GraphicsPipelineCreateInfo::builder()
@@ -184,18 +651,189 @@ pub unsafe fn create_pipeline(
         .rasterization_state(&rasterization_state)
         .multisample_state(&multisample_state)
         .color_blend_state(&color_blend_state)
-        .layout(pipeline_layout)
+        .dynamic_state(&dynamic_state)
+        .layout(*pipeline_layout)
         .render_pass(render_pass)
         .subpass(0);
 
     let pipeline = device
-        .create_graphics_pipelines(vk::PipelineCache::null(), &[info], None)?
+        .create_graphics_pipelines(pipeline_cache, &[info], None)?
         .0[0];
 
-    device.destroy_shader_module(vert_shader_module, None);
-    device.destroy_shader_module(frag_shader_module, None);
+    Ok((pipeline, pipeline_layout.release()))
+}
 
-    Ok((pipeline, pipeline_layout))
+/// Creates a pipeline drawing one triangle over the whole target, for post-processing
+///
+/// `vertex` makes the triangle from `gl_VertexIndex` alone, so there is no
+/// vertex input. The fragment shader samples the images of
+/// `descriptor_set_layouts` and gets `push_constant_size` bytes of push
+/// constants; its output replaces what was there, unless `blend` says how
+/// to blend it (with dynamic blend constants). Viewport and scissor are
+/// dynamic, so one pipeline serves targets of any size.
+pub unsafe fn create_fullscreen_pipeline(
+    device: &Device,
+    pipeline_cache: vk::PipelineCache,
+    render_pass: vk::RenderPass,
+    descriptor_set_layouts: &[vk::DescriptorSetLayout],
+    vertex: &Path,
+    fragment: &Path,
+    push_constant_size: u32,
+    color_format: vk::Format,
+    blend: Option<vk::PipelineColorBlendAttachmentState>,
+) -> Result<(vk::Pipeline, vk::PipelineLayout)> {
+    let vert = std::fs::read(vertex)?;
+    let frag = std::fs::read(fragment)?;
+
+    // Only needed until the pipeline is built, so the guards destroy them on every return
+    let vert_shader_module = guard(device, create_shader_module(device, &vert)?);
+    let frag_shader_module = guard(device, create_shader_module(device, &frag)?);
+
+    let vert_stage = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::VERTEX)
+        .module(*vert_shader_module)
+        .name(b"main\0");
+
+    let encode_srgb = encode_srgb_constant(color_format);
+    let map_entries = &[ENCODE_SRGB_ENTRY];
+    let specialization = vk::SpecializationInfo::builder()
+        .map_entries(map_entries)
+        .data(&encode_srgb);
+
+    let frag_stage = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::FRAGMENT)
+        .module(*frag_shader_module)
+        .name(b"main\0")
+        .specialization_info(&specialization);
+
+    let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder();
+
+    let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+        .primitive_restart_enable(false);
+
+    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissor_count(1);
+
+    let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+        .depth_clamp_enable(false)
+        .rasterizer_discard_enable(false)
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.0)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .depth_bias_enable(false);
+
+    let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+        .sample_shading_enable(false)
+        .rasterization_samples(vk::SampleCountFlags::_1);
+
+    let attachment = blend.unwrap_or_else(|| {
+        vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::all())
+            .blend_enable(false)
+            .build()
+    });
+
+    let attachments = &[attachment];
+    let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+        .logic_op_enable(false)
+        .attachments(attachments);
+
+    let mut dynamic_states = vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    if blend.is_some() {
+        dynamic_states.push(vk::DynamicState::BLEND_CONSTANTS);
+    }
+    let dynamic_state =
+        vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+
+    let push_constant_range = vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+        .offset(0)
+        .size(push_constant_size);
+
+    let push_constant_ranges = &[push_constant_range];
+    let layout_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(descriptor_set_layouts)
+        .push_constant_ranges(push_constant_ranges);
+
+    let pipeline_layout = guard(device, device.create_pipeline_layout(&layout_info, None)?);
+
+    let stages = &[vert_stage, frag_stage];
+    let info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(stages)
+        .vertex_input_state(&vertex_input_state)
+        .input_assembly_state(&input_assembly_state)
+        .viewport_state(&viewport_state)
+        .rasterization_state(&rasterization_state)
+        .multisample_state(&multisample_state)
+        .color_blend_state(&color_blend_state)
+        .dynamic_state(&dynamic_state)
+        .layout(*pipeline_layout)
+        .render_pass(render_pass)
+        .subpass(0);
+
+    let pipeline = device
+        .create_graphics_pipelines(pipeline_cache, &[info], None)?
+        .0[0];
+
+    Ok((pipeline, pipeline_layout.release()))
+}
+
+/// Creates the pipeline drawing the `[shaders.backgrounds]` entry `name`
+/// over the whole of `render_pass`, with [`BackgroundConstants`] and the
+/// frame uniforms of `frame_layout` at set 0
+///
+/// `None` without a name, or (with a warning) when the shaders in use have
+/// no background called that, as after a reload that added it.
+unsafe fn create_background_pipeline(
+    device: &Device,
+    pipeline_cache: vk::PipelineCache,
+    render_pass: vk::RenderPass,
+    frame_layout: vk::DescriptorSetLayout,
+    shader_config: &ShaderConfig,
+    name: Option<&str>,
+    color_format: vk::Format,
+) -> Result<Option<(vk::Pipeline, vk::PipelineLayout)>> {
+    let Some(name) = name else {
+        return Ok(None);
+    };
+    let Some(fragment) = shader_config.backgrounds.get(name) else {
+        warn!(
+            "No background `{}` in the `[shaders.backgrounds]` in use, restart to draw it.",
+            name
+        );
+        return Ok(None);
+    };
+    let pipeline = create_fullscreen_pipeline(
+        device,
+        pipeline_cache,
+        render_pass,
+        &[frame_layout],
+        &shader_config.fullscreen_vertex,
+        fragment,
+        size_of::<BackgroundConstants>() as u32,
+        color_format,
+        None,
+    )
+    .with_context(|| format!("Failed to create the pipeline for `{}`", fragment.display()))?;
+    Ok(Some(pipeline))
+}
+
+/// The fragment shaders' `ENCODE_SRGB` specialization constant (`constant_id = 0`)
+const ENCODE_SRGB_ENTRY: vk::SpecializationMapEntry = vk::SpecializationMapEntry {
+    constant_id: 0,
+    offset: 0,
+    size: size_of::<vk::Bool32>(),
+};
+
+/// `ENCODE_SRGB` for drawing into `color_format`: set unless the attachment
+/// encodes sRGB itself or keeps linear colors in floats
+fn encode_srgb_constant(color_format: vk::Format) -> [u8; 4] {
+    let linear = is_srgb_format(color_format) || color_format == HDR_FORMAT;
+    let encode = !linear as vk::Bool32;
+    encode.to_ne_bytes()
 }
 
 unsafe fn create_shader_module(device: &Device, bytecode: &[u8]) -> Result<vk::ShaderModule> {
@@ -212,7 +850,7 @@ pub unsafe fn create_framebuffers(
     swapchain_extent: vk::Extent2D,
     render_pass: vk::RenderPass,
 ) -> Result<Vec<vk::Framebuffer>> {
-    let mut framebuffers = Vec::new();
+    let mut framebuffers = guard(device, Vec::new());
     for &i in swapchain_image_views {
         let attachments = &[i];
         let create_info = vk::FramebufferCreateInfo::builder()
@@ -223,5 +861,35 @@ pub unsafe fn create_framebuffers(
             .layers(1);
         framebuffers.push(device.create_framebuffer(&create_info, None)?);
     }
-    Ok(framebuffers)
+    Ok(framebuffers.release())
+}
+
+/// Creates the pipeline cache, seeded from the previous run if it left one.
+///
+/// Drivers check the header themselves and ignore data from another device
+/// or driver version, so a stale file only costs a slower start.
+pub unsafe fn create_pipeline_cache(device: &Device) -> Result<vk::PipelineCache> {
+    let data = paths::pipeline_cache_file()
+        .and_then(|path| fs::read(path).ok())
+        .unwrap_or_default();
+
+    let info = vk::PipelineCacheCreateInfo::builder().initial_data(&data);
+    Ok(device.create_pipeline_cache(&info, None)?)
+}
+
+/// Writes the pipeline cache to disk for the next run
+pub unsafe fn save_pipeline_cache(
+    device: &Device,
+    pipeline_cache: vk::PipelineCache,
+) -> Result<()> {
+    let Some(path) = paths::pipeline_cache_file() else {
+        return Ok(());
+    };
+
+    let data = device.get_pipeline_cache_data(pipeline_cache)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, data).with_context(|| format!("Failed to write `{}`", path.display()))?;
+    Ok(())
 }
@@ -1,20 +1,86 @@
 use anyhow::Result;
+use log::*;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+use tracing::info_span;
 use vulkanalia::prelude::v1_0::*;
 use vulkanalia::vk::KhrSwapchainExtensionDeviceCommands;
 use winit::window::Window;
 
+use super::bloom::{Bloom, HDR_FORMAT};
 use super::command::{create_command_buffers, create_command_pools};
 use super::context::VulkanContext;
-use super::pipeline::{create_framebuffers, create_pipeline, create_render_pass};
-use super::swapchain::{create_swapchain, create_swapchain_image_views};
-use crate::types::RECT_INDICES;
+use super::erase::EraseLayer;
+use super::owned::{guard, Destroy};
+use super::pacing::{FramePacer, PresentTiming};
+use super::pipeline::{create_framebuffers, create_render_pass, PipelineRegistry, Primitive};
+use super::scaled::{letterbox, scaled_extent, ScaledTargets};
+use super::swapchain::{create_swapchain, create_swapchain_image_views, is_srgb_format};
+use super::timer::GpuTimer;
+use super::ui::UiRenderer;
+use super::uniforms::{FrameBinding, FrameUniformBuffers, FrameUniforms, ParamBlock};
+use super::upload::LineUploads;
+use super::watchdog::{wait_for_fences, Watchdog};
+use crate::camera::Camera;
+use crate::types::{Color, StrokeStyle, RECT_INDICES};
 use crate::{
     config::Config,
-    types::{Vec3, RECT},
+    types::{Rect, Vec2, Vec4},
 };
 
 const MAX_FRAMES_IN_FLIGHT: usize = 2;
 
+/// Region of a swapchain image that is stale and must be redrawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Damage {
+    None,
+    Partial(Rect),
+    Full,
+}
+
+/// Where the CPU waited while submitting a frame, and how much line data it uploaded
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FrameWaits {
+    /// Waiting on fences for an earlier frame (or image) to finish on the GPU
+    pub fence: Duration,
+    /// Acquiring the next swapchain image
+    pub acquire: Duration,
+    /// Queueing the present
+    pub present: Duration,
+    /// Bytes of queued strokes copied to device-local buffers
+    pub upload_bytes: usize,
+}
+
+/// What a recorded command buffer drew, to tell whether it can be submitted again unchanged
+#[derive(Debug, Clone, PartialEq)]
+struct RecordedFrame {
+    /// `App`'s stroke generation
+    scene: u64,
+    /// `UiRenderer::generation`
+    ui: u64,
+    /// Frame slot whose UI buffers and timer queries the commands use
+    frame: usize,
+    extent: vk::Extent2D,
+    camera: Camera,
+    style: StrokeStyle,
+    background: Color,
+    /// Seconds of the animated background, `None` without one
+    background_time: Option<f32>,
+    batches: Vec<(vk::Buffer, Range<u32>)>,
+    /// Whether the erase layer was drawn
+    pixel_erasing: bool,
+}
+
+impl Damage {
+    fn add(&mut self, rect: Rect) {
+        *self = match *self {
+            Damage::None => Damage::Partial(rect),
+            Damage::Partial(damaged) => Damage::Partial(damaged.union(&rect)),
+            Damage::Full => Damage::Full,
+        };
+    }
+}
+
 /// Manages swapchain-dependent rendering resources
 pub struct Renderer {
     // Swapchain
@@ -23,11 +89,29 @@ pub struct Renderer {
     pub swapchain_image_views: Vec<vk::ImageView>,
     pub swapchain_format: vk::Format,
     pub swapchain_extent: vk::Extent2D,
+    /// Whether the compositor reads the frame's alpha, and how
+    pub composite_alpha: vk::CompositeAlphaFlagsKHR,
+    /// Pixels frames are drawn at, the canvas extent times `[vulkan] resolution_scale`
+    pub render_extent: vk::Extent2D,
+    /// Pixels of the canvas before the resolution scale: `[window] canvas_size`,
+    /// else (or if frames can't be blitted) the swapchain extent
+    pub canvas_extent: vk::Extent2D,
+    /// Where in the swapchain images frames are shown, all of them unless the
+    /// canvas has a fixed size of another shape
+    pub letterbox: vk::Rect2D,
+    /// What frames are drawn into when `render_extent` differs from the swapchain's
+    scaled: Option<ScaledTargets>,
+    /// Where strokes are drawn first with `[bloom] enabled`, to add a glow to them
+    bloom: Option<Bloom>,
+    /// Where pixel eraser strokes punch holes into the strokes, in the
+    /// bloom scene with bloom and the frame otherwise
+    erase: EraseLayer,
 
     // Pipeline
     pub render_pass: vk::RenderPass,
-    pub pipeline_layout: vk::PipelineLayout,
-    pub pipeline: vk::Pipeline,
+    pub load_render_pass: vk::RenderPass,
+    /// The stroke pipelines, one per brush, and `[background] shader`'s
+    pub pipelines: PipelineRegistry,
 
     // Framebuffers
     pub framebuffers: Vec<vk::Framebuffer>,
@@ -42,56 +126,197 @@ pub struct Renderer {
     pub in_flight_fences: Vec<vk::Fence>,
     pub images_in_flight: Vec<vk::Fence>,
 
+    // Overlay drawn on top of the strokes
+    pub ui: UiRenderer,
+
+    // Line data copied into device-local buffers at the start of the next frame
+    pub uploads: LineUploads,
+
+    // Time, frame size and pointer for the shaders, one set per frame in flight
+    frame_uniforms: FrameUniformBuffers,
+
+    // GPU frame timing, `None` when the device has no timestamp queries
+    gpu_timer: Option<GpuTimer>,
+
+    // When presents reach the screen, for starting frames just in time
+    pub pacer: FramePacer,
+
+    // Where the last frame waited, for frame statistics
+    pub waits: FrameWaits,
+
+    // Damage tracking (one entry per swapchain image)
+    damage: Vec<Damage>,
+    partial_redraw: bool,
+
+    // What each swapchain image's command buffer was last recorded with, `None` if it must be re-recorded
+    recorded: Vec<Option<RecordedFrame>>,
+
     pub frame: usize,
+
+    // Kept to destroy everything above on drop
+    device: Device,
 }
 
 impl Renderer {
     /// Creates a new renderer with all swapchain-dependent resources
+    ///
+    /// Everything is held in a guard until the renderer is assembled, so a
+    /// failure halfway destroys what was already created.
     pub unsafe fn create(
         window: &Window,
         context: &VulkanContext,
         config: &Config,
     ) -> Result<Self> {
+        let device = &context.device;
+
         // Create swapchain
-        let (swapchain, swapchain_images, swapchain_format, swapchain_extent) = create_swapchain(
-            window,
-            &context.instance,
-            &context.device,
-            context.surface,
-            context.physical_device,
-        )?;
+        let (swapchain, swapchain_images, swapchain_format, swapchain_extent, composite_alpha) =
+            create_swapchain(
+                window,
+                &context.instance,
+                device,
+                context.surface,
+                context.physical_device,
+                config.vulkan.partial_redraw,
+                config.vulkan.present_mode,
+                config.vulkan.composite_alpha,
+                config.window.is_transparent(),
+            )?;
+        let swapchain = guard(device, swapchain);
+        if !is_srgb_format(swapchain_format) {
+            warn!(
+                "No sRGB swapchain format, drawing to {:?}: colors are encoded by the shaders, but blend in sRGB space",
+                swapchain_format
+            );
+        }
 
-        let swapchain_image_views =
-            create_swapchain_image_views(&context.device, &swapchain_images, swapchain_format)?;
+        let swapchain_image_views = guard(
+            device,
+            create_swapchain_image_views(device, &swapchain_images, swapchain_format)?,
+        );
 
-        // Create render pass and pipeline
-        let render_pass = create_render_pass(&context.device, swapchain_format)?;
+        let scaled = guard(
+            device,
+            create_scaled_targets(
+                context,
+                config,
+                swapchain_format,
+                swapchain_extent,
+                swapchain_images.len(),
+            )?,
+        );
+        let render_extent = scaled.as_ref().map_or(swapchain_extent, |s| s.extent);
+        let canvas_extent = canvas_extent(config, &scaled, swapchain_extent);
+        let layout = frame_layout(&scaled);
 
-        let (pipeline, pipeline_layout) = create_pipeline(
-            &context.device,
-            swapchain_extent,
-            render_pass,
-            &config.shaders,
-        )?;
+        let frame_uniforms = guard(
+            device,
+            FrameUniformBuffers::create(
+                context,
+                MAX_FRAMES_IN_FLIGHT,
+                config.shaders.param_block_count(),
+            )?,
+        );
+
+        // Create render passes and pipeline
+        let render_pass = guard(
+            device,
+            create_render_pass(device, swapchain_format, false, layout)?,
+        );
+        let load_render_pass = guard(
+            device,
+            create_render_pass(device, swapchain_format, true, layout)?,
+        );
+
+        let pipelines = guard(
+            device,
+            PipelineRegistry::create(
+                device,
+                context.pipeline_cache,
+                render_extent,
+                *render_pass,
+                frame_uniforms.layout,
+                &config.shaders,
+                config.background.shader.as_deref(),
+                config.window.is_transparent(),
+                context.vertex_format,
+                swapchain_format,
+            )?,
+        );
+
+        let bloom = guard(
+            device,
+            create_bloom(
+                context,
+                config,
+                *render_pass,
+                frame_uniforms.layout,
+                swapchain_format,
+                render_extent,
+                swapchain_images.len(),
+            )?,
+        );
+
+        let erase = guard(
+            device,
+            create_erase_layer(
+                context,
+                config,
+                *render_pass,
+                bloom.as_ref(),
+                frame_uniforms.layout,
+                swapchain_format,
+                render_extent,
+                swapchain_images.len(),
+            )?,
+        );
+
+        let ui = guard(
+            device,
+            UiRenderer::create(
+                context,
+                *render_pass,
+                &config.shaders,
+                swapchain_format,
+                MAX_FRAMES_IN_FLIGHT,
+            )?,
+        );
+        let gpu_timer = guard(device, GpuTimer::create(context, MAX_FRAMES_IN_FLIGHT)?);
+        let uploads = guard(
+            device,
+            LineUploads::create(
+                context,
+                MAX_FRAMES_IN_FLIGHT,
+                config.vulkan.staging_buffer_vertex_count as usize,
+            )?,
+        );
 
         // Create framebuffers
-        let framebuffers = create_framebuffers(
-            &context.device,
-            &swapchain_image_views,
-            swapchain_extent,
-            render_pass,
-        )?;
+        let framebuffers = guard(
+            device,
+            create_framebuffers(
+                device,
+                scaled
+                    .as_ref()
+                    .map_or(&swapchain_image_views, |s| &s.image_views),
+                render_extent,
+                *render_pass,
+            )?,
+        );
 
         // Create command pools and buffers
-        let command_pools = create_command_pools(
-            &context.instance,
-            &context.device,
-            context.surface,
-            context.physical_device,
-            swapchain_images.len(),
-        )?;
+        let command_pools = guard(
+            device,
+            create_command_pools(
+                &context.instance,
+                device,
+                context.surface,
+                context.physical_device,
+                swapchain_images.len(),
+            )?,
+        );
 
-        let command_buffers = create_command_buffers(&context.device, &command_pools)?;
+        let command_buffers = create_command_buffers(device, &command_pools)?;
 
         // Create sync objects
         let (
@@ -99,58 +324,184 @@ impl Renderer {
             render_finished_semaphores,
             in_flight_fences,
             images_in_flight,
-        ) = super::sync::create_sync_objects(
-            &context.device,
-            MAX_FRAMES_IN_FLIGHT,
-            swapchain_images.len(),
-        )?;
+        ) = super::sync::create_sync_objects(device, MAX_FRAMES_IN_FLIGHT, swapchain_images.len())?;
 
+        let damage = vec![Damage::Full; swapchain_images.len()];
+        let recorded = vec![None; swapchain_images.len()];
         Ok(Self {
-            swapchain,
+            swapchain: swapchain.release(),
             swapchain_images,
-            swapchain_image_views,
+            swapchain_image_views: swapchain_image_views.release(),
             swapchain_format,
             swapchain_extent,
-            render_pass,
-            pipeline_layout,
-            pipeline,
-            framebuffers,
-            command_pools,
+            composite_alpha,
+            render_extent,
+            canvas_extent,
+            letterbox: letterbox(canvas_extent, swapchain_extent),
+            scaled: scaled.release(),
+            bloom: bloom.release(),
+            erase: erase.release(),
+            render_pass: render_pass.release(),
+            load_render_pass: load_render_pass.release(),
+            pipelines: pipelines.release(),
+            framebuffers: framebuffers.release(),
+            command_pools: command_pools.release(),
             command_buffers,
             image_available_semaphores,
             render_finished_semaphores,
             in_flight_fences,
             images_in_flight,
+            ui: ui.release(),
+            uploads: uploads.release(),
+            frame_uniforms: frame_uniforms.release(),
+            gpu_timer: gpu_timer.release(),
+            pacer: FramePacer::new(device),
+            waits: FrameWaits::default(),
+            damage,
+            partial_redraw: config.vulkan.partial_redraw,
+            recorded,
             frame: 0,
+            device: device.clone(),
         })
     }
 
+    /// How long the GPU took for the most recently finished frame
+    pub fn gpu_time(&self) -> Option<Duration> {
+        self.gpu_timer.as_ref().and_then(|t| t.last())
+    }
+
+    /// Whether frames draw an animated `[background] shader`
+    pub fn has_background(&self) -> bool {
+        !self.pipelines.get(Primitive::Background).is_empty()
+    }
+
+    /// Marks a region (in NDC) as changed so it is redrawn on every swapchain image
+    pub fn damage(&mut self, rect: Rect) {
+        self.damage.iter_mut().for_each(|d| d.add(rect));
+    }
+
+    /// Forces the next frame on every swapchain image to clear and redraw everything
+    pub fn damage_all(&mut self) {
+        self.damage.fill(Damage::Full);
+    }
+
+    /// How many frame pixels there are per window pixel along each axis
+    pub fn resolution_scale(&self) -> f32 {
+        self.render_extent.height as f32 / self.letterbox.extent.height.max(1) as f32
+    }
+
+    /// Converts a window position in physical pixels to frame NDC (-1 to 1
+    /// across the letterbox, beyond that on the bars around it)
+    pub fn to_ndc(&self, x: f32, y: f32) -> Vec2 {
+        let vk::Rect2D { offset, extent } = self.letterbox;
+        Vec2::new(
+            (x - offset.x as f32) / extent.width.max(1) as f32 * 2.0 - 1.0,
+            (y - offset.y as f32) / extent.height.max(1) as f32 * 2.0 - 1.0,
+        )
+    }
+
+    /// Converts an NDC rectangle to a pixel scissor clamped to the frame
+    fn scissor_for(&self, rect: Rect) -> Option<vk::Rect2D> {
+        let width = self.render_extent.width as f32;
+        let height = self.render_extent.height as f32;
+
+        let x0 = (((rect.min.x + 1.0) / 2.0 * width).floor() - 1.0).clamp(0.0, width);
+        let y0 = (((rect.min.y + 1.0) / 2.0 * height).floor() - 1.0).clamp(0.0, height);
+        let x1 = (((rect.max.x + 1.0) / 2.0 * width).ceil() + 1.0).clamp(0.0, width);
+        let y1 = (((rect.max.y + 1.0) / 2.0 * height).ceil() + 1.0).clamp(0.0, height);
+
+        if x1 <= x0 || y1 <= y0 {
+            return None;
+        }
+
+        Some(
+            vk::Rect2D::builder()
+                .offset(vk::Offset2D {
+                    x: x0 as i32,
+                    y: y0 as i32,
+                })
+                .extent(vk::Extent2D {
+                    width: (x1 - x0) as u32,
+                    height: (y1 - y0) as u32,
+                })
+                .build(),
+        )
+    }
+
     /// Renders a frame
+    ///
+    /// `scene` is a generation number that changes whenever the strokes do, or
+    /// `None` while something is animating (like the stroke being drawn).
+    /// If it, the view and the UI all match what the acquired image's command
+    /// buffer was last recorded with, that buffer is submitted again as is.
+    /// The shaders' [`FrameUniforms`] are updated either way, with `cursor`
+    /// (in NDC) and the time since `start_time`.
     pub unsafe fn render(
         &mut self,
         window: &Window,
         context: &VulkanContext,
         config: &Config,
         rect_buffer: vk::Buffer,
-        line_buffer: vk::Buffer,
-        staging_line_buffer: vk::Buffer,
         index_buffer: vk::Buffer,
-        start_time: std::time::Instant,
-        line_count: u32,
-        new_line_count: u32,
+        start_time: Instant,
+        background: Color,
+        background_time: Option<f32>,
+        cursor: Option<Vec2>,
+        camera: &Camera,
+        style: &StrokeStyle,
+        batches: &[(vk::Buffer, Range<u32>)],
+        pixel_erasing: bool,
+        scene: Option<u64>,
     ) -> Result<bool> {
         let in_flight_fence = self.in_flight_fences[self.frame];
+        self.waits = FrameWaits {
+            upload_bytes: self.uploads.pending_bytes(),
+            ..FrameWaits::default()
+        };
 
-        context
-            .device
-            .wait_for_fences(&[in_flight_fence], true, u64::MAX)?;
+        let acquire_span = info_span!("acquire").entered();
+        let timeout = config.vulkan.gpu_timeout();
+        let waited = Instant::now();
+        wait_for_fences(
+            &context.device,
+            &[in_flight_fence],
+            timeout,
+            "an earlier frame",
+            || self.diagnostics(None),
+        )?;
+        self.waits.fence = waited.elapsed();
+
+        // The frame slot's fence was waited on, so its uniforms are free to change
+        let extent = self.render_extent;
+        let uniforms = FrameUniforms {
+            cursor: cursor.map_or(Vec2::new(-1.0, -1.0), |ndc| {
+                Vec2::new(
+                    (ndc.x + 1.0) / 2.0 * extent.width as f32,
+                    (ndc.y + 1.0) / 2.0 * extent.height as f32,
+                )
+            }),
+            time: start_time.elapsed().as_secs_f32(),
+            ..FrameUniforms::still(extent)
+        };
+        self.frame_uniforms.write(self.frame, &uniforms);
+        let params = config.shader_params.blocks(&config.shaders);
+        for (block, params) in params.enumerate() {
+            self.frame_uniforms
+                .write_params(self.frame, block, &ParamBlock::new(params));
+        }
 
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.collect(&context.device, self.frame)?;
+        }
+
+        let acquired = Instant::now();
         let result = context.device.acquire_next_image_khr(
             self.swapchain,
             u64::MAX,
             self.image_available_semaphores[self.frame],
             vk::Fence::null(),
         );
+        self.waits.acquire = acquired.elapsed();
 
         let image_index = match result {
             Ok((image_index, _)) => image_index as usize,
@@ -163,28 +514,65 @@ impl Renderer {
 
         let image_in_flight = self.images_in_flight[image_index];
         if !image_in_flight.is_null() {
-            context
-                .device
-                .wait_for_fences(&[image_in_flight], true, u64::MAX)?;
+            let waited = Instant::now();
+            wait_for_fences(
+                &context.device,
+                &[image_in_flight],
+                timeout,
+                "the frame drawing to the acquired image",
+                || self.diagnostics(Some(image_index)),
+            )?;
+            self.waits.fence += waited.elapsed();
         }
+        drop(acquire_span);
 
         self.images_in_flight[image_index] = in_flight_fence;
 
-        self.update_command_buffer(
-            context,
-            self.pipeline_layout,
-            image_index,
-            rect_buffer,
-            line_buffer,
-            staging_line_buffer,
-            index_buffer,
-            start_time,
-            line_count,
-            new_line_count,
-        )?;
+        let recorded = |renderer: &Self| {
+            scene.map(|scene| RecordedFrame {
+                scene,
+                ui: renderer.ui.generation(),
+                frame: renderer.frame,
+                extent: renderer.swapchain_extent,
+                camera: *camera,
+                style: *style,
+                background,
+                background_time,
+                batches: batches.to_vec(),
+                pixel_erasing,
+            })
+        };
+
+        let unchanged = self.uploads.is_empty()
+            && recorded(self).is_some_and(|r| self.recorded[image_index].as_ref() == Some(&r));
+        if unchanged {
+            // Redraws the same region with the same content
+            self.damage[image_index] = Damage::None;
+            if let Some(timer) = &mut self.gpu_timer {
+                timer.expect(self.frame);
+            }
+        } else {
+            let _record = info_span!("record").entered();
+            self.update_command_buffer(
+                context,
+                image_index,
+                rect_buffer,
+                index_buffer,
+                background,
+                background_time,
+                camera,
+                style,
+                batches,
+                pixel_erasing,
+            )?;
+            // Recording may have reallocated UI buffers, so read the generation afterwards
+            self.recorded[image_index] = recorded(self);
+        }
 
         let wait_semaphores = &[self.image_available_semaphores[self.frame]];
-        let wait_stages = &[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+        // A scaled frame first touches the swapchain image when blitting to it
+        let wait_stages =
+            &[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::TRANSFER];
         let command_buffers = &[self.command_buffers[image_index]];
         let signal_semaphores = &[self.render_finished_semaphores[self.frame]];
         let submit_info = vk::SubmitInfo::builder()
@@ -193,22 +581,47 @@ impl Renderer {
             .command_buffers(command_buffers)
             .signal_semaphores(signal_semaphores);
 
+        let submit_span = info_span!("submit").entered();
         context.device.reset_fences(&[in_flight_fence])?;
 
         context
             .device
             .queue_submit(context.graphics_queue, &[submit_info], in_flight_fence)?;
+        drop(submit_span);
 
         let swapchains = &[self.swapchain];
         let image_indices = &[image_index as u32];
-        let present_info = vk::PresentInfoKHR::builder()
+        let mut present_info = vk::PresentInfoKHR::builder()
             .wait_semaphores(signal_semaphores)
             .swapchains(swapchains)
             .image_indices(image_indices);
 
-        let result = context
-            .device
-            .queue_present_khr(context.present_queue, &present_info);
+        // Tag the present so the pacer can tell when it reaches the screen
+        let present_id = self.pacer.next_present_id();
+        let present_ids = &[present_id];
+        let mut present_id_info = vk::PresentIdKHR::builder().present_ids(present_ids);
+        let present_times = &[vk::PresentTimeGOOGLE {
+            present_id: present_id as u32,
+            desired_present_time: 0,
+        }];
+        let mut present_times_info = vk::PresentTimesInfoGOOGLE::builder().times(present_times);
+        match self.pacer.timing {
+            PresentTiming::PresentWait => {
+                present_info = present_info.push_next(&mut present_id_info)
+            }
+            PresentTiming::DisplayTiming => {
+                present_info = present_info.push_next(&mut present_times_info)
+            }
+            PresentTiming::Unavailable => {}
+        }
+
+        let presented = Instant::now();
+        let result = info_span!("present").in_scope(|| {
+            context
+                .device
+                .queue_present_khr(context.present_queue, &present_info)
+        });
+        self.waits.present = presented.elapsed();
         let changed = result == Ok(vk::SuccessCode::SUBOPTIMAL_KHR)
             || result == Err(vk::ErrorCode::OUT_OF_DATE_KHR);
 
@@ -230,15 +643,15 @@ impl Renderer {
     unsafe fn update_command_buffer(
         &mut self,
         context: &VulkanContext,
-        pipeline_layout: vk::PipelineLayout,
         image_index: usize,
         rect_buffer: vk::Buffer,
-        line_buffer: vk::Buffer,
-        staging_line_buffer: vk::Buffer,
         index_buffer: vk::Buffer,
-        start_time: std::time::Instant,
-        line_count: u32,
-        new_line_count: u32,
+        background: Color,
+        background_time: Option<f32>,
+        camera: &Camera,
+        style: &StrokeStyle,
+        batches: &[(vk::Buffer, Range<u32>)],
+        pixel_erasing: bool,
     ) -> Result<()> {
         let command_pool = self.command_pools[image_index];
         context
@@ -247,24 +660,124 @@ impl Renderer {
 
         let command_buffer = self.command_buffers[image_index];
 
-        let info = vk::CommandBufferBeginInfo::builder()
-            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        // Not one-time: an unchanged frame submits the same commands again
+        let info = vk::CommandBufferBeginInfo::builder();
 
         context.device.begin_command_buffer(command_buffer, &info)?;
 
+        if let Some(timer) = &self.gpu_timer {
+            timer.cmd_begin(&context.device, command_buffer, self.frame);
+        }
+
+        // This frame's fence was waited on, so its staging buffer is free again
+        self.uploads
+            .cmd_record(context, command_buffer, self.frame)?;
+
         let render_area = vk::Rect2D::builder()
             .offset(vk::Offset2D::default())
-            .extent(self.swapchain_extent);
+            .extent(self.render_extent)
+            .build();
+        let frame = self.frame_uniforms.binding(self.frame);
+
+        // Only redraw what changed since this image was last presented, which
+        // the glow of bloom spreads beyond and an animated background covers
+        let animated = background_time.is_some() && self.has_background();
+        let damage = if self.partial_redraw && self.bloom.is_none() && !animated {
+            std::mem::replace(&mut self.damage[image_index], Damage::None)
+        } else {
+            Damage::Full
+        };
 
+        let (render_pass, scissor) = match damage {
+            Damage::Full => (self.render_pass, Some(render_area)),
+            Damage::Partial(rect) => (self.load_render_pass, self.scissor_for(rect)),
+            Damage::None => (self.load_render_pass, None),
+        };
+
+        let mut clear_color = if is_srgb_format(self.swapchain_format) {
+            background.to_linear()
+        } else {
+            background.to_srgb()
+        };
+        // Strokes blend into the clear, so they come out premultiplied too
+        let premultiplied =
+            vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED | vk::CompositeAlphaFlagsKHR::INHERIT;
+        if premultiplied.contains(self.composite_alpha) {
+            let alpha = clear_color[3];
+            clear_color[..3].iter_mut().for_each(|c| *c *= alpha);
+        }
         let color_clear_value = vk::ClearValue {
             color: vk::ClearColorValue {
-                float32: [0.0, 0.0, 0.0, 1.0],
+                float32: clear_color,
             },
         };
 
+        if pixel_erasing {
+            self.erase.cmd_mask(
+                &context.device,
+                command_buffer,
+                image_index,
+                frame,
+                camera,
+                style,
+                rect_buffer,
+                index_buffer,
+                batches,
+            );
+        }
+
+        if let Some(bloom) = &self.bloom {
+            // The scene is sampled as linear premultiplied colors, whatever the swapchain's
+            let mut clear = background.to_linear();
+            let alpha = clear[3];
+            clear[..3].iter_mut().for_each(|c| *c *= alpha);
+            bloom.cmd_begin_scene(&context.device, command_buffer, image_index, clear);
+            if let Some(time) = background_time {
+                let constants = BackgroundConstants {
+                    color: clear.into(),
+                    aspect: camera.aspect,
+                    time,
+                };
+                cmd_draw_background(
+                    &context.device,
+                    command_buffer,
+                    &bloom.pipelines,
+                    frame,
+                    self.render_extent,
+                    &constants,
+                );
+            }
+            cmd_draw_lines(
+                &context.device,
+                command_buffer,
+                &bloom.pipelines,
+                frame,
+                render_area,
+                camera,
+                style,
+                rect_buffer,
+                index_buffer,
+                batches,
+            );
+            if pixel_erasing {
+                self.erase.cmd_composite(
+                    &context.device,
+                    command_buffer,
+                    image_index,
+                    render_area,
+                    clear,
+                );
+            }
+            context.device.cmd_end_render_pass(command_buffer);
+
+            // Only what stands out from the background glows
+            let luminance = clear[0] * 0.2126 + clear[1] * 0.7152 + clear[2] * 0.0722;
+            bloom.cmd_glow(&context.device, command_buffer, image_index, luminance);
+        }
+
         let clear_values = &[color_clear_value];
         let info = vk::RenderPassBeginInfo::builder()
-            .render_pass(self.render_pass)
+            .render_pass(render_pass)
             .framebuffer(self.framebuffers[image_index])
             .render_area(render_area)
             .clear_values(clear_values);
@@ -273,73 +786,132 @@ impl Renderer {
             .device
             .cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
 
-        // Bind pipeline
-        context.device.cmd_bind_pipeline(
-            command_buffer,
-            vk::PipelineBindPoint::GRAPHICS,
-            self.pipeline,
-        );
+        // Nothing changed on this image, keep its previous contents
+        let Some(scissor) = scissor else {
+            return self.end_frame(&context.device, command_buffer, image_index);
+        };
 
-        context.device.cmd_bind_index_buffer(
-            command_buffer,
-            index_buffer,
-            0,
-            vk::IndexType::UINT16,
-        );
+        if render_pass == self.load_render_pass {
+            let clear_attachment = vk::ClearAttachment::builder()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .color_attachment(0)
+                .clear_value(color_clear_value);
 
-        context
-            .device
-            .cmd_bind_vertex_buffers(command_buffer, 0, &[rect_buffer], &[0]);
+            let clear_rect = vk::ClearRect::builder()
+                .rect(scissor)
+                .base_array_layer(0)
+                .layer_count(1);
 
-        let totally_temporary_view_vector = Vec3::new(0., 0., 1.);
+            context.device.cmd_clear_attachments(
+                command_buffer,
+                &[clear_attachment],
+                &[clear_rect],
+            );
+        }
 
-        let view_bytes = std::slice::from_raw_parts(
-            &totally_temporary_view_vector as *const Vec3 as *const u8,
-            size_of::<Vec3>(),
-        );
+        match &self.bloom {
+            Some(bloom) => bloom.cmd_composite(&context.device, command_buffer, image_index),
+            None => {
+                // In the clear color's space, which the shaders encode like a stroke's
+                let mut color = background.to_linear();
+                if premultiplied.contains(self.composite_alpha) {
+                    let alpha = color[3];
+                    color[..3].iter_mut().for_each(|c| *c *= alpha);
+                }
+                if let Some(time) = background_time {
+                    let constants = BackgroundConstants {
+                        color: color.into(),
+                        aspect: camera.aspect,
+                        time,
+                    };
+                    cmd_draw_background(
+                        &context.device,
+                        command_buffer,
+                        &self.pipelines,
+                        frame,
+                        self.render_extent,
+                        &constants,
+                    );
+                }
+                cmd_draw_lines(
+                    &context.device,
+                    command_buffer,
+                    &self.pipelines,
+                    frame,
+                    scissor,
+                    camera,
+                    style,
+                    rect_buffer,
+                    index_buffer,
+                    batches,
+                );
+                if pixel_erasing {
+                    self.erase.cmd_composite(
+                        &context.device,
+                        command_buffer,
+                        image_index,
+                        scissor,
+                        color,
+                    );
+                }
+            }
+        }
 
-        context.device.cmd_push_constants(
+        self.ui.record(
+            context,
             command_buffer,
-            pipeline_layout,
-            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
-            0,
-            view_bytes,
-        );
+            self.frame,
+            self.render_extent,
+            scissor,
+        )?;
 
-        if line_count > 0 {
-            context
-                .device
-                .cmd_bind_vertex_buffers(command_buffer, 1, &[line_buffer], &[0]);
-            context.device.cmd_draw_indexed(
-                command_buffer,
-                RECT_INDICES.len() as u32,
-                line_count,
-                0,
-                0,
-                0,
-            );
-        }
+        self.end_frame(&context.device, command_buffer, image_index)
+    }
 
-        if new_line_count > 0 {
-            context
-                .device
-                .cmd_bind_vertex_buffers(command_buffer, 1, &[staging_line_buffer], &[0]);
-            context.device.cmd_draw_indexed(
+    /// Ends the render pass, blits a scaled frame to the swapchain image and
+    /// finishes the command buffer
+    unsafe fn end_frame(
+        &mut self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        image_index: usize,
+    ) -> Result<()> {
+        device.cmd_end_render_pass(command_buffer);
+
+        if let Some(scaled) = &self.scaled {
+            scaled.cmd_blit(
+                device,
                 command_buffer,
-                RECT_INDICES.len() as u32,
-                new_line_count,
-                0,
-                0,
-                0,
+                image_index,
+                self.swapchain_images[image_index],
+                self.swapchain_extent,
+                self.letterbox,
             );
         }
 
-        context.device.cmd_end_render_pass(command_buffer);
-        context.device.end_command_buffer(command_buffer)?;
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.cmd_end(device, command_buffer, self.frame);
+        }
 
+        device.end_command_buffer(command_buffer)?;
         Ok(())
     }
 
+    /// What the renderer is doing, for reports of a GPU hang, with the acquired image if there is one
+    pub fn diagnostics(&self, image_index: Option<usize>) -> String {
+        format!(
+            "frame slot {}, image {}, {} bytes of uploads queued, swapchain {}x{} {:?} with {} images, last present id {}",
+            self.frame,
+            image_index.map_or("none".to_string(), |i| i.to_string()),
+            self.uploads.pending_bytes(),
+            self.swapchain_extent.width,
+            self.swapchain_extent.height,
+            self.swapchain_format,
+            self.swapchain_images.len(),
+            self.pacer.present_id(),
+        )
+    }
+
     /// Recreates the swapchain and dependent resources
     pub unsafe fn recreate_swapchain(
         &mut self,
@@ -347,20 +919,33 @@ impl Renderer {
         context: &VulkanContext,
         config: &Config,
     ) -> Result<()> {
+        let watchdog = Watchdog::arm(
+            "its work before the swapchain is replaced",
+            config.vulkan.gpu_timeout(),
+            self.diagnostics(None),
+        );
         context.device.device_wait_idle()?;
-        self.destroy_swapchain(&context.device);
+        drop(watchdog);
+        self.pacer.swapchain_replaced();
+        self.destroy_swapchain();
 
-        let (swapchain, swapchain_images, swapchain_format, swapchain_extent) = create_swapchain(
-            window,
-            &context.instance,
-            &context.device,
-            context.surface,
-            context.physical_device,
-        )?;
+        let (swapchain, swapchain_images, swapchain_format, swapchain_extent, composite_alpha) =
+            create_swapchain(
+                window,
+                &context.instance,
+                &context.device,
+                context.surface,
+                context.physical_device,
+                self.partial_redraw,
+                config.vulkan.present_mode,
+                config.vulkan.composite_alpha,
+                config.window.is_transparent(),
+            )?;
         self.swapchain = swapchain;
         self.swapchain_images = swapchain_images;
         self.swapchain_format = swapchain_format;
         self.swapchain_extent = swapchain_extent;
+        self.composite_alpha = composite_alpha;
 
         self.swapchain_image_views = create_swapchain_image_views(
             &context.device,
@@ -368,22 +953,73 @@ impl Renderer {
             self.swapchain_format,
         )?;
 
-        self.render_pass = create_render_pass(&context.device, self.swapchain_format)?;
+        self.scaled = create_scaled_targets(
+            context,
+            config,
+            self.swapchain_format,
+            self.swapchain_extent,
+            self.swapchain_images.len(),
+        )?;
+        self.render_extent = self
+            .scaled
+            .as_ref()
+            .map_or(self.swapchain_extent, |s| s.extent);
+        self.canvas_extent = canvas_extent(config, &self.scaled, self.swapchain_extent);
+        self.letterbox = letterbox(self.canvas_extent, self.swapchain_extent);
+        let layout = frame_layout(&self.scaled);
+
+        self.render_pass =
+            create_render_pass(&context.device, self.swapchain_format, false, layout)?;
+        self.load_render_pass =
+            create_render_pass(&context.device, self.swapchain_format, true, layout)?;
 
-        let (pipeline, pipeline_layout) = create_pipeline(
+        self.pipelines = PipelineRegistry::create(
             &context.device,
-            self.swapchain_extent,
+            context.pipeline_cache,
+            self.render_extent,
             self.render_pass,
+            self.frame_uniforms.layout,
             &config.shaders,
+            config.background.shader.as_deref(),
+            config.window.is_transparent(),
+            context.vertex_format,
+            self.swapchain_format,
+        )?;
+
+        self.bloom = create_bloom(
+            context,
+            config,
+            self.render_pass,
+            self.frame_uniforms.layout,
+            self.swapchain_format,
+            self.render_extent,
+            self.swapchain_images.len(),
         )?;
 
-        self.pipeline = pipeline;
-        self.pipeline_layout = pipeline_layout;
+        self.erase = create_erase_layer(
+            context,
+            config,
+            self.render_pass,
+            self.bloom.as_ref(),
+            self.frame_uniforms.layout,
+            self.swapchain_format,
+            self.render_extent,
+            self.swapchain_images.len(),
+        )?;
+
+        self.ui.recreate_pipeline(
+            context,
+            self.render_pass,
+            &config.shaders,
+            self.swapchain_format,
+        )?;
 
         self.framebuffers = create_framebuffers(
             &context.device,
-            &self.swapchain_image_views,
-            self.swapchain_extent,
+            self.scaled
+                .as_ref()
+                .map_or(&self.swapchain_image_views, |s| &s.image_views),
+            self.render_extent,
             self.render_pass,
         )?;
 
@@ -393,38 +1029,334 @@ impl Renderer {
         self.images_in_flight
             .resize(self.swapchain_images.len(), vk::Fence::null());
 
+        // New images have no valid contents to load from
+        self.damage = vec![Damage::Full; self.swapchain_images.len()];
+        self.recorded = vec![None; self.swapchain_images.len()];
+
         Ok(())
     }
 
-    /// Destroys swapchain-dependent resources
-    unsafe fn destroy_swapchain(&self, device: &Device) {
-        self.framebuffers
-            .iter()
-            .for_each(|f| device.destroy_framebuffer(*f, None));
-        device.destroy_pipeline(self.pipeline, None);
-        device.destroy_pipeline_layout(self.pipeline_layout, None);
-        device.destroy_render_pass(self.render_pass, None);
-        self.swapchain_image_views
-            .iter()
-            .for_each(|v| device.destroy_image_view(*v, None));
-        device.destroy_swapchain_khr(self.swapchain, None);
+    /// Destroys swapchain-dependent resources, leaving null handles so nothing is destroyed twice
+    unsafe fn destroy_swapchain(&mut self) {
+        let device = &self.device;
+        std::mem::take(&mut self.framebuffers).destroy(device);
+        std::mem::take(&mut self.pipelines).destroy(device);
+        std::mem::take(&mut self.erase).destroy(device);
+        std::mem::take(&mut self.bloom).destroy(device);
+        std::mem::take(&mut self.render_pass).destroy(device);
+        std::mem::take(&mut self.load_render_pass).destroy(device);
+        std::mem::take(&mut self.scaled).destroy(device);
+        std::mem::take(&mut self.swapchain_image_views).destroy(device);
+        std::mem::take(&mut self.swapchain).destroy(device);
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(e) = self.device.device_wait_idle() {
+                log::warn!(
+                    "Failed to wait for the GPU before destroying the renderer: {}",
+                    e
+                );
+            }
+
+            self.destroy_swapchain();
+            let device = &self.device;
+            self.ui.destroy(device);
+            self.uploads.destroy(device);
+            self.frame_uniforms.destroy(device);
+            self.gpu_timer.destroy(device);
+            self.in_flight_fences.destroy(device);
+            self.render_finished_semaphores.destroy(device);
+            self.image_available_semaphores.destroy(device);
+            // Frees the command buffers too
+            self.command_pools.destroy(device);
+        }
+    }
+}
+
+/// Targets to draw frames into at `[window] canvas_size` and `[vulkan]
+/// resolution_scale`, `None` when that is the swapchain extent
+unsafe fn create_scaled_targets(
+    context: &VulkanContext,
+    config: &Config,
+    format: vk::Format,
+    swapchain_extent: vk::Extent2D,
+    count: usize,
+) -> Result<Option<ScaledTargets>> {
+    let scale = config.vulkan.resolution_scale;
+    match scaled_extent(
+        context,
+        format,
+        swapchain_extent,
+        fixed_canvas(config),
+        scale,
+    )? {
+        Some(extent) => Ok(Some(ScaledTargets::create(context, format, extent, count)?)),
+        None => Ok(None),
+    }
+}
+
+/// What adds a glow to strokes drawn into `render_pass`, `None` without `[bloom] enabled`
+unsafe fn create_bloom(
+    context: &VulkanContext,
+    config: &Config,
+    render_pass: vk::RenderPass,
+    frame_layout: vk::DescriptorSetLayout,
+    format: vk::Format,
+    render_extent: vk::Extent2D,
+    count: usize,
+) -> Result<Option<Bloom>> {
+    if !config.bloom.enabled {
+        return Ok(None);
     }
+    let bloom = Bloom::create(
+        context,
+        frame_layout,
+        &config.bloom,
+        &config.shaders,
+        config.background.shader.as_deref(),
+        render_pass,
+        format,
+        render_extent,
+        config.window.is_transparent(),
+        count,
+    )?;
+    Ok(Some(bloom))
+}
+
+/// The erase layer compositing into the bloom scene with `bloom`, else into `render_pass`
+unsafe fn create_erase_layer(
+    context: &VulkanContext,
+    config: &Config,
+    render_pass: vk::RenderPass,
+    bloom: Option<&Bloom>,
+    frame_layout: vk::DescriptorSetLayout,
+    format: vk::Format,
+    render_extent: vk::Extent2D,
+    count: usize,
+) -> Result<EraseLayer> {
+    let (render_pass, format) = match bloom {
+        Some(bloom) => (bloom.scene_render_pass(), HDR_FORMAT),
+        None => (render_pass, format),
+    };
+    EraseLayer::create(
+        context,
+        frame_layout,
+        &config.shaders,
+        render_pass,
+        format,
+        render_extent,
+        count,
+    )
+}
+
+fn fixed_canvas(config: &Config) -> Option<vk::Extent2D> {
+    config
+        .window
+        .canvas_size
+        .map(|[width, height]| vk::Extent2D { width, height })
+}
+
+/// The canvas extent with `scaled` targets (or none) for `swapchain_extent`
+///
+/// Drawing straight into the swapchain, whether because the canvas already
+/// matches or as a fallback, leaves the canvas the swapchain's size.
+fn canvas_extent(
+    config: &Config,
+    scaled: &Option<ScaledTargets>,
+    swapchain_extent: vk::Extent2D,
+) -> vk::Extent2D {
+    match scaled {
+        Some(_) => fixed_canvas(config).unwrap_or(swapchain_extent),
+        None => swapchain_extent,
+    }
+}
+
+/// The layout frames are kept in between renders: ready to present, or to blit from
+fn frame_layout(scaled: &Option<ScaledTargets>) -> vk::ImageLayout {
+    match scaled {
+        Some(_) => vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        None => vk::ImageLayout::PRESENT_SRC_KHR,
+    }
+}
+
+/// Push constants of the stroke pipeline, laid out like the block in `shader.vert` and `shader.frag`
+#[repr(C)]
+pub struct StrokeConstants {
+    /// `(offset_x, offset_y, scale, thickness)`
+    pub transform: Vec4,
+    pub min_luminance: f32,
+    /// Viewport width over height, to undo the stretch of NDC on non-square windows
+    pub aspect: f32,
+    /// Seconds since the app started, for fading strokes
+    pub time: f32,
+    /// Tag bits of segments the vertex shader drops
+    pub hidden_tags: u32,
+    /// Brush of the pipeline drawing, the vertex shader drops other brushes' segments
+    pub brush: u32,
+}
 
-    /// Destroys all renderer resources
-    pub unsafe fn destroy(&self, device: &Device) {
-        self.destroy_swapchain(device);
-
-        self.in_flight_fences
-            .iter()
-            .for_each(|f| device.destroy_fence(*f, None));
-        self.render_finished_semaphores
-            .iter()
-            .for_each(|s| device.destroy_semaphore(*s, None));
-        self.image_available_semaphores
-            .iter()
-            .for_each(|s| device.destroy_semaphore(*s, None));
-        self.command_pools
-            .iter()
-            .for_each(|p| device.destroy_command_pool(*p, None));
+/// Push constants of the background pipeline, laid out like the block in the
+/// `[shaders.backgrounds]` shaders
+#[repr(C)]
+pub struct BackgroundConstants {
+    /// The canvas background in linear light, premultiplied where the clear color is
+    pub color: Vec4,
+    /// Viewport width over height
+    pub aspect: f32,
+    /// Seconds the animation has run, times `[background] speed`
+    pub time: f32,
+}
+
+/// Records drawing the animated background of `pipelines`, if it has one,
+/// over the whole frame of `extent`, inside an active render pass and before
+/// the strokes
+pub unsafe fn cmd_draw_background(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    pipelines: &PipelineRegistry,
+    frame: FrameBinding,
+    extent: vk::Extent2D,
+    constants: &BackgroundConstants,
+) {
+    let Some(&(pipeline, pipeline_layout)) = pipelines.get(Primitive::Background).first() else {
+        return;
+    };
+    device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+    let params = pipelines.param_block(Primitive::Background, 0);
+    frame.cmd_bind(device, command_buffer, pipeline_layout, params);
+
+    let viewport = vk::Viewport::builder()
+        .width(extent.width as f32)
+        .height(extent.height as f32)
+        .max_depth(1.0);
+    let scissor = vk::Rect2D {
+        offset: vk::Offset2D::default(),
+        extent,
+    };
+    device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+    device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+
+    let bytes = std::slice::from_raw_parts(
+        constants as *const BackgroundConstants as *const u8,
+        size_of::<BackgroundConstants>(),
+    );
+    device.cmd_push_constants(
+        command_buffer,
+        pipeline_layout,
+        vk::ShaderStageFlags::FRAGMENT,
+        0,
+        bytes,
+    );
+    device.cmd_draw(command_buffer, 3, 1, 0, 0);
+}
+
+/// Records the stroke draw calls into `command_buffer` inside an active render pass
+///
+/// Each batch is an instance buffer of `Line`s and the range of lines to draw
+/// from it. `pipelines` holds one [`Primitive::Lines`] pipeline per brush,
+/// and every batch is drawn with each of them, binding `frame` with the
+/// brush's parameter block; the vertex shader drops the segments of other brushes. Outside transparent windows stroke blending keeps the brightest
+/// color rather than the latest, so drawing brush by brush rather than in
+/// stroke order looks the same.
+pub unsafe fn cmd_draw_lines(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    pipelines: &PipelineRegistry,
+    frame: FrameBinding,
+    scissor: vk::Rect2D,
+    camera: &Camera,
+    style: &StrokeStyle,
+    rect_buffer: vk::Buffer,
+    index_buffer: vk::Buffer,
+    batches: &[(vk::Buffer, Range<u32>)],
+) {
+    let lines = pipelines.get(Primitive::Lines);
+    for (brush, &pipeline) in lines.iter().enumerate() {
+        cmd_draw_brush(
+            device,
+            command_buffer,
+            pipeline,
+            frame,
+            pipelines.param_block(Primitive::Lines, brush),
+            brush as u32,
+            scissor,
+            camera,
+            style,
+            rect_buffer,
+            index_buffer,
+            batches,
+        );
+    }
+}
+
+/// Records drawing the segments of brush `brush` in `batches` with
+/// `pipeline`, binding `frame` with parameter block `params`
+pub unsafe fn cmd_draw_brush(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    (pipeline, pipeline_layout): (vk::Pipeline, vk::PipelineLayout),
+    frame: FrameBinding,
+    params: u32,
+    brush: u32,
+    scissor: vk::Rect2D,
+    camera: &Camera,
+    style: &StrokeStyle,
+    rect_buffer: vk::Buffer,
+    index_buffer: vk::Buffer,
+    batches: &[(vk::Buffer, Range<u32>)],
+) {
+    device.cmd_bind_index_buffer(command_buffer, index_buffer, 0, vk::IndexType::UINT16);
+
+    device.cmd_bind_vertex_buffers(command_buffer, 0, &[rect_buffer], &[0]);
+
+    device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+
+    frame.cmd_bind(device, command_buffer, pipeline_layout, params);
+
+    device.cmd_set_scissor(command_buffer, 0, &[scissor]);
+
+    let constants = StrokeConstants {
+        transform: camera.push_constant().extend(style.thickness),
+        min_luminance: style.min_luminance,
+        aspect: camera.aspect,
+        time: style.time,
+        hidden_tags: style.hidden_tags,
+        brush,
+    };
+
+    let view_bytes = std::slice::from_raw_parts(
+        &constants as *const StrokeConstants as *const u8,
+        size_of::<StrokeConstants>(),
+    );
+
+    device.cmd_push_constants(
+        command_buffer,
+        pipeline_layout,
+        vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+        0,
+        view_bytes,
+    );
+
+    let mut bound = None;
+    for (line_buffer, lines) in batches {
+        if lines.is_empty() {
+            continue;
+        }
+
+        if bound != Some(*line_buffer) {
+            device.cmd_bind_vertex_buffers(command_buffer, 1, &[*line_buffer], &[0]);
+            bound = Some(*line_buffer);
+        }
+        device.cmd_draw_indexed(
+            command_buffer,
+            RECT_INDICES.len() as u32,
+            lines.len() as u32,
+            0,
+            0,
+            lines.start,
+        );
     }
 }
@@ -0,0 +1,315 @@
+use anyhow::Result;
+use vulkanalia::prelude::v1_0::*;
+
+use super::context::VulkanContext;
+use super::device::SwapchainSupport;
+use super::image::{create_image, create_image_view};
+use super::owned::{guard, Destroy};
+
+/// Color images frames are drawn into at a resolution other than the
+/// swapchain's, one per swapchain image, and blitted to it at the end
+///
+/// That is the swapchain extent times `[vulkan] resolution_scale`, or the
+/// fixed `[window] canvas_size` (times the scale), which is blitted into the
+/// [`letterbox`] with bars around it.
+///
+/// Each keeps its contents between frames like a swapchain image would, so
+/// partial redraws work the same. Between frames they are in
+/// `TRANSFER_SRC_OPTIMAL`, the layout the render passes leave them in.
+pub struct ScaledTargets {
+    pub extent: vk::Extent2D,
+    pub images: Vec<(vk::Image, vk::DeviceMemory)>,
+    pub image_views: Vec<vk::ImageView>,
+    /// `LINEAR` where the format can be filtered, else `NEAREST`
+    filter: vk::Filter,
+}
+
+impl ScaledTargets {
+    /// Creates `count` targets of `extent` pixels in the swapchain `format`
+    pub unsafe fn create(
+        context: &VulkanContext,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        count: usize,
+    ) -> Result<Self> {
+        let device = &context.device;
+
+        let mut images = guard(device, Vec::new());
+        let mut image_views = guard(device, Vec::new());
+        for _ in 0..count {
+            let image = create_image(
+                &context.instance,
+                device,
+                context.physical_device,
+                extent.width,
+                extent.height,
+                1,
+                vk::SampleCountFlags::_1,
+                format,
+                vk::ImageTiling::OPTIMAL,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?;
+            images.push(image);
+            image_views.push(create_image_view(
+                device,
+                image.0,
+                format,
+                vk::ImageAspectFlags::COLOR,
+            )?);
+        }
+
+        let features = context
+            .instance
+            .get_physical_device_format_properties(context.physical_device, format)
+            .optimal_tiling_features;
+        let filter = if features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR) {
+            vk::Filter::LINEAR
+        } else {
+            vk::Filter::NEAREST
+        };
+
+        Ok(Self {
+            extent,
+            images: images.release(),
+            image_views: image_views.release(),
+            filter,
+        })
+    }
+
+    /// Records scaling target `index` into `letterbox` of `swapchain_image`,
+    /// after the render pass and leaving the swapchain image ready to present
+    ///
+    /// The whole swapchain image is overwritten, so its old contents are
+    /// discarded: what the letterbox leaves of it is cleared to transparent black.
+    pub unsafe fn cmd_blit(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        index: usize,
+        swapchain_image: vk::Image,
+        swapchain_extent: vk::Extent2D,
+        letterbox: vk::Rect2D,
+    ) {
+        let subresource_range = vk::ImageSubresourceRange::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1)
+            .build();
+        let barrier = |image, old_layout, new_layout, src_access, dst_access| {
+            vk::ImageMemoryBarrier::builder()
+                .image(image)
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .subresource_range(subresource_range)
+                .build()
+        };
+
+        // The render pass wrote the target, and the swapchain image was only acquired
+        let target = self.images[index].0;
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[] as &[vk::MemoryBarrier],
+            &[] as &[vk::BufferMemoryBarrier],
+            &[
+                barrier(
+                    target,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    vk::AccessFlags::TRANSFER_READ,
+                ),
+                barrier(
+                    swapchain_image,
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::AccessFlags::empty(),
+                    vk::AccessFlags::TRANSFER_WRITE,
+                ),
+            ],
+        );
+
+        if letterbox.extent != swapchain_extent {
+            device.cmd_clear_color_image(
+                command_buffer,
+                swapchain_image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 0.0],
+                },
+                &[subresource_range],
+            );
+            // The blit overwrites part of what was just cleared
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[] as &[vk::MemoryBarrier],
+                &[] as &[vk::BufferMemoryBarrier],
+                &[barrier(
+                    swapchain_image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::AccessFlags::TRANSFER_WRITE,
+                )],
+            );
+        }
+
+        let subresource = vk::ImageSubresourceLayers::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .mip_level(0)
+            .base_array_layer(0)
+            .layer_count(1);
+        let corner = |offset: vk::Offset2D, extent: vk::Extent2D| vk::Offset3D {
+            x: offset.x + extent.width as i32,
+            y: offset.y + extent.height as i32,
+            z: 1,
+        };
+        let origin = |offset: vk::Offset2D| vk::Offset3D {
+            x: offset.x,
+            y: offset.y,
+            z: 0,
+        };
+        let blit = vk::ImageBlit::builder()
+            .src_subresource(subresource)
+            .src_offsets([
+                vk::Offset3D::default(),
+                corner(vk::Offset2D::default(), self.extent),
+            ])
+            .dst_subresource(subresource)
+            .dst_offsets([
+                origin(letterbox.offset),
+                corner(letterbox.offset, letterbox.extent),
+            ]);
+
+        device.cmd_blit_image(
+            command_buffer,
+            target,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            swapchain_image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[blit],
+            self.filter,
+        );
+
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[] as &[vk::MemoryBarrier],
+            &[] as &[vk::BufferMemoryBarrier],
+            &[barrier(
+                swapchain_image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::empty(),
+            )],
+        );
+    }
+}
+
+impl Destroy for ScaledTargets {
+    unsafe fn destroy(&mut self, device: &Device) {
+        self.image_views.destroy(device);
+        self.images.destroy(device);
+    }
+}
+
+/// The size to draw frames at: `canvas` (or else `swapchain_extent`) times
+/// `[vulkan] resolution_scale`, or `None` to draw straight into the swapchain images
+///
+/// That is also the fallback, with a warning, when the device can't blit
+/// `format` into the swapchain or the scaled size exceeds its image limits.
+pub unsafe fn scaled_extent(
+    context: &VulkanContext,
+    format: vk::Format,
+    swapchain_extent: vk::Extent2D,
+    canvas: Option<vk::Extent2D>,
+    scale: f32,
+) -> Result<Option<vk::Extent2D>> {
+    let base = canvas.unwrap_or(swapchain_extent);
+    let scaled = |size: u32| ((size as f32 * scale).round() as u32).max(1);
+    let extent = vk::Extent2D {
+        width: scaled(base.width),
+        height: scaled(base.height),
+    };
+    if extent == swapchain_extent {
+        return Ok(None);
+    }
+
+    let (instance, physical_device) = (&context.instance, context.physical_device);
+    let usage = SwapchainSupport::get(instance, context.surface, physical_device)?
+        .capabilities
+        .supported_usage_flags;
+    let features = instance
+        .get_physical_device_format_properties(physical_device, format)
+        .optimal_tiling_features;
+    let max_size = instance
+        .get_physical_device_properties(physical_device)
+        .limits
+        .max_image_dimension_2d;
+
+    let problem = if !usage.contains(vk::ImageUsageFlags::TRANSFER_DST) {
+        Some("the swapchain can't be blitted to".to_string())
+    } else if !features
+        .contains(vk::FormatFeatureFlags::BLIT_SRC | vk::FormatFeatureFlags::BLIT_DST)
+    {
+        Some(format!("{:?} images can't be blitted", format))
+    } else if extent.width.max(extent.height) > max_size {
+        Some(format!(
+            "{}x{} exceeds the device's {} pixel limit",
+            extent.width, extent.height, max_size
+        ))
+    } else {
+        None
+    };
+
+    match problem {
+        Some(problem) => {
+            log::warn!(
+                "Drawing at the window's size instead of {}x{}, {}.",
+                extent.width,
+                extent.height,
+                problem
+            );
+            Ok(None)
+        }
+        None => Ok(Some(extent)),
+    }
+}
+
+/// Where a frame of `canvas` pixels goes in a window of `window` pixels:
+/// as large as fits without stretching, centered
+///
+/// A canvas of the window's shape fills it.
+pub fn letterbox(canvas: vk::Extent2D, window: vk::Extent2D) -> vk::Rect2D {
+    let scale = (window.width as f32 / canvas.width.max(1) as f32)
+        .min(window.height as f32 / canvas.height.max(1) as f32);
+    let fit = |size: u32, available: u32| {
+        ((size as f32 * scale).round() as u32).clamp(1, available.max(1))
+    };
+    let extent = vk::Extent2D {
+        width: fit(canvas.width, window.width),
+        height: fit(canvas.height, window.height),
+    };
+    vk::Rect2D {
+        offset: vk::Offset2D {
+            x: ((window.width - extent.width.min(window.width)) / 2) as i32,
+            y: ((window.height - extent.height.min(window.height)) / 2) as i32,
+        },
+        extent,
+    }
+}
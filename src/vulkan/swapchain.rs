@@ -1,9 +1,12 @@
 use anyhow::Result;
+use log::*;
 use vulkanalia::prelude::v1_0::*;
 use vulkanalia::vk::KhrSwapchainExtensionDeviceCommands;
 use winit::window::Window;
 
 use super::device::{QueueFamilyIndices, SwapchainSupport};
+use super::owned::guard;
+use crate::config::{CompositeAlpha, PresentMode};
 
 //================================================
 // Swapchain Creation
@@ -15,14 +18,26 @@ pub unsafe fn create_swapchain(
     device: &Device,
     surface: vk::SurfaceKHR,
     physical_device: vk::PhysicalDevice,
-) -> Result<(vk::SwapchainKHR, Vec<vk::Image>, vk::Format, vk::Extent2D)> {
+    preserve_contents: bool,
+    present_mode: PresentMode,
+    composite_alpha: CompositeAlpha,
+    transparent: bool,
+) -> Result<(
+    vk::SwapchainKHR,
+    Vec<vk::Image>,
+    vk::Format,
+    vk::Extent2D,
+    vk::CompositeAlphaFlagsKHR,
+)> {
     // Get swapchain support
     let indices = QueueFamilyIndices::get(instance, surface, physical_device)?;
     let support = SwapchainSupport::get(instance, surface, physical_device)?;
 
     let surface_format = get_swapchain_surface_format(&support.formats);
-    let present_mode = get_swapchain_present_mode(&support.present_modes);
+    let present_mode = get_swapchain_present_mode(&support.present_modes, present_mode);
     let extent = get_swapchain_extent(window, support.capabilities);
+    let composite_alpha =
+        get_swapchain_composite_alpha(support.capabilities, composite_alpha, transparent);
 
     let mut image_count = support.capabilities.min_image_count + 1;
     if support.capabilities.max_image_count != 0
@@ -31,6 +46,10 @@ pub unsafe fn create_swapchain(
         image_count = support.capabilities.max_image_count;
     }
 
+    // Frames drawn at a resolution scale are blitted into the images
+    let image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT
+        | (support.capabilities.supported_usage_flags & vk::ImageUsageFlags::TRANSFER_DST);
+
     let mut queue_family_indices = vec![];
     let image_sharing_mode = if indices.graphics != indices.present {
         queue_family_indices.push(indices.graphics);
@@ -48,19 +67,26 @@ pub unsafe fn create_swapchain(
         .image_color_space(surface_format.color_space)
         .image_extent(extent)
         .image_array_layers(1)
-        .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+        .image_usage(image_usage)
         .image_sharing_mode(image_sharing_mode)
         .queue_family_indices(&queue_family_indices)
         .pre_transform(support.capabilities.current_transform)
-        .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+        .composite_alpha(composite_alpha)
         .present_mode(present_mode)
-        .clipped(true)
+        // Obscured pixels must stay valid when frames only redraw damaged regions
+        .clipped(!preserve_contents)
         .old_swapchain(vk::SwapchainKHR::null());
 
     let swapchain = device.create_swapchain_khr(&info, None)?;
     let images = device.get_swapchain_images_khr(swapchain)?;
 
-    Ok((swapchain, images, surface_format.format, extent))
+    Ok((
+        swapchain,
+        images,
+        surface_format.format,
+        extent,
+        composite_alpha,
+    ))
 }
 
 pub unsafe fn create_swapchain_image_views(
@@ -68,35 +94,105 @@ pub unsafe fn create_swapchain_image_views(
     swapchain_images: &[vk::Image],
     swapchain_format: vk::Format,
 ) -> Result<Vec<vk::ImageView>> {
-    swapchain_images
-        .iter()
-        .map(|&image| create_image_view(device, image, swapchain_format))
-        .collect::<Result<Vec<_>, _>>()
+    let mut views = guard(device, Vec::new());
+    for &image in swapchain_images {
+        views.push(create_image_view(device, image, swapchain_format)?);
+    }
+    Ok(views.release())
 }
 
 //================================================
 // Helper Functions
 //================================================
 
+/// 8-bit formats whose attachments encode to sRGB on write and blend in linear space
+const SRGB_FORMATS: [vk::Format; 3] = [
+    vk::Format::B8G8R8A8_SRGB,
+    vk::Format::R8G8B8A8_SRGB,
+    vk::Format::A8B8G8R8_SRGB_PACK32,
+];
+
+/// Whether attachments of `format` take linear colors and store them sRGB encoded
+///
+/// For any other format the fragment shaders encode colors themselves.
+pub fn is_srgb_format(format: vk::Format) -> bool {
+    SRGB_FORMATS.contains(&format)
+}
+
 fn get_swapchain_surface_format(formats: &[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR {
-    formats
+    let srgb = |f: &&vk::SurfaceFormatKHR| f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR;
+    if let Some(format) = SRGB_FORMATS
         .iter()
-        .cloned()
-        .find(|f| {
-            f.format == vk::Format::B8G8R8A8_SRGB
-                && f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
-        })
-        .unwrap_or_else(|| formats[0])
+        .find_map(|&format| formats.iter().filter(srgb).find(|f| f.format == format))
+    {
+        return *format;
+    }
+
+    // Still shown right in the sRGB color space, since the shaders encode its colors
+    *formats.iter().find(srgb).unwrap_or(&formats[0])
 }
 
-fn get_swapchain_present_mode(present_modes: &[vk::PresentModeKHR]) -> vk::PresentModeKHR {
+fn get_swapchain_present_mode(
+    present_modes: &[vk::PresentModeKHR],
+    preferred: PresentMode,
+) -> vk::PresentModeKHR {
+    let preferred = match preferred {
+        PresentMode::Mailbox => vk::PresentModeKHR::MAILBOX,
+        PresentMode::Fifo => vk::PresentModeKHR::FIFO,
+        PresentMode::Immediate => vk::PresentModeKHR::IMMEDIATE,
+    };
+
     present_modes
         .iter()
         .cloned()
-        .find(|m| *m == vk::PresentModeKHR::MAILBOX)
+        .find(|m| *m == preferred)
         .unwrap_or(vk::PresentModeKHR::FIFO)
 }
 
+/// How the compositor blends the window with what is behind it
+///
+/// An explicit `preferred` mode is used if the surface supports it. Otherwise
+/// a transparent window needs the frames' alpha to be honored, ideally as
+/// premultiplied colors, which is what the transparent pipeline writes. Surfaces
+/// that only composite opaquely get a warning and an opaque window.
+fn get_swapchain_composite_alpha(
+    capabilities: vk::SurfaceCapabilitiesKHR,
+    preferred: CompositeAlpha,
+    transparent: bool,
+) -> vk::CompositeAlphaFlagsKHR {
+    let supported = capabilities.supported_composite_alpha;
+    let explicit = match preferred {
+        CompositeAlpha::Auto => None,
+        CompositeAlpha::Opaque => Some(vk::CompositeAlphaFlagsKHR::OPAQUE),
+        CompositeAlpha::PreMultiplied => Some(vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED),
+        CompositeAlpha::PostMultiplied => Some(vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED),
+        CompositeAlpha::Inherit => Some(vk::CompositeAlphaFlagsKHR::INHERIT),
+    };
+    if let Some(mode) = explicit {
+        if supported.contains(mode) {
+            return mode;
+        }
+        warn!(
+            "The surface does not support {:?} composite alpha.",
+            preferred
+        );
+    }
+
+    if transparent {
+        let preferred = [
+            vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+            vk::CompositeAlphaFlagsKHR::INHERIT,
+            vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+        ];
+        if let Some(mode) = preferred.into_iter().find(|m| supported.contains(*m)) {
+            return mode;
+        }
+        warn!("The surface does not support transparency, the overlay will be opaque.");
+    }
+
+    vk::CompositeAlphaFlagsKHR::OPAQUE
+}
+
 fn get_swapchain_extent(window: &Window, capabilities: vk::SurfaceCapabilitiesKHR) -> vk::Extent2D {
     if capabilities.current_extent.width != u32::MAX {
         capabilities.current_extent
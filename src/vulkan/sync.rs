@@ -1,6 +1,8 @@
 use anyhow::Result;
 use vulkanalia::prelude::v1_0::*;
 
+use super::owned::guard;
+
 //================================================
 // Synchronization Objects
 //================================================
@@ -18,9 +20,9 @@ pub unsafe fn create_sync_objects(
     let semaphore_info = vk::SemaphoreCreateInfo::builder();
     let fence_info = vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
 
-    let mut image_available_semaphores = Vec::new();
-    let mut render_finished_semaphores = Vec::new();
-    let mut in_flight_fences = Vec::new();
+    let mut image_available_semaphores = guard(device, Vec::new());
+    let mut render_finished_semaphores = guard(device, Vec::new());
+    let mut in_flight_fences = guard(device, Vec::new());
 
     for _ in 0..swapchain_image_count {
         image_available_semaphores.push(device.create_semaphore(&semaphore_info, None)?);
@@ -34,9 +36,9 @@ pub unsafe fn create_sync_objects(
     let images_in_flight = vec![vk::Fence::null(); swapchain_image_count];
 
     Ok((
-        image_available_semaphores,
-        render_finished_semaphores,
-        in_flight_fences,
+        image_available_semaphores.release(),
+        render_finished_semaphores.release(),
+        in_flight_fences.release(),
         images_in_flight,
     ))
 }
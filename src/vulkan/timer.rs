@@ -0,0 +1,132 @@
+use anyhow::Result;
+use log::*;
+use std::time::Duration;
+use vulkanalia::prelude::v1_0::*;
+
+use super::context::VulkanContext;
+use super::device::QueueFamilyIndices;
+use super::owned::Destroy;
+
+/// Measures how long the GPU spends on each frame with timestamp queries
+///
+/// Every frame in flight has its own pair of queries, written at the start
+/// and end of its command buffer and read back once its fence has signaled.
+pub struct GpuTimer {
+    query_pool: vk::QueryPool,
+    /// Nanoseconds per timestamp tick
+    period: f64,
+    /// Bits of each timestamp that are meaningful
+    valid_mask: u64,
+    /// Whether the queries of each slot were written and not read back yet
+    pending: Vec<bool>,
+    last: Option<Duration>,
+}
+
+impl GpuTimer {
+    /// Creates a timer with `slots` query pairs, `None` if the device has no timestamps
+    pub unsafe fn create(context: &VulkanContext, slots: usize) -> Result<Option<Self>> {
+        let limits = context
+            .instance
+            .get_physical_device_properties(context.physical_device)
+            .limits;
+
+        let indices =
+            QueueFamilyIndices::get(&context.instance, context.surface, context.physical_device)?;
+        let valid_bits = context
+            .instance
+            .get_physical_device_queue_family_properties(context.physical_device)
+            [indices.graphics as usize]
+            .timestamp_valid_bits;
+
+        if valid_bits == 0 || limits.timestamp_period <= 0.0 {
+            info!("GPU timestamps are not supported, GPU frame times will be unavailable.");
+            return Ok(None);
+        }
+
+        let info = vk::QueryPoolCreateInfo::builder()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(2 * slots as u32);
+        let query_pool = context.device.create_query_pool(&info, None)?;
+
+        Ok(Some(Self {
+            query_pool,
+            period: limits.timestamp_period as f64,
+            valid_mask: u64::MAX >> (64 - valid_bits.min(64)),
+            pending: vec![false; slots],
+            last: None,
+        }))
+    }
+
+    /// GPU time of the most recently finished frame
+    pub fn last(&self) -> Option<Duration> {
+        self.last
+    }
+
+    /// Reads back the times of `slot`, whose fence must have signaled
+    pub unsafe fn collect(&mut self, device: &Device, slot: usize) -> Result<()> {
+        if !std::mem::take(&mut self.pending[slot]) {
+            return Ok(());
+        }
+
+        let mut timestamps = [0u64; 2];
+        let data = std::slice::from_raw_parts_mut(timestamps.as_mut_ptr() as *mut u8, 16);
+        let result = device.get_query_pool_results(
+            self.query_pool,
+            2 * slot as u32,
+            2,
+            data,
+            8,
+            vk::QueryResultFlags::_64,
+        )?;
+
+        if result == vk::SuccessCode::SUCCESS {
+            let ticks = (timestamps[1].wrapping_sub(timestamps[0])) & self.valid_mask;
+            self.last = Some(Duration::from_nanos((ticks as f64 * self.period) as u64));
+        }
+
+        Ok(())
+    }
+
+    /// Records the start timestamp of `slot`; call outside of a render pass
+    pub unsafe fn cmd_begin(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        slot: usize,
+    ) {
+        device.cmd_reset_query_pool(command_buffer, self.query_pool, 2 * slot as u32, 2);
+        device.cmd_write_timestamp(
+            command_buffer,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            self.query_pool,
+            2 * slot as u32,
+        );
+    }
+
+    /// Records the end timestamp of `slot`
+    pub unsafe fn cmd_end(
+        &mut self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        slot: usize,
+    ) {
+        device.cmd_write_timestamp(
+            command_buffer,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            self.query_pool,
+            2 * slot as u32 + 1,
+        );
+        self.pending[slot] = true;
+    }
+
+    /// Expects results for `slot` from a command buffer recorded earlier and submitted again
+    pub fn expect(&mut self, slot: usize) {
+        self.pending[slot] = true;
+    }
+}
+
+impl Destroy for GpuTimer {
+    unsafe fn destroy(&mut self, device: &Device) {
+        self.query_pool.destroy(device);
+    }
+}
@@ -0,0 +1,699 @@
+use anyhow::Result;
+use egui::epaint::{ImageDelta, Primitive, Vertex};
+use egui::{ClippedPrimitive, ImageData, TextureId};
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::ptr::copy_nonoverlapping as memcpy;
+use vulkanalia::prelude::v1_0::*;
+
+use super::buffer::create_buffer;
+use super::context::VulkanContext;
+use super::helpers::{begin_single_time_commands, end_single_time_commands};
+use super::image::{create_image, create_image_view};
+use super::owned::{guard, Destroy};
+use super::pipeline::create_ui_pipeline;
+use crate::config::ShaderConfig;
+use crate::types::Vec2;
+
+/// Textures egui can allocate before the descriptor pool runs out
+const MAX_TEXTURES: u32 = 64;
+
+/// A texture egui asked us to keep, e.g. the font atlas
+struct UiTexture {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    descriptor_set: vk::DescriptorSet,
+}
+
+/// Host-visible mesh buffers for one frame in flight, grown on demand
+#[derive(Default)]
+struct UiFrameBuffers {
+    vertex_buffer: vk::Buffer,
+    vertex_memory: vk::DeviceMemory,
+    vertex_capacity: usize,
+    index_buffer: vk::Buffer,
+    index_memory: vk::DeviceMemory,
+    index_capacity: usize,
+}
+
+/// Draws egui output on top of the strokes, inside the stroke render pass
+///
+/// Textures are uploaded as egui creates them; meshes are copied into
+/// per-frame buffers when the command buffer is recorded, after that frame's
+/// fence has been waited on.
+pub struct UiRenderer {
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    sampler: vk::Sampler,
+    textures: HashMap<TextureId, UiTexture>,
+    frames: Vec<UiFrameBuffers>,
+    primitives: Vec<ClippedPrimitive>,
+    pixels_per_point: f32,
+    /// Changes whenever the next recorded frame would draw something different
+    generation: u64,
+}
+
+impl UiRenderer {
+    pub unsafe fn create(
+        context: &VulkanContext,
+        render_pass: vk::RenderPass,
+        shader_config: &ShaderConfig,
+        color_format: vk::Format,
+        frames_in_flight: usize,
+    ) -> Result<Self> {
+        let device = &context.device;
+
+        let binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT);
+
+        let bindings = &[binding];
+        let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
+        let descriptor_set_layout =
+            guard(device, device.create_descriptor_set_layout(&info, None)?);
+
+        let pool_size = vk::DescriptorPoolSize::builder()
+            .type_(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(MAX_TEXTURES);
+
+        let pool_sizes = &[pool_size];
+        let info = vk::DescriptorPoolCreateInfo::builder()
+            .flags(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
+            .pool_sizes(pool_sizes)
+            .max_sets(MAX_TEXTURES);
+        let descriptor_pool = guard(device, device.create_descriptor_pool(&info, None)?);
+
+        let info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE);
+        let sampler = guard(device, device.create_sampler(&info, None)?);
+
+        let (pipeline, pipeline_layout) = create_ui_pipeline(
+            device,
+            context.pipeline_cache,
+            render_pass,
+            *descriptor_set_layout,
+            shader_config,
+            color_format,
+        )?;
+
+        Ok(Self {
+            pipeline,
+            pipeline_layout,
+            descriptor_set_layout: descriptor_set_layout.release(),
+            descriptor_pool: descriptor_pool.release(),
+            sampler: sampler.release(),
+            textures: HashMap::new(),
+            frames: (0..frames_in_flight)
+                .map(|_| UiFrameBuffers::default())
+                .collect(),
+            primitives: Vec::new(),
+            pixels_per_point: 1.0,
+            generation: 0,
+        })
+    }
+
+    /// Recreates the pipeline for a new render pass (after swapchain recreation)
+    pub unsafe fn recreate_pipeline(
+        &mut self,
+        context: &VulkanContext,
+        render_pass: vk::RenderPass,
+        shader_config: &ShaderConfig,
+        color_format: vk::Format,
+    ) -> Result<()> {
+        self.destroy_pipeline(&context.device);
+
+        let (pipeline, pipeline_layout) = create_ui_pipeline(
+            &context.device,
+            context.pipeline_cache,
+            render_pass,
+            self.descriptor_set_layout,
+            shader_config,
+            color_format,
+        )?;
+        self.pipeline = pipeline;
+        self.pipeline_layout = pipeline_layout;
+        self.generation += 1;
+
+        Ok(())
+    }
+
+    /// Sets the meshes drawn by the next recorded frames
+    pub fn set_primitives(&mut self, primitives: Vec<ClippedPrimitive>, pixels_per_point: f32) {
+        if pixels_per_point != self.pixels_per_point
+            || !same_primitives(&primitives, &self.primitives)
+        {
+            self.generation += 1;
+        }
+        self.primitives = primitives;
+        self.pixels_per_point = pixels_per_point;
+    }
+
+    /// Counts changes to what the UI draws, for reusing recorded command buffers
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Creates or updates the textures in an egui `TexturesDelta::set`
+    pub unsafe fn set_textures(
+        &mut self,
+        context: &VulkanContext,
+        textures: &[(TextureId, ImageDelta)],
+    ) -> Result<()> {
+        if textures.is_empty() {
+            return Ok(());
+        }
+
+        // Textures may still be sampled by frames in flight
+        context.device.device_wait_idle()?;
+        self.generation += 1;
+
+        for (id, delta) in textures {
+            let width = delta.image.width() as u32;
+            let height = delta.image.height() as u32;
+            let pixels = match &delta.image {
+                ImageData::Color(image) => image.pixels.clone(),
+                ImageData::Font(font) => font.srgba_pixels(None).collect(),
+            };
+
+            let offset = match delta.pos {
+                Some([x, y]) => vk::Offset3D {
+                    x: x as i32,
+                    y: y as i32,
+                    z: 0,
+                },
+                None => {
+                    // A whole new image replaces whatever had this id
+                    if let Some(texture) = self.textures.remove(id) {
+                        self.destroy_texture(&context.device, texture)?;
+                    }
+                    let texture = self.create_texture(context, width, height)?;
+                    self.textures.insert(*id, texture);
+                    vk::Offset3D::default()
+                }
+            };
+
+            let Some(texture) = self.textures.get(id) else {
+                continue;
+            };
+
+            let extent = vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            };
+            upload_pixels(
+                context,
+                texture.image,
+                delta.pos.is_none(),
+                offset,
+                extent,
+                color32_bytes(&pixels),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Destroys the textures in an egui `TexturesDelta::free`
+    pub unsafe fn free_textures(&mut self, device: &Device, textures: &[TextureId]) -> Result<()> {
+        if textures.is_empty() {
+            return Ok(());
+        }
+
+        device.device_wait_idle()?;
+        self.generation += 1;
+        for id in textures {
+            if let Some(texture) = self.textures.remove(id) {
+                self.destroy_texture(device, texture)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    unsafe fn create_texture(
+        &self,
+        context: &VulkanContext,
+        width: u32,
+        height: u32,
+    ) -> Result<UiTexture> {
+        let device = &context.device;
+        let format = vk::Format::R8G8B8A8_SRGB;
+
+        // Allocating the descriptor set fails once egui has made `MAX_TEXTURES` textures
+        let image = guard(
+            device,
+            create_image(
+                &context.instance,
+                device,
+                context.physical_device,
+                width,
+                height,
+                1,
+                vk::SampleCountFlags::_1,
+                format,
+                vk::ImageTiling::OPTIMAL,
+                vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?,
+        );
+
+        let view = guard(
+            device,
+            create_image_view(device, image.0, format, vk::ImageAspectFlags::COLOR)?,
+        );
+
+        let set_layouts = &[self.descriptor_set_layout];
+        let info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.descriptor_pool)
+            .set_layouts(set_layouts);
+        let descriptor_set = device.allocate_descriptor_sets(&info)?[0];
+
+        let image_info = vk::DescriptorImageInfo::builder()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(*view)
+            .sampler(self.sampler);
+
+        let image_infos = &[image_info];
+        let write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(image_infos);
+
+        device.update_descriptor_sets(&[write], &[] as &[vk::CopyDescriptorSet]);
+
+        let (image, memory) = image.release();
+        Ok(UiTexture {
+            image,
+            memory,
+            view: view.release(),
+            descriptor_set,
+        })
+    }
+
+    unsafe fn destroy_texture(&self, device: &Device, mut texture: UiTexture) -> Result<()> {
+        device.free_descriptor_sets(self.descriptor_pool, &[texture.descriptor_set])?;
+        texture.destroy(device);
+        Ok(())
+    }
+
+    /// Records the current egui meshes into `command_buffer` inside an active render pass
+    ///
+    /// Drawing is limited to `scissor`, the region of the image being redrawn.
+    pub unsafe fn record(
+        &mut self,
+        context: &VulkanContext,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        extent: vk::Extent2D,
+        scissor: vk::Rect2D,
+    ) -> Result<()> {
+        let meshes = self
+            .primitives
+            .iter()
+            .filter_map(|p| match &p.primitive {
+                Primitive::Mesh(mesh) => Some((p.clip_rect, mesh)),
+                Primitive::Callback(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        if meshes.is_empty() {
+            return Ok(());
+        }
+
+        let vertex_count = meshes.iter().map(|(_, m)| m.vertices.len()).sum::<usize>();
+        let index_count = meshes.iter().map(|(_, m)| m.indices.len()).sum::<usize>();
+        let buffers = &mut self.frames[frame];
+        let previous = (buffers.vertex_buffer, buffers.index_buffer);
+        reserve(context, buffers, vertex_count, index_count)?;
+        if (buffers.vertex_buffer, buffers.index_buffer) != previous {
+            // Frames recorded earlier still point at the old buffers
+            self.generation += 1;
+        }
+
+        let device = &context.device;
+        let buffers = &self.frames[frame];
+
+        // Copy every mesh into this frame's buffers
+        let vertices = device.map_memory(
+            buffers.vertex_memory,
+            0,
+            vk::WHOLE_SIZE,
+            vk::MemoryMapFlags::empty(),
+        )? as *mut Vertex;
+        let indices = device.map_memory(
+            buffers.index_memory,
+            0,
+            vk::WHOLE_SIZE,
+            vk::MemoryMapFlags::empty(),
+        )? as *mut u32;
+
+        let (mut first_vertex, mut first_index) = (0, 0);
+        for (_, mesh) in &meshes {
+            memcpy(
+                mesh.vertices.as_ptr(),
+                vertices.add(first_vertex),
+                mesh.vertices.len(),
+            );
+            memcpy(
+                mesh.indices.as_ptr(),
+                indices.add(first_index),
+                mesh.indices.len(),
+            );
+            first_vertex += mesh.vertices.len();
+            first_index += mesh.indices.len();
+        }
+
+        device.unmap_memory(buffers.vertex_memory);
+        device.unmap_memory(buffers.index_memory);
+
+        // Draw
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            self.pipeline,
+        );
+
+        let viewport = vk::Viewport::builder()
+            .x(0.0)
+            .y(0.0)
+            .width(extent.width as f32)
+            .height(extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0);
+        device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+
+        device.cmd_bind_vertex_buffers(command_buffer, 0, &[buffers.vertex_buffer], &[0]);
+        device.cmd_bind_index_buffer(
+            command_buffer,
+            buffers.index_buffer,
+            0,
+            vk::IndexType::UINT32,
+        );
+
+        let screen_size = Vec2::new(
+            extent.width as f32 / self.pixels_per_point,
+            extent.height as f32 / self.pixels_per_point,
+        );
+        let screen_size_bytes =
+            std::slice::from_raw_parts(&screen_size as *const Vec2 as *const u8, size_of::<Vec2>());
+        device.cmd_push_constants(
+            command_buffer,
+            self.pipeline_layout,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            screen_size_bytes,
+        );
+
+        let (mut first_vertex, mut first_index) = (0, 0);
+        for (clip_rect, mesh) in &meshes {
+            let texture = self.textures.get(&mesh.texture_id);
+            let clip = clip_to_scissor(*clip_rect, self.pixels_per_point, scissor);
+
+            if let (Some(texture), Some(clip)) = (texture, clip) {
+                device.cmd_set_scissor(command_buffer, 0, &[clip]);
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.pipeline_layout,
+                    0,
+                    &[texture.descriptor_set],
+                    &[],
+                );
+                device.cmd_draw_indexed(
+                    command_buffer,
+                    mesh.indices.len() as u32,
+                    1,
+                    first_index as u32,
+                    first_vertex as i32,
+                    0,
+                );
+            }
+
+            first_vertex += mesh.vertices.len();
+            first_index += mesh.indices.len();
+        }
+
+        Ok(())
+    }
+
+    /// Destroys the pipeline, leaving null handles so it is never destroyed twice
+    unsafe fn destroy_pipeline(&mut self, device: &Device) {
+        std::mem::take(&mut self.pipeline).destroy(device);
+        std::mem::take(&mut self.pipeline_layout).destroy(device);
+    }
+}
+
+impl Destroy for UiRenderer {
+    unsafe fn destroy(&mut self, device: &Device) {
+        // Descriptor sets go with the pool
+        for (_, mut texture) in std::mem::take(&mut self.textures) {
+            texture.destroy(device);
+        }
+
+        self.frames.destroy(device);
+        self.destroy_pipeline(device);
+        self.sampler.destroy(device);
+        self.descriptor_pool.destroy(device);
+        self.descriptor_set_layout.destroy(device);
+    }
+}
+
+impl Destroy for UiTexture {
+    unsafe fn destroy(&mut self, device: &Device) {
+        self.view.destroy(device);
+        (self.image, self.memory).destroy(device);
+    }
+}
+
+impl Destroy for UiFrameBuffers {
+    unsafe fn destroy(&mut self, device: &Device) {
+        (self.vertex_buffer, self.vertex_memory).destroy(device);
+        (self.index_buffer, self.index_memory).destroy(device);
+    }
+}
+
+/// Whether two frames of egui output draw exactly the same meshes
+fn same_primitives(a: &[ClippedPrimitive], b: &[ClippedPrimitive]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| {
+            a.clip_rect == b.clip_rect
+                && match (&a.primitive, &b.primitive) {
+                    (Primitive::Mesh(a), Primitive::Mesh(b)) => a == b,
+                    // Callbacks can't be compared, so they always count as changed
+                    _ => false,
+                }
+        })
+}
+
+/// Grows `buffers` so they hold at least the given number of vertices and indices
+unsafe fn reserve(
+    context: &VulkanContext,
+    buffers: &mut UiFrameBuffers,
+    vertex_count: usize,
+    index_count: usize,
+) -> Result<()> {
+    let properties = vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+
+    if vertex_count > buffers.vertex_capacity {
+        let capacity = vertex_count.next_power_of_two();
+        // The old buffers stay valid until the new ones exist, so a failure leaves nothing dangling
+        let grown = create_buffer(
+            &context.instance,
+            &context.device,
+            context.physical_device,
+            (size_of::<Vertex>() * capacity) as u64,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            properties,
+        )?;
+        (buffers.vertex_buffer, buffers.vertex_memory).destroy(&context.device);
+        (buffers.vertex_buffer, buffers.vertex_memory) = grown;
+        buffers.vertex_capacity = capacity;
+    }
+
+    if index_count > buffers.index_capacity {
+        let capacity = index_count.next_power_of_two();
+        let grown = create_buffer(
+            &context.instance,
+            &context.device,
+            context.physical_device,
+            (size_of::<u32>() * capacity) as u64,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            properties,
+        )?;
+        (buffers.index_buffer, buffers.index_memory).destroy(&context.device);
+        (buffers.index_buffer, buffers.index_memory) = grown;
+        buffers.index_capacity = capacity;
+    }
+
+    Ok(())
+}
+
+/// Views egui colors as the raw RGBA bytes they are stored as
+fn color32_bytes(pixels: &[egui::Color32]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(pixels.as_ptr() as *const u8, pixels.len() * 4) }
+}
+
+/// Converts an egui clip rectangle (in points) to pixels, limited to `scissor`
+fn clip_to_scissor(
+    clip_rect: egui::Rect,
+    pixels_per_point: f32,
+    scissor: vk::Rect2D,
+) -> Option<vk::Rect2D> {
+    let x0 = ((clip_rect.min.x * pixels_per_point).round() as i32).max(scissor.offset.x);
+    let y0 = ((clip_rect.min.y * pixels_per_point).round() as i32).max(scissor.offset.y);
+    let x1 = ((clip_rect.max.x * pixels_per_point).round() as i32)
+        .min(scissor.offset.x + scissor.extent.width as i32);
+    let y1 = ((clip_rect.max.y * pixels_per_point).round() as i32)
+        .min(scissor.offset.y + scissor.extent.height as i32);
+
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+
+    Some(
+        vk::Rect2D::builder()
+            .offset(vk::Offset2D { x: x0, y: y0 })
+            .extent(vk::Extent2D {
+                width: (x1 - x0) as u32,
+                height: (y1 - y0) as u32,
+            })
+            .build(),
+    )
+}
+
+/// Copies RGBA8 pixels into a region of a sampled image
+///
+/// A `new` image starts out undefined; otherwise its current contents outside
+/// the region are kept.
+unsafe fn upload_pixels(
+    context: &VulkanContext,
+    image: vk::Image,
+    new: bool,
+    offset: vk::Offset3D,
+    extent: vk::Extent3D,
+    pixels: &[u8],
+) -> Result<()> {
+    let device = &context.device;
+
+    // Destroyed when the guard goes out of scope, after the copy has finished
+    let staging = guard(
+        device,
+        create_buffer(
+            &context.instance,
+            device,
+            context.physical_device,
+            pixels.len() as u64,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?,
+    );
+    let (staging_buffer, staging_memory) = *staging;
+
+    let memory = device.map_memory(
+        staging_memory,
+        0,
+        vk::WHOLE_SIZE,
+        vk::MemoryMapFlags::empty(),
+    )?;
+    memcpy(pixels.as_ptr(), memory.cast(), pixels.len());
+    device.unmap_memory(staging_memory);
+
+    let command_buffer = begin_single_time_commands(device, context.command_pool)?;
+
+    let subresource_range = vk::ImageSubresourceRange::builder()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .base_mip_level(0)
+        .level_count(1)
+        .base_array_layer(0)
+        .layer_count(1);
+
+    let old_layout = if new {
+        vk::ImageLayout::UNDEFINED
+    } else {
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+    };
+
+    let to_transfer = vk::ImageMemoryBarrier::builder()
+        .old_layout(old_layout)
+        .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(subresource_range)
+        .src_access_mask(vk::AccessFlags::SHADER_READ)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE);
+
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::FRAGMENT_SHADER,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::DependencyFlags::empty(),
+        &[] as &[vk::MemoryBarrier],
+        &[] as &[vk::BufferMemoryBarrier],
+        &[to_transfer],
+    );
+
+    let subresource = vk::ImageSubresourceLayers::builder()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .mip_level(0)
+        .base_array_layer(0)
+        .layer_count(1);
+
+    let region = vk::BufferImageCopy::builder()
+        .buffer_offset(0)
+        .buffer_row_length(0)
+        .buffer_image_height(0)
+        .image_subresource(subresource)
+        .image_offset(offset)
+        .image_extent(extent);
+
+    device.cmd_copy_buffer_to_image(
+        command_buffer,
+        staging_buffer,
+        image,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        &[region],
+    );
+
+    let to_shader = vk::ImageMemoryBarrier::builder()
+        .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(subresource_range)
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ);
+
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::PipelineStageFlags::FRAGMENT_SHADER,
+        vk::DependencyFlags::empty(),
+        &[] as &[vk::MemoryBarrier],
+        &[] as &[vk::BufferMemoryBarrier],
+        &[to_shader],
+    );
+
+    end_single_time_commands(
+        device,
+        context.graphics_queue,
+        context.command_pool,
+        command_buffer,
+    )?;
+
+    Ok(())
+}
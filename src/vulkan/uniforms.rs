@@ -0,0 +1,265 @@
+use anyhow::Result;
+use std::mem::size_of;
+use vulkanalia::prelude::v1_0::*;
+
+use super::buffer::create_buffer;
+use super::context::VulkanContext;
+use super::owned::{guard, Destroy};
+use crate::config::{ShaderParam, ShaderParams, MAX_SHADER_PARAMS};
+use crate::types::Vec2;
+
+/// What the `Frame` uniform block of stroke and background shaders holds,
+/// laid out like it (`set = 0, binding = 0`, std140)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameUniforms {
+    /// Pixels of the frame being drawn
+    pub resolution: Vec2,
+    /// The pointer in frame pixels from the top left, `(-1, -1)` until it
+    /// first moves over the canvas
+    pub cursor: Vec2,
+    /// Seconds since the app started
+    pub time: f32,
+}
+
+impl FrameUniforms {
+    /// For frames of `extent` pixels with no pointer over them
+    pub fn still(extent: vk::Extent2D) -> Self {
+        Self {
+            resolution: Vec2::new(extent.width as f32, extent.height as f32),
+            cursor: Vec2::new(-1.0, -1.0),
+            time: 0.0,
+        }
+    }
+}
+
+/// What the `Params` uniform block of a stroke or background shader holds
+/// (`set = 0, binding = 1`, std140): its `[shader_params]` in name order,
+/// numbers in `x` and colors as linear RGBA
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamBlock {
+    pub values: [[f32; 4]; MAX_SHADER_PARAMS],
+}
+
+impl ParamBlock {
+    /// Leaves what doesn't fit zero, which `Config::validate` reports
+    pub fn new(params: &ShaderParams) -> Self {
+        let mut values = [[0.0; 4]; MAX_SHADER_PARAMS];
+        for (value, param) in values.iter_mut().zip(params.values()) {
+            *value = match *param {
+                ShaderParam::Float(x) => [x, 0.0, 0.0, 0.0],
+                ShaderParam::Color(color) => color.to_linear(),
+            };
+        }
+        Self { values }
+    }
+}
+
+/// One host-visible [`FrameUniforms`] buffer, [`ParamBlock`] buffer and
+/// descriptor set per frame in flight
+///
+/// Recorded command buffers bind their frame slot's set, so writing the
+/// slot's values after waiting for its fence updates them without
+/// re-recording anything. The parameter buffer holds a block per shader,
+/// picked with a dynamic offset when binding (see [`FrameBinding`]).
+pub struct FrameUniformBuffers {
+    /// Layout of set 0 in the stroke and background pipelines
+    pub layout: vk::DescriptorSetLayout,
+    pool: vk::DescriptorPool,
+    buffers: Vec<(vk::Buffer, vk::DeviceMemory)>,
+    /// Each frame buffer's memory, mapped for as long as it lives
+    mapped: Vec<*mut FrameUniforms>,
+    /// Each parameter buffer's memory, likewise
+    mapped_params: Vec<*mut u8>,
+    /// Bytes from one parameter block to the next
+    param_stride: u32,
+    sets: Vec<vk::DescriptorSet>,
+}
+
+/// A frame slot's descriptor set, to bind with some shader's parameters
+#[derive(Debug, Clone, Copy)]
+pub struct FrameBinding {
+    set: vk::DescriptorSet,
+    param_stride: u32,
+}
+
+impl FrameBinding {
+    /// Records binding the set as set 0 of `pipeline_layout` with parameter block `block`
+    pub unsafe fn cmd_bind(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        pipeline_layout: vk::PipelineLayout,
+        block: u32,
+    ) {
+        device.cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::GRAPHICS,
+            pipeline_layout,
+            0,
+            &[self.set],
+            &[block * self.param_stride],
+        );
+    }
+}
+
+impl FrameUniformBuffers {
+    /// Creates `slots` sets with room for `param_blocks` shaders' parameters each
+    pub unsafe fn create(
+        context: &VulkanContext,
+        slots: usize,
+        param_blocks: usize,
+    ) -> Result<Self> {
+        let device = &context.device;
+
+        let frame_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT);
+
+        let param_binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(1)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT);
+
+        let bindings = &[frame_binding, param_binding];
+        let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
+        let layout = guard(device, device.create_descriptor_set_layout(&info, None)?);
+
+        let frame_pool_size = vk::DescriptorPoolSize::builder()
+            .type_(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(slots as u32);
+
+        let param_pool_size = vk::DescriptorPoolSize::builder()
+            .type_(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+            .descriptor_count(slots as u32);
+
+        let pool_sizes = &[frame_pool_size, param_pool_size];
+        let info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(pool_sizes)
+            .max_sets(slots as u32);
+        let pool = guard(device, device.create_descriptor_pool(&info, None)?);
+
+        let set_layouts = vec![*layout; slots];
+        let info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(*pool)
+            .set_layouts(&set_layouts);
+        let sets = device.allocate_descriptor_sets(&info)?;
+
+        // Dynamic offsets must be multiples of the device's alignment
+        let alignment = context
+            .instance
+            .get_physical_device_properties(context.physical_device)
+            .limits
+            .min_uniform_buffer_offset_alignment;
+        let block_size = size_of::<ParamBlock>() as vk::DeviceSize;
+        let param_stride = block_size.div_ceil(alignment) * alignment;
+
+        let mut buffers = guard(device, Vec::new());
+        let mut mapped = Vec::new();
+        let mut mapped_params = Vec::new();
+        for &set in &sets {
+            let mut create = |size: vk::DeviceSize| -> Result<_> {
+                let (buffer, memory) = create_buffer(
+                    &context.instance,
+                    device,
+                    context.physical_device,
+                    size,
+                    vk::BufferUsageFlags::UNIFORM_BUFFER,
+                    vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+                )?;
+                buffers.push((buffer, memory));
+                let pointer = device.map_memory(memory, 0, size, vk::MemoryMapFlags::empty())?;
+                Ok((buffer, pointer))
+            };
+
+            let frame_size = size_of::<FrameUniforms>() as vk::DeviceSize;
+            let (frame_buffer, pointer) = create(frame_size)?;
+            mapped.push(pointer.cast::<FrameUniforms>());
+            let (param_buffer, pointer) = create(param_stride * param_blocks.max(1) as u64)?;
+            mapped_params.push(pointer.cast::<u8>());
+
+            let frame_info = vk::DescriptorBufferInfo::builder()
+                .buffer(frame_buffer)
+                .offset(0)
+                .range(frame_size);
+
+            let param_info = vk::DescriptorBufferInfo::builder()
+                .buffer(param_buffer)
+                .offset(0)
+                .range(block_size);
+
+            let frame_infos = &[frame_info];
+            let param_infos = &[param_info];
+            let writes = [
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(set)
+                    .dst_binding(0)
+                    .dst_array_element(0)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .buffer_info(frame_infos),
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(set)
+                    .dst_binding(1)
+                    .dst_array_element(0)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+                    .buffer_info(param_infos),
+            ];
+
+            device.update_descriptor_sets(&writes, &[] as &[vk::CopyDescriptorSet]);
+        }
+
+        let mut uniforms = Self {
+            layout: layout.release(),
+            pool: pool.release(),
+            buffers: buffers.release(),
+            mapped,
+            mapped_params,
+            param_stride: param_stride as u32,
+            sets,
+        };
+        let none = ParamBlock::new(&ShaderParams::new());
+        for slot in 0..slots {
+            uniforms.write(slot, &FrameUniforms::still(vk::Extent2D::default()));
+            for block in 0..param_blocks {
+                uniforms.write_params(slot, block, &none);
+            }
+        }
+        Ok(uniforms)
+    }
+
+    /// Sets what frames drawn with `slot`'s set read, once the GPU is done with its previous frame
+    pub unsafe fn write(&mut self, slot: usize, uniforms: &FrameUniforms) {
+        self.mapped[slot].write(*uniforms);
+    }
+
+    /// Sets the parameters of shader `block` in `slot`, likewise
+    pub unsafe fn write_params(&mut self, slot: usize, block: usize, params: &ParamBlock) {
+        let offset = block * self.param_stride as usize;
+        self.mapped_params[slot]
+            .add(offset)
+            .cast::<ParamBlock>()
+            .write(*params);
+    }
+
+    pub fn binding(&self, slot: usize) -> FrameBinding {
+        FrameBinding {
+            set: self.sets[slot],
+            param_stride: self.param_stride,
+        }
+    }
+}
+
+impl Destroy for FrameUniformBuffers {
+    unsafe fn destroy(&mut self, device: &Device) {
+        // Freeing the memory unmaps it, and the sets go with the pool
+        self.mapped.clear();
+        self.mapped_params.clear();
+        self.buffers.destroy(device);
+        self.pool.destroy(device);
+        self.layout.destroy(device);
+    }
+}
@@ -0,0 +1,270 @@
+use std::ops::Range;
+
+use anyhow::Result;
+use log::*;
+use tracing::info_span;
+use vulkanalia::prelude::v1_0::*;
+
+use super::buffer::create_buffer;
+use super::context::VulkanContext;
+use super::helpers::{begin_single_time_commands, end_single_time_commands};
+use super::owned::{guard, Destroy};
+use crate::config::VertexFormat;
+use crate::types::Line;
+
+/// Line data waiting to be copied into device-local buffers by the next frame
+///
+/// Instead of submitting and waiting on its own command buffer for every
+/// edit, [`LineUploads::queue`] keeps a CPU copy of the lines and
+/// [`LineUploads::cmd_record`] writes everything queued into the staging
+/// buffer of the frame being recorded and records the copies ahead of its
+/// render pass. Each frame in flight has its own staging buffer, reused only
+/// after the renderer has waited on that frame's fence, so no upload ever
+/// blocks on the GPU. A staging buffer too small for what is queued is
+/// replaced by a larger one. Lines are converted to the context's
+/// `vertex_format` as they are written to staging.
+pub struct LineUploads {
+    frames: Vec<StagingBuffer>,
+    format: VertexFormat,
+    pending: Vec<PendingUpload>,
+    pending_lines: usize,
+}
+
+struct StagingBuffer {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    ptr: *mut u8,
+    capacity: usize,
+}
+
+struct PendingUpload {
+    destination: vk::Buffer,
+    first_line: usize,
+    lines: Vec<Line>,
+}
+
+impl PendingUpload {
+    fn range(&self) -> Range<usize> {
+        self.first_line..self.first_line + self.lines.len()
+    }
+}
+
+impl LineUploads {
+    /// Creates one staging buffer of `capacity` lines per frame in flight
+    pub unsafe fn create(context: &VulkanContext, frames: usize, capacity: usize) -> Result<Self> {
+        let mut staging = guard(&context.device, Vec::with_capacity(frames));
+        for _ in 0..frames {
+            staging.push(create_staging_buffer(context, capacity.max(1))?);
+        }
+
+        Ok(Self {
+            frames: staging.release(),
+            format: context.vertex_format,
+            pending: Vec::new(),
+            pending_lines: 0,
+        })
+    }
+
+    /// Queues `lines` to be written into `destination` starting at line index `first_line`
+    ///
+    /// Later uploads to the same lines win, since copies run in the order they were queued.
+    pub fn queue(&mut self, destination: vk::Buffer, first_line: usize, lines: &[Line]) {
+        if lines.is_empty() {
+            return;
+        }
+
+        self.pending_lines += lines.len();
+        match self.pending.last_mut() {
+            // Appending strokes one after another becomes a single copy
+            Some(last) if last.destination == destination && last.range().end == first_line => {
+                last.lines.extend_from_slice(lines)
+            }
+            _ => self.pending.push(PendingUpload {
+                destination,
+                first_line,
+                lines: lines.to_vec(),
+            }),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Bytes the queued lines take in staging
+    pub fn pending_bytes(&self) -> usize {
+        self.pending_lines * Line::stride(self.format)
+    }
+
+    /// Records the queued copies into `command_buffer`, outside of any render pass
+    ///
+    /// The staging buffer of `frame` must no longer be in use by the GPU, i.e.
+    /// that frame's fence has been waited on.
+    pub unsafe fn cmd_record(
+        &mut self,
+        context: &VulkanContext,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+    ) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let _upload = info_span!("upload").entered();
+
+        let device = &context.device;
+        if self.frames[frame].capacity < self.pending_lines {
+            let capacity = self.pending_lines.next_power_of_two();
+            debug!("Growing upload staging buffer to {} lines.", capacity);
+            let staging = create_staging_buffer(context, capacity)?;
+            std::mem::replace(&mut self.frames[frame], staging).destroy(device);
+        }
+
+        // Earlier frames may still be drawing from the lines about to be overwritten
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[] as &[vk::MemoryBarrier],
+            &[] as &[vk::BufferMemoryBarrier],
+            &[] as &[vk::ImageMemoryBarrier],
+        );
+
+        let staging = &self.frames[frame];
+        let stride = Line::stride(self.format);
+        let mut offset = 0;
+        let mut written: Vec<(vk::Buffer, Range<usize>)> = Vec::new();
+        for upload in self.pending.drain(..) {
+            let range = upload.range();
+            let overlaps = written.iter().any(|(buffer, written)| {
+                *buffer == upload.destination
+                    && written.start < range.end
+                    && range.start < written.end
+            });
+
+            // Copies in one batch may run in any order, so rewrites of the same lines wait
+            if overlaps {
+                cmd_memory_barrier(
+                    device,
+                    command_buffer,
+                    (
+                        vk::PipelineStageFlags::TRANSFER,
+                        vk::AccessFlags::TRANSFER_WRITE,
+                    ),
+                    (
+                        vk::PipelineStageFlags::TRANSFER,
+                        vk::AccessFlags::TRANSFER_WRITE,
+                    ),
+                );
+                written.clear();
+            }
+
+            Line::write(self.format, &upload.lines, staging.ptr.add(stride * offset));
+            let region = vk::BufferCopy::builder()
+                .src_offset((stride * offset) as u64)
+                .dst_offset((stride * range.start) as u64)
+                .size((stride * upload.lines.len()) as u64);
+            device.cmd_copy_buffer(
+                command_buffer,
+                staging.buffer,
+                upload.destination,
+                &[region],
+            );
+
+            offset += upload.lines.len();
+            written.push((upload.destination, range));
+        }
+        self.pending_lines = 0;
+
+        cmd_memory_barrier(
+            device,
+            command_buffer,
+            (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+            ),
+            (
+                vk::PipelineStageFlags::VERTEX_INPUT,
+                vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+            ),
+        );
+
+        Ok(())
+    }
+
+    /// Copies everything queued right away, for work outside the frame loop like exports
+    ///
+    /// Waits for the device to go idle, so it is not meant for every frame.
+    pub unsafe fn flush(&mut self, context: &VulkanContext) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        context.device.device_wait_idle()?;
+        let command_buffer = begin_single_time_commands(&context.device, context.command_pool)?;
+        self.cmd_record(context, command_buffer, 0)?;
+        end_single_time_commands(
+            &context.device,
+            context.graphics_queue,
+            context.command_pool,
+            command_buffer,
+        )
+    }
+}
+
+impl Destroy for LineUploads {
+    unsafe fn destroy(&mut self, device: &Device) {
+        self.frames.destroy(device);
+    }
+}
+
+impl Destroy for StagingBuffer {
+    unsafe fn destroy(&mut self, device: &Device) {
+        device.unmap_memory(self.memory);
+        (self.buffer, self.memory).destroy(device);
+    }
+}
+
+unsafe fn create_staging_buffer(context: &VulkanContext, capacity: usize) -> Result<StagingBuffer> {
+    let size = (Line::stride(context.vertex_format) * capacity) as u64;
+    let (buffer, memory) = create_buffer(
+        &context.instance,
+        &context.device,
+        context.physical_device,
+        size,
+        vk::BufferUsageFlags::TRANSFER_SRC,
+        vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+    )?;
+    let ptr = context
+        .device
+        .map_memory(memory, 0, size, vk::MemoryMapFlags::empty())?
+        .cast();
+
+    Ok(StagingBuffer {
+        buffer,
+        memory,
+        ptr,
+        capacity,
+    })
+}
+
+unsafe fn cmd_memory_barrier(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    (src_stage, src_access): (vk::PipelineStageFlags, vk::AccessFlags),
+    (dst_stage, dst_access): (vk::PipelineStageFlags, vk::AccessFlags),
+) {
+    let barrier = vk::MemoryBarrier::builder()
+        .src_access_mask(src_access)
+        .dst_access_mask(dst_access);
+
+    device.cmd_pipeline_barrier(
+        command_buffer,
+        src_stage,
+        dst_stage,
+        vk::DependencyFlags::empty(),
+        &[barrier],
+        &[] as &[vk::BufferMemoryBarrier],
+        &[] as &[vk::ImageMemoryBarrier],
+    );
+}
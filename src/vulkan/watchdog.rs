@@ -0,0 +1,90 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use log::*;
+use thiserror::Error;
+use vulkanalia::prelude::v1_0::*;
+
+use crate::crash;
+
+/// The GPU did not finish work it was waited on in time, most likely a hang
+#[derive(Debug, Error)]
+#[error("The GPU did not finish {what} within {timeout:?} ({state})")]
+pub struct GpuHang {
+    pub what: &'static str,
+    pub timeout: Duration,
+    /// What the renderer was doing, for the log and crash report
+    pub state: String,
+}
+
+/// Waits for `fences` for at most `timeout`, failing with [`GpuHang`] after that
+///
+/// `state` describes the renderer and is only built on a timeout.
+pub unsafe fn wait_for_fences(
+    device: &Device,
+    fences: &[vk::Fence],
+    timeout: Duration,
+    what: &'static str,
+    state: impl FnOnce() -> String,
+) -> Result<()> {
+    let nanos = timeout.as_nanos().min(u64::MAX as u128) as u64;
+    match device.wait_for_fences(fences, true, nanos)? {
+        vk::SuccessCode::TIMEOUT => {
+            let hang = GpuHang {
+                what,
+                timeout,
+                state: state(),
+            };
+            error!("{}", hang);
+            Err(hang.into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Watches a wait that Vulkan can't time out, like `vkDeviceWaitIdle`
+///
+/// A thread waits alongside it. If the watchdog is still alive after
+/// `timeout` it logs `state`, and if it is after twice that, the main thread
+/// is taken to be stuck for good: the thread writes a crash report and ends
+/// the process rather than leave a frozen window. Dropping the watchdog
+/// when the wait returns disarms it.
+pub struct Watchdog {
+    _disarm: mpsc::Sender<()>,
+}
+
+impl Watchdog {
+    pub fn arm(what: &'static str, timeout: Duration, state: String) -> Self {
+        let (disarm, disarmed) = mpsc::channel::<()>();
+        let spawned = thread::Builder::new()
+            .name("gpu-watchdog".to_string())
+            .spawn(move || {
+                if disarmed.recv_timeout(timeout) != Err(RecvTimeoutError::Timeout) {
+                    return;
+                }
+                error!(
+                    "The GPU has not finished {} after {:?} ({}).",
+                    what, timeout, state
+                );
+                if disarmed.recv_timeout(timeout) != Err(RecvTimeoutError::Timeout) {
+                    warn!("The GPU finished {} after all.", what);
+                    return;
+                }
+                let hang = GpuHang {
+                    what,
+                    timeout: timeout * 2,
+                    state,
+                };
+                crash::report(&hang.to_string());
+                error!("Exiting, the GPU appears to have hung.");
+                std::process::exit(1);
+            });
+        if let Err(e) = spawned {
+            warn!("Failed to start the GPU watchdog: {}", e);
+        }
+
+        Self { _disarm: disarm }
+    }
+}
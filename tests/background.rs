@@ -0,0 +1,46 @@
+//! Animated backgrounds under the strokes and the frame uniforms they read
+
+mod common;
+
+use scribble_vk::config::{BackgroundConfig, KeyBindingConfig, ShaderConfig};
+use scribble_vk::keybindings::{Action, KeyBindings};
+use scribble_vk::vulkan::uniforms::FrameUniforms;
+use std::mem::{offset_of, size_of};
+use vulkanalia::vk;
+use winit::keyboard::{KeyCode, ModifiersState};
+
+#[test]
+fn the_shipped_config_offers_backgrounds_but_starts_plain() {
+    let shaders: ShaderConfig = common::shipped_section("shaders");
+    let names: Vec<_> = shaders.backgrounds.keys().map(String::as_str).collect();
+    assert_eq!(names, ["gradient", "noise", "starfield"]);
+
+    let background: BackgroundConfig = common::shipped_section("background");
+    assert_eq!(background, BackgroundConfig::default());
+    assert_eq!(background.shader, None);
+}
+
+#[test]
+fn f12_toggles_the_background_by_default() {
+    let bindings = KeyBindings::new(&KeyBindingConfig::default());
+    assert_eq!(
+        bindings.action_for(KeyCode::F12, ModifiersState::empty()),
+        Some(&Action::ToggleBackground)
+    );
+}
+
+#[test]
+fn frame_uniforms_match_the_std140_block() {
+    assert_eq!(offset_of!(FrameUniforms, resolution), 0);
+    assert_eq!(offset_of!(FrameUniforms, cursor), 8);
+    assert_eq!(offset_of!(FrameUniforms, time), 16);
+    assert_eq!(size_of::<FrameUniforms>(), 20);
+
+    let still = FrameUniforms::still(vk::Extent2D {
+        width: 640,
+        height: 480,
+    });
+    assert_eq!((still.resolution.x, still.resolution.y), (640.0, 480.0));
+    assert!(still.cursor.x < 0.0 && still.cursor.y < 0.0);
+    assert_eq!(still.time, 0.0);
+}
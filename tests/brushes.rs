@@ -0,0 +1,90 @@
+//! Drawing strokes with their own fragment shaders
+
+mod common;
+
+use std::path::PathBuf;
+
+use scribble_vk::config::{ScriptConfig, PIXEL_ERASER};
+use scribble_vk::edit::EditCommand;
+use scribble_vk::scripting::{document_script, ScriptCommand, ScriptEngine};
+use scribble_vk::strokes::StrokeBuilder;
+use scribble_vk::tools::ToolRegistry;
+use scribble_vk::types::{Color, Line, Vec2, LINE_STYLE_DASHED, PIXEL_ERASER_BRUSH};
+use scribble_vk::{Document, Stroke};
+
+#[test]
+fn brushes_follow_the_default_shader_in_name_order() {
+    let shaders = common::shader_config();
+
+    let fragments: Vec<_> = shaders.stroke_fragments().cloned().collect();
+    let expected: Vec<PathBuf> = ["frag.spv", "neon.spv", "pencil.spv"]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    assert_eq!(fragments, expected);
+    assert_eq!(shaders.brush_index("neon"), Some(1));
+    assert_eq!(shaders.brush_index("pencil"), Some(2));
+    assert_eq!(shaders.brush_index("glow"), None);
+    assert_eq!(shaders.brush_name(0), None);
+    assert_eq!(shaders.brush_name(2), Some("pencil"));
+    assert_eq!(shaders.brush_name(3), None);
+}
+
+#[test]
+fn segments_keep_their_brush_next_to_other_style_bits() {
+    let mut line = Line::new(Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Color::WHITE);
+    line.style = LINE_STYLE_DASHED;
+    line.set_brush(2);
+    assert_eq!(line.brush(), 2);
+    assert_eq!(line.style & LINE_STYLE_DASHED, LINE_STYLE_DASHED);
+
+    line.set_brush(0);
+    assert_eq!(line.style, LINE_STYLE_DASHED);
+
+    let mut stroke = StrokeBuilder::new(16);
+    stroke.set_brush(1);
+    stroke.extend_to(Vec2::new(0.0, 0.0), Color::WHITE);
+    let line = stroke.extend_to(Vec2::new(1.0, 0.0), Color::WHITE).unwrap();
+    assert_eq!(line.brush(), 1);
+}
+
+#[test]
+fn pixel_erasers_keep_a_brush_past_every_shader() {
+    let shaders = common::shader_config();
+    assert_eq!(shaders.brush_index(PIXEL_ERASER), Some(PIXEL_ERASER_BRUSH));
+    assert_eq!(shaders.brush_name(PIXEL_ERASER_BRUSH), Some(PIXEL_ERASER));
+
+    let mut line = Line::new(Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Color::WHITE);
+    line.style = LINE_STYLE_DASHED;
+    line.set_brush(PIXEL_ERASER_BRUSH);
+    assert_eq!(line.brush(), PIXEL_ERASER_BRUSH);
+    assert_eq!(line.style & LINE_STYLE_DASHED, LINE_STYLE_DASHED);
+
+    let mut tools = ToolRegistry::default();
+    assert!(!tools.erases_pixels());
+    tools.select_by_name(PIXEL_ERASER).unwrap();
+    assert!(tools.erases_pixels());
+}
+
+#[test]
+fn scripts_pick_brushes_by_name() {
+    let mut neon = Stroke::from_points(
+        &[Vec2::new(0.0, 0.0), Vec2::new(0.5, 0.5)],
+        Color::rgb(255, 0, 255),
+    );
+    neon.brush = Some("neon".to_string());
+    let document = Document {
+        strokes: vec![neon],
+    };
+
+    let engine = ScriptEngine::new(&ScriptConfig::default());
+    let commands = engine
+        .run(&document_script(&document), Color::WHITE)
+        .unwrap();
+    match commands.as_slice() {
+        [ScriptCommand::Edit(EditCommand::AddStroke(stroke))] => {
+            assert_eq!(stroke, &document.strokes[0])
+        }
+        other => panic!("expected a stroke, got {:?}", other),
+    }
+}
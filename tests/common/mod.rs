@@ -0,0 +1,227 @@
+//! Headless rendering and config fixtures shared by the integration tests
+//!
+//! The rendering is meant for a software implementation such as lavapipe or SwiftShader on CI,
+//! after `shaders/compile.sh`. Debug builds also need the Khronos validation
+//! layer. Where no Vulkan device or compiled shaders are found, the tests
+//! pass after saying they skipped, unless `SCRIBBLE_REQUIRE_VULKAN` is set,
+//! which makes that a failure.
+
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+
+use scribble_vk::camera::Camera;
+use scribble_vk::config::{Config, ShaderConfig};
+use scribble_vk::types::{Color, Line, StrokeStyle};
+use scribble_vk::vulkan::buffer::{create_buffers, upload_lines};
+use scribble_vk::vulkan::context::VulkanContext;
+use scribble_vk::vulkan::offscreen::OffscreenRenderer;
+use scribble_vk::vulkan::owned::guard;
+use scribble_vk::vulkan::upload::LineUploads;
+use toml::{Table, Value};
+use vulkanalia::prelude::v1_0::*;
+
+/// Whether missing Vulkan or shaders fail the tests instead of skipping them
+pub fn required() -> bool {
+    std::env::var_os("SCRIBBLE_REQUIRE_VULKAN").is_some()
+}
+
+/// The repository's default config with `vertex_format` and a headless
+/// context, or `None` (after saying why) when the tests should skip
+///
+/// Loading the config fails when the shaders haven't been compiled.
+pub fn setup(vertex_format: &str) -> Option<(Config, VulkanContext)> {
+    let skip = |reason: String| {
+        assert!(!required(), "{}", reason);
+        eprintln!("Skipping: {}", reason);
+        None
+    };
+
+    let mut overrides = Table::new();
+    scribble_vk::config::set_override(
+        &mut overrides,
+        "vulkan.vertex_format",
+        Value::String(vertex_format.to_string()),
+    );
+    let config = match Config::load_with(Some(Path::new("config.toml")), overrides) {
+        Ok(config) => config,
+        Err(e) => return skip(format!("{:#}", e)),
+    };
+    match unsafe { VulkanContext::create_headless(&config) } {
+        Ok(context) => Some((config, context)),
+        Err(e) => skip(format!("no Vulkan device ({:#})", e)),
+    }
+}
+
+/// How lines get into the vertex buffer before drawing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upload {
+    /// `upload_lines`, as exports do
+    Direct,
+    /// [`LineUploads`] with a one-line staging buffer, as frames in the window do
+    Queued,
+}
+
+/// What [`render`] draws and how
+pub struct Scene<'a> {
+    pub size: u32,
+    pub lines: &'a [Line],
+    pub camera: Camera,
+    pub style: StrokeStyle,
+    /// The canvas color, the theme's when `None`
+    pub background: Option<Color>,
+    pub upload: Upload,
+}
+
+impl<'a> Scene<'a> {
+    /// `lines` in a `size` pixel square with the default view and style
+    pub fn new(size: u32, lines: &'a [Line]) -> Self {
+        Self {
+            size,
+            lines,
+            camera: Camera::default(),
+            style: StrokeStyle::default(),
+            background: None,
+            upload: Upload::Direct,
+        }
+    }
+}
+
+/// Uploads the scene's lines into fresh buffers and draws them offscreen,
+/// returning tightly packed RGBA8 rows
+///
+/// With [`Upload::Queued`], `queued_after` is queued right after the lines,
+/// e.g. to rewrite some of them.
+pub unsafe fn render(
+    context: &VulkanContext,
+    config: &Config,
+    scene: &Scene,
+    queued_after: &[Line],
+) -> Vec<u8> {
+    let device = &context.device;
+    let (
+        vertex_buffer,
+        vertex_buffer_memory,
+        staging_buffer,
+        staging_buffer_memory,
+        geometry_buffer,
+        geometry_buffer_memory,
+        geometry_index_buffer,
+        geometry_index_buffer_memory,
+    ) = create_buffers(
+        &context.instance,
+        device,
+        context.physical_device,
+        context.graphics_queue,
+        context.command_pool,
+        config.vulkan.max_vertices,
+        config.vulkan.staging_buffer_vertex_count,
+        context.vertex_format,
+    )
+    .unwrap();
+    let _buffers = guard(
+        device,
+        vec![
+            (vertex_buffer, vertex_buffer_memory),
+            (staging_buffer, staging_buffer_memory),
+            (geometry_buffer, geometry_buffer_memory),
+            (geometry_index_buffer, geometry_index_buffer_memory),
+        ],
+    );
+
+    match scene.upload {
+        Upload::Queued => {
+            let mut uploads = guard(device, LineUploads::create(context, 1, 1).unwrap());
+            uploads.queue(vertex_buffer, 0, scene.lines);
+            uploads.queue(vertex_buffer, 0, queued_after);
+            uploads.flush(context).unwrap();
+        }
+        Upload::Direct => {
+            let staging_ptr = device
+                .map_memory(
+                    staging_buffer_memory,
+                    0,
+                    vk::WHOLE_SIZE,
+                    vk::MemoryMapFlags::empty(),
+                )
+                .unwrap()
+                .cast();
+            upload_lines(
+                device,
+                context.graphics_queue,
+                context.command_pool,
+                staging_buffer,
+                staging_ptr,
+                config.vulkan.staging_buffer_vertex_count as usize,
+                context.vertex_format,
+                vertex_buffer,
+                0,
+                scene.lines,
+            )
+            .unwrap();
+            device.unmap_memory(staging_buffer_memory);
+        }
+    }
+
+    let mut offscreen =
+        OffscreenRenderer::create(context, config, scene.size, scene.size, false).unwrap();
+    if let Some(background) = scene.background {
+        offscreen.background = background;
+    }
+    let pixels = offscreen
+        .render_view(
+            context,
+            geometry_buffer,
+            geometry_index_buffer,
+            &scene.camera.with_viewport(scene.size, scene.size),
+            &scene.style,
+            &[(vertex_buffer, 0..scene.lines.len() as u32)],
+        )
+        .unwrap();
+    device.device_wait_idle().unwrap();
+    pixels
+}
+
+/// RGBA of the pixel at `x`, `y` in tightly packed rows of `width` pixels
+pub fn pixel(pixels: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+    let i = ((y * width + x) * 4) as usize;
+    pixels[i..i + 4].try_into().unwrap()
+}
+
+pub fn assert_color(actual: [u8; 4], expected: Color) {
+    let expected = [expected.r, expected.g, expected.b];
+    let close = actual[..3]
+        .iter()
+        .zip(expected)
+        .all(|(&a, e)| a.abs_diff(e) <= 2);
+    assert!(close, "expected {:?}, got {:?}", expected, actual);
+}
+
+/// A `[shaders]` section with two brushes and two backgrounds, each named
+/// out of name order, and nothing on disk behind the paths
+pub fn shader_config() -> ShaderConfig {
+    toml::from_str(
+        r#"
+        vertex = "vert.spv"
+        fragment = "frag.spv"
+
+        [brushes]
+        pencil = "pencil.spv"
+        neon = "neon.spv"
+
+        [backgrounds]
+        starfield = "starfield.spv"
+        gradient = "gradient.spv"
+        "#,
+    )
+    .unwrap()
+}
+
+/// Section `key` of the repository's `config.toml` as written, without
+/// loading its shaders
+pub fn shipped_section<T: DeserializeOwned>(key: &str) -> T {
+    let config: Table = toml::from_str(include_str!("../../config.toml")).unwrap();
+    config[key].clone().try_into().unwrap()
+}
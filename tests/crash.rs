@@ -0,0 +1,20 @@
+//! What crash reports include
+
+use scribble_vk::{crash, logging};
+
+#[test]
+fn reports_the_error_device_and_recent_log() {
+    logging::init();
+    log::info!("Opened drawing.json");
+    crash::set_gpu("Device: Test GPU (DISCRETE_GPU)\n".to_string());
+
+    let report = crash::report_text("panicked at src/app.rs:1:1:\nout of strokes");
+    let section = |name: &str| report.find(&format!("## {}", name)).unwrap();
+    assert!(section("Error") < section("GPU"));
+    assert!(section("GPU") < section("Configuration"));
+    assert!(section("Configuration") < section("Recent log"));
+    assert!(report.contains("out of strokes"));
+    assert!(report.contains("Test GPU"));
+    assert!(report.contains("Not loaded yet."));
+    assert!(report[section("Recent log")..].contains("Opened drawing.json"));
+}
@@ -0,0 +1,48 @@
+//! Cropping the canvas to a dragged rectangle
+
+use cgmath::AbsDiffEq;
+use scribble_vk::camera::Camera;
+use scribble_vk::edit::EditCommand;
+use scribble_vk::tools::crop::CropTool;
+use scribble_vk::tools::Tool;
+use scribble_vk::types::{Rect, Vec2};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+#[test]
+fn dragging_crops_to_the_rectangle() {
+    let mut tool = CropTool::default();
+    tool.pointer_down(Vec2::new(0.5, -0.25));
+    tool.pointer_moved(Vec2::new(0.0, 0.25));
+    assert!(tool.marquee().is_some());
+
+    let edits = tool.pointer_up(Vec2::new(-0.5, 0.75));
+    let area = Rect::from_corners(Vec2::new(-0.5, -0.25), Vec2::new(0.5, 0.75));
+    assert_eq!(edits, vec![EditCommand::Crop(area)]);
+    assert!(tool.marquee().is_none());
+}
+
+#[test]
+fn clicks_and_escape_crop_nothing() {
+    let mut tool = CropTool::default();
+    tool.pointer_down(Vec2::new(0.1, 0.1));
+    assert!(tool.pointer_up(Vec2::new(0.1, 0.1)).is_empty());
+
+    // A flat rectangle has no canvas to keep
+    tool.pointer_down(Vec2::new(0.1, 0.1));
+    assert!(tool.pointer_up(Vec2::new(0.6, 0.1)).is_empty());
+
+    tool.pointer_down(Vec2::new(0.1, 0.1));
+    tool.pointer_moved(Vec2::new(0.6, 0.6));
+    tool.key_pressed(KeyCode::Escape, ModifiersState::empty());
+    assert!(tool.pointer_up(Vec2::new(0.6, 0.6)).is_empty());
+}
+
+#[test]
+fn the_cropped_canvas_fills_a_frame_of_its_shape() {
+    let area = Rect::from_corners(Vec2::new(-0.5, -0.25), Vec2::new(1.5, 0.75));
+    let camera = Camera::framing(&area, 400, 200);
+
+    let corners = camera.rect_to_screen(&area);
+    assert!(corners.min.abs_diff_eq(&Vec2::new(-1.0, -1.0), 1e-5));
+    assert!(corners.max.abs_diff_eq(&Vec2::new(1.0, 1.0), 1e-5));
+}
@@ -0,0 +1,73 @@
+//! PNG export options from `[export]`
+
+use scribble_vk::camera::Camera;
+use scribble_vk::config::{ExportArea, ExportConfig};
+use scribble_vk::export::{unpremultiply, ExportView};
+use scribble_vk::types::{Rect, Vec2};
+
+#[test]
+fn unpremultiply_leaves_opaque_and_empty_pixels_alone() {
+    let mut pixels = [10, 20, 30, 255, 0, 0, 0, 0];
+    unpremultiply(&mut pixels);
+    assert_eq!(pixels, [10, 20, 30, 255, 0, 0, 0, 0]);
+}
+
+#[test]
+fn unpremultiply_divides_by_alpha_in_linear_light() {
+    // Half-covered white reads back as 0.5 in linear light, 188 in sRGB
+    let mut pixels = [188, 188, 188, 128, 0, 188, 0, 128];
+    unpremultiply(&mut pixels);
+    assert_eq!(pixels, [255, 255, 255, 128, 0, 255, 0, 128]);
+}
+
+#[test]
+fn scale_multiplies_the_canvas_size() {
+    let export = ExportConfig {
+        scale: 4.0,
+        ..ExportConfig::default()
+    };
+    assert_eq!(export.size([1920, 1080]), [7680, 4320]);
+
+    let export = ExportConfig {
+        scale: 0.001,
+        ..ExportConfig::default()
+    };
+    assert_eq!(export.size([1920, 1080]), [2, 1]);
+}
+
+#[test]
+fn tiles_cover_their_part_of_the_view() {
+    let camera = Camera {
+        offset: Vec2::new(0.2, -0.1),
+        zoom: 1.5,
+        aspect: 2.0,
+    };
+    let size = [200, 100];
+    assert_eq!(camera.tile(size, [0, 0], size), camera);
+
+    // A point in the right half, lower quarter, of the view
+    let point = camera.to_canvas(Vec2::new(0.5, 0.5));
+    let tile = camera.tile(size, [100, 50], [100, 50]);
+    let screen = tile.to_screen(point);
+    assert!((screen.x - 0.0).abs() < 1e-5 && (screen.y - 0.0).abs() < 1e-5);
+}
+
+#[test]
+fn drawing_area_keeps_the_canvas_pixel_density() {
+    let export = ExportConfig {
+        area: ExportArea::Drawing,
+        scale: 2.0,
+        ..ExportConfig::default()
+    };
+    let canvas = [400, 200];
+    let camera = Camera::default().with_viewport(400, 200);
+    let drawing = Rect::from_corners(Vec2::new(-10.0, -1.0), Vec2::new(10.0, 1.0));
+
+    // 100 pixels per canvas unit, doubled, over the drawing and its margin
+    let view = ExportView::new(&export, canvas, &camera, Some(drawing));
+    assert_eq!(view.width, 4008);
+    assert_eq!(view.height, 408);
+
+    let view = ExportView::new(&export, canvas, &camera, None);
+    assert_eq!((view.width, view.height), (800, 400));
+}
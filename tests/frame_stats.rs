@@ -0,0 +1,68 @@
+//! The frame timing ring buffer and the reports written from it
+
+use std::time::Duration;
+
+use scribble_vk::stats::{FrameRecord, FrameStats, Summary};
+
+fn frame(index: u64, gpu: Option<u64>) -> FrameRecord {
+    FrameRecord {
+        at: Duration::from_millis(index * 16),
+        cpu: Duration::from_millis(index + 1),
+        gpu: gpu.map(Duration::from_millis),
+        fence: Duration::ZERO,
+        acquire: Duration::from_micros(500),
+        present: Duration::ZERO,
+        upload_bytes: 64,
+    }
+}
+
+#[test]
+fn keeps_the_latest_frames() {
+    let mut stats = FrameStats::new(3);
+    for i in 0..5 {
+        stats.push(frame(i, None));
+    }
+    let kept: Vec<_> = stats.iter().map(|f| f.at.as_millis()).collect();
+    assert_eq!(kept, [32, 48, 64]);
+
+    stats.set_capacity(1);
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats.iter().next().unwrap().at.as_millis(), 64);
+}
+
+#[test]
+fn writes_a_row_per_frame() {
+    let mut stats = FrameStats::new(10);
+    stats.push(frame(0, Some(2)));
+    stats.push(frame(1, None));
+
+    let mut csv = Vec::new();
+    stats.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "at_ms,cpu_ms,gpu_ms,fence_ms,acquire_ms,present_ms,upload_bytes",
+            "0.000,1.000,2.000,0.000,0.500,0.000,64",
+            "16.000,2.000,,0.000,0.500,0.000,64",
+        ]
+    );
+
+    let mut json = Vec::new();
+    stats.write_json(&mut json).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(json["frames"], 2);
+    assert_eq!(json["gpu_ms"]["max"], 2.0);
+    assert_eq!(json["records"][1]["gpu_ms"], serde_json::Value::Null);
+}
+
+#[test]
+fn summarizes_a_distribution() {
+    let summary = Summary::of((1..=100).map(Duration::from_millis)).unwrap();
+    assert_eq!(summary.median, 51.0);
+    assert_eq!(summary.p99, 99.0);
+    assert_eq!(summary.max, 100.0);
+    assert!((summary.mean - 50.5).abs() < 1e-9);
+    assert!(Summary::of([]).is_none());
+}
@@ -0,0 +1,233 @@
+//! Renders fixed scenes and compares them with reference images in
+//! `tests/golden`, so shader and pipeline changes can't silently change how
+//! strokes look
+//!
+//! A missing reference fails the test like a mismatch would, so a run can't
+//! pass without comparing anything. `SCRIBBLE_UPDATE_GOLDEN=1` records every
+//! reference from the render, for new scenes and after an intended change;
+//! commit them rendered by lavapipe like on CI, since rasterizers differ
+//! slightly at edges. On a mismatch or a missing reference the render is
+//! written next to the reference as `<name>.actual.png`.
+
+mod common;
+
+use std::f32::consts::TAU;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use common::{render, setup, Scene};
+use scribble_vk::camera::Camera;
+use scribble_vk::export::write_png;
+use scribble_vk::types::{Color, Line, StrokeStyle, Vec2, LINE_STYLE_DASHED};
+use scribble_vk::Stroke;
+
+const SIZE: u32 = 128;
+const BACKGROUND: Color = Color::rgb(24, 24, 24);
+
+/// How far a channel may be off before the pixel counts as different
+const CHANNEL_TOLERANCE: u8 = 16;
+/// Share of pixels that may differ, for anti-aliased edges
+const MAX_DIFFERING: f32 = 0.005;
+
+/// Half width the scenes draw strokes at, about two pixels
+const THICKNESS: f32 = 0.03;
+
+fn stroke(points: &[(f32, f32)], color: Color) -> Vec<Line> {
+    let points: Vec<Vec2> = points.iter().map(|&(x, y)| Vec2::new(x, y)).collect();
+    Stroke::from_points(&points, color).lines()
+}
+
+fn circle(center: (f32, f32), radius: f32, color: Color) -> Vec<Line> {
+    let points: Vec<(f32, f32)> = (0..=48)
+        .map(|i| {
+            let angle = i as f32 / 48.0 * TAU;
+            (
+                center.0 + radius * angle.cos(),
+                center.1 + radius * angle.sin(),
+            )
+        })
+        .collect();
+    stroke(&points, color)
+}
+
+fn zigzag() -> Vec<Line> {
+    stroke(
+        &[
+            (-0.8, -0.6),
+            (-0.4, 0.6),
+            (0.0, -0.6),
+            (0.4, 0.6),
+            (0.8, -0.6),
+        ],
+        Color::rgb(255, 160, 0),
+    )
+}
+
+fn scene(lines: &[Line]) -> Scene<'_> {
+    Scene {
+        style: StrokeStyle {
+            thickness: THICKNESS,
+            ..StrokeStyle::default()
+        },
+        background: Some(BACKGROUND),
+        ..Scene::new(SIZE, lines)
+    }
+}
+
+fn reference_path(name: &str) -> PathBuf {
+    Path::new("tests/golden").join(format!("{}.png", name))
+}
+
+fn read_png(path: &Path) -> (u32, u32, Vec<u8>) {
+    let decoder = png::Decoder::new(File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!(
+        (info.color_type, info.bit_depth),
+        (png::ColorType::Rgba, png::BitDepth::Eight),
+        "{} must be 8-bit RGBA",
+        path.display()
+    );
+    pixels.truncate(info.buffer_size());
+    (info.width, info.height, pixels)
+}
+
+/// Renders `scene` with `vertex_format` and compares it with reference `name`
+fn check(name: &str, vertex_format: &str, scene: &Scene) {
+    let Some((config, context)) = setup(vertex_format) else {
+        return;
+    };
+    let pixels = unsafe { render(&context, &config, scene, &[]) };
+
+    let path = reference_path(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    if std::env::var_os("SCRIBBLE_UPDATE_GOLDEN").is_some() {
+        write_png(&path, SIZE, SIZE, &pixels).unwrap();
+        eprintln!("Recorded {}", path.display());
+        return;
+    }
+    if !path.exists() {
+        let actual = path.with_extension("actual.png");
+        write_png(&actual, SIZE, SIZE, &pixels).unwrap();
+        panic!(
+            "No reference {}, the render is in {}; record it with SCRIBBLE_UPDATE_GOLDEN=1",
+            path.display(),
+            actual.display()
+        );
+    }
+
+    let (width, height, reference) = read_png(&path);
+    assert_eq!(
+        (width, height),
+        (SIZE, SIZE),
+        "{} has the wrong size",
+        path.display()
+    );
+    let differing = pixels
+        .chunks_exact(4)
+        .zip(reference.chunks_exact(4))
+        .filter(|(a, b)| {
+            a.iter()
+                .zip(*b)
+                .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE)
+        })
+        .count();
+
+    let allowed = (MAX_DIFFERING * (SIZE * SIZE) as f32) as usize;
+    if differing > allowed {
+        let actual = path.with_extension("actual.png");
+        write_png(&actual, SIZE, SIZE, &pixels).unwrap();
+        panic!(
+            "{} pixels differ from {} (at most {} may), the render is in {}",
+            differing,
+            path.display(),
+            allowed,
+            actual.display()
+        );
+    }
+}
+
+#[test]
+fn polyline() {
+    check("polyline", "f32", &scene(&zigzag()));
+}
+
+#[test]
+fn polyline_from_half_floats() {
+    // Half precision moves points by well under a pixel, so the reference is shared
+    check("polyline", "f16", &scene(&zigzag()));
+}
+
+#[test]
+fn translucent_overlap() {
+    let translucent = |color: Color| Color { a: 160, ..color };
+    let mut lines = stroke(
+        &[(-0.8, -0.8), (0.8, 0.8)],
+        translucent(Color::rgb(0, 160, 255)),
+    );
+    lines.extend(stroke(
+        &[(-0.8, 0.8), (0.8, -0.8)],
+        translucent(Color::rgb(255, 64, 64)),
+    ));
+    check("translucent_overlap", "f32", &scene(&lines));
+}
+
+#[test]
+fn width_and_pressure() {
+    let mut lines = stroke(
+        &[(-0.8, 0.0), (-0.4, 0.0), (0.0, 0.0), (0.4, 0.0), (0.8, 0.0)],
+        Color::WHITE,
+    );
+    for (i, line) in lines.iter_mut().enumerate() {
+        line.width = 0.12;
+        line.pressure = 0.25 * (i + 1) as f32;
+    }
+    check("width_and_pressure", "f32", &scene(&lines));
+}
+
+#[test]
+fn dashed() {
+    let lines: Vec<Line> = stroke(&[(-0.8, 0.3), (0.8, 0.3)], Color::rgb(120, 255, 120))
+        .into_iter()
+        .chain(stroke(
+            &[(-0.8, -0.3), (0.8, -0.3)],
+            Color::rgb(120, 255, 120),
+        ))
+        .enumerate()
+        .map(|(i, line)| Line {
+            width: 0.05,
+            style: if i == 0 { LINE_STYLE_DASHED } else { 0 },
+            ..line
+        })
+        .collect();
+    check("dashed", "f32", &scene(&lines));
+}
+
+#[test]
+fn zoomed_and_panned() {
+    let lines = circle((0.2, 0.1), 0.5, Color::rgb(200, 120, 255));
+    let view = Scene {
+        camera: Camera {
+            offset: Vec2::new(-0.3, 0.2),
+            zoom: 1.5,
+            ..Camera::default()
+        },
+        ..scene(&lines)
+    };
+    check("zoomed_and_panned", "f32", &view);
+}
+
+#[test]
+fn high_contrast() {
+    let lines = stroke(&[(-0.8, 0.0), (0.8, 0.0)], Color::rgb(40, 0, 80));
+    let view = Scene {
+        style: StrokeStyle {
+            thickness: THICKNESS,
+            min_luminance: 0.4,
+            ..StrokeStyle::default()
+        },
+        ..scene(&lines)
+    };
+    check("high_contrast", "f32", &view);
+}
@@ -0,0 +1,86 @@
+//! Drives the Vulkan code (context, pipeline, stroke uploads and drawing)
+//! without a window, rendering into an offscreen image and checking its pixels
+
+mod common;
+
+use common::{assert_color, pixel, render, setup, Scene, Upload};
+use scribble_vk::types::{Color, Line, Vec2};
+use scribble_vk::vulkan::offscreen::OffscreenRenderer;
+use scribble_vk::vulkan::pipeline::Primitive;
+
+const SIZE: u32 = 64;
+const RED: Color = Color::rgb(255, 0, 0);
+const BLUE: Color = Color::rgb(0, 0, 255);
+
+/// A horizontal segment through the middle of the image, 6 pixels wide
+fn segment(color: Color) -> Line {
+    Line {
+        width: 0.1,
+        ..Line::new(Vec2::new(-0.8, 0.0), Vec2::new(0.8, 0.0), color)
+    }
+}
+
+fn check_segment_drawn(vertex_format: &str) {
+    let Some((config, context)) = setup(vertex_format) else {
+        return;
+    };
+    let lines = [segment(RED)];
+    let pixels = unsafe { render(&context, &config, &Scene::new(SIZE, &lines), &[]) };
+
+    assert_color(pixel(&pixels, SIZE, SIZE / 2, SIZE / 2), RED);
+    assert_color(pixel(&pixels, SIZE, 2, 2), config.theme.active().background);
+}
+
+#[test]
+fn draws_a_segment_offscreen() {
+    check_segment_drawn("f32");
+}
+
+#[test]
+fn draws_a_segment_from_half_floats() {
+    // Devices without half float attributes fall back to F32, which draws the same
+    check_segment_drawn("f16");
+}
+
+#[test]
+fn empty_canvas_is_the_background() {
+    let Some((config, context)) = setup("f32") else {
+        return;
+    };
+    let pixels = unsafe { render(&context, &config, &Scene::new(SIZE, &[]), &[]) };
+
+    let background = config.theme.active().background;
+    for (x, y) in [(0, 0), (SIZE / 2, SIZE / 2), (SIZE - 1, SIZE - 1)] {
+        assert_color(pixel(&pixels, SIZE, x, y), background);
+    }
+}
+
+#[test]
+fn queued_uploads_apply_in_order() {
+    let Some((config, context)) = setup("f32") else {
+        return;
+    };
+    // A staging buffer of one line has to grow for both uploads
+    let lines = [segment(RED)];
+    let scene = Scene {
+        upload: Upload::Queued,
+        ..Scene::new(SIZE, &lines)
+    };
+    let pixels = unsafe { render(&context, &config, &scene, &[segment(BLUE)]) };
+
+    assert_color(pixel(&pixels, SIZE, SIZE / 2, SIZE / 2), BLUE);
+}
+
+#[test]
+fn offscreen_pipelines_cover_every_brush_but_no_background() {
+    let Some((config, context)) = setup("f32") else {
+        return;
+    };
+    let offscreen =
+        unsafe { OffscreenRenderer::create(&context, &config, SIZE, SIZE, false).unwrap() };
+
+    let lines = offscreen.pipelines.get(Primitive::Lines);
+    assert_eq!(lines.len(), config.shaders.brushes.len() + 1);
+    assert!(offscreen.pipelines.get(Primitive::Background).is_empty());
+    assert_eq!(offscreen.pipelines.param_block(Primitive::Lines, 2), 2);
+}
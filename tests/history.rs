@@ -0,0 +1,165 @@
+//! The undo history's memory budget: which revisions it drops, and that
+//! undoing and redoing keeps its byte count in step with what it holds
+
+use scribble_vk::history::{History, Revision};
+use scribble_vk::strokes::StrokeStore;
+use scribble_vk::types::{Color, Line, Vec2};
+
+/// A straight line of `count` segments
+fn segments(count: usize) -> Vec<Line> {
+    (0..count)
+        .map(|i| {
+            let x = i as f32 * 0.01;
+            Line::new(
+                Vec2::new(x, 0.0),
+                Vec2::new(x + 0.01, 0.0),
+                Color::rgb(255, 255, 255),
+            )
+        })
+        .collect()
+}
+
+/// A stroke revision with `segments` segments
+fn stroke(store: &mut StrokeStore, count: usize) -> Revision {
+    store.push(segments(count));
+    Revision::Stroke(vec![store.pop().unwrap()])
+}
+
+fn lengths(history: &History) -> Vec<usize> {
+    history
+        .iter()
+        .map(|(revision, _)| match revision {
+            Revision::Stroke(pieces) => pieces.iter().map(|s| s.lines.len()).sum(),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+#[test]
+fn drops_the_oldest_edits_past_the_budget() {
+    let mut store = StrokeStore::default();
+    let size = stroke(&mut store, 10).size();
+    let mut history = History::new(3 * size);
+
+    for _ in 0..5 {
+        history.push(stroke(&mut store, 10));
+    }
+
+    assert_eq!(history.undo_len(), 3);
+    assert_eq!(history.bytes(), 3 * size);
+}
+
+#[test]
+fn keeps_the_latest_edit_however_large() {
+    let mut store = StrokeStore::default();
+    let mut history = History::new(0);
+
+    history.push(stroke(&mut store, 1));
+    history.push(stroke(&mut store, 1000));
+
+    assert_eq!(lengths(&history), [1000]);
+    let revision = history.pop_undo().unwrap();
+    history.undone(revision);
+    assert_eq!(history.redo_len(), 1);
+}
+
+#[test]
+fn drops_undoable_edits_before_redoable_ones() {
+    let mut store = StrokeStore::default();
+    let mut history = History::new(usize::MAX);
+    for segments in 1..=4 {
+        history.push(stroke(&mut store, segments));
+    }
+    for _ in 0..2 {
+        let revision = history.pop_undo().unwrap();
+        history.undone(revision);
+    }
+
+    // Room for the latest undoable edit and both undone ones
+    let budget = history.iter().skip(1).map(|(r, _)| r.size()).sum();
+    history.set_budget(budget);
+    assert_eq!(lengths(&history), [2, 3, 4]);
+    assert_eq!(history.undo_len(), 1);
+
+    // Then the furthest redo goes before the last undo
+    let latest = history.iter().next().unwrap().0.size();
+    history.set_budget(latest);
+    assert_eq!(lengths(&history), [2]);
+}
+
+#[test]
+fn tracks_bytes_through_undo_and_redo() {
+    let mut store = StrokeStore::default();
+    let mut history = History::new(usize::MAX);
+    for segments in [3, 5, 8] {
+        history.push(stroke(&mut store, segments));
+    }
+    let full = history.bytes();
+
+    let revision = history.pop_undo().unwrap();
+    history.undone(revision);
+    let revision = history.pop_redo().unwrap();
+    history.redone(revision);
+    assert_eq!(history.bytes(), full);
+
+    let revision = history.pop_undo().unwrap();
+    history.undone(revision);
+    history.push(stroke(&mut store, 1));
+    assert_eq!(lengths(&history), [3, 5, 1]);
+    assert_eq!(
+        history.bytes(),
+        history.iter().map(|(r, _)| r.size()).sum::<usize>()
+    );
+}
+
+#[test]
+fn pieces_of_a_long_stroke_join_its_revision() {
+    let mut store = StrokeStore::default();
+    let mut history = History::new(usize::MAX);
+    history.push(stroke(&mut store, 2));
+
+    let mut piece = |count| {
+        store.push(segments(count));
+        store.pop().unwrap()
+    };
+    let first = piece(4);
+    let first_id = first.id;
+    history.push(Revision::Stroke(vec![first]));
+    let second = piece(4);
+    let second_id = second.id;
+    history.push_piece(first_id, second);
+    history.push_piece(second_id, piece(3));
+    assert_eq!(lengths(&history), [2, 11]);
+
+    // Not after the latest piece, as when that was undone
+    history.push_piece(first_id, piece(5));
+    assert_eq!(lengths(&history), [2, 11, 5]);
+    assert_eq!(
+        history.bytes(),
+        history.iter().map(|(r, _)| r.size()).sum::<usize>()
+    );
+}
+
+#[test]
+fn a_cleared_board_comes_back_under_strokes_drawn_since() {
+    let mut store = StrokeStore::default();
+    let mut history = History::new(usize::MAX);
+    history.push(stroke(&mut store, 2));
+    store.push(segments(2));
+    store.push(segments(3));
+    let previous = store.take_all();
+
+    // A peer draws after the clear
+    let theirs = store.push(segments(4));
+    store.restore_all(previous);
+
+    let counts: Vec<usize> = store.iter().map(|s| s.lines.len()).collect();
+    assert_eq!(counts, [2, 3, 4]);
+    assert_eq!(store.index_of(theirs), Some(2));
+    assert_eq!(store.line_count(), 9);
+    assert_eq!(store[2].range, 5..9);
+
+    history.clear();
+    assert_eq!(history.undo_len(), 0);
+    assert_eq!(history.bytes(), 0);
+}
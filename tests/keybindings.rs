@@ -0,0 +1,60 @@
+//! Binding mouse and pen buttons
+
+use scribble_vk::config::KeyBindingConfig;
+use scribble_vk::input::parse_mouse_button;
+use scribble_vk::keybindings::{Action, ButtonBinding, KeyBindings};
+use winit::event::MouseButton;
+use winit::keyboard::{KeyCode, ModifiersState};
+
+#[test]
+fn parses_button_names() {
+    assert_eq!(parse_mouse_button("middle"), Some(MouseButton::Middle));
+    assert_eq!(parse_mouse_button(" Forward "), Some(MouseButton::Forward));
+    assert_eq!(parse_mouse_button("Button8"), Some(MouseButton::Other(8)));
+    assert_eq!(parse_mouse_button("Button"), None);
+    assert_eq!(parse_mouse_button("Wheel"), None);
+}
+
+#[test]
+fn binds_buttons_over_the_defaults() {
+    let config = KeyBindingConfig {
+        buttons: [
+            ("Back", "undo"),
+            ("Forward", "eraser"),
+            ("Left", "pan"),
+            ("Nope", "pan"),
+        ]
+        .into_iter()
+        .map(|(button, binding)| (button.to_string(), binding.to_string()))
+        .collect(),
+        ..Default::default()
+    };
+    let bindings = KeyBindings::new(&config);
+
+    assert_eq!(
+        bindings.button_for(MouseButton::Middle),
+        Some(&ButtonBinding::Pan)
+    );
+    assert_eq!(
+        bindings.button_for(MouseButton::Right),
+        Some(&ButtonBinding::Menu)
+    );
+    assert_eq!(
+        bindings.button_for(MouseButton::Back),
+        Some(&ButtonBinding::Action(Action::Undo))
+    );
+    assert_eq!(
+        bindings.button_for(MouseButton::Forward),
+        Some(&ButtonBinding::Tool("eraser".to_string()))
+    );
+    assert_eq!(bindings.button_for(MouseButton::Left), None);
+}
+
+#[test]
+fn space_is_held_to_pan_by_default() {
+    let bindings = KeyBindings::new(&KeyBindingConfig::default());
+    assert_eq!(
+        bindings.action_for(KeyCode::Space, ModifiersState::empty()),
+        Some(&Action::HoldToPan)
+    );
+}
@@ -0,0 +1,61 @@
+//! Log file rotation and the timestamps written into it
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+use log::{Level, Record};
+use scribble_vk::config::LoggingConfig;
+use scribble_vk::logging::{timestamp, RotatingFile, LOG_FILE_NAME};
+
+/// An empty directory of its own for each test
+fn directory(name: &str) -> PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("scribble-vk-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    directory
+}
+
+fn log(file: &mut RotatingFile, message: &str) {
+    file.write(
+        &Record::builder()
+            .args(format_args!("{}", message))
+            .level(Level::Info)
+            .target("test")
+            .build(),
+    );
+}
+
+#[test]
+fn rotates_past_the_size_limit() {
+    let directory = directory("rotate");
+    let config = LoggingConfig {
+        file: true,
+        // Room for about two lines
+        max_size_mib: 100.0 / (1024.0 * 1024.0),
+        keep: 2,
+        ..LoggingConfig::default()
+    };
+
+    let mut file = RotatingFile::open(&directory, &config).unwrap();
+    for i in 0..8 {
+        log(&mut file, &format!("message {}", i));
+    }
+    drop(file);
+
+    let read = |name: String| fs::read_to_string(directory.join(name)).unwrap();
+    let current = read(LOG_FILE_NAME.to_string());
+    assert!(current.ends_with("test: message 7\n"), "{}", current);
+    assert!(read(format!("{}.1", LOG_FILE_NAME)).contains("message 5"));
+    assert!(read(format!("{}.2", LOG_FILE_NAME)).contains("message 3"));
+    assert!(!directory.join(format!("{}.3", LOG_FILE_NAME)).exists());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn formats_utc_timestamps() {
+    assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    let leap_day = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+    assert_eq!(timestamp(leap_day), "2024-02-29T12:34:56.789Z");
+}
@@ -0,0 +1,37 @@
+//! Extrapolating the pointer ahead of the in-progress stroke
+
+use std::time::{Duration, Instant};
+
+use scribble_vk::input::MotionPredictor;
+use scribble_vk::types::Vec2;
+
+#[test]
+fn extrapolates_steady_motion() {
+    let start = Instant::now();
+    let mut predictor = MotionPredictor::default();
+    for i in 0..5 {
+        let at = start + Duration::from_millis(i * 5);
+        predictor.push(at, Vec2::new(i as f32 * 0.01, 0.0));
+    }
+
+    let last = start + Duration::from_millis(20);
+    let tip = predictor.predict(last, Duration::from_millis(10)).unwrap();
+    assert!((tip.x - 0.06).abs() < 1e-4, "{:?}", tip);
+    assert!(tip.y.abs() < 1e-4);
+}
+
+#[test]
+fn stops_predicting_once_the_pointer_rests() {
+    let start = Instant::now();
+    let mut predictor = MotionPredictor::default();
+    predictor.push(start, Vec2::new(0.0, 0.0));
+    assert!(predictor
+        .predict(start, Duration::from_millis(10))
+        .is_none());
+
+    predictor.push(start + Duration::from_millis(5), Vec2::new(0.01, 0.0));
+    let expiry = predictor.expires_at().unwrap();
+    assert!(predictor.predict(expiry, Duration::ZERO).is_some());
+    let later = expiry + Duration::from_millis(1);
+    assert!(predictor.predict(later, Duration::ZERO).is_none());
+}
@@ -0,0 +1,137 @@
+//! Drawing from scripts
+
+use scribble_vk::config::ScriptConfig;
+use scribble_vk::edit::EditCommand;
+use scribble_vk::scripting::{document_script, ScriptCommand, ScriptEngine};
+use scribble_vk::types::{Color, Vec2};
+use scribble_vk::{Document, Stroke};
+
+fn strokes(source: &str) -> Vec<Stroke> {
+    let engine = ScriptEngine::new(&ScriptConfig::default());
+    engine
+        .run(source, Color::WHITE)
+        .unwrap()
+        .into_iter()
+        .map(|command| match command {
+            ScriptCommand::Edit(EditCommand::AddStroke(stroke)) => stroke,
+            other => panic!("expected a stroke, got {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn polylines_take_a_style() {
+    let strokes = strokes(
+        r##"
+        polyline([[0, 0], [0.5, 0.5], [1.0, 0]]);
+        stroke([[0, 0], [1, 1]], #{ color: "#ff0000", fade: 2.0, tag: "grid" });
+        "##,
+    );
+
+    assert_eq!(strokes.len(), 2);
+    assert_eq!(strokes[0].points.len(), 3);
+    assert_eq!(strokes[0].color, Color::WHITE);
+    assert_eq!(strokes[1].color, Color::rgb(255, 0, 0));
+    assert_eq!(strokes[1].fade_after, Some(2.0));
+    assert_eq!(strokes[1].tag.as_deref(), Some("grid"));
+}
+
+#[test]
+fn circles_close_around_their_center() {
+    let strokes = strokes(r##"circle(0.5, -0.5, 0.25, #{ color: "#00ff00" });"##);
+
+    let points = &strokes[0].points;
+    assert!(points.len() > 16);
+    assert_eq!(points.first(), points.last());
+    for &[x, y] in points {
+        let distance = ((x - 0.5).powi(2) + (y + 0.5).powi(2)).sqrt();
+        assert!((distance - 0.25).abs() < 1e-4, "{} away", distance);
+    }
+    assert_eq!(strokes[0].color, Color::rgb(0, 255, 0));
+}
+
+#[test]
+fn rejects_unknown_styles() {
+    let engine = ScriptEngine::new(&ScriptConfig::default());
+    assert!(engine
+        .run(r#"circle(0, 0, 1, #{ width: 3 });"#, Color::WHITE)
+        .is_err());
+}
+
+#[test]
+fn turtle_paths_become_one_stroke_each() {
+    let strokes = strokes(
+        r##"
+        for i in 0..4 {
+            forward(0.5);
+            right(90);
+        }
+        pen_up();
+        go_to(1, 1);
+        pen_down();
+        color("#0000ff");
+        back(0.25);
+        "##,
+    );
+
+    assert_eq!(strokes.len(), 2);
+    let square = &strokes[0].points;
+    assert_eq!(square.len(), 5);
+    let [x, y] = square[1];
+    assert!(x.abs() < 1e-6 && (y + 0.5).abs() < 1e-6, "{:?}", square);
+    let [x, y] = square[4];
+    assert!(x.abs() < 1e-6 && y.abs() < 1e-6, "{:?}", square);
+
+    assert_eq!(strokes[1].points, vec![[1.0, 1.0], [1.0, 1.25]]);
+    assert_eq!(strokes[1].color, Color::rgb(0, 0, 255));
+}
+
+#[test]
+fn turtle_paths_stay_in_order_with_other_commands() {
+    let engine = ScriptEngine::new(&ScriptConfig::default());
+    let commands = engine
+        .run("forward(0.5); clear(); forward(0.5);", Color::WHITE)
+        .unwrap();
+
+    assert!(matches!(
+        commands.as_slice(),
+        [
+            ScriptCommand::Edit(EditCommand::AddStroke(_)),
+            ScriptCommand::Edit(EditCommand::Clear),
+            ScriptCommand::Edit(EditCommand::AddStroke(_)),
+        ]
+    ));
+}
+
+#[test]
+fn exported_scripts_draw_the_document_again() {
+    let mut tagged = Stroke::from_points(
+        &[
+            Vec2::new(-0.125, 0.3),
+            Vec2::new(0.7, -0.9),
+            Vec2::new(1.0, 2.0),
+        ],
+        Color::from_hex("#12345680").unwrap(),
+    );
+    tagged.tag = Some("notes \"1\"".to_string());
+    let document = Document {
+        strokes: vec![
+            tagged,
+            Stroke::circle(Vec2::new(0.1, 0.2), 0.3, Color::WHITE),
+        ],
+    };
+
+    assert_eq!(strokes(&document_script(&document)), document.strokes);
+}
+
+#[test]
+fn runaway_scripts_fail_instead_of_hanging() {
+    let engine = ScriptEngine::new(&ScriptConfig::default());
+    assert!(engine.run("loop {}", Color::WHITE).is_err());
+}
+
+#[test]
+fn numbers_too_large_for_the_canvas_fail_the_script() {
+    let engine = ScriptEngine::new(&ScriptConfig::default());
+    assert!(engine.run("line(0, 0, 1e39, 0);", Color::WHITE).is_err());
+}
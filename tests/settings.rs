@@ -0,0 +1,37 @@
+//! Settings written back to the config file on exit
+
+use std::fs;
+
+use scribble_vk::config::Config;
+use scribble_vk::types::Color;
+use toml::Table;
+
+#[test]
+fn only_settings_changed_at_runtime_are_saved() {
+    let mut config: Config = toml::from_str(&fs::read_to_string("config.toml").unwrap()).unwrap();
+    // As if started with `--set window.width=640`
+    config.window.width = 640;
+    let loaded = config.saved_settings();
+    config.brush.color = Color::rgb(0x12, 0x34, 0x56);
+
+    let path =
+        std::env::temp_dir().join(format!("scribble-vk-settings-{}.toml", std::process::id()));
+    let _ = fs::remove_file(&path);
+    config.save(&path, &loaded).unwrap();
+    let saved: Table = toml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    let _ = fs::remove_file(&path);
+
+    // The `--set` width stays out, and so does everything left alone
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved["brush"]["color"].as_str(), Some("#123456"));
+}
+
+#[test]
+fn absurd_fade_times_are_rejected() {
+    let mut config: Config = toml::from_str(&fs::read_to_string("config.toml").unwrap()).unwrap();
+    config.brush.fade_seconds = 1e30;
+    assert!(config
+        .validate()
+        .iter()
+        .any(|problem| problem.contains("brush.fade_seconds")));
+}
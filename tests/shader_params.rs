@@ -0,0 +1,54 @@
+//! Shader parameters from `[shader_params]`
+
+mod common;
+
+use scribble_vk::config::{ShaderParam, ShaderParamConfig};
+use scribble_vk::types::Color;
+use scribble_vk::vulkan::uniforms::ParamBlock;
+
+#[test]
+fn blocks_follow_the_brush_indices_then_the_backgrounds() {
+    let shaders = common::shader_config();
+    let params: ShaderParamConfig = toml::from_str(
+        r##"
+        [stroke]
+        width = 2
+
+        [brushes.pencil]
+        grain = 0.5
+
+        [backgrounds.starfield]
+        tint = "#ff0000"
+        "##,
+    )
+    .unwrap();
+
+    let blocks: Vec<_> = params.blocks(&shaders).collect();
+    assert_eq!(blocks.len(), shaders.param_block_count());
+    assert_eq!(blocks[0]["width"], ShaderParam::Float(2.0));
+    assert!(blocks[1].is_empty());
+    assert_eq!(blocks[2]["grain"], ShaderParam::Float(0.5));
+    assert!(blocks[3].is_empty());
+    assert_eq!(blocks[4]["tint"], ShaderParam::Color(Color::rgb(255, 0, 0)));
+    assert_eq!(shaders.background_param_block("gradient"), Some(3));
+    assert_eq!(shaders.background_param_block("starfield"), Some(4));
+    assert_eq!(shaders.background_param_block("clouds"), None);
+}
+
+#[test]
+fn blocks_hold_values_in_name_order() {
+    let params = [
+        (
+            "tint".to_string(),
+            ShaderParam::Color(Color::rgb(255, 255, 255)),
+        ),
+        ("glow".to_string(), ShaderParam::Float(1.5)),
+    ]
+    .into_iter()
+    .collect();
+
+    let block = ParamBlock::new(&params);
+    assert_eq!(block.values[0], [1.5, 0.0, 0.0, 0.0]);
+    assert_eq!(block.values[1], [1.0, 1.0, 1.0, 1.0]);
+    assert_eq!(block.values[2], [0.0; 4]);
+}
@@ -0,0 +1,103 @@
+//! Ramer-Douglas-Peucker simplification of committed strokes: straight
+//! stretches collapse, corners, gaps and changes in look stay. Also the
+//! decimated levels of detail, which keep the look of what they join.
+
+use scribble_vk::lod::{decimate, simplify};
+use scribble_vk::types::{Color, Line, Vec2, PIXEL_ERASER_BRUSH};
+
+const WHITE: Color = Color::WHITE;
+
+/// Connected segments through `points`
+fn polyline(points: &[Vec2]) -> Vec<Line> {
+    points
+        .windows(2)
+        .map(|p| Line::new(p[0], p[1], WHITE))
+        .collect()
+}
+
+fn ends(line: &Line) -> (Vec2, Vec2) {
+    (
+        line.position - line.dir / 2.0,
+        line.position + line.dir / 2.0,
+    )
+}
+
+#[test]
+fn collapses_a_jittery_straight_line() {
+    let points: Vec<Vec2> = (0..=100)
+        .map(|i| Vec2::new(i as f32 * 0.01, if i % 2 == 0 { 0.0 } else { 0.0005 }))
+        .collect();
+
+    let simplified = simplify(&polyline(&points), 0.001);
+
+    assert_eq!(simplified.len(), 1);
+    let (start, end) = ends(&simplified[0]);
+    assert!((start - points[0]).x.abs() < 1e-6 && (end - points[100]).x.abs() < 1e-6);
+}
+
+#[test]
+fn keeps_corners_and_gaps() {
+    let corner = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(0.5, 0.0),
+        Vec2::new(1.0, 0.0),
+        Vec2::new(1.0, 0.5),
+        Vec2::new(1.0, 1.0),
+    ];
+    let mut lines = polyline(&corner);
+    // A separate run, e.g. another side of a loose shape
+    lines.extend(polyline(&[Vec2::new(2.0, 0.0), Vec2::new(3.0, 0.0)]));
+
+    let simplified = simplify(&lines, 0.01);
+
+    let ends: Vec<_> = simplified.iter().map(ends).collect();
+    assert_eq!(
+        ends,
+        [
+            (corner[0], corner[2]),
+            (corner[2], corner[4]),
+            (Vec2::new(2.0, 0.0), Vec2::new(3.0, 0.0)),
+        ]
+    );
+}
+
+#[test]
+fn keeps_points_where_the_look_changes() {
+    let points: Vec<Vec2> = (0..=4).map(|i| Vec2::new(i as f32 * 0.1, 0.0)).collect();
+    let mut lines = polyline(&points);
+    lines[2].color = Color::rgb(255, 0, 0);
+    lines[3].color = Color::rgb(255, 0, 0);
+    lines[3].width = 0.02;
+
+    let simplified = simplify(&lines, 0.001);
+
+    assert_eq!(simplified.len(), 3);
+    assert_eq!(simplified[1].color, Color::rgb(255, 0, 0));
+    assert_eq!(simplified[2].width, 0.02);
+}
+
+#[test]
+fn decimated_lines_keep_their_look() {
+    let points: Vec<Vec2> = (0..=20).map(|i| Vec2::new(i as f32 * 0.01, 0.0)).collect();
+    let lines: Vec<Line> = polyline(&points)
+        .into_iter()
+        .map(|mut line| {
+            line.set_brush(PIXEL_ERASER_BRUSH);
+            line.fade = Vec2::new(3.0, 3.5);
+            line.tilt = Vec2::new(0.2, -0.4);
+            line.tag = 2;
+            line
+        })
+        .collect();
+
+    let decimated = decimate(&lines, 0.05);
+
+    assert!(decimated.len() < lines.len());
+    for line in &decimated {
+        assert_eq!(line.style, lines[0].style);
+        assert_eq!(line.brush(), PIXEL_ERASER_BRUSH);
+        assert_eq!(line.fade, lines[0].fade);
+        assert_eq!(line.tilt, lines[0].tilt);
+        assert_eq!(line.tag, 2);
+    }
+}
@@ -0,0 +1,38 @@
+//! The stroke index, fed points from scripts, peers and documents that
+//! can't all be drawn
+
+use scribble_vk::spatial::StrokeIndex;
+use scribble_vk::strokes::StrokeStore;
+use scribble_vk::types::{Color, Rect, Vec2};
+use scribble_vk::Stroke;
+
+#[test]
+fn points_that_are_not_finite_are_left_out() {
+    let stroke = Stroke {
+        points: vec![
+            [0.0, 0.0],
+            [f32::INFINITY, 0.0],
+            [1.0, 0.0],
+            [f32::NAN, 1.0],
+        ],
+        ..Stroke::from_points(&[], Color::WHITE)
+    };
+
+    assert_eq!(stroke.to_points().len(), 2);
+    assert_eq!(stroke.lines().len(), 1);
+}
+
+#[test]
+fn far_out_strokes_are_kept_but_not_indexed() {
+    let mut strokes = StrokeStore::default();
+    let far = Stroke::from_points(&[Vec2::new(0.0, 0.0), Vec2::new(3e38, 0.0)], Color::WHITE);
+    let near = Stroke::from_points(&[Vec2::new(0.0, 0.0), Vec2::new(1.0, 1.0)], Color::WHITE);
+    strokes.push(far.lines());
+    strokes.push(near.lines());
+
+    let index = StrokeIndex::new(&strokes);
+
+    assert_eq!(index.len(), 2);
+    let everywhere = Rect::around_point(Vec2::new(0.0, 0.0), 1e30);
+    assert_eq!(index.query(&everywhere), vec![1]);
+}
@@ -0,0 +1,304 @@
+//! Replays randomized pointer input through the drawing tools, the
+//! in-progress [`StrokeBuilder`] and the [`StrokeStore`], checking after every
+//! event that the stroke never outgrows its staging buffer, that committed
+//! strokes and their buffer ranges add up, and that no segment goes missing
+//!
+//! `App` needs a window, so [`Canvas`] drives the same types the way
+//! `App::append_vertex`, `App::commit_new_line`, `App::set_new_line` and
+//! `App::pointer_cancel` do: pieces come out of [`StrokeBuilder::take_full`]
+//! and are committed with [`StrokeStore::push_within`], which refuses what
+//! doesn't fit into `max_vertices` and has `App` drop it. Cases are generated from seeds, so a failure names
+//! the seed to replay with `SCRIBBLE_FUZZ_SEED`; `SCRIBBLE_FUZZ_CASES` runs
+//! more (or fewer) than the default.
+
+use cgmath::AbsDiffEq;
+use scribble_vk::camera::Camera;
+use scribble_vk::edit::EditCommand;
+use scribble_vk::strokes::{StrokeBuilder, StrokeStore};
+use scribble_vk::tools::line::LineTool;
+use scribble_vk::tools::pen::PenTool;
+use scribble_vk::tools::shape::{Shape, ShapeTool};
+use scribble_vk::tools::Tool;
+use scribble_vk::types::{Color, Line, Vec2};
+use scribble_vk::Stroke;
+
+const DEFAULT_CASES: u64 = 500;
+const EVENTS_PER_CASE: usize = 300;
+
+/// xorshift64*, enough to spread cases around without a dependency
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// Uniform in `min..max`
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (self.next() >> 40) as f32 / (1u64 << 24) as f32 * (max - min)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Event {
+    Press(Vec2),
+    Move(Vec2),
+    Release(Vec2),
+    Resize(u32, u32),
+    /// A touch turning into a gesture
+    Cancel,
+    /// Another tool becoming active
+    SwitchTool(usize),
+}
+
+fn tool(index: usize) -> Box<dyn Tool> {
+    match index {
+        0 => Box::new(PenTool::default()),
+        1 => Box::new(LineTool::default()),
+        2 => Box::new(ShapeTool::new(Shape::Rectangle)),
+        _ => Box::new(ShapeTool::new(Shape::Ellipse)),
+    }
+}
+
+/// The drawing state of `App` without anything Vulkan, plus the bookkeeping the checks need
+struct Canvas {
+    camera: Camera,
+    tool: Box<dyn Tool>,
+    new_stroke: StrokeBuilder,
+    strokes: StrokeStore,
+    max_vertices: usize,
+    /// Segments drawn or set so far, those thrown away by replacing the
+    /// stroke and those dropped for not fitting into `max_vertices`
+    drawn: usize,
+    discarded: usize,
+    dropped: usize,
+    /// Whether the stroke was last replaced wholesale, which may exceed the staging buffer
+    replaced: bool,
+}
+
+impl Canvas {
+    fn new(capacity: usize, max_vertices: usize) -> Self {
+        Self {
+            camera: Camera::default(),
+            tool: tool(0),
+            new_stroke: StrokeBuilder::new(capacity),
+            strokes: StrokeStore::default(),
+            max_vertices,
+            drawn: 0,
+            discarded: 0,
+            dropped: 0,
+            replaced: false,
+        }
+    }
+
+    fn handle(&mut self, event: Event) {
+        let edits = match event {
+            Event::Press(p) => self.tool.pointer_down(self.camera.to_canvas(p)),
+            Event::Move(p) => self.tool.pointer_moved(self.camera.to_canvas(p)),
+            Event::Release(p) => self.tool.pointer_up(self.camera.to_canvas(p)),
+            Event::Resize(width, height) => {
+                self.camera = self.camera.with_viewport(width, height);
+                vec![]
+            }
+            Event::Cancel => {
+                let mut edits = self.tool.cancel();
+                edits.push(EditCommand::SetStroke(vec![]));
+                edits
+            }
+            Event::SwitchTool(index) => {
+                let edits = self.tool.deactivate();
+                self.tool = tool(index);
+                edits
+            }
+        };
+        for edit in edits {
+            self.apply(edit);
+        }
+    }
+
+    fn apply(&mut self, edit: EditCommand) {
+        match edit {
+            EditCommand::StrokeTo(point) => self.append_vertex(point),
+            EditCommand::SetStroke(points) => {
+                self.discarded += self.new_stroke.len();
+                let lines = Stroke::from_points(&points, Color::WHITE).lines();
+                self.drawn += lines.len();
+                self.new_stroke.replace(lines, points.first().copied());
+                self.replaced = true;
+            }
+            EditCommand::CommitStroke => {
+                let lines = self.new_stroke.take();
+                self.commit(lines);
+            }
+            edit => panic!("tools drew with {:?}", edit),
+        }
+    }
+
+    fn append_vertex(&mut self, point: Vec2) {
+        let was_full = self.new_stroke.is_full();
+        if self.new_stroke.extend_to(point, Color::WHITE).is_some() {
+            assert!(!was_full, "a full stroke grew");
+            self.drawn += 1;
+            self.replaced = false;
+        }
+
+        if let Some(piece) = self.new_stroke.take_full() {
+            let piece_end = piece.last().map(end_of);
+            assert_eq!(
+                piece_end,
+                self.new_stroke.end(),
+                "the stroke doesn't go on where the committed piece ends"
+            );
+            self.commit(piece);
+        }
+    }
+
+    /// Commits `lines` as one stroke, or drops them like `App::push_stroke`
+    /// when they don't fit into `max_vertices`
+    fn commit(&mut self, lines: Vec<Line>) {
+        self.replaced = false;
+        if lines.is_empty() {
+            return;
+        }
+        let (count, committed) = (lines.len(), self.strokes.line_count());
+        if self.strokes.push_within(lines, self.max_vertices).is_none() {
+            assert!(
+                committed + count > self.max_vertices,
+                "a stroke that fits was refused"
+            );
+            assert_eq!(
+                self.strokes.line_count(),
+                committed,
+                "a refused stroke was kept"
+            );
+            self.dropped += count;
+        }
+    }
+
+    fn check(&self) {
+        let capacity = self.new_stroke.capacity();
+        let pending = self.new_stroke.lines();
+        assert!(
+            pending.len() <= capacity || self.replaced,
+            "{} segments in progress overflow a staging buffer of {}",
+            pending.len(),
+            capacity
+        );
+        // Segments store their midpoint and direction, so ends only meet up
+        // to rounding relative to those
+        for pair in pending.windows(2) {
+            let (end, start) = (end_of(&pair[0]), start_of(&pair[1]));
+            let size = pair
+                .iter()
+                .flat_map(|l| [l.position.x, l.position.y, l.dir.x, l.dir.y])
+                .fold(1.0f32, |size, v| size.max(v.abs()));
+            let tolerance = 1e-5 * size;
+            assert!(
+                end.abs_diff_eq(&start, tolerance),
+                "the stroke in progress has a gap from {:?} to {:?}",
+                end,
+                start
+            );
+        }
+
+        let mut next = 0;
+        for stroke in &self.strokes {
+            assert!(!stroke.lines.is_empty(), "an empty stroke was committed");
+            assert_eq!(stroke.range, next..next + stroke.lines.len() as u32);
+            next = stroke.range.end;
+            assert_eq!(
+                self.strokes.by_id(stroke.id).map(|s| s.range.clone()),
+                Some(stroke.range.clone())
+            );
+        }
+        assert_eq!(self.strokes.line_count(), next as usize);
+        assert!(self.strokes.line_count() <= self.max_vertices);
+        assert_eq!(
+            self.strokes.line_count() + pending.len() + self.discarded + self.dropped,
+            self.drawn,
+            "segments went missing"
+        );
+    }
+}
+
+fn start_of(line: &Line) -> Vec2 {
+    line.position - line.dir / 2.0
+}
+
+fn end_of(line: &Line) -> Vec2 {
+    line.position + line.dir / 2.0
+}
+
+/// A screen position in NDC, sometimes slightly off screen, sometimes (almost) where the last one was
+fn position(rng: &mut Rng, last: &mut Vec2) -> Vec2 {
+    *last = match rng.below(4) {
+        0 => *last,
+        1 => *last + Vec2::new(rng.range(-1e-3, 1e-3), rng.range(-1e-3, 1e-3)),
+        _ => Vec2::new(rng.range(-1.2, 1.2), rng.range(-1.2, 1.2)),
+    };
+    *last
+}
+
+fn run_case(seed: u64) {
+    let mut rng = Rng::new(seed);
+    let capacity = 1 + rng.below(8) as usize;
+    let max_vertices = rng.below(64) as usize;
+    let mut canvas = Canvas::new(capacity, max_vertices);
+    let mut last = Vec2::new(0.0, 0.0);
+
+    for _ in 0..EVENTS_PER_CASE {
+        let event = match rng.below(20) {
+            0..=2 => Event::Press(position(&mut rng, &mut last)),
+            3..=14 => Event::Move(position(&mut rng, &mut last)),
+            15..=16 => Event::Release(position(&mut rng, &mut last)),
+            17 => Event::Resize(rng.below(4000) as u32, rng.below(4000) as u32),
+            18 => Event::Cancel,
+            _ => Event::SwitchTool(rng.below(4) as usize),
+        };
+        canvas.handle(event);
+        canvas.check();
+    }
+}
+
+#[test]
+fn random_input_keeps_strokes_consistent() {
+    let env = |name: &str| std::env::var(name).ok().map(|v| v.parse::<u64>().unwrap());
+    let seeds = match env("SCRIBBLE_FUZZ_SEED") {
+        Some(seed) => seed..seed + 1,
+        None => 0..env("SCRIBBLE_FUZZ_CASES").unwrap_or(DEFAULT_CASES),
+    };
+
+    for seed in seeds {
+        if let Err(panic) = std::panic::catch_unwind(|| run_case(seed)) {
+            eprintln!("Failed with SCRIBBLE_FUZZ_SEED={}", seed);
+            std::panic::resume_unwind(panic);
+        }
+    }
+}
+
+#[test]
+fn full_vertex_buffer_drops_the_stroke() {
+    let mut canvas = Canvas::new(4, 6);
+    for x in 0..12 {
+        canvas.handle(Event::Press(Vec2::new(0.0, 0.0)));
+        canvas.handle(Event::Move(Vec2::new(0.1 * x as f32 - 0.5, 0.3)));
+        canvas.handle(Event::Move(Vec2::new(0.1 * x as f32 - 0.5, -0.3)));
+        canvas.handle(Event::Release(Vec2::new(0.1 * x as f32 - 0.5, -0.3)));
+        canvas.check();
+    }
+
+    assert_eq!(canvas.strokes.line_count(), 6);
+    assert!(canvas.dropped > 0);
+    assert!(canvas.new_stroke.is_empty());
+}
@@ -0,0 +1,38 @@
+//! Stretching the brush along the pen's tilt or a calligraphy nib
+
+use scribble_vk::config::BrushConfig;
+use scribble_vk::strokes::StrokeBuilder;
+use scribble_vk::types::{Color, Line, Rect, Vec2};
+
+#[test]
+fn nibs_point_along_their_angle() {
+    let brush = BrushConfig {
+        nib_stretch: 2.0,
+        nib_angle_degrees: 90.0,
+        ..Default::default()
+    };
+
+    let tilt = brush.nib_tilt();
+    assert!(
+        tilt.x.abs() < 1e-6 && (tilt.y - 2.0).abs() < 1e-6,
+        "{:?}",
+        tilt
+    );
+    assert_eq!(BrushConfig::default().nib_tilt(), Vec2::new(0.0, 0.0));
+}
+
+#[test]
+fn tilted_segments_reach_further() {
+    let mut stroke = StrokeBuilder::new(16);
+    stroke.set_tilt(Vec2::new(0.0, 1.0));
+    stroke.extend_to(Vec2::new(0.0, 0.0), Color::WHITE);
+    let line = stroke.extend_to(Vec2::new(1.0, 0.0), Color::WHITE).unwrap();
+    assert_eq!(line.tilt, Vec2::new(0.0, 1.0));
+    assert_eq!(line.stretch(), 2.0);
+
+    // Stretched twice as long, the footprint pads the damage twice as far
+    let round = Line::new(Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Color::WHITE);
+    let height = |rect: Rect| rect.max.y - rect.min.y;
+    let tilted = height(Rect::around_line(&line, 0.1));
+    assert!((tilted - 2.0 * height(Rect::around_line(&round, 0.1))).abs() < 1e-6);
+}
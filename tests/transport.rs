@@ -0,0 +1,158 @@
+//! Collaborating over a connection that isn't TCP
+
+use std::io::{Cursor, Write};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use scribble_vk::edit::EditCommand;
+use scribble_vk::net::client::CollabClient;
+use scribble_vk::net::{write_message, Authority, Connection, Envelope, NetMessage};
+use scribble_vk::types::{Color, Vec2};
+use scribble_vk::{Document, Stroke};
+
+/// Collects what the client writes
+#[derive(Clone, Default)]
+struct Sent(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Sent {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn envelope(message: NetMessage, seen: u64) -> Envelope {
+    Envelope { message, seen }
+}
+
+fn stroke() -> Stroke {
+    let points = [Vec2::new(0.0, 0.0), Vec2::new(0.5, 0.5)];
+    Stroke::from_points(&points, Color::rgb(255, 0, 0))
+}
+
+#[test]
+fn exchanges_edits_over_any_connection() {
+    let mut incoming = Vec::new();
+    write_message(&mut incoming, &envelope(NetMessage::Stroke(stroke()), 0)).unwrap();
+    let sent = Sent::default();
+    let connection = Connection {
+        reader: Box::new(Cursor::new(incoming)),
+        writer: Box::new(sent.clone()),
+        peer: "test".to_string(),
+    };
+
+    let (woken, wakes) = mpsc::channel();
+    let mut client = CollabClient::start(connection, move || {
+        let _ = woken.send(());
+    })
+    .unwrap();
+    wakes.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(matches!(
+        client.receive().as_slice(),
+        [EditCommand::AddStroke(_)]
+    ));
+
+    client.send(&EditCommand::Clear);
+    let written = String::from_utf8(sent.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(written, "{\"type\":\"clear\",\"seen\":1}\n");
+}
+
+#[test]
+fn spectators_send_nothing_but_their_role() {
+    let sent = Sent::default();
+    let connection = Connection {
+        reader: Box::new(Cursor::new(Vec::new())),
+        writer: Box::new(sent.clone()),
+        peer: "test".to_string(),
+    };
+
+    let mut client = CollabClient::start(connection, || {}).unwrap();
+    client.spectate();
+    client.send(&EditCommand::AddStroke(stroke()));
+    client.send(&EditCommand::Clear);
+
+    let written = String::from_utf8(sent.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(written, "{\"type\":\"spectate\",\"seen\":0}\n");
+}
+
+#[test]
+fn undo_is_sent_as_the_edit_it_resolved_to() {
+    assert!(NetMessage::from_edit(&EditCommand::Undo).is_none());
+    assert!(NetMessage::from_edit(&EditCommand::Redo).is_none());
+
+    let inserted = EditCommand::InsertStrokes(vec![(3, stroke())]);
+    let message = NetMessage::from_edit(&inserted).unwrap();
+    let json = serde_json::to_string(&envelope(message, 0)).unwrap();
+    let read: Envelope = serde_json::from_str(&json).unwrap();
+    assert_eq!(read.message.to_edits(), vec![inserted]);
+}
+
+#[test]
+fn edits_made_before_seeing_ours_are_resolved_by_the_authority() {
+    let mut authority = Authority::default();
+    authority.sent();
+    let remove = || NetMessage::Remove { strokes: vec![0] };
+
+    // Made after seeing our message
+    let resolution = authority.resolve(&envelope(remove(), 1));
+    assert!(resolution.apply && !resolution.resync);
+
+    // Made without it: the index may name another stroke now
+    let resolution = authority.resolve(&envelope(remove(), 0));
+    assert!(!resolution.apply && resolution.resync);
+
+    // New strokes are kept, the snapshot puts them in our order
+    let resolution = authority.resolve(&envelope(NetMessage::Stroke(stroke()), 0));
+    assert!(resolution.apply && resolution.resync && !resolution.echo);
+
+    // With a snapshot on its way, the stroke goes back to the peer instead
+    authority.synced();
+    let resolution = authority.resolve(&envelope(NetMessage::Stroke(stroke()), 1));
+    assert!(resolution.apply && !resolution.resync && resolution.echo);
+    let resolution = authority.resolve(&envelope(remove(), 1));
+    assert!(!resolution.apply && !resolution.resync);
+}
+
+#[test]
+fn the_listening_side_sends_the_board_after_dropping_a_stale_edit() {
+    let mut incoming = Vec::new();
+    let remove = NetMessage::Remove { strokes: vec![0] };
+    write_message(&mut incoming, &envelope(remove, 0)).unwrap();
+    let sent = Sent::default();
+    let connection = Connection {
+        reader: Box::new(Cursor::new(incoming)),
+        writer: Box::new(sent.clone()),
+        peer: "test".to_string(),
+    };
+
+    let (woken, wakes) = mpsc::channel();
+    let mut client = CollabClient::start(connection, move || {
+        let _ = woken.send(());
+    })
+    .unwrap()
+    .with_authority();
+    client.send(&EditCommand::AddStroke(stroke()));
+    wakes.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(client.receive().is_empty());
+
+    client.resync(Document::default);
+    let written = String::from_utf8(sent.0.lock().unwrap().clone()).unwrap();
+    let last: Envelope = serde_json::from_str(written.lines().last().unwrap()).unwrap();
+    assert!(matches!(last.message, NetMessage::Snapshot(_)));
+}
+
+#[test]
+fn snapshots_replace_the_board_instead_of_adding_edits() {
+    let document = Document {
+        strokes: vec![stroke(), stroke()],
+    };
+    assert_eq!(
+        NetMessage::Snapshot(document.clone()).to_edits(),
+        vec![EditCommand::ReplaceAll(document.strokes)]
+    );
+}